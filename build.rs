@@ -0,0 +1,108 @@
+//! Generates the comb tables embedded by `src/gadgets/point/comb.rs`: the ones of
+//! the Sonny basepoint and of the blinding generator `H` of `SonnyPedersenGens`.
+use merlin::Transcript;
+use rand::rngs::StdRng;
+use rand::SeedableRng;
+use std::env;
+use std::fmt::Write;
+use std::fs;
+use std::path::Path;
+use zerocaf::edwards::EdwardsPoint;
+use zerocaf::ristretto::RistrettoPoint;
+use zerocaf::traits::ops::Double;
+use zerocaf::traits::Identity;
+
+const TEETH: usize = 4;
+const SPACING: usize = 1;
+const BITS: usize = 253;
+
+/// Same construction as `CombTable::new`.
+fn comb_table(base: &EdwardsPoint) -> Vec<Vec<EdwardsPoint>> {
+    let n_combs = (BITS + TEETH * SPACING - 1) / (TEETH * SPACING);
+    // tooth_bases[k] = 2^(k * SPACING) * base
+    let mut tooth_bases = Vec::with_capacity(n_combs * TEETH);
+    let mut tooth_base = *base;
+    for _ in 0..n_combs * TEETH {
+        tooth_bases.push(tooth_base);
+        for _ in 0..SPACING {
+            tooth_base = tooth_base.double();
+        }
+    }
+    tooth_bases
+        .chunks(TEETH)
+        .map(|teeth| {
+            let mut comb = vec![EdwardsPoint::identity(); 1 << TEETH];
+            for j in 1..(1usize << TEETH) {
+                let top = (usize::BITS - 1 - j.leading_zeros()) as usize;
+                comb[j] = comb[j ^ (1 << top)] + teeth[top];
+            }
+            comb
+        })
+        .collect()
+}
+
+/// Same derivation as `SonnyPedersenGens::new`.
+fn blinding_gen() -> EdwardsPoint {
+    let mut transcript = Transcript::new(b"bulletproofs_gadgets SonnyPedersenGens");
+    let mut seed = [0u8; 32];
+    transcript.challenge_bytes(b"seed", &mut seed);
+    let mut rng = StdRng::from_seed(seed);
+    RistrettoPoint::new_random_point(&mut rng).0
+}
+
+fn write_table(out: &mut String, name: &str, doc: &str, table: &[Vec<EdwardsPoint>]) {
+    writeln!(out, "/// {}", doc).unwrap();
+    writeln!(
+        out,
+        "static {}: [[[[u8; 32]; 4]; COMB_ENTRIES]; {}] = [",
+        name,
+        table.len()
+    )
+    .unwrap();
+    for comb in table {
+        out.push_str("    [\n");
+        for p in comb {
+            writeln!(
+                out,
+                "        [{:?}, {:?}, {:?}, {:?}],",
+                p.X.to_bytes(),
+                p.Y.to_bytes(),
+                p.Z.to_bytes(),
+                p.T.to_bytes()
+            )
+            .unwrap();
+        }
+        out.push_str("    ],\n");
+    }
+    out.push_str("];\n");
+}
+
+fn main() {
+    println!("cargo:rerun-if-changed=build.rs");
+    let mut out = String::new();
+    writeln!(out, "/// Teeth of the embedded comb tables.").unwrap();
+    writeln!(out, "pub const COMB_TEETH: usize = {};", TEETH).unwrap();
+    writeln!(out, "/// Spacing of the teeth of the embedded comb tables.").unwrap();
+    writeln!(out, "pub const COMB_SPACING: usize = {};", SPACING).unwrap();
+    writeln!(
+        out,
+        "/// Bits of the scalars covered by the embedded tables."
+    )
+    .unwrap();
+    writeln!(out, "pub const COMB_BITS: usize = {};", BITS).unwrap();
+    writeln!(out, "const COMB_ENTRIES: usize = {};", 1 << TEETH).unwrap();
+    write_table(
+        &mut out,
+        "BASEPOINT_COMB",
+        "Comb table of the Sonny basepoint.",
+        &comb_table(&zerocaf::constants::RISTRETTO_BASEPOINT.0),
+    );
+    write_table(
+        &mut out,
+        "BLINDING_COMB",
+        "Comb table of the blinding generator of `SonnyPedersenGens`.",
+        &comb_table(&blinding_gen()),
+    );
+    let path = Path::new(&env::var("OUT_DIR").unwrap()).join("comb_tables.rs");
+    fs::write(path, out).unwrap();
+}
@@ -0,0 +1,132 @@
+//! Labels of groups of constraints, for debugging.
+//!
+//! Gadgets open nested scopes with `annotate!`, e.g. `"scalar_mul"`, then
+//! `"bit 17"`, then `"doubling"`. Every constraint recorded while scopes are open
+//! is labelled with their path, `"scalar_mul/bit 17/doubling"`, which
+//! `RecordingCS::describe` and the synthesis spans of `instrument` report
+//! instead of a raw constraint index.
+//!
+//! Labels are only kept in debug builds: in release builds the scopes are
+//! no-ops and the labels are not even formatted.
+#[cfg(debug_assertions)]
+use std::cell::RefCell;
+
+#[cfg(debug_assertions)]
+thread_local! {
+    static PATH: RefCell<Vec<String>> = RefCell::new(Vec::new());
+}
+
+/// Opens a scope labelled with the given `format!` arguments, closed when the
+/// returned `Scope` is dropped:
+///
+/// ```ignore
+/// let _scope = annotate!("bit {}", i);
+/// ```
+#[macro_export]
+macro_rules! annotate {
+    ($($arg:tt)*) => {
+        $crate::annotate::Scope::enter(|| format!($($arg)*))
+    };
+}
+
+/// An open scope, closed when dropped.
+#[must_use]
+pub struct Scope(());
+
+impl Scope {
+    /// Opens a scope labelled `label()`.
+    #[cfg(debug_assertions)]
+    pub fn enter<F: FnOnce() -> String>(label: F) -> Scope {
+        PATH.with(|path| path.borrow_mut().push(label()));
+        Scope(())
+    }
+
+    /// Opens a scope labelled `label()`.
+    #[cfg(not(debug_assertions))]
+    pub fn enter<F: FnOnce() -> String>(_: F) -> Scope {
+        Scope(())
+    }
+}
+
+impl Drop for Scope {
+    #[cfg(debug_assertions)]
+    fn drop(&mut self) {
+        PATH.with(|path| path.borrow_mut().pop());
+    }
+
+    #[cfg(not(debug_assertions))]
+    fn drop(&mut self) {}
+}
+
+/// Path of the open scopes, `None` outside any scope and in release builds.
+#[cfg(debug_assertions)]
+pub fn current() -> Option<String> {
+    PATH.with(|path| {
+        let path = path.borrow();
+        if path.is_empty() {
+            None
+        } else {
+            Some(path.join("/"))
+        }
+    })
+}
+
+/// Path of the open scopes, `None` outside any scope and in release builds.
+#[cfg(not(debug_assertions))]
+pub fn current() -> Option<String> {
+    None
+}
+
+mod test {
+    use super::*;
+    use crate::gadgets::point::edwards_point::SonnyEdwardsPointGadget;
+    use crate::recording::RecordingCS;
+    use bulletproofs::r1cs::{ConstraintSystem, LinearCombination as LC};
+    use curve25519_dalek::scalar::Scalar;
+
+    #[test]
+    #[cfg(debug_assertions)]
+    fn nested_scopes() {
+        assert_eq!(current(), None);
+        let _outer = annotate!("scalar_mul");
+        for i in 0..2 {
+            let _bit = annotate!("bit {}", i);
+            let _doubling = annotate!("doubling");
+            assert_eq!(current(), Some(format!("scalar_mul/bit {}/doubling", i)));
+        }
+        assert_eq!(current(), Some("scalar_mul".to_string()));
+    }
+
+    #[test]
+    #[cfg(debug_assertions)]
+    fn labelled_constraints() {
+        let mut cs = RecordingCS::new(b"Annotate");
+        let x = cs.commit(Some(Scalar::one()));
+        cs.constrain(LC::from(x) - Scalar::one());
+        {
+            let _scope = annotate!("check {}", 2);
+            cs.constrain(LC::from(x) - Scalar::from(2u64));
+        }
+        assert_eq!(cs.label(0), None);
+        assert_eq!(cs.first_unsatisfied(), Some(1));
+        assert_eq!(cs.label(1), Some("check 2"));
+        assert!(cs.describe(1).starts_with("constraint 1 (check 2): "));
+    }
+
+    #[test]
+    #[cfg(debug_assertions)]
+    fn scalar_mul_labels() {
+        let mut cs = RecordingCS::new(b"Annotate");
+        let bits: Vec<_> = (0..2).map(|_| cs.commit(Some(Scalar::one()))).collect();
+        let _ = SonnyEdwardsPointGadget::scalar_mul(
+            SonnyEdwardsPointGadget::basepoint(),
+            None,
+            bits,
+            None,
+            &mut cs,
+        );
+        let labels: Vec<_> = (0..cs.constraints().len()).map(|i| cs.label(i)).collect();
+        assert!(labels.contains(&Some("scalar_mul/bit 1/doubling")));
+        assert_eq!(labels.last(), Some(&Some("scalar_mul/bit 0/addition")));
+    }
+}
@@ -0,0 +1,98 @@
+//! Adapter that replays the circuits of this crate on top of an `ark-relations`
+//! `ConstraintSystem`, so they can be proven with other arkworks backends.
+//!
+//! The gadgets are synthesized once with a `RecordingCS` (with assignments on the
+//! prover side) and the recorded R1CS is re-emitted as arkworks constraints: every
+//! multiplier becomes `a_L * a_R = a_O` and every linear constraint `lc * 1 = 0`.
+//!
+//! Note that the gadgets are arithmetized over the scalar field of Curve25519 (the
+//! base field of the Sonny curve), so the target field `F` must have that same
+//! modulus for the retargeted circuit to mean the same thing. This is checked when
+//! the constraints are generated.
+use crate::recording::{lc_terms, RecordingCS};
+use ark_ff::{BigInteger, PrimeField};
+use ark_relations::r1cs::{
+    ConstraintSynthesizer, ConstraintSystemRef, LinearCombination as ArkLC, SynthesisError,
+    Variable as ArkVariable,
+};
+use bulletproofs::r1cs::{ConstraintSystem, LinearCombination as LC, Variable};
+use curve25519_dalek::scalar::Scalar;
+
+/// A circuit of this crate, recorded and ready to be replayed on arkworks.
+pub struct ArkCircuit {
+    cs: RecordingCS,
+}
+
+impl ArkCircuit {
+    /// Synthesizes the circuit with the given committed assignments (`None` for
+    /// every value when only the constraints are needed, e.g. for a setup).
+    pub fn new<F>(committed: &[Option<Scalar>], circuit: F) -> ArkCircuit
+    where
+        F: FnOnce(&mut dyn ConstraintSystem, Vec<Variable>),
+    {
+        let mut cs = RecordingCS::new(b"ArkCircuit");
+        let vars = committed.iter().map(|v| cs.commit(*v)).collect();
+        circuit(&mut cs, vars);
+        ArkCircuit { cs }
+    }
+}
+
+fn to_field<F: PrimeField>(s: &Scalar) -> F {
+    F::from_le_bytes_mod_order(s.as_bytes())
+}
+
+fn assignment<F: PrimeField>(value: Option<Scalar>) -> Result<F, SynthesisError> {
+    value
+        .map(|v| to_field(&v))
+        .ok_or(SynthesisError::AssignmentMissing)
+}
+
+impl<F: PrimeField> ConstraintSynthesizer<F> for ArkCircuit {
+    fn generate_constraints(self, cs: ConstraintSystemRef<F>) -> Result<(), SynthesisError> {
+        // Compare `modulus - 1` since `Scalar` cannot hold `l` itself. The lowest
+        // byte of `l` is non-zero, so subtracting one never borrows.
+        let modulus = (Scalar::zero() - Scalar::one()).to_bytes();
+        let mut field_modulus = F::Params::MODULUS.to_bytes_le();
+        field_modulus.resize(32, 0);
+        field_modulus[0] = field_modulus[0].wrapping_sub(1);
+        if field_modulus[..] != modulus[..] {
+            return Err(SynthesisError::Unsatisfiable);
+        }
+
+        let mut committed = Vec::new();
+        for v in self.cs.committed_assignments() {
+            committed.push(cs.new_witness_variable(|| assignment(*v))?);
+        }
+        let mut multipliers = Vec::new();
+        for (l, r, o) in self.cs.multiplier_assignments() {
+            let l_var = cs.new_witness_variable(|| assignment(*l))?;
+            let r_var = cs.new_witness_variable(|| assignment(*r))?;
+            let o_var = cs.new_witness_variable(|| assignment(*o))?;
+            cs.enforce_constraint(
+                ArkLC::from(l_var),
+                ArkLC::from(r_var),
+                ArkLC::from(o_var),
+            )?;
+            multipliers.push((l_var, r_var, o_var));
+        }
+
+        let to_ark_lc = |lc: &LC| -> ArkLC<F> {
+            lc_terms(lc)
+                .iter()
+                .fold(ArkLC::zero(), |acc, (var, coeff)| {
+                    let var = match var {
+                        Variable::Committed(i) => committed[*i],
+                        Variable::MultiplierLeft(i) => multipliers[*i].0,
+                        Variable::MultiplierRight(i) => multipliers[*i].1,
+                        Variable::MultiplierOutput(i) => multipliers[*i].2,
+                        Variable::One() => ArkVariable::One,
+                    };
+                    acc + (to_field::<F>(coeff), var)
+                })
+        };
+        for lc in self.cs.constraints() {
+            cs.enforce_constraint(to_ark_lc(lc), ArkLC::from(ArkVariable::One), ArkLC::zero())?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,12 @@
+//! R1CS backend.
+//!
+//! The crate is built against the dusk-network fork of `bulletproofs` and
+//! `curve25519-dalek` 2.
+
+/// Verifies an `R1CSProof` consuming the `Verifier`.
+/// The dusk fork requires an RNG to batch the verification equation.
+macro_rules! verify_proof {
+    ($verifier:expr, $proof:expr, $pc_gens:expr, $bp_gens:expr) => {
+        $verifier.verify($proof, $pc_gens, $bp_gens, &mut rand::thread_rng())
+    };
+}
@@ -0,0 +1,161 @@
+//! Proving several instances of the same circuit with a single proof.
+//!
+//! The instances are laid out side by side in one constraint system: the
+//! witnesses of every instance are committed first, then the circuit is
+//! synthesized once per instance over its own variables and its own public
+//! values (its slot). A proof costs `O(log n)` group elements plus a fixed
+//! overhead, so batching `K` small instances amortizes that overhead.
+//!
+//! The transcript binds the number of instances and the `statement_hash` of
+//! every slot, in order, so a batch proof only verifies against the exact
+//! sequence of public values it was generated for.
+use crate::errors::GadgetError;
+use crate::session;
+use crate::statement::Statement;
+use bulletproofs::r1cs::{ConstraintSystem, R1CSError, R1CSProof, Variable, Verifier};
+#[cfg(feature = "prover")]
+use bulletproofs::r1cs::Prover;
+use bulletproofs::{BulletproofGens, PedersenGens};
+use curve25519_dalek::{ristretto::CompressedRistretto, scalar::Scalar};
+use merlin::Transcript;
+
+/// A proof of several instances, with the commitments of every instance.
+#[derive(Clone, Debug)]
+pub struct BatchProof {
+    pub commitments: Vec<Vec<CompressedRistretto>>,
+    pub proof: R1CSProof,
+}
+
+fn batch_transcript<P: Statement>(publics: &[&P]) -> Transcript {
+    let mut transcript = session::transcript(P::LABEL);
+    transcript.append_message(b"dom-sep", b"batch");
+    transcript.append_u64(b"instances", publics.len() as u64);
+    for public in publics {
+        transcript.append_message(b"statement-hash", &public.statement_hash());
+    }
+    transcript
+}
+
+/// Proves every `(public values, witness)` instance of `circuit`, which
+/// receives the public values of the instance, its committed variables and its
+/// witness (`None` on the verifier side).
+#[cfg(feature = "prover")]
+pub fn prove_batch<P, F>(
+    pc_gens: &PedersenGens,
+    bp_gens: &BulletproofGens,
+    instances: &[(P, Vec<Scalar>)],
+    circuit: F,
+) -> Result<BatchProof, R1CSError>
+where
+    P: Statement,
+    F: Fn(&mut dyn ConstraintSystem, &P, Vec<Variable>, Option<&[Scalar]>),
+{
+    let publics: Vec<&P> = instances.iter().map(|(p, _)| p).collect();
+    let mut transcript = batch_transcript(&publics);
+    let mut prover = Prover::new(pc_gens, &mut transcript);
+    let mut rng = rand::thread_rng();
+    let mut commitments = Vec::with_capacity(instances.len());
+    let mut vars = Vec::with_capacity(instances.len());
+    for (_, witness) in instances {
+        let (comms, instance_vars): (Vec<CompressedRistretto>, Vec<Variable>) = witness
+            .iter()
+            .map(|v| prover.commit(*v, Scalar::random(&mut rng)))
+            .unzip();
+        commitments.push(comms);
+        vars.push(instance_vars);
+    }
+    for ((public, witness), vars) in instances.iter().zip(vars) {
+        circuit(&mut prover, public, vars, Some(&witness[..]));
+    }
+    let proof = prover.prove(bp_gens)?;
+    Ok(BatchProof { commitments, proof })
+}
+
+/// Verifies `batch` against the public values of every instance, in order.
+/// Malformed commitments are rejected before any transcript work, with their
+/// index in the concatenation of the commitments of all instances.
+pub fn verify_batch<P, F>(
+    pc_gens: &PedersenGens,
+    bp_gens: &BulletproofGens,
+    publics: &[P],
+    batch: &BatchProof,
+    circuit: F,
+) -> Result<(), R1CSError>
+where
+    P: Statement,
+    F: Fn(&mut dyn ConstraintSystem, &P, Vec<Variable>, Option<&[Scalar]>),
+{
+    if batch.commitments.len() != publics.len() {
+        return Err(GadgetError::InvalidCommitmentsLength {
+            expected: publics.len(),
+            found: batch.commitments.len(),
+        }
+        .into());
+    }
+    session::check_commitments(batch.commitments.iter().flatten())?;
+    let mut transcript = batch_transcript(&publics.iter().collect::<Vec<_>>());
+    let mut verifier = Verifier::new(&mut transcript);
+    let vars: Vec<Vec<Variable>> = batch
+        .commitments
+        .iter()
+        .map(|comms| comms.iter().map(|V| verifier.commit(*V)).collect())
+        .collect();
+    for (public, vars) in publics.iter().zip(vars) {
+        circuit(&mut verifier, public, vars, None);
+    }
+    verify_proof!(verifier, &batch.proof, pc_gens, bp_gens)
+}
+
+#[cfg(feature = "prover")]
+mod test {
+    use super::*;
+    use crate::statement::encode_u64;
+    use bulletproofs::r1cs::LinearCombination as LC;
+
+    /// Public product of the two committed values of an instance.
+    struct Product(u64);
+
+    impl Statement for Product {
+        const LABEL: &'static [u8] = b"Product";
+
+        fn encode(&self, out: &mut Vec<u8>) {
+            encode_u64(out, self.0);
+        }
+    }
+
+    fn product(
+        cs: &mut dyn ConstraintSystem,
+        p: &Product,
+        vars: Vec<Variable>,
+        _: Option<&[Scalar]>,
+    ) {
+        let (_, _, o) = cs.multiply(vars[0].into(), vars[1].into());
+        cs.constrain(LC::from(o) - Scalar::from(p.0));
+    }
+
+    fn instance(a: u64, b: u64, p: u64) -> (Product, Vec<Scalar>) {
+        (Product(p), vec![Scalar::from(a), Scalar::from(b)])
+    }
+
+    #[test]
+    fn batch() {
+        let pc_gens = PedersenGens::default();
+        let bp_gens = BulletproofGens::new(8, 1);
+        let instances = vec![instance(2, 3, 6), instance(4, 5, 20), instance(1, 7, 7)];
+        let batch = prove_batch(&pc_gens, &bp_gens, &instances, product).unwrap();
+        assert_eq!(batch.commitments.len(), 3);
+        let publics = [Product(6), Product(20), Product(7)];
+        assert!(verify_batch(&pc_gens, &bp_gens, &publics, &batch, product).is_ok());
+
+        // The slots are bound in order.
+        let swapped = [Product(20), Product(6), Product(7)];
+        assert!(verify_batch(&pc_gens, &bp_gens, &swapped, &batch, product).is_err());
+        assert!(verify_batch(&pc_gens, &bp_gens, &publics[..2], &batch, product).is_err());
+
+        // A single invalid instance invalidates the batch.
+        let instances = vec![instance(2, 3, 6), instance(4, 5, 21)];
+        let batch = prove_batch(&pc_gens, &bp_gens, &instances, product).unwrap();
+        let publics = [Product(6), Product(21)];
+        assert!(verify_batch(&pc_gens, &bp_gens, &publics, &batch, product).is_err());
+    }
+}
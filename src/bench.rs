@@ -0,0 +1,162 @@
+//! Programmatic benchmarks of the shipped gadgets.
+//!
+//! Unlike a Criterion bench, the numbers are returned in a `BenchReport` so they
+//! can be consumed by other tooling (e.g. capacity planning).
+use crate::gadgets::point::edwards_point::SonnyEdwardsPointGadget;
+use crate::gadgets::point::ristretto_point::SonnyRistrettoPointGadget;
+use crate::gadgets::sk_knowledge::sk_know_gadget::sk_knowledge_gadget;
+use crate::helpers::{PointCoords, PointLCs};
+use crate::session;
+use bulletproofs::r1cs::{ConstraintSystem, Prover, R1CSError, Variable, Verifier};
+use bulletproofs::{BulletproofGens, PedersenGens};
+use curve25519_dalek::{ristretto::CompressedRistretto, scalar::Scalar};
+use std::time::{Duration, Instant};
+use zerocaf::edwards::EdwardsPoint as SonnyEdwardsPoint;
+use zerocaf::scalar::Scalar as SonnyScalar;
+
+/// Measurements of a gadget synthesized at a given size.
+#[derive(Clone, Debug)]
+pub struct BenchEntry {
+    pub gadget: &'static str,
+    /// Meaning depends on the gadget: number of scalar bits or number of
+    /// chained operations.
+    pub size: usize,
+    pub multipliers: usize,
+    pub constraints: usize,
+    /// Capacity of the `BulletproofGens` needed to prove the circuit.
+    pub gens_capacity: usize,
+    pub proof_size: usize,
+    pub prove_time: Duration,
+    pub verify_time: Duration,
+}
+
+/// Measurements of every shipped gadget.
+#[derive(Clone, Debug, Default)]
+pub struct BenchReport {
+    pub entries: Vec<BenchEntry>,
+}
+
+/// Proves and verifies `circuit` over the committed `witness`, timing both sides.
+/// Generators are created (outside of the timings) with the smallest capacity that fits.
+pub fn measure<F>(
+    gadget: &'static str,
+    size: usize,
+    witness: &[Scalar],
+    circuit: F,
+) -> Result<BenchEntry, R1CSError>
+where
+    F: Fn(&mut dyn ConstraintSystem, Vec<Variable>, Option<&[Scalar]>),
+{
+    let pc_gens = PedersenGens::default();
+
+    let mut transcript = session::transcript(b"Bench");
+    let mut prover = Prover::new(&pc_gens, &mut transcript);
+    let start = Instant::now();
+    let (commitments, vars): (Vec<CompressedRistretto>, Vec<Variable>) = witness
+        .iter()
+        .map(|v| prover.commit(*v, Scalar::random(&mut rand::thread_rng())))
+        .unzip();
+    circuit(&mut prover, vars, Some(witness));
+    let mut prove_time = start.elapsed();
+
+    let metrics = prover.metrics();
+    let gens_capacity = metrics.multipliers.next_power_of_two();
+    let bp_gens = BulletproofGens::new(gens_capacity, 1);
+
+    let start = Instant::now();
+    let proof = prover.prove(&bp_gens)?;
+    prove_time += start.elapsed();
+
+    let mut transcript = session::transcript(b"Bench");
+    let start = Instant::now();
+    let mut verifier = Verifier::new(&mut transcript);
+    let vars: Vec<Variable> = commitments.iter().map(|V| verifier.commit(*V)).collect();
+    circuit(&mut verifier, vars, None);
+    verify_proof!(verifier, &proof, &pc_gens, &bp_gens)?;
+    let verify_time = start.elapsed();
+
+    Ok(BenchEntry {
+        gadget,
+        size,
+        multipliers: metrics.multipliers,
+        constraints: metrics.constraints,
+        gens_capacity,
+        proof_size: proof.to_bytes().len(),
+        prove_time,
+        verify_time,
+    })
+}
+
+/// Benchmarks `size` chained additions of committed points.
+pub fn edwards_add(size: usize) -> Result<BenchEntry, R1CSError> {
+    let p = SonnyEdwardsPoint::new_random_point(&mut rand::thread_rng());
+    measure("edwards_add", size, &p.coords(), |cs, vars, _| {
+        let p: SonnyEdwardsPointGadget = PointLCs::from_vars(&vars)
+            .expect("the witness is one point")
+            .into();
+        let mut acc = p.clone();
+        for _ in 0..size {
+            acc = acc.add(&p, cs);
+        }
+    })
+}
+
+/// Benchmarks `size` chained doublings of a committed point.
+pub fn edwards_double(size: usize) -> Result<BenchEntry, R1CSError> {
+    let p = SonnyEdwardsPoint::new_random_point(&mut rand::thread_rng());
+    measure("edwards_double", size, &p.coords(), |cs, vars, _| {
+        let mut acc: SonnyEdwardsPointGadget = PointLCs::from_vars(&vars)
+            .expect("the witness is one point")
+            .into();
+        for _ in 0..size {
+            acc = acc.double(cs);
+        }
+    })
+}
+
+/// Benchmarks the multiplication of a public point by a committed scalar of `size` bits.
+pub fn edwards_scalar_mul(size: usize) -> Result<BenchEntry, R1CSError> {
+    let p = SonnyEdwardsPoint::new_random_point(&mut rand::thread_rng());
+    let k = SonnyScalar::random(&mut rand::thread_rng());
+    let bits: Vec<Scalar> = k.into_bits()[..size]
+        .iter()
+        .map(|bit| Scalar::from(*bit))
+        .collect();
+    measure("edwards_scalar_mul", size, &bits, |cs, vars, _| {
+        SonnyEdwardsPointGadget::scalar_mul(
+            SonnyEdwardsPointGadget::from_point(&p),
+            None,
+            vars,
+            None,
+            cs,
+        );
+    })
+}
+
+/// Benchmarks the proof of knowledge of a secret key of `size` bits.
+pub fn sk_knowledge(size: usize) -> Result<BenchEntry, R1CSError> {
+    let basep = zerocaf::constants::RISTRETTO_BASEPOINT;
+    let sk = SonnyScalar::random(&mut rand::thread_rng());
+    let bits: Vec<u8> = sk.into_bits()[..size].to_vec();
+    let pk = crate::gadgets::point::witness::ristretto::scalar_mul(&basep, &bits);
+    let bits: Vec<Scalar> = bits.iter().map(|bit| Scalar::from(*bit)).collect();
+    measure("sk_knowledge", size, &bits, |cs, vars, _| {
+        let basep_gadget = SonnyRistrettoPointGadget::from_point(basep, cs);
+        let pk_gadget = SonnyRistrettoPointGadget::from_point(pk, cs);
+        sk_knowledge_gadget(cs, basep_gadget, pk_gadget, vars);
+    })
+}
+
+/// Benchmarks every shipped gadget at several sizes.
+pub fn run() -> Result<BenchReport, R1CSError> {
+    let mut report = BenchReport::default();
+    for size in &[1, 16, 64] {
+        report.entries.push(edwards_add(*size)?);
+        report.entries.push(edwards_double(*size)?);
+    }
+    for size in &[8, 64, 256] {
+        report.entries.push(edwards_scalar_mul(*size)?);
+        report.entries.push(sk_knowledge(*size)?);
+    }
+    Ok(report)
+}
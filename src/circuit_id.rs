@@ -0,0 +1,223 @@
+//! Identifiers of circuits derived from their synthesized structure.
+//!
+//! The `CircuitId` of a circuit hashes its label, the version of the gadget set
+//! and every constraint it synthesizes (see `export::R1CSExport`), so any change
+//! in the sequence of gadgets, in the number of multipliers or in a single
+//! coefficient gives another id. Coefficients derived from transcript challenges
+//! are taken as drawn during a synthesis without commitments, which is
+//! deterministic.
+//!
+//! A `ProofBundle` carries the id of the circuit it was proven for, which is
+//! also bound into its transcript. Verifiers compare it with the id they expect
+//! before synthesizing anything.
+use crate::errors::GadgetError;
+use crate::export::R1CSExport;
+use crate::recording::var_name;
+use crate::session::{self, GADGETS_VERSION};
+use bulletproofs::r1cs::{ConstraintSystem, R1CSError, R1CSProof, Variable, Verifier};
+#[cfg(feature = "prover")]
+use bulletproofs::r1cs::Prover;
+use bulletproofs::{BulletproofGens, PedersenGens};
+use curve25519_dalek::{ristretto::CompressedRistretto, scalar::Scalar};
+use merlin::Transcript;
+#[cfg(feature = "prover")]
+use rand::{CryptoRng, Rng};
+
+/// Hash of the structure of a circuit.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct CircuitId(pub [u8; 32]);
+
+impl CircuitId {
+    /// Hashes the structure of an exported circuit.
+    pub fn from_export(label: &'static [u8], export: &R1CSExport) -> CircuitId {
+        let mut transcript = Transcript::new(b"bulletproofs_gadgets circuit-id");
+        transcript.append_message(b"label", label);
+        transcript.append_u64(b"gadgets-version", GADGETS_VERSION);
+        transcript.append_u64(b"committed", export.committed as u64);
+        transcript.append_u64(b"multipliers", export.multipliers as u64);
+        transcript.append_u64(b"constraints", export.constraints.len() as u64);
+        for constraint in &export.constraints {
+            transcript.append_u64(b"terms", constraint.len() as u64);
+            for (var, coeff) in constraint {
+                transcript.append_message(b"var", var_name(var).as_bytes());
+                transcript.append_message(b"coeff", coeff.as_bytes());
+            }
+        }
+        let mut id = [0u8; 32];
+        transcript.challenge_bytes(b"circuit-id", &mut id);
+        CircuitId(id)
+    }
+
+    pub fn append_to_transcript(&self, transcript: &mut Transcript) {
+        transcript.append_message(b"circuit-id", &self.0);
+    }
+}
+
+/// Synthesizes `circuit` over `committed` high-level variables and returns its id.
+pub fn circuit_id<F>(label: &'static [u8], committed: usize, circuit: F) -> CircuitId
+where
+    F: FnOnce(&mut dyn ConstraintSystem, Vec<Variable>),
+{
+    CircuitId::from_export(label, &R1CSExport::from_circuit(committed, circuit))
+}
+
+/// Transcript of a proof of the circuit `id`.
+fn transcript(label: &'static [u8], id: &CircuitId) -> Transcript {
+    let mut transcript = session::transcript(label);
+    id.append_to_transcript(&mut transcript);
+    transcript
+}
+
+/// A proof along with the commitments it refers to and the id of its circuit.
+#[derive(Clone, Debug)]
+pub struct ProofBundle {
+    pub circuit_id: CircuitId,
+    pub commitments: Vec<CompressedRistretto>,
+    pub proof: R1CSProof,
+}
+
+impl ProofBundle {
+    /// Commits to `witness` and proves `circuit`, which receives the committed
+    /// variables and the witness (`None` on the verifier side).
+    #[cfg(feature = "prover")]
+    pub fn prove<F>(
+        pc_gens: &PedersenGens,
+        bp_gens: &BulletproofGens,
+        label: &'static [u8],
+        witness: &[Scalar],
+        circuit: F,
+    ) -> Result<ProofBundle, R1CSError>
+    where
+        F: Fn(&mut dyn ConstraintSystem, Vec<Variable>, Option<&[Scalar]>),
+    {
+        ProofBundle::prove_with_rng(
+            pc_gens,
+            bp_gens,
+            label,
+            witness,
+            circuit,
+            &mut rand::thread_rng(),
+        )
+    }
+
+    /// Same as `prove`, drawing the blindings of the commitments from `rng`.
+    #[cfg(feature = "prover")]
+    pub fn prove_with_rng<F, R: Rng + CryptoRng>(
+        pc_gens: &PedersenGens,
+        bp_gens: &BulletproofGens,
+        label: &'static [u8],
+        witness: &[Scalar],
+        circuit: F,
+        rng: &mut R,
+    ) -> Result<ProofBundle, R1CSError>
+    where
+        F: Fn(&mut dyn ConstraintSystem, Vec<Variable>, Option<&[Scalar]>),
+    {
+        let circuit_id = circuit_id(label, witness.len(), |cs, vars| circuit(cs, vars, None));
+        let mut transcript = transcript(label, &circuit_id);
+        let mut prover = Prover::new(pc_gens, &mut transcript);
+        let (commitments, vars): (Vec<CompressedRistretto>, Vec<Variable>) = witness
+            .iter()
+            .map(|v| prover.commit(*v, Scalar::random(rng)))
+            .unzip();
+        circuit(&mut prover, vars, Some(witness));
+        let proof = prover.prove(bp_gens)?;
+        Ok(ProofBundle {
+            circuit_id,
+            commitments,
+            proof,
+        })
+    }
+
+    /// Verifies the bundle against the circuit `expected`, failing before
+    /// synthesizing `circuit` if the bundle was proven for another one or
+    /// holds malformed commitments.
+    pub fn verify<F>(
+        &self,
+        pc_gens: &PedersenGens,
+        bp_gens: &BulletproofGens,
+        label: &'static [u8],
+        expected: &CircuitId,
+        circuit: F,
+    ) -> Result<(), R1CSError>
+    where
+        F: FnOnce(&mut dyn ConstraintSystem, Vec<Variable>, Option<&[Scalar]>),
+    {
+        if self.circuit_id != *expected {
+            return Err(GadgetError::CircuitMismatch.into());
+        }
+        session::check_commitments(&self.commitments)?;
+        let mut transcript = transcript(label, expected);
+        let mut verifier = Verifier::new(&mut transcript);
+        let vars: Vec<Variable> = self
+            .commitments
+            .iter()
+            .map(|V| verifier.commit(*V))
+            .collect();
+        circuit(&mut verifier, vars, None);
+        verify_proof!(verifier, &self.proof, pc_gens, bp_gens)
+    }
+}
+
+#[cfg(feature = "prover")]
+mod test {
+    use super::*;
+    use bulletproofs::r1cs::LinearCombination as LC;
+
+    /// Constrains the committed values to multiply to `product`.
+    fn product(
+        product: u64,
+    ) -> impl Fn(&mut dyn ConstraintSystem, Vec<Variable>, Option<&[Scalar]>) {
+        move |cs, vars, _| {
+            let (_, _, o) = cs.multiply(vars[0].into(), vars[1].into());
+            cs.constrain(LC::from(o) - Scalar::from(product));
+        }
+    }
+
+    fn id(product_value: u64) -> CircuitId {
+        let circuit = product(product_value);
+        circuit_id(b"Product", 2, |cs, vars| circuit(cs, vars, None))
+    }
+
+    #[test]
+    fn ids_follow_structure() {
+        assert_eq!(id(6), id(6));
+        assert_ne!(id(6), id(8));
+        // Same constraints, under another label.
+        let circuit = product(6);
+        assert_ne!(
+            id(6),
+            circuit_id(b"Other", 2, |cs, vars| circuit(cs, vars, None))
+        );
+    }
+
+    #[test]
+    fn bundle() {
+        let pc_gens = PedersenGens::default();
+        let bp_gens = BulletproofGens::new(8, 1);
+        let witness = [Scalar::from(2u64), Scalar::from(3u64)];
+        let bundle =
+            ProofBundle::prove(&pc_gens, &bp_gens, b"Product", &witness, product(6)).unwrap();
+        assert_eq!(bundle.circuit_id, id(6));
+        assert!(bundle
+            .verify(&pc_gens, &bp_gens, b"Product", &id(6), product(6))
+            .is_ok());
+
+        // A proof for another circuit is rejected before verification.
+        let other = ProofBundle {
+            circuit_id: id(8),
+            ..bundle.clone()
+        };
+        assert_eq!(
+            other
+                .verify(&pc_gens, &bp_gens, b"Product", &id(6), product(6))
+                .unwrap_err()
+                .to_string(),
+            R1CSError::from(GadgetError::CircuitMismatch).to_string()
+        );
+        // Lying about the id does not help either, since it is in the transcript.
+        assert!(other
+            .verify(&pc_gens, &bp_gens, b"Product", &id(8), product(8))
+            .is_err());
+    }
+}
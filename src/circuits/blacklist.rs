@@ -0,0 +1,200 @@
+//! Proof that a hidden public key is not in a public blacklist.
+//!
+//! Keys are the Schnorr keys `PK = sk * B` of `gadgets::signature`, and a key
+//! is identified by the MiMC hash `H(PK.x, PK.y)` of its affine coordinates.
+//! The blacklist accumulates the identifiers of its keys into an
+//! `Accumulator`. A proof shows knowledge of a secret key `sk`, recomputing its
+//! key in-circuit, and that the identifier of the key is not in the blacklist
+//! (see `non_membership_gadget`).
+//!
+//! The key stays hidden under the commitments to its affine coordinates,
+//! which applications can link to the other proofs about the same key.
+use crate::errors::GadgetError;
+use crate::gadgets::accumulator::{non_membership_gadget, Accumulator};
+use crate::gadgets::boolean::bit_decomposition_gadget;
+use crate::gadgets::hash::{mimc_hash, mimc_hash_gadget};
+use crate::gadgets::point::edwards_point::SonnyEdwardsPointGadget;
+use crate::gadgets::signature::{basepoint, S_BITS};
+#[cfg(feature = "prover")]
+use crate::gadgets::signature::public_key;
+use crate::helpers::{affine_coords, sonny_scalar_bits, sonny_scalar_to_scalar};
+use crate::statement::{encode_scalar, encode_u64, Statement};
+use bulletproofs::r1cs::{
+    ConstraintSystem as CS, LinearCombination as LC, R1CSError, R1CSProof, Variable, Verifier,
+};
+#[cfg(feature = "prover")]
+use bulletproofs::r1cs::Prover;
+use bulletproofs::{BulletproofGens, PedersenGens};
+use curve25519_dalek::{ristretto::CompressedRistretto, scalar::Scalar};
+use zerocaf::edwards::EdwardsPoint as SonnyEdwardsPoint;
+use zerocaf::scalar::Scalar as SonnyScalar;
+
+/// Commitments of a proof: the secret key and the affine coordinates of its
+/// public key.
+const COMMITMENTS: usize = 3;
+
+/// Identifier of `pk` in a blacklist.
+pub fn key_id(pk: &SonnyEdwardsPoint) -> Scalar {
+    let (x, y) = affine_coords(pk);
+    mimc_hash(&[x, y])
+}
+
+/// A public set of blacklisted keys.
+#[derive(Clone, Debug)]
+pub struct Blacklist {
+    accumulator: Accumulator,
+}
+
+impl Blacklist {
+    pub fn new(keys: &[SonnyEdwardsPoint]) -> Blacklist {
+        Blacklist {
+            accumulator: Accumulator::new(keys.iter().map(key_id).collect()),
+        }
+    }
+
+    pub fn contains(&self, pk: &SonnyEdwardsPoint) -> bool {
+        self.accumulator.contains(&key_id(pk))
+    }
+
+    pub fn accumulator(&self) -> &Accumulator {
+        &self.accumulator
+    }
+}
+
+/// Public values of a proof: the blacklist, given by the coefficients of its
+/// accumulator.
+#[derive(Clone, Copy, Debug)]
+pub struct BlacklistStatement<'a> {
+    pub blacklist: &'a Blacklist,
+}
+
+impl<'a> Statement for BlacklistStatement<'a> {
+    const LABEL: &'static [u8] = b"Blacklist non-membership";
+
+    fn encode(&self, out: &mut Vec<u8>) {
+        let polynomial = self.blacklist.accumulator.polynomial();
+        encode_u64(out, polynomial.len() as u64);
+        for c in polynomial {
+            encode_scalar(out, c);
+        }
+    }
+}
+
+/// Proof that the key committed in `commitments` is not blacklisted.
+#[derive(Clone, Debug)]
+pub struct NotBlacklisted {
+    /// Commitments to the secret key and to the affine coordinates of the key.
+    pub commitments: Vec<CompressedRistretto>,
+    pub proof: R1CSProof,
+}
+
+/// Constrains the committed `vars` (see `NotBlacklisted::commitments`) to hold
+/// a secret key and the coordinates of its public key, which is not in
+/// `blacklist`. The prover provides the secret key.
+pub fn blacklist_gadget(
+    cs: &mut dyn CS,
+    blacklist: &Blacklist,
+    vars: Vec<Variable>,
+    sk: Option<&SonnyScalar>,
+) {
+    // PK = sk * B
+    let sk_bits =
+        bit_decomposition_gadget(cs, vars[0].into(), sk.map(sonny_scalar_to_scalar), S_BITS);
+    let sk_assign = sk.map(|sk| sonny_scalar_bits(sk, S_BITS));
+    let basep = basepoint();
+    let (pk, pk_assign) = SonnyEdwardsPointGadget::scalar_mul(
+        SonnyEdwardsPointGadget::from_point(&basep),
+        Some(basep),
+        sk_bits,
+        sk_assign.as_deref(),
+        cs,
+    );
+    let (x, y) = pk.to_affine(pk_assign, cs);
+    cs.constrain(LC::from(x) - vars[1]);
+    cs.constrain(LC::from(y) - vars[2]);
+
+    // H(PK.x, PK.y) is not blacklisted.
+    let id = mimc_hash_gadget(cs, &[x.into(), y.into()]);
+    non_membership_gadget(
+        cs,
+        id,
+        pk_assign.as_ref().map(key_id),
+        &blacklist.accumulator,
+    );
+}
+
+/// Proves that the public key of `sk` is not in `blacklist`, failing if it is.
+#[cfg(feature = "prover")]
+pub fn prove_not_blacklisted(
+    pc_gens: &PedersenGens,
+    bp_gens: &BulletproofGens,
+    sk: &SonnyScalar,
+    blacklist: &Blacklist,
+) -> Result<NotBlacklisted, R1CSError> {
+    let pk = public_key(sk);
+    if blacklist.contains(&pk) {
+        return Err(R1CSError::GadgetError {
+            description: "blacklisted key".to_string(),
+        });
+    }
+    let mut transcript = BlacklistStatement { blacklist }.transcript();
+    let mut prover = Prover::new(pc_gens, &mut transcript);
+
+    let (x, y) = affine_coords(&pk);
+    let (commitments, vars): (Vec<_>, Vec<_>) = [sonny_scalar_to_scalar(sk), x, y]
+        .iter()
+        .map(|v| prover.commit(*v, Scalar::random(&mut rand::thread_rng())))
+        .unzip();
+
+    blacklist_gadget(&mut prover, blacklist, vars, Some(sk));
+    let proof = prover.prove(bp_gens)?;
+    Ok(NotBlacklisted { commitments, proof })
+}
+
+/// Verifies that the key of `not_blacklisted` is not in `blacklist`.
+pub fn verify_not_blacklisted(
+    pc_gens: &PedersenGens,
+    bp_gens: &BulletproofGens,
+    blacklist: &Blacklist,
+    not_blacklisted: &NotBlacklisted,
+) -> Result<(), R1CSError> {
+    if not_blacklisted.commitments.len() != COMMITMENTS {
+        return Err(GadgetError::InvalidCommitmentsLength {
+            expected: COMMITMENTS,
+            found: not_blacklisted.commitments.len(),
+        }
+        .into());
+    }
+    let mut transcript = BlacklistStatement { blacklist }.transcript();
+    let mut verifier = Verifier::new(&mut transcript);
+    let vars = not_blacklisted
+        .commitments
+        .iter()
+        .map(|V| verifier.commit(*V))
+        .collect();
+    blacklist_gadget(&mut verifier, blacklist, vars, None);
+    verify_proof!(verifier, &not_blacklisted.proof, pc_gens, bp_gens)
+}
+
+#[cfg(feature = "prover")]
+mod test {
+    use super::*;
+
+    #[test]
+    fn blacklist() {
+        let pc_gens = PedersenGens::default();
+        let bp_gens = BulletproofGens::new(16384, 1);
+        let mut rng = rand::thread_rng();
+
+        let sks: Vec<SonnyScalar> = (0..4).map(|_| SonnyScalar::random(&mut rng)).collect();
+        let blacklist = Blacklist::new(&[public_key(&sks[0]), public_key(&sks[1])]);
+
+        let proof = prove_not_blacklisted(&pc_gens, &bp_gens, &sks[2], &blacklist).unwrap();
+        assert!(verify_not_blacklisted(&pc_gens, &bp_gens, &blacklist, &proof).is_ok());
+        assert!(prove_not_blacklisted(&pc_gens, &bp_gens, &sks[0], &blacklist).is_err());
+
+        // The proof is bound to the blacklist it was made for.
+        let updated = Blacklist::new(&[public_key(&sks[0]), public_key(&sks[3])]);
+        assert!(verify_not_blacklisted(&pc_gens, &bp_gens, &updated, &proof).is_err());
+    }
+}
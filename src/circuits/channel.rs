@@ -0,0 +1,357 @@
+//! Balance updates of a two-party payment channel.
+//!
+//! The balances of both parties are kept under Pedersen commitments. An update
+//! moves a hidden `amount` from the hidden payer to the other party, and its
+//! proof shows that:
+//! - the old balances add up to the public capacity of the channel, so the
+//!   update neither creates nor destroys funds;
+//! - `amount` and the new balance of the first party lie in `[0, capacity]`,
+//!   so the payer can not go negative nor sign away a "negative" amount;
+//! - the payer signed `(channel id, sequence, amount, payer)`.
+//!
+//! The sequence number is public so the verifier can reject replayed updates.
+use crate::errors::GadgetError;
+use crate::gadgets::boolean::bit_decomposition_gadget;
+use crate::gadgets::mux::mux_gadget;
+use crate::gadgets::point::edwards_point::SonnyEdwardsPointGadget;
+use crate::gadgets::range::range_gadget;
+use crate::gadgets::signature::{schnorr_verify_gadget, sign, Signature, SignatureGadget, S_BITS};
+use crate::gadgets::typed::Bit;
+use crate::helpers::{sonny_scalar_to_scalar, PointCoords, PointLCs};
+use crate::statement::{encode_commitment, encode_point, encode_scalar, encode_u64, Statement};
+use bulletproofs::r1cs::{
+    ConstraintSystem as CS, LinearCombination as LC, R1CSError, R1CSProof, Variable, Verifier,
+};
+#[cfg(feature = "prover")]
+use bulletproofs::r1cs::Prover;
+use bulletproofs::{BulletproofGens, PedersenGens};
+use curve25519_dalek::{ristretto::CompressedRistretto, scalar::Scalar};
+use rand::{CryptoRng, Rng};
+use zerocaf::edwards::EdwardsPoint as SonnyEdwardsPoint;
+use zerocaf::scalar::Scalar as SonnyScalar;
+
+/// Number of committed variables of an update besides the balances: the
+/// amount, the payer, `s` and the coordinates of `R`.
+pub const UPDATE_COMMITMENTS: usize = 3 + SonnyEdwardsPointGadget::COMMITMENTS;
+
+/// Public parameters of a channel.
+#[derive(Clone, Copy, Debug)]
+pub struct Channel {
+    pub id: Scalar,
+    /// Public keys of both parties.
+    pub pks: [SonnyEdwardsPoint; 2],
+    /// Total funds locked in the channel.
+    pub capacity: u64,
+}
+
+/// Balances of both parties, with the blindings of their commitments.
+#[derive(Clone, Debug)]
+pub struct Balances {
+    pub amounts: [u64; 2],
+    blindings: [Scalar; 2],
+}
+
+impl Balances {
+    pub fn new<R: Rng + CryptoRng>(amounts: [u64; 2], rng: &mut R) -> Balances {
+        Balances {
+            amounts,
+            blindings: [Scalar::random(rng), Scalar::random(rng)],
+        }
+    }
+
+    /// Pedersen commitments to both balances.
+    pub fn commitments(&self, pc_gens: &PedersenGens) -> [CompressedRistretto; 2] {
+        let commit = |i: usize| {
+            pc_gens
+                .commit(Scalar::from(self.amounts[i]), self.blindings[i])
+                .compress()
+        };
+        [commit(0), commit(1)]
+    }
+}
+
+/// Transfer of `amount` from party `payer` (0 or 1) to the other one.
+#[derive(Clone, Copy, Debug)]
+pub struct Transfer {
+    pub sequence: u64,
+    pub payer: usize,
+    pub amount: u64,
+}
+
+impl Transfer {
+    /// The message signed by the payer.
+    pub fn message(&self, channel: &Channel) -> Vec<Scalar> {
+        vec![
+            channel.id,
+            Scalar::from(self.sequence),
+            Scalar::from(self.amount),
+            Scalar::from(self.payer as u64),
+        ]
+    }
+
+    /// Signs the transfer with the secret key of the payer.
+    pub fn sign<R: Rng + CryptoRng>(
+        &self,
+        channel: &Channel,
+        sk: &SonnyScalar,
+        rng: &mut R,
+    ) -> Signature {
+        sign(sk, &self.message(channel), rng)
+    }
+}
+
+/// Public values of a balance update.
+#[derive(Clone, Copy, Debug)]
+pub struct UpdateStatement {
+    pub channel: Channel,
+    pub sequence: u64,
+    pub old: [CompressedRistretto; 2],
+    pub new: [CompressedRistretto; 2],
+}
+
+impl Statement for UpdateStatement {
+    const LABEL: &'static [u8] = b"Channel balance update";
+
+    fn encode(&self, out: &mut Vec<u8>) {
+        encode_scalar(out, &self.channel.id);
+        encode_point(out, &self.channel.pks[0]);
+        encode_point(out, &self.channel.pks[1]);
+        encode_u64(out, self.channel.capacity);
+        encode_u64(out, self.sequence);
+        for c in self.old.iter().chain(&self.new) {
+            encode_commitment(out, c);
+        }
+    }
+}
+
+/// Proof of a balance update.
+#[derive(Clone, Debug)]
+pub struct BalanceUpdate {
+    /// Commitments to the variables counted by `UPDATE_COMMITMENTS`, in order.
+    pub commitments: Vec<CompressedRistretto>,
+    pub proof: R1CSProof,
+}
+
+/// Builds the update circuit. `vars` holds the committed old and new balances
+/// followed by the variables counted by `UPDATE_COMMITMENTS`.
+fn balance_update_gadget(
+    cs: &mut dyn CS,
+    channel: &Channel,
+    sequence: u64,
+    vars: &[Variable],
+    assign: Option<(&Balances, &Transfer, &Signature)>,
+) -> Result<(), GadgetError> {
+    let lc = |i: usize| LC::from(vars[i]);
+    let (old_a, old_b, new_a, new_b) = (lc(0), lc(1), lc(2), lc(3));
+    let (amount, payer, s) = (lc(4), lc(5), lc(6));
+    let R = PointLCs::from_vars(&vars[7..])?.into();
+    let amount_assign = assign.map(|(_, t, _)| t.amount);
+    let payer_assign = assign.map(|(_, t, _)| t.payer);
+
+    // Conservation of funds.
+    cs.constrain(old_a.clone() + old_b.clone() - Scalar::from(channel.capacity));
+    range_gadget(cs, amount.clone(), amount_assign, 0, channel.capacity)?;
+    let payer_bit = Bit::constrain(cs, vars[5]);
+    // The first party pays `amount` if `payer = 0`, and gets it otherwise.
+    let delta = mux_gadget(cs, payer_bit, amount.clone(), -amount.clone());
+    cs.constrain(new_a.clone() - old_a + delta.clone());
+    cs.constrain(new_b - old_b - delta);
+    range_gadget(
+        cs,
+        new_a,
+        assign.map(|(old, t, _)| match t.payer {
+            0 => old.amounts[0] - t.amount,
+            _ => old.amounts[0] + t.amount,
+        }),
+        0,
+        channel.capacity,
+    )?;
+
+    // The signature of the payer, whose key is selected linearly by `payer`.
+    let a = channel.pks[0].coords();
+    let b = channel.pks[1].coords();
+    let select = |i: usize| LC::from(a[i]) + payer.clone() * (b[i] - a[i]);
+    let pk = SonnyEdwardsPointGadget {
+        X: select(0),
+        Y: select(1),
+        Z: select(2),
+        T: select(3),
+    };
+    let message = vec![
+        LC::from(channel.id),
+        LC::from(Scalar::from(sequence)),
+        amount,
+        payer,
+    ];
+    let message_assign = assign.map(|(_, t, _)| t.message(channel));
+    let s_bits = bit_decomposition_gadget(
+        cs,
+        s,
+        assign.map(|(_, _, sig)| sonny_scalar_to_scalar(&sig.s)),
+        S_BITS,
+    );
+    schnorr_verify_gadget(
+        cs,
+        &pk,
+        payer_assign.map(|p| channel.pks[p]),
+        &message,
+        message_assign.as_deref(),
+        SignatureGadget { R, s: s_bits },
+        assign.map(|(_, _, sig)| *sig),
+    );
+    Ok(())
+}
+
+/// Applies `transfer` to the `old` balances of `channel` and proves the update
+/// with the `signature` of the payer. Returns the new balances, committed with
+/// fresh blindings.
+#[cfg(feature = "prover")]
+pub fn prove_update<R: Rng + CryptoRng>(
+    pc_gens: &PedersenGens,
+    bp_gens: &BulletproofGens,
+    channel: &Channel,
+    old: &Balances,
+    transfer: &Transfer,
+    signature: &Signature,
+    rng: &mut R,
+) -> Result<(Balances, BalanceUpdate), R1CSError> {
+    let [a, b] = old.amounts;
+    if transfer.payer > 1 || old.amounts[transfer.payer] < transfer.amount {
+        return Err(R1CSError::GadgetError {
+            description: "invalid transfer".to_string(),
+        });
+    }
+    let new_amounts = match transfer.payer {
+        0 => [a - transfer.amount, b + transfer.amount],
+        _ => [a + transfer.amount, b - transfer.amount],
+    };
+    let new = Balances::new(new_amounts, rng);
+
+    let statement = UpdateStatement {
+        channel: *channel,
+        sequence: transfer.sequence,
+        old: old.commitments(pc_gens),
+        new: new.commitments(pc_gens),
+    };
+    let mut transcript = statement.transcript();
+    let mut prover = Prover::new(pc_gens, &mut transcript);
+    let balances = old
+        .amounts
+        .iter()
+        .zip(&old.blindings)
+        .chain(new.amounts.iter().zip(&new.blindings))
+        .map(|(v, r)| prover.commit(Scalar::from(*v), *r).1);
+    let mut vars: Vec<Variable> = balances.collect();
+
+    let mut witness = vec![
+        Scalar::from(transfer.amount),
+        Scalar::from(transfer.payer as u64),
+        sonny_scalar_to_scalar(&signature.s),
+    ];
+    witness.extend_from_slice(&signature.R.coords());
+    let (commitments, witness_vars): (Vec<_>, Vec<_>) = witness
+        .iter()
+        .map(|v| prover.commit(*v, Scalar::random(rng)))
+        .unzip();
+    vars.extend(witness_vars);
+
+    balance_update_gadget(
+        &mut prover,
+        channel,
+        transfer.sequence,
+        &vars,
+        Some((old, transfer, signature)),
+    )?;
+    let proof = prover.prove(bp_gens)?;
+    Ok((new, BalanceUpdate { commitments, proof }))
+}
+
+/// Verifies that the balances committed in `new` follow from those committed
+/// in `old` by the transfer number `sequence` of `channel`.
+pub fn verify_update(
+    pc_gens: &PedersenGens,
+    bp_gens: &BulletproofGens,
+    channel: &Channel,
+    sequence: u64,
+    old: &[CompressedRistretto; 2],
+    new: &[CompressedRistretto; 2],
+    update: &BalanceUpdate,
+) -> Result<(), R1CSError> {
+    if update.commitments.len() != UPDATE_COMMITMENTS {
+        return Err(GadgetError::InvalidCommitmentsLength {
+            expected: UPDATE_COMMITMENTS,
+            found: update.commitments.len(),
+        }
+        .into());
+    }
+    let statement = UpdateStatement {
+        channel: *channel,
+        sequence,
+        old: *old,
+        new: *new,
+    };
+    let mut transcript = statement.transcript();
+    let mut verifier = Verifier::new(&mut transcript);
+    let vars: Vec<Variable> = old
+        .iter()
+        .chain(new)
+        .chain(&update.commitments)
+        .map(|V| verifier.commit(*V))
+        .collect();
+    balance_update_gadget(&mut verifier, channel, sequence, &vars, None)?;
+    verify_proof!(verifier, &update.proof, pc_gens, bp_gens)
+}
+
+#[cfg(feature = "prover")]
+mod test {
+    use super::*;
+    use crate::gadgets::signature::public_key;
+
+    #[test]
+    fn balance_update() {
+        let pc_gens = PedersenGens::default();
+        let bp_gens = BulletproofGens::new(32768, 1);
+        let mut rng = rand::thread_rng();
+        let sks = [SonnyScalar::random(&mut rng), SonnyScalar::random(&mut rng)];
+        let channel = Channel {
+            id: Scalar::from(7u64),
+            pks: [public_key(&sks[0]), public_key(&sks[1])],
+            capacity: 100,
+        };
+        let old = Balances::new([60, 40], &mut rng);
+        let old_comms = old.commitments(&pc_gens);
+
+        let transfer = Transfer {
+            sequence: 1,
+            payer: 1,
+            amount: 15,
+        };
+        let signature = transfer.sign(&channel, &sks[1], &mut rng);
+        let (new, update) = prove_update(
+            &pc_gens, &bp_gens, &channel, &old, &transfer, &signature, &mut rng,
+        )
+        .unwrap();
+        assert_eq!(new.amounts, [75, 25]);
+        let new_comms = new.commitments(&pc_gens);
+        assert!(
+            verify_update(&pc_gens, &bp_gens, &channel, 1, &old_comms, &new_comms, &update).is_ok()
+        );
+        // Replayed under another sequence number.
+        assert!(
+            verify_update(&pc_gens, &bp_gens, &channel, 2, &old_comms, &new_comms, &update)
+                .is_err()
+        );
+
+        // Signed by the payee instead of the payer.
+        let signature = transfer.sign(&channel, &sks[0], &mut rng);
+        let (new, update) = prove_update(
+            &pc_gens, &bp_gens, &channel, &old, &transfer, &signature, &mut rng,
+        )
+        .unwrap();
+        let new_comms = new.commitments(&pc_gens);
+        assert!(
+            verify_update(&pc_gens, &bp_gens, &channel, 1, &old_comms, &new_comms, &update)
+                .is_err()
+        );
+    }
+}
@@ -0,0 +1,314 @@
+//! Anonymous credentials with selective disclosure.
+//!
+//! The issuer commits to the attributes of the holder with a Sonny Pedersen
+//! commitment `C` (see `gadgets::commitment`) and signs its affine coordinates
+//! (see `gadgets::signature`). A presentation proves knowledge of such a signed
+//! commitment while revealing only some of the attributes: the others stay
+//! hidden under the Pedersen commitments of the proof, optionally constrained
+//! to lie in a range (e.g. "age in [18, 120]"). Neither `C` nor the signature
+//! are revealed, so presentations of the same credential are unlinkable.
+use crate::errors::GadgetError;
+use crate::gadgets::boolean::bit_decomposition_gadget;
+use crate::gadgets::commitment::{opening_gadget, SonnyPedersenGens};
+use crate::gadgets::point::edwards_point::SonnyEdwardsPointGadget;
+use crate::gadgets::range::range_gadget;
+use crate::gadgets::signature::{
+    public_key, schnorr_verify_gadget, sign, Signature, SignatureGadget, S_BITS,
+};
+use crate::helpers::{self, affine_coords, sonny_scalar_bits, sonny_scalar_to_scalar, PointLCs};
+use crate::statement::{encode_point, encode_u64, Statement};
+use bulletproofs::r1cs::{
+    ConstraintSystem as CS, LinearCombination as LC, R1CSError, R1CSProof, Variable, Verifier,
+};
+#[cfg(feature = "prover")]
+use bulletproofs::r1cs::Prover;
+use bulletproofs::{BulletproofGens, PedersenGens};
+use curve25519_dalek::{ristretto::CompressedRistretto, scalar::Scalar};
+use rand::{CryptoRng, Rng};
+use zerocaf::edwards::EdwardsPoint as SonnyEdwardsPoint;
+use zerocaf::scalar::Scalar as SonnyScalar;
+
+/// Bits of every attribute.
+pub const ATTRIBUTE_BITS: usize = 64;
+
+/// Whether (and how) an attribute is disclosed in a presentation.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Disclosure {
+    /// The attribute is revealed with the given value.
+    Revealed(u64),
+    /// The attribute stays hidden.
+    Hidden,
+    /// The attribute stays hidden, but is proven to lie in `[min, max]`.
+    InRange { min: u64, max: u64 },
+}
+
+/// Public values of a presentation.
+#[derive(Clone, Debug)]
+pub struct PresentationStatement {
+    pub issuer_pk: SonnyEdwardsPoint,
+    pub policy: Vec<Disclosure>,
+}
+
+impl Statement for PresentationStatement {
+    const LABEL: &'static [u8] = b"Credential presentation";
+
+    fn encode(&self, out: &mut Vec<u8>) {
+        encode_point(out, &self.issuer_pk);
+        encode_u64(out, self.policy.len() as u64);
+        for disclosure in &self.policy {
+            match disclosure {
+                Disclosure::Revealed(v) => {
+                    out.push(0);
+                    encode_u64(out, *v);
+                }
+                Disclosure::Hidden => out.push(1),
+                Disclosure::InRange { min, max } => {
+                    out.push(2);
+                    encode_u64(out, *min);
+                    encode_u64(out, *max);
+                }
+            }
+        }
+    }
+}
+
+/// Issuer of credentials.
+pub struct Issuer {
+    sk: SonnyScalar,
+    pub pk: SonnyEdwardsPoint,
+}
+
+/// A credential held by its owner.
+#[derive(Clone, Debug)]
+pub struct Credential {
+    pub attributes: Vec<u64>,
+    blinding: SonnyScalar,
+    signature: Signature,
+}
+
+/// A presentation of a credential.
+#[derive(Clone, Debug)]
+pub struct Presentation {
+    pub commitments: Vec<CompressedRistretto>,
+    pub proof: R1CSProof,
+}
+
+fn attribute_bits(v: u64) -> Vec<u8> {
+    (0..ATTRIBUTE_BITS).map(|i| ((v >> i) & 1) as u8).collect()
+}
+
+impl Issuer {
+    pub fn new<R: Rng + CryptoRng>(rng: &mut R) -> Issuer {
+        let sk = SonnyScalar::random(rng);
+        Issuer {
+            sk,
+            pk: public_key(&sk),
+        }
+    }
+
+    /// Issues a credential over `attributes`.
+    pub fn issue<R: Rng + CryptoRng>(&self, attributes: Vec<u64>, rng: &mut R) -> Credential {
+        let gens = SonnyPedersenGens::new(attributes.len());
+        let blinding = SonnyScalar::random(rng);
+        let values: Vec<Vec<u8>> = attributes.iter().map(|a| attribute_bits(*a)).collect();
+        let C = gens
+            .commit(&values, &sonny_scalar_bits(&blinding, S_BITS))
+            .expect("one generator per attribute");
+        let (cx, cy) = affine_coords(&C);
+        Credential {
+            attributes,
+            blinding,
+            signature: sign(&self.sk, &[cx, cy], rng),
+        }
+    }
+}
+
+/// Number of committed variables of a presentation: the non-revealed
+/// attributes, the blinding, `s` and the coordinates of `R`.
+fn commitments_len(policy: &[Disclosure]) -> usize {
+    let hidden = policy
+        .iter()
+        .filter(|d| match d {
+            Disclosure::Revealed(_) => false,
+            _ => true,
+        })
+        .count();
+    hidden + 2 + SonnyEdwardsPointGadget::COMMITMENTS
+}
+
+/// Builds the presentation circuit. `vars` holds the committed variables in the
+/// order described by `commitments_len`.
+fn credential_gadget(
+    cs: &mut dyn CS,
+    issuer_pk: SonnyEdwardsPoint,
+    policy: &[Disclosure],
+    vars: Vec<Variable>,
+    credential: Option<&Credential>,
+) -> Result<(), GadgetError> {
+    let mut vars = vars.into_iter();
+
+    // Attributes, decomposed into bits to open the commitment.
+    let mut values = Vec::with_capacity(policy.len());
+    for (i, disclosure) in policy.iter().enumerate() {
+        let (lc, assign) = match disclosure {
+            Disclosure::Revealed(v) => (LC::from(Scalar::from(*v)), Some(*v)),
+            _ => (
+                LC::from(vars.next().unwrap()),
+                credential.map(|c| c.attributes[i]),
+            ),
+        };
+        if let Disclosure::InRange { min, max } = disclosure {
+            range_gadget(cs, lc.clone(), assign, *min, *max)?;
+        }
+        let bits = bit_decomposition_gadget(cs, lc, assign.map(Scalar::from), ATTRIBUTE_BITS);
+        values.push((bits, assign.map(attribute_bits)));
+    }
+    let blinding_bits = bit_decomposition_gadget(
+        cs,
+        vars.next().unwrap().into(),
+        credential.map(|c| sonny_scalar_to_scalar(&c.blinding)),
+        S_BITS,
+    );
+    let blinding_assign = credential.map(|c| sonny_scalar_bits(&c.blinding, S_BITS));
+
+    // C = sum(a_i * G_i) + r * H
+    let gens = SonnyPedersenGens::new(policy.len());
+    let (C, C_assign) = opening_gadget(cs, &gens, values, (blinding_bits, blinding_assign))?;
+    let (cx, cy) = C.to_affine(C_assign, cs);
+    let message_assign = C_assign.map(|C| {
+        let (cx, cy) = affine_coords(&C);
+        vec![cx, cy]
+    });
+
+    // The signature of the issuer on C.
+    let s_bits = bit_decomposition_gadget(
+        cs,
+        vars.next().unwrap().into(),
+        credential.map(|c| sonny_scalar_to_scalar(&c.signature.s)),
+        S_BITS,
+    );
+    let R_vars: Vec<Variable> = vars.collect();
+    let R: SonnyEdwardsPointGadget = PointLCs::from_vars(&R_vars)?.into();
+    schnorr_verify_gadget(
+        cs,
+        &SonnyEdwardsPointGadget::from_point(&issuer_pk),
+        Some(issuer_pk),
+        &[cx.into(), cy.into()],
+        message_assign.as_deref(),
+        SignatureGadget { R, s: s_bits },
+        credential.map(|c| c.signature),
+    );
+    Ok(())
+}
+
+/// Creates a presentation of `credential` disclosing its attributes as `policy` says.
+#[cfg(feature = "prover")]
+pub fn present(
+    pc_gens: &PedersenGens,
+    bp_gens: &BulletproofGens,
+    issuer_pk: SonnyEdwardsPoint,
+    credential: &Credential,
+    policy: &[Disclosure],
+) -> Result<Presentation, R1CSError> {
+    if policy.len() != credential.attributes.len() {
+        return Err(GadgetError::InvalidLCsLength {
+            expected: credential.attributes.len(),
+            found: policy.len(),
+        }
+        .into());
+    }
+    let statement = PresentationStatement {
+        issuer_pk,
+        policy: policy.to_vec(),
+    };
+    let mut transcript = statement.transcript();
+    let mut prover = Prover::new(pc_gens, &mut transcript);
+
+    let mut witness: Vec<Scalar> = policy
+        .iter()
+        .zip(&credential.attributes)
+        .filter(|(d, _)| match d {
+            Disclosure::Revealed(_) => false,
+            _ => true,
+        })
+        .map(|(_, a)| Scalar::from(*a))
+        .collect();
+    witness.push(sonny_scalar_to_scalar(&credential.blinding));
+    witness.push(sonny_scalar_to_scalar(&credential.signature.s));
+    witness.extend_from_slice(&helpers::PointCoords::coords(&credential.signature.R));
+    let (commitments, vars): (Vec<_>, Vec<_>) = witness
+        .iter()
+        .map(|v| prover.commit(*v, Scalar::random(&mut rand::thread_rng())))
+        .unzip();
+
+    credential_gadget(&mut prover, issuer_pk, policy, vars, Some(credential))?;
+    let proof = prover.prove(bp_gens)?;
+    Ok(Presentation { commitments, proof })
+}
+
+/// Verifies a presentation of a credential of `issuer_pk` under `policy`.
+pub fn verify(
+    pc_gens: &PedersenGens,
+    bp_gens: &BulletproofGens,
+    issuer_pk: SonnyEdwardsPoint,
+    policy: &[Disclosure],
+    presentation: &Presentation,
+) -> Result<(), R1CSError> {
+    if presentation.commitments.len() != commitments_len(policy) {
+        return Err(GadgetError::InvalidCommitmentsLength {
+            expected: commitments_len(policy),
+            found: presentation.commitments.len(),
+        }
+        .into());
+    }
+    let statement = PresentationStatement {
+        issuer_pk,
+        policy: policy.to_vec(),
+    };
+    let mut transcript = statement.transcript();
+    let mut verifier = Verifier::new(&mut transcript);
+    let vars = presentation
+        .commitments
+        .iter()
+        .map(|V| verifier.commit(*V))
+        .collect();
+    credential_gadget(&mut verifier, issuer_pk, policy, vars, None)?;
+    verify_proof!(verifier, &presentation.proof, pc_gens, bp_gens)
+}
+
+#[cfg(feature = "prover")]
+mod test {
+    use super::*;
+
+    #[test]
+    fn selective_disclosure() {
+        let pc_gens = PedersenGens::default();
+        let bp_gens = BulletproofGens::new(32768, 1);
+        let mut rng = rand::thread_rng();
+
+        let issuer = Issuer::new(&mut rng);
+        // (age, id, country)
+        let credential = issuer.issue(vec![42, 123_456, 34], &mut rng);
+        let policy = [
+            Disclosure::InRange { min: 18, max: 120 },
+            Disclosure::Hidden,
+            Disclosure::Revealed(34),
+        ];
+        let presentation = present(&pc_gens, &bp_gens, issuer.pk, &credential, &policy).unwrap();
+        assert!(verify(&pc_gens, &bp_gens, issuer.pk, &policy, &presentation).is_ok());
+
+        // Claiming another revealed value.
+        let wrong_policy = [policy[0], policy[1], Disclosure::Revealed(35)];
+        assert!(verify(&pc_gens, &bp_gens, issuer.pk, &wrong_policy, &presentation).is_err());
+
+        // Age out of range.
+        let minor = issuer.issue(vec![16, 123_456, 34], &mut rng);
+        let presentation = present(&pc_gens, &bp_gens, issuer.pk, &minor, &policy).unwrap();
+        assert!(verify(&pc_gens, &bp_gens, issuer.pk, &policy, &presentation).is_err());
+
+        // Credential of another issuer.
+        let other = Issuer::new(&mut rng);
+        let presentation = present(&pc_gens, &bp_gens, other.pk, &credential, &policy).unwrap();
+        assert!(verify(&pc_gens, &bp_gens, other.pk, &policy, &presentation).is_err());
+    }
+}
@@ -0,0 +1,67 @@
+//! Age check: the holder of an identity credential `(age, id)` proves to be
+//! an adult without revealing its age nor its id, with the presentations of
+//! `circuits::credential`.
+use crate::circuits::credential::{verify, Credential, Disclosure, Presentation};
+#[cfg(feature = "prover")]
+use crate::circuits::credential::present;
+use bulletproofs::r1cs::R1CSError;
+use bulletproofs::{BulletproofGens, PedersenGens};
+use zerocaf::edwards::EdwardsPoint as SonnyEdwardsPoint;
+
+/// Attributes `(age, id)` of an identity credential.
+pub const ATTRIBUTES: usize = 2;
+
+/// Minimum age of an adult.
+pub const ADULT_AGE: u64 = 18;
+
+/// Discloses only that the age is at least `ADULT_AGE`.
+pub const ADULT_POLICY: [Disclosure; ATTRIBUTES] = [
+    Disclosure::InRange {
+        min: ADULT_AGE,
+        max: 150,
+    },
+    Disclosure::Hidden,
+];
+
+/// Proves that the holder of `credential`, issued by `issuer_pk`, is an adult.
+#[cfg(feature = "prover")]
+pub fn prove_adult(
+    pc_gens: &PedersenGens,
+    bp_gens: &BulletproofGens,
+    issuer_pk: SonnyEdwardsPoint,
+    credential: &Credential,
+) -> Result<Presentation, R1CSError> {
+    present(pc_gens, bp_gens, issuer_pk, credential, &ADULT_POLICY)
+}
+
+/// Verifies that `presentation` shows an adult identity issued by `issuer_pk`.
+pub fn verify_adult(
+    pc_gens: &PedersenGens,
+    bp_gens: &BulletproofGens,
+    issuer_pk: SonnyEdwardsPoint,
+    presentation: &Presentation,
+) -> Result<(), R1CSError> {
+    verify(pc_gens, bp_gens, issuer_pk, &ADULT_POLICY, presentation)
+}
+
+#[cfg(feature = "prover")]
+mod test {
+    use super::*;
+    use crate::circuits::credential::Issuer;
+
+    #[test]
+    fn adult_roundtrip() {
+        let pc_gens = PedersenGens::default();
+        let bp_gens = BulletproofGens::new(32768, 1);
+        let mut rng = rand::thread_rng();
+        let issuer = Issuer::new(&mut rng);
+
+        let adult = issuer.issue(vec![30, 1001], &mut rng);
+        let presentation = prove_adult(&pc_gens, &bp_gens, issuer.pk, &adult).unwrap();
+        assert!(verify_adult(&pc_gens, &bp_gens, issuer.pk, &presentation).is_ok());
+
+        let minor = issuer.issue(vec![17, 1002], &mut rng);
+        let presentation = prove_adult(&pc_gens, &bp_gens, issuer.pk, &minor).unwrap();
+        assert!(verify_adult(&pc_gens, &bp_gens, issuer.pk, &presentation).is_err());
+    }
+}
@@ -0,0 +1,136 @@
+//! Set membership: a hidden value is a leaf of the `MerkleTree` of a public
+//! root, at a hidden position.
+use crate::errors::GadgetError;
+use crate::gadgets::merkle::merkle_root_gadget;
+#[cfg(feature = "prover")]
+use crate::gadgets::merkle::MerkleTree;
+use crate::gadgets::typed::Bit;
+use crate::statement::{encode_scalar, encode_u64, Statement};
+use bulletproofs::r1cs::{
+    ConstraintSystem as CS, LinearCombination as LC, R1CSError, R1CSProof, Variable, Verifier,
+};
+#[cfg(feature = "prover")]
+use bulletproofs::r1cs::Prover;
+use bulletproofs::{BulletproofGens, PedersenGens};
+use curve25519_dalek::{ristretto::CompressedRistretto, scalar::Scalar};
+
+/// Proof of membership.
+#[derive(Clone, Debug)]
+pub struct Membership {
+    /// Commitments to the value and its index, followed by the siblings.
+    pub commitments: Vec<CompressedRistretto>,
+    pub proof: R1CSProof,
+}
+
+/// Public values of a membership proof.
+#[derive(Clone, Copy, Debug)]
+pub struct MembershipStatement {
+    pub root: Scalar,
+    pub depth: usize,
+}
+
+impl Statement for MembershipStatement {
+    const LABEL: &'static [u8] = b"Example membership";
+
+    fn encode(&self, out: &mut Vec<u8>) {
+        encode_scalar(out, &self.root);
+        encode_u64(out, self.depth as u64);
+    }
+}
+
+/// Constrains the value of `vars[0]` to sit at the index `vars[1]` of the tree
+/// of root `root`, with the siblings `vars[2..]`.
+fn membership_gadget(
+    cs: &mut dyn CS,
+    statement: &MembershipStatement,
+    vars: &[Variable],
+    index_assign: Option<usize>,
+) -> Result<(), GadgetError> {
+    if vars.len() != statement.depth + 2 {
+        return Err(GadgetError::InvalidCommitmentsLength {
+            expected: statement.depth + 2,
+            found: vars.len(),
+        });
+    }
+    let index_bits = Bit::decompose(
+        cs,
+        vars[1].into(),
+        index_assign.map(|i| Scalar::from(i as u64)),
+        statement.depth,
+    );
+    let siblings: Vec<LC> = vars[2..].iter().map(|v| (*v).into()).collect();
+    let root = merkle_root_gadget(cs, vars[0].into(), &index_bits, &siblings)?;
+    cs.constrain(root - statement.root);
+    Ok(())
+}
+
+/// Proves that the value at `index` of `tree` is in it, hiding both.
+#[cfg(feature = "prover")]
+pub fn prove_membership(
+    pc_gens: &PedersenGens,
+    bp_gens: &BulletproofGens,
+    tree: &MerkleTree,
+    value: Scalar,
+    index: usize,
+) -> Result<Membership, R1CSError> {
+    let statement = MembershipStatement {
+        root: tree.root(),
+        depth: tree.depth(),
+    };
+    let mut transcript = statement.transcript();
+    let mut prover = Prover::new(pc_gens, &mut transcript);
+    let (commitments, vars): (Vec<_>, Vec<_>) = [value, Scalar::from(index as u64)]
+        .iter()
+        .cloned()
+        .chain(tree.path(index))
+        .map(|v| prover.commit(v, Scalar::random(&mut rand::thread_rng())))
+        .unzip();
+    membership_gadget(&mut prover, &statement, &vars, Some(index))?;
+    let proof = prover.prove(bp_gens)?;
+    Ok(Membership { commitments, proof })
+}
+
+/// Verifies that `membership` proves a value to be in the tree of `root` and
+/// depth `depth`.
+pub fn verify_membership(
+    pc_gens: &PedersenGens,
+    bp_gens: &BulletproofGens,
+    root: Scalar,
+    depth: usize,
+    membership: &Membership,
+) -> Result<(), R1CSError> {
+    let statement = MembershipStatement { root, depth };
+    let mut transcript = statement.transcript();
+    let mut verifier = Verifier::new(&mut transcript);
+    let vars: Vec<Variable> = membership
+        .commitments
+        .iter()
+        .map(|V| verifier.commit(*V))
+        .collect();
+    membership_gadget(&mut verifier, &statement, &vars, None)?;
+    verify_proof!(verifier, &membership.proof, pc_gens, bp_gens)
+}
+
+#[cfg(feature = "prover")]
+mod test {
+    use super::*;
+
+    #[test]
+    fn membership_roundtrip() {
+        let pc_gens = PedersenGens::default();
+        let bp_gens = BulletproofGens::new(4096, 1);
+        let values: Vec<Scalar> = (0..10u64).map(|v| Scalar::from(100 + v)).collect();
+        let tree = MerkleTree::new(&values, 4);
+
+        let membership = prove_membership(&pc_gens, &bp_gens, &tree, values[6], 6).unwrap();
+        assert!(verify_membership(&pc_gens, &bp_gens, tree.root(), 4, &membership).is_ok());
+
+        // Another tree.
+        let other = MerkleTree::new(&values[1..], 4);
+        assert!(verify_membership(&pc_gens, &bp_gens, other.root(), 4, &membership).is_err());
+
+        // A value which is not at the index.
+        let forged = prove_membership(&pc_gens, &bp_gens, &tree, values[5], 6).unwrap();
+        assert!(verify_membership(&pc_gens, &bp_gens, tree.root(), 4, &forged).is_err());
+    }
+}
@@ -0,0 +1,15 @@
+//! End-to-end example circuits, kept compiled and tested with the crate.
+//!
+//! Every example goes from native values to a verified proof using only the
+//! public API of the crate: a `Statement` for the transcript, the prover and
+//! verifier commitments, the gadgets, and the prove/verify wrappers around
+//! them. They double as integration tests: a change that makes the public
+//! API unusable for one of these circuits breaks their tests.
+//!
+//! - `payment`: a hidden note split into a hidden payment, its change and a
+//!   public fee.
+//! - `membership`: a hidden value in the Merkle tree of a public root.
+//! - `credential`: an age check on an anonymous credential.
+pub mod credential;
+pub mod membership;
+pub mod payment;
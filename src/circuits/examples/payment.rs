@@ -0,0 +1,120 @@
+//! A minimal payment: a hidden note of value `value` pays a hidden `payment`
+//! and a public `fee`, the rest going back as a hidden `change`.
+use crate::errors::GadgetError;
+use crate::gadgets::split::change_payment_gadget;
+use crate::gadgets::typed::AmountVar;
+use crate::statement::{encode_u64, Statement};
+use bulletproofs::r1cs::{
+    ConstraintSystem as CS, LinearCombination as LC, R1CSError, R1CSProof, Variable, Verifier,
+};
+#[cfg(feature = "prover")]
+use bulletproofs::r1cs::Prover;
+use bulletproofs::{BulletproofGens, PedersenGens};
+use curve25519_dalek::{ristretto::CompressedRistretto, scalar::Scalar};
+
+/// Proof of a payment.
+#[derive(Clone, Debug)]
+pub struct Payment {
+    /// Commitments to the note value, the payment and the change.
+    pub commitments: Vec<CompressedRistretto>,
+    pub proof: R1CSProof,
+}
+
+/// Public values of a payment.
+#[derive(Clone, Copy, Debug)]
+pub struct PaymentStatement {
+    pub fee: u64,
+}
+
+impl Statement for PaymentStatement {
+    const LABEL: &'static [u8] = b"Example payment";
+
+    fn encode(&self, out: &mut Vec<u8>) {
+        encode_u64(out, self.fee);
+    }
+}
+
+/// Constrains `value = payment + change + fee`, the payment and the change
+/// being amounts.
+fn payment_gadget(
+    cs: &mut dyn CS,
+    fee: u64,
+    vars: &[Variable],
+    assign: Option<(u64, u64)>,
+) -> Result<(), GadgetError> {
+    if vars.len() != 3 {
+        return Err(GadgetError::InvalidCommitmentsLength {
+            expected: 3,
+            found: vars.len(),
+        });
+    }
+    let payment = AmountVar::constrain(cs, vars[1], assign.map(|(p, _)| p));
+    let change = AmountVar::constrain(cs, vars[2], assign.map(|(_, c)| c));
+    let value = LC::from(vars[0]) - Scalar::from(fee);
+    change_payment_gadget(cs, value, payment, change);
+    Ok(())
+}
+
+/// Pays `payment` and `fee` out of a note of value `value`.
+#[cfg(feature = "prover")]
+pub fn prove_payment(
+    pc_gens: &PedersenGens,
+    bp_gens: &BulletproofGens,
+    value: u64,
+    payment: u64,
+    fee: u64,
+) -> Result<Payment, R1CSError> {
+    let change = value
+        .checked_sub(payment)
+        .and_then(|rest| rest.checked_sub(fee))
+        .ok_or_else(|| R1CSError::GadgetError {
+            description: "the note does not cover the payment and the fee".to_string(),
+        })?;
+    let statement = PaymentStatement { fee };
+    let mut transcript = statement.transcript();
+    let mut prover = Prover::new(pc_gens, &mut transcript);
+    let (commitments, vars): (Vec<_>, Vec<_>) = [value, payment, change]
+        .iter()
+        .map(|v| prover.commit(Scalar::from(*v), Scalar::random(&mut rand::thread_rng())))
+        .unzip();
+    payment_gadget(&mut prover, fee, &vars, Some((payment, change)))?;
+    let proof = prover.prove(bp_gens)?;
+    Ok(Payment { commitments, proof })
+}
+
+/// Verifies that `payment` spends its note into a payment, a change and `fee`.
+pub fn verify_payment(
+    pc_gens: &PedersenGens,
+    bp_gens: &BulletproofGens,
+    fee: u64,
+    payment: &Payment,
+) -> Result<(), R1CSError> {
+    let statement = PaymentStatement { fee };
+    let mut transcript = statement.transcript();
+    let mut verifier = Verifier::new(&mut transcript);
+    let vars: Vec<Variable> = payment
+        .commitments
+        .iter()
+        .map(|V| verifier.commit(*V))
+        .collect();
+    payment_gadget(&mut verifier, fee, &vars, None)?;
+    verify_proof!(verifier, &payment.proof, pc_gens, bp_gens)
+}
+
+#[cfg(feature = "prover")]
+mod test {
+    use super::*;
+
+    #[test]
+    fn payment_roundtrip() {
+        let pc_gens = PedersenGens::default();
+        let bp_gens = BulletproofGens::new(256, 1);
+
+        let payment = prove_payment(&pc_gens, &bp_gens, 100, 70, 2).unwrap();
+        assert!(verify_payment(&pc_gens, &bp_gens, 2, &payment).is_ok());
+        // The fee is bound to the proof.
+        assert!(verify_payment(&pc_gens, &bp_gens, 3, &payment).is_err());
+
+        assert!(prove_payment(&pc_gens, &bp_gens, 100, 99, 2).is_err());
+    }
+}
@@ -0,0 +1,7 @@
+//! Higher-level circuits composed from the gadgets, with their prove/verify wrappers.
+pub mod blacklist;
+pub mod channel;
+pub mod credential;
+pub mod examples;
+pub mod nullifier;
+pub mod shuffle;
@@ -0,0 +1,206 @@
+//! Insertion of a nullifier into a nullifier set.
+//!
+//! The set is a `SparseMerkleTree` where every nullifier sits at the leaf
+//! indexed by its low `depth` bits, and empty leaves hold zero. An insertion
+//! proof shows, for a nullifier hidden under a Pedersen commitment, that its
+//! leaf is empty under the old root and holds the nullifier under the new one,
+//! with the same path: the new set is the old one plus the nullifier, and the
+//! nullifier was not in it before.
+//!
+//! Two nullifiers sharing their low `depth` bits cannot both be inserted, so
+//! the depth must be large enough for collisions to be negligible (nullifiers
+//! are expected to be hash outputs; a depth of 64 allows billions of them).
+use crate::errors::GadgetError;
+use crate::gadgets::boolean::le_constant_gadget;
+use crate::gadgets::hash::{scalar_bits, SCALAR_BITS};
+use crate::gadgets::merkle::{merkle_root_gadget, SparseMerkleTree};
+use crate::gadgets::scalar::nonzero_gadget;
+use crate::gadgets::typed::Bit;
+use crate::statement::{encode_scalar, encode_u64, Statement};
+use bulletproofs::r1cs::{
+    ConstraintSystem as CS, LinearCombination as LC, R1CSError, R1CSProof, Verifier,
+};
+#[cfg(feature = "prover")]
+use bulletproofs::r1cs::Prover;
+use bulletproofs::{BulletproofGens, PedersenGens};
+use curve25519_dalek::{ristretto::CompressedRistretto, scalar::Scalar};
+
+/// Proof that a hidden nullifier was inserted into a nullifier set.
+#[derive(Clone, Debug)]
+pub struct NullifierInsertion {
+    /// Commitment to the nullifier, followed by the commitments to the path.
+    pub commitments: Vec<CompressedRistretto>,
+    pub proof: R1CSProof,
+}
+
+/// Public values of an insertion.
+#[derive(Clone, Copy, Debug)]
+pub struct InsertionStatement {
+    pub old_root: Scalar,
+    pub new_root: Scalar,
+    pub depth: usize,
+}
+
+impl Statement for InsertionStatement {
+    const LABEL: &'static [u8] = b"Nullifier insertion";
+
+    fn encode(&self, out: &mut Vec<u8>) {
+        encode_scalar(out, &self.old_root);
+        encode_scalar(out, &self.new_root);
+        encode_u64(out, self.depth as u64);
+    }
+}
+
+/// Position of `nullifier` in a set of the given depth.
+pub fn nullifier_index(nullifier: &Scalar, depth: usize) -> usize {
+    scalar_bits(nullifier)[..depth]
+        .iter()
+        .rev()
+        .fold(0, |index, bit| (index << 1) | *bit as usize)
+}
+
+/// Constrains `new_root` to be the root of the set of root `old_root` after
+/// inserting the (non-zero) `nullifier`, which must not have been in it.
+/// The depth of the set is the length of `siblings`.
+pub fn nullifier_insertion_gadget(
+    cs: &mut dyn CS,
+    nullifier: LC,
+    nullifier_assign: Option<Scalar>,
+    siblings: &[LC],
+    old_root: LC,
+    new_root: LC,
+) -> Result<(), GadgetError> {
+    assert!(siblings.len() < 64, "the depth must fit the leaf indices");
+    // Zero marks the empty leaves.
+    nonzero_gadget(nullifier.clone(), nullifier_assign, cs);
+
+    // The decomposition must be canonical: otherwise `nullifier + l` would give
+    // another leaf and the same nullifier could be inserted twice.
+    let bits = Bit::decompose(cs, nullifier.clone(), nullifier_assign, SCALAR_BITS);
+    le_constant_gadget(cs, &Bit::variables(&bits), &-Scalar::one());
+    let index_bits = &bits[..siblings.len()];
+
+    let before = merkle_root_gadget(cs, Scalar::zero().into(), index_bits, siblings)?;
+    cs.constrain(before - old_root);
+    let after = merkle_root_gadget(cs, nullifier, index_bits, siblings)?;
+    cs.constrain(after - new_root);
+    Ok(())
+}
+
+/// Inserts `nullifier` into `set` and proves the insertion. Fails, leaving
+/// `set` untouched, if the nullifier is zero or its leaf is already taken.
+#[cfg(feature = "prover")]
+pub fn prove_insertion(
+    pc_gens: &PedersenGens,
+    bp_gens: &BulletproofGens,
+    set: &mut SparseMerkleTree,
+    nullifier: Scalar,
+) -> Result<NullifierInsertion, R1CSError> {
+    let index = nullifier_index(&nullifier, set.depth());
+    if nullifier == Scalar::zero() {
+        return Err(R1CSError::GadgetError {
+            description: "the nullifier must be non-zero".to_string(),
+        });
+    }
+    if set.contains(index) {
+        return Err(R1CSError::GadgetError {
+            description: "nullifier already in the set".to_string(),
+        });
+    }
+    let old_root = set.root();
+    let path = set.path(index);
+    set.insert(index, nullifier);
+
+    let statement = InsertionStatement {
+        old_root,
+        new_root: set.root(),
+        depth: set.depth(),
+    };
+    let mut transcript = statement.transcript();
+    let mut prover = Prover::new(pc_gens, &mut transcript);
+    let (commitments, vars): (Vec<_>, Vec<_>) = std::iter::once(nullifier)
+        .chain(path)
+        .map(|v| prover.commit(v, Scalar::random(&mut rand::thread_rng())))
+        .unzip();
+    let siblings: Vec<LC> = vars[1..].iter().map(|v| (*v).into()).collect();
+    nullifier_insertion_gadget(
+        &mut prover,
+        vars[0].into(),
+        Some(nullifier),
+        &siblings,
+        old_root.into(),
+        statement.new_root.into(),
+    )?;
+    let proof = prover.prove(bp_gens)?;
+    Ok(NullifierInsertion { commitments, proof })
+}
+
+/// Verifies that `new_root` is the root of the set of root `old_root` and
+/// depth `depth` after inserting a fresh nullifier.
+pub fn verify_insertion(
+    pc_gens: &PedersenGens,
+    bp_gens: &BulletproofGens,
+    old_root: Scalar,
+    new_root: Scalar,
+    depth: usize,
+    insertion: &NullifierInsertion,
+) -> Result<(), R1CSError> {
+    if insertion.commitments.len() != depth + 1 {
+        return Err(GadgetError::InvalidCommitmentsLength {
+            expected: depth + 1,
+            found: insertion.commitments.len(),
+        }
+        .into());
+    }
+    let statement = InsertionStatement {
+        old_root,
+        new_root,
+        depth,
+    };
+    let mut transcript = statement.transcript();
+    let mut verifier = Verifier::new(&mut transcript);
+    let vars: Vec<_> = insertion
+        .commitments
+        .iter()
+        .map(|V| verifier.commit(*V))
+        .collect();
+    let siblings: Vec<LC> = vars[1..].iter().map(|v| (*v).into()).collect();
+    nullifier_insertion_gadget(
+        &mut verifier,
+        vars[0].into(),
+        None,
+        &siblings,
+        old_root.into(),
+        new_root.into(),
+    )?;
+    verify_proof!(verifier, &insertion.proof, pc_gens, bp_gens)
+}
+
+#[cfg(feature = "prover")]
+mod test {
+    use super::*;
+
+    #[test]
+    fn nullifier_insertion() {
+        let pc_gens = PedersenGens::default();
+        let bp_gens = BulletproofGens::new(8192, 1);
+        let mut set = SparseMerkleTree::new(4);
+        set.insert(3, Scalar::from(19u64));
+
+        let old_root = set.root();
+        let nullifier = Scalar::from(1234u64);
+        let insertion = prove_insertion(&pc_gens, &bp_gens, &mut set, nullifier).unwrap();
+        let new_root = set.root();
+        assert!(verify_insertion(&pc_gens, &bp_gens, old_root, new_root, 4, &insertion).is_ok());
+
+        // The roots are not interchangeable.
+        assert!(verify_insertion(&pc_gens, &bp_gens, new_root, old_root, 4, &insertion).is_err());
+        assert!(verify_insertion(&pc_gens, &bp_gens, old_root, old_root, 4, &insertion).is_err());
+
+        // Spent nullifiers (and colliding ones) are rejected.
+        assert!(prove_insertion(&pc_gens, &bp_gens, &mut set, nullifier).is_err());
+        let colliding = nullifier + Scalar::from(16u64);
+        assert!(prove_insertion(&pc_gens, &bp_gens, &mut set, colliding).is_err());
+        assert_eq!(set.root(), new_root);
+    }
+}
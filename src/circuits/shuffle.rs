@@ -0,0 +1,244 @@
+//! Verifiable shuffle of ElGamal ciphertexts, the building block of a mixnet.
+//!
+//! A shuffle proof shows that the public output list is a re-randomization of
+//! the public input list (see `gadgets::elgamal`) in a hidden order. The prover
+//! commits to the inputs in the order of the outputs; the permutation argument
+//! of `gadgets::permutation` constrains them to be the inputs, and every output
+//! is checked to be the re-randomization of the committed ciphertext at its
+//! position with an allocated randomness.
+use crate::errors::GadgetError;
+use crate::gadgets::elgamal::{
+    rerandomize, rerandomize_gadget, Ciphertext, CiphertextGadget, ElGamalTables, RANDOMNESS_BITS,
+};
+use crate::gadgets::permutation::permutation_gadget;
+use crate::helpers::{sonny_scalar_bits, PointLCs};
+use crate::statement::{encode_point, encode_u64, Statement};
+use bulletproofs::r1cs::{
+    ConstraintSystem as CS, LinearCombination as LC, R1CSError, R1CSProof, Variable, Verifier,
+};
+#[cfg(feature = "prover")]
+use bulletproofs::r1cs::Prover;
+use bulletproofs::{BulletproofGens, PedersenGens};
+use curve25519_dalek::{ristretto::CompressedRistretto, scalar::Scalar};
+use zerocaf::scalar::Scalar as SonnyScalar;
+
+/// Proof of a shuffle.
+#[derive(Clone, Debug)]
+pub struct ShuffleProof {
+    /// Commitments to the coordinates of the permuted inputs, eight per ciphertext.
+    pub commitments: Vec<CompressedRistretto>,
+    pub proof: R1CSProof,
+}
+
+/// Public values of a shuffle.
+#[derive(Clone, Debug)]
+pub struct ShuffleStatement {
+    pub inputs: Vec<Ciphertext>,
+    pub outputs: Vec<Ciphertext>,
+}
+
+impl Statement for ShuffleStatement {
+    const LABEL: &'static [u8] = b"Shuffle";
+
+    fn encode(&self, out: &mut Vec<u8>) {
+        for list in &[&self.inputs, &self.outputs] {
+            encode_u64(out, list.len() as u64);
+            for ct in list.iter() {
+                encode_point(out, &ct.A);
+                encode_point(out, &ct.C);
+            }
+        }
+    }
+}
+
+/// Constrains `outputs` to be the re-randomization of `inputs` in the order of
+/// the committed `permuted` coordinates, with the randomness of `randomness`.
+fn shuffle_gadget(
+    cs: &mut dyn CS,
+    tables: &ElGamalTables,
+    inputs: &[Ciphertext],
+    outputs: &[Ciphertext],
+    permuted: &[Variable],
+    randomness: Option<&[SonnyScalar]>,
+) -> Result<(), GadgetError> {
+    let input_tuples: Vec<Vec<LC>> = inputs
+        .iter()
+        .map(|ct| ct.coords().iter().map(|c| LC::from(*c)).collect())
+        .collect();
+    let permuted_tuples: Vec<Vec<LC>> = permuted
+        .chunks(8)
+        .map(|chunk| chunk.iter().map(|v| LC::from(*v)).collect())
+        .collect();
+    permutation_gadget(cs, &input_tuples, &permuted_tuples)?;
+
+    for (j, output) in outputs.iter().enumerate() {
+        let ct = CiphertextGadget {
+            A: PointLCs::from_vars(&permuted[8 * j..8 * j + 4])?.into(),
+            C: PointLCs::from_vars(&permuted[8 * j + 4..8 * (j + 1)])?.into(),
+        };
+        let r_assign = randomness.map(|r| sonny_scalar_bits(&r[j], RANDOMNESS_BITS));
+        let r_bits: Vec<Variable> = (0..RANDOMNESS_BITS)
+            .map(|i| {
+                cs.allocate(r_assign.as_ref().map(|bits| Scalar::from(bits[i])))
+                    .unwrap()
+            })
+            .collect();
+        rerandomize_gadget(cs, tables, &ct, &r_bits)
+            .equal(&CiphertextGadget::from_ciphertext(output), cs);
+    }
+    Ok(())
+}
+
+/// Shuffles `inputs` with `permutation` (output `j` re-randomizes input
+/// `permutation[j]`) and `randomness`, returning the outputs and the proof.
+#[cfg(feature = "prover")]
+pub fn prove_shuffle(
+    pc_gens: &PedersenGens,
+    bp_gens: &BulletproofGens,
+    tables: &ElGamalTables,
+    inputs: &[Ciphertext],
+    permutation: &[usize],
+    randomness: &[SonnyScalar],
+) -> Result<(Vec<Ciphertext>, ShuffleProof), R1CSError> {
+    let mut sorted = permutation.to_vec();
+    sorted.sort_unstable();
+    if sorted != (0..inputs.len()).collect::<Vec<_>>() {
+        return Err(R1CSError::GadgetError {
+            description: "invalid permutation".to_string(),
+        });
+    }
+    if randomness.len() != inputs.len() {
+        return Err(GadgetError::InvalidLCsLength {
+            expected: inputs.len(),
+            found: randomness.len(),
+        }
+        .into());
+    }
+    let permuted: Vec<Ciphertext> = permutation.iter().map(|i| inputs[*i]).collect();
+    let outputs: Vec<Ciphertext> = permuted
+        .iter()
+        .zip(randomness)
+        .map(|(ct, r)| rerandomize(&tables.pk, ct, r))
+        .collect();
+
+    let statement = ShuffleStatement {
+        inputs: inputs.to_vec(),
+        outputs: outputs.clone(),
+    };
+    let mut transcript = statement.transcript();
+    let mut prover = Prover::new(pc_gens, &mut transcript);
+    let (commitments, vars): (Vec<_>, Vec<_>) = permuted
+        .iter()
+        .flat_map(|ct| ct.coords().to_vec())
+        .map(|c| prover.commit(c, Scalar::random(&mut rand::thread_rng())))
+        .unzip();
+    shuffle_gadget(
+        &mut prover,
+        tables,
+        inputs,
+        &outputs,
+        &vars,
+        Some(randomness),
+    )?;
+    let proof = prover.prove(bp_gens)?;
+    Ok((outputs, ShuffleProof { commitments, proof }))
+}
+
+/// Verifies that `outputs` is a shuffle of `inputs` under the key of `tables`.
+pub fn verify_shuffle(
+    pc_gens: &PedersenGens,
+    bp_gens: &BulletproofGens,
+    tables: &ElGamalTables,
+    inputs: &[Ciphertext],
+    outputs: &[Ciphertext],
+    shuffle: &ShuffleProof,
+) -> Result<(), R1CSError> {
+    if outputs.len() != inputs.len() {
+        return Err(GadgetError::InvalidLCsLength {
+            expected: inputs.len(),
+            found: outputs.len(),
+        }
+        .into());
+    }
+    if shuffle.commitments.len() != 8 * inputs.len() {
+        return Err(GadgetError::InvalidCommitmentsLength {
+            expected: 8 * inputs.len(),
+            found: shuffle.commitments.len(),
+        }
+        .into());
+    }
+    let statement = ShuffleStatement {
+        inputs: inputs.to_vec(),
+        outputs: outputs.to_vec(),
+    };
+    let mut transcript = statement.transcript();
+    let mut verifier = Verifier::new(&mut transcript);
+    let vars: Vec<Variable> = shuffle
+        .commitments
+        .iter()
+        .map(|V| verifier.commit(*V))
+        .collect();
+    shuffle_gadget(&mut verifier, tables, inputs, outputs, &vars, None)?;
+    verify_proof!(verifier, &shuffle.proof, pc_gens, bp_gens)
+}
+
+#[cfg(feature = "prover")]
+mod test {
+    use super::*;
+    use crate::gadgets::elgamal::{decrypt, encrypt};
+    use crate::gadgets::signature::public_key;
+    use crate::helpers::affine_coords;
+    use zerocaf::edwards::EdwardsPoint as SonnyEdwardsPoint;
+
+    #[test]
+    fn shuffle() {
+        let pc_gens = PedersenGens::default();
+        let bp_gens = BulletproofGens::new(16384, 1);
+        let mut rng = rand::thread_rng();
+        let sk = SonnyScalar::random(&mut rng);
+        let tables = ElGamalTables::new(&public_key(&sk), 4);
+
+        let messages: Vec<SonnyEdwardsPoint> = (0..3)
+            .map(|_| SonnyEdwardsPoint::new_random_point(&mut rng))
+            .collect();
+        let inputs: Vec<Ciphertext> = messages
+            .iter()
+            .map(|m| encrypt(&tables.pk, m, &SonnyScalar::random(&mut rng)))
+            .collect();
+        let randomness: Vec<SonnyScalar> = (0..3).map(|_| SonnyScalar::random(&mut rng)).collect();
+        let permutation = [2, 0, 1];
+
+        let (outputs, proof) = prove_shuffle(
+            &pc_gens,
+            &bp_gens,
+            &tables,
+            &inputs,
+            &permutation,
+            &randomness,
+        )
+        .unwrap();
+        assert!(verify_shuffle(&pc_gens, &bp_gens, &tables, &inputs, &outputs, &proof).is_ok());
+        for (output, i) in outputs.iter().zip(&permutation) {
+            assert_eq!(
+                affine_coords(&decrypt(&sk, output)),
+                affine_coords(&messages[*i])
+            );
+        }
+
+        // An output replaced by a fresh encryption of another message.
+        let mut forged = outputs.clone();
+        forged[1] = encrypt(&tables.pk, &messages[0], &randomness[1]);
+        assert!(verify_shuffle(&pc_gens, &bp_gens, &tables, &inputs, &forged, &proof).is_err());
+
+        // Not a permutation.
+        assert!(prove_shuffle(
+            &pc_gens,
+            &bp_gens,
+            &tables,
+            &inputs,
+            &[0, 0, 1],
+            &randomness
+        )
+        .is_err());
+    }
+}
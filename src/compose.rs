@@ -0,0 +1,216 @@
+//! Incremental construction of circuits out of sub-circuits.
+//!
+//! A `Composer` synthesizes sub-circuits one after the other into the same CS.
+//! Every sub-circuit returns its outputs (LCs, point gadgets, bits...) wrapped
+//! into a typed `Handle`, which later sub-circuits take as inputs, e.g. a hash
+//! feeding a Merkle path feeding a nullifier check, instead of all of them
+//! living in a single function. The composer records, for every sub-circuit,
+//! the handles it depends on and the multipliers and constraints it added, so
+//! the structure of the composed circuit can be inspected.
+//!
+//! `repeat_gadget` synthesizes a bounded loop of a sub-gadget, threading a
+//! state of any type through its iterations.
+use crate::instrument;
+use bulletproofs::r1cs::ConstraintSystem as CS;
+
+/// Identifier of a sub-circuit within its `Composer`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct HandleId(usize);
+
+/// Outputs of a synthesized sub-circuit.
+#[derive(Clone, Debug)]
+pub struct Handle<T> {
+    id: HandleId,
+    value: T,
+}
+
+impl<T> Handle<T> {
+    pub fn id(&self) -> HandleId {
+        self.id
+    }
+
+    pub fn get(&self) -> &T {
+        &self.value
+    }
+
+    pub fn into_inner(self) -> T {
+        self.value
+    }
+}
+
+/// A synthesized sub-circuit.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Node {
+    pub name: &'static str,
+    /// Sub-circuits whose outputs it takes as inputs.
+    pub dependencies: Vec<HandleId>,
+    pub multipliers: usize,
+    pub constraints: usize,
+}
+
+/// Synthesizes sub-circuits into a CS, tracking their dependencies.
+pub struct Composer<'a> {
+    cs: &'a mut dyn CS,
+    nodes: Vec<Node>,
+}
+
+impl<'a> Composer<'a> {
+    /// Composes into `cs`, whose high-level variables are already committed.
+    pub fn new(cs: &'a mut dyn CS) -> Composer<'a> {
+        Composer {
+            cs,
+            nodes: Vec::new(),
+        }
+    }
+
+    /// Synthesizes the sub-circuit `f`, which takes the outputs of the
+    /// `dependencies` (captured from their handles) as inputs.
+    ///
+    /// Panics if a dependency was not added to this composer.
+    pub fn add<T, F>(&mut self, name: &'static str, dependencies: &[HandleId], f: F) -> Handle<T>
+    where
+        F: FnOnce(&mut dyn CS) -> T,
+    {
+        assert!(
+            dependencies.iter().all(|d| d.0 < self.nodes.len()),
+            "unknown dependency"
+        );
+        let before = self.cs.metrics();
+        let value = f(self.cs);
+        let after = self.cs.metrics();
+        self.nodes.push(Node {
+            name,
+            dependencies: dependencies.to_vec(),
+            multipliers: after.multipliers - before.multipliers,
+            constraints: after.constraints - before.constraints,
+        });
+        Handle {
+            id: HandleId(self.nodes.len() - 1),
+            value,
+        }
+    }
+
+    /// Synthesized sub-circuits, in order.
+    pub fn nodes(&self) -> &[Node] {
+        &self.nodes
+    }
+
+    pub fn node(&self, id: HandleId) -> &Node {
+        &self.nodes[id.0]
+    }
+
+    /// Every sub-circuit `id` depends on, directly or not, in increasing order.
+    pub fn dependencies(&self, id: HandleId) -> Vec<HandleId> {
+        // Dependencies always precede their dependents.
+        let mut needed = vec![false; id.0];
+        for d in &self.nodes[id.0].dependencies {
+            needed[d.0] = true;
+        }
+        for i in (0..id.0).rev() {
+            if needed[i] {
+                for d in &self.nodes[i].dependencies {
+                    needed[d.0] = true;
+                }
+            }
+        }
+        (0..id.0).filter(|i| needed[*i]).map(HandleId).collect()
+    }
+}
+
+/// Synthesizes `n` iterations of the sub-gadget `step`, each of them taking the
+/// state returned by the previous one (`init` for the first), e.g. the links of
+/// a hash chain or the steps of a ladder. Returns the state after the last
+/// iteration.
+///
+/// Every iteration is synthesized as the gadget `name` (see
+/// `instrument::synthesis`): with the `tracing` feature each one gets its own
+/// span with the multipliers and constraints it added, and its constraints are
+/// annotated with `name` and the index of the iteration.
+pub fn repeat_gadget<S, F>(cs: &mut dyn CS, name: &'static str, n: usize, init: S, mut step: F) -> S
+where
+    F: FnMut(&mut dyn CS, S) -> S,
+{
+    let mut state = init;
+    for i in 0..n {
+        state = instrument::synthesis(name, cs, |cs| {
+            let _iteration = annotate!("{}", i);
+            step(cs, state)
+        });
+    }
+    state
+}
+
+mod test {
+    use super::*;
+    use crate::gadgets::hash::{mimc_hash, mimc_hash_gadget, ROUNDS};
+    use crate::recording::RecordingCS;
+    use bulletproofs::r1cs::LinearCombination as LC;
+    use curve25519_dalek::scalar::Scalar;
+
+    #[test]
+    fn compose() {
+        let (x, y) = (Scalar::from(3u64), Scalar::from(4u64));
+        let mut cs = RecordingCS::new(b"Compose");
+        let x_var = cs.commit(Some(x));
+        let y_var = cs.commit(Some(y));
+
+        let mut composer = Composer::new(&mut cs);
+        let hx = composer.add("hash x", &[], |cs| mimc_hash_gadget(cs, &[x_var.into()]));
+        let hy = composer.add("hash y", &[], |cs| mimc_hash_gadget(cs, &[y_var.into()]));
+        let node = composer.add("node", &[hx.id(), hy.id()], |cs| {
+            mimc_hash_gadget(cs, &[hx.get().clone(), hy.get().clone()])
+        });
+        let expected = mimc_hash(&[mimc_hash(&[x]), mimc_hash(&[y])]);
+        let check = composer.add("check", &[node.id()], |cs| {
+            cs.constrain(node.get().clone() - expected)
+        });
+
+        assert_eq!(
+            composer.dependencies(check.id()),
+            vec![hx.id(), hy.id(), node.id()]
+        );
+        assert_eq!(composer.dependencies(hy.id()), vec![]);
+        assert_eq!(composer.node(node.id()).multipliers, 2 * 3 * ROUNDS);
+        assert_eq!(composer.node(check.id()).constraints, 1);
+        assert_eq!(composer.nodes().len(), 4);
+        assert!(cs.first_unsatisfied().is_none());
+    }
+
+    #[test]
+    fn repeat() {
+        let x = Scalar::from(3u64);
+        let mut cs = RecordingCS::new(b"Repeat");
+        let x_var = cs.commit(Some(x));
+
+        // Iterated squaring: x^(2^10).
+        let (res, res_assign) = repeat_gadget(
+            &mut cs,
+            "square",
+            10,
+            (LC::from(x_var), Some(x)),
+            |cs, (lc, assign)| {
+                let (_, _, sq) = cs.multiply(lc.clone(), lc);
+                (sq.into(), assign.map(|a| a * a))
+            },
+        );
+        let expected = (0..10).fold(x, |acc, _| acc * acc);
+        assert_eq!(res_assign, Some(expected));
+        assert_eq!(cs.eval(&res), Some(expected));
+        assert_eq!(cs.metrics().multipliers, 10);
+
+        // No iteration gives the initial state back.
+        let init = LC::from(x_var);
+        let res = repeat_gadget(&mut cs, "none", 0, init, |_, _| unreachable!());
+        assert_eq!(cs.eval(&res), Some(x));
+        assert_eq!(cs.metrics().multipliers, 10);
+    }
+
+    #[test]
+    #[should_panic(expected = "unknown dependency")]
+    fn foreign_handle() {
+        let mut cs = RecordingCS::new(b"Compose");
+        let mut other_cs = RecordingCS::new(b"Compose");
+        let foreign = Composer::new(&mut other_cs).add("foreign", &[], |_| ());
+        Composer::new(&mut cs).add("node", &[foreign.id()], |_| ());
+    }
+}
@@ -0,0 +1,116 @@
+//! Static cost estimation of circuits.
+//!
+//! `estimate` synthesizes a circuit without witness and predicts, from its
+//! number of multipliers, the size of its proof and the work of its verifier,
+//! so a circuit can be sized (and its generators allocated) before anything is
+//! proven. The predictions follow the serialization and the verification
+//! equation of the single-phase `R1CSProof`, the only kind the gadgets produce.
+use crate::recording::RecordingCS;
+use bulletproofs::r1cs::{ConstraintSystem, Variable};
+
+/// Bytes of a compressed point or a scalar.
+const ELEMENT_SIZE: usize = 32;
+
+/// Points (`A_I`, `A_O`, `S`, `T_1`, `T_3`..`T_6`) and scalars (`t_x`,
+/// `t_x_blinding`, `e_blinding`) of a single-phase proof outside of its
+/// inner-product proof, plus its version byte.
+const PROOF_OVERHEAD: usize = 1 + (8 + 3) * ELEMENT_SIZE;
+
+/// Points of the verification equation that do not depend on the circuit: the
+/// six phase commitments, the five `T_i` and both Pedersen generators.
+const VERIFIER_OVERHEAD: usize = 6 + 5 + 2;
+
+/// Predicted cost of proving and verifying a circuit.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct CostReport {
+    pub multipliers: usize,
+    pub constraints: usize,
+    /// Number of high-level (committed) variables.
+    pub committed: usize,
+    /// Capacity of the `BulletproofGens` needed to prove the circuit.
+    pub gens_capacity: usize,
+    /// Size in bytes of `R1CSProof::to_bytes`.
+    pub proof_size: usize,
+    /// Number of terms of the single multiscalar multiplication that checks the proof.
+    pub verifier_scalar_muls: usize,
+}
+
+impl CostReport {
+    /// Predicts the costs of a circuit from its size.
+    pub fn new(multipliers: usize, constraints: usize, committed: usize) -> CostReport {
+        // The prover pads the multipliers to a power of two.
+        let gens_capacity = multipliers.next_power_of_two();
+        let rounds = gens_capacity.trailing_zeros() as usize;
+        CostReport {
+            multipliers,
+            constraints,
+            committed,
+            gens_capacity,
+            // The inner-product proof has two points per round and two scalars.
+            proof_size: PROOF_OVERHEAD + (2 * rounds + 2) * ELEMENT_SIZE,
+            // `G` and `H` (`n` points each), `L` and `R` (one point per round)
+            // and the commitments.
+            verifier_scalar_muls: VERIFIER_OVERHEAD + 2 * gens_capacity + 2 * rounds + committed,
+        }
+    }
+
+    /// Returns whether generators of `capacity` are enough to prove the circuit.
+    pub fn fits(&self, capacity: usize) -> bool {
+        self.gens_capacity <= capacity
+    }
+}
+
+/// Synthesizes `circuit` over `committed` high-level variables, without
+/// assignments, and predicts its costs.
+pub fn estimate<F>(committed: usize, circuit: F) -> CostReport
+where
+    F: FnOnce(&mut dyn ConstraintSystem, Vec<Variable>),
+{
+    let mut cs = RecordingCS::new(b"Cost");
+    let vars: Vec<Variable> = (0..committed).map(|_| cs.commit(None)).collect();
+    circuit(&mut cs, vars);
+    CostReport::new(cs.multipliers(), cs.constraints().len(), committed)
+}
+
+#[cfg(feature = "prover")]
+mod test {
+    use super::*;
+    use crate::bench;
+    use crate::gadgets::point::edwards_point::SonnyEdwardsPointGadget;
+    use crate::helpers::{PointCoords, PointLCs};
+    use zerocaf::edwards::EdwardsPoint as SonnyEdwardsPoint;
+
+    fn doublings(cs: &mut dyn ConstraintSystem, vars: Vec<Variable>, size: usize) {
+        let mut acc: SonnyEdwardsPointGadget = PointLCs::from_vars(&vars).unwrap().into();
+        for _ in 0..size {
+            acc = acc.double(cs);
+        }
+    }
+
+    #[test]
+    fn estimate_matches_measurement() {
+        let p = SonnyEdwardsPoint::new_random_point(&mut rand::thread_rng());
+        for size in &[1, 5] {
+            let report = estimate(4, |cs, vars| doublings(cs, vars, *size));
+            let entry = bench::measure("edwards_double", *size, &p.coords(), |cs, vars, _| {
+                doublings(cs, vars, *size)
+            })
+            .unwrap();
+            assert_eq!(report.multipliers, entry.multipliers);
+            assert_eq!(report.constraints, entry.constraints);
+            assert_eq!(report.gens_capacity, entry.gens_capacity);
+            assert_eq!(report.proof_size, entry.proof_size);
+            assert!(report.fits(entry.gens_capacity));
+            assert!(!report.fits(entry.gens_capacity / 2));
+        }
+    }
+
+    #[test]
+    fn verifier_cost() {
+        // 5 multipliers are padded to 8, in 3 rounds.
+        let report = CostReport::new(5, 9, 2);
+        assert_eq!(report.gens_capacity, 8);
+        assert_eq!(report.proof_size, 1 + 11 * 32 + 8 * 32);
+        assert_eq!(report.verifier_scalar_muls, 13 + 16 + 6 + 2);
+    }
+}
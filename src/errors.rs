@@ -0,0 +1,124 @@
+//! Errors returned by the gadgets of this crate.
+use bulletproofs::r1cs::R1CSError;
+use std::fmt;
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum GadgetError {
+    /// The number of commitments received does not match the number of
+    /// high-level variables the gadget(s) commit to.
+    InvalidCommitmentsLength { expected: usize, found: usize },
+    /// The number of linear combinations received does not match the number
+    /// the gadget is built from.
+    InvalidLCsLength { expected: usize, found: usize },
+    /// The proof was generated with a version of the gadget set that the
+    /// verifier's `CompatibilityPolicy` does not accept.
+    IncompatibleVersion { version: u64 },
+    /// A serialized `Witness` or `PublishedCommitments` could not be decoded.
+    MalformedWitness,
+    /// A proof committed to other values than the `PublishedCommitments` it
+    /// was proven from.
+    CommitmentsMismatch,
+    /// The proof was generated for a circuit with another `CircuitId`.
+    CircuitMismatch,
+    /// The generators differ from the pinned ones (see `gens::GensDigest`).
+    UnexpectedGenerators,
+    /// Serialized `gens::BulletproofGensParams` could not be decoded.
+    MalformedGens,
+    /// A high-level variable was committed after the CS already held
+    /// multipliers or constraints (see `session::checked_commit`).
+    CommitAfterConstraints {
+        multipliers: usize,
+        constraints: usize,
+    },
+    /// The commitment at `index` is not the encoding of a Ristretto point
+    /// (see `session::check_commitments`).
+    MalformedCommitment { index: usize },
+    /// A shared scalar was requested with another value than the one its
+    /// label was committed with (see `session::ProverSession`).
+    SharedScalarMismatch { label: String },
+    /// The circuit takes more multipliers than the `SessionConfig` allows.
+    CircuitTooLarge { max_multipliers: usize },
+    /// A variable allocated by the circuit appears in no constraint, see
+    /// `SessionConfig::strict`.
+    UnconstrainedVariable { variable: String },
+    /// The version of the circuit, or one with the same structure, is
+    /// already registered (see `registry::CircuitRegistry`).
+    DuplicateCircuit { name: String, version: u64 },
+    /// The proof was generated for a circuit missing from the registry.
+    UnknownCircuit,
+    /// The lower bound of a range is greater than its upper bound (see
+    /// `range::range_gadget`).
+    EmptyRange { min: u64, max: u64 },
+}
+
+impl fmt::Display for GadgetError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            GadgetError::InvalidCommitmentsLength { expected, found } => write!(
+                f,
+                "invalid number of commitments: expected {}, found {}",
+                expected, found
+            ),
+            GadgetError::InvalidLCsLength { expected, found } => write!(
+                f,
+                "invalid number of linear combinations: expected {}, found {}",
+                expected, found
+            ),
+            GadgetError::IncompatibleVersion { version } => write!(
+                f,
+                "proof generated with incompatible gadgets version {}",
+                version
+            ),
+            GadgetError::MalformedWitness => write!(f, "malformed witness"),
+            GadgetError::CommitmentsMismatch => {
+                write!(f, "commitments differ from the published ones")
+            }
+            GadgetError::CircuitMismatch => write!(f, "proof generated for another circuit"),
+            GadgetError::UnexpectedGenerators => write!(f, "unexpected generators"),
+            GadgetError::MalformedGens => write!(f, "malformed generators parameters"),
+            GadgetError::CommitAfterConstraints {
+                multipliers,
+                constraints,
+            } => write!(
+                f,
+                "commitment after {} multipliers and {} constraints were added",
+                multipliers, constraints
+            ),
+            GadgetError::MalformedCommitment { index } => {
+                write!(f, "commitment {} is not a valid Ristretto point", index)
+            }
+            GadgetError::SharedScalarMismatch { label } => {
+                write!(f, "shared scalar `{}` used with different values", label)
+            }
+            GadgetError::CircuitTooLarge { max_multipliers } => write!(
+                f,
+                "circuit exceeds the limit of {} multipliers",
+                max_multipliers
+            ),
+            GadgetError::UnconstrainedVariable { variable } => {
+                write!(f, "variable {} is not constrained", variable)
+            }
+            GadgetError::DuplicateCircuit { name, version } => {
+                write!(
+                    f,
+                    "circuit {} version {} is already registered",
+                    name, version
+                )
+            }
+            GadgetError::UnknownCircuit => write!(f, "proof generated for an unknown circuit"),
+            GadgetError::EmptyRange { min, max } => {
+                write!(f, "empty range: {} is greater than {}", min, max)
+            }
+        }
+    }
+}
+
+impl std::error::Error for GadgetError {}
+
+impl From<GadgetError> for R1CSError {
+    fn from(e: GadgetError) -> R1CSError {
+        R1CSError::GadgetError {
+            description: e.to_string(),
+        }
+    }
+}
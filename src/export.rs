@@ -0,0 +1,76 @@
+//! Export of synthesized circuits so that external tools and other proving
+//! backends can consume them.
+//!
+//! A circuit is described in the Bulletproofs R1CS form: `n` multiplication gates
+//! `a_L[i] * a_R[i] = a_O[i]`, `m` committed values `v[j]` and a list of linear
+//! constraints, each of them meaning `sum(coeff * var) = 0`.
+//!
+//! The text format written by `R1CSExport::write` is line based:
+//!
+//! ```text
+//! bulletproofs-r1cs v1
+//! committed <m>
+//! multipliers <n>
+//! constraints <q>
+//! <k> <term_1> ... <term_k>      (q lines, one per constraint)
+//! ```
+//!
+//! where every term is `<var>:<coefficient>`, `<var>` being one of `L<i>`, `R<i>`,
+//! `O<i>`, `V<j>` or `1` (the constant one), and `<coefficient>` the 32-byte
+//! little-endian encoding of the scalar in hex.
+use crate::recording::{lc_terms, var_name, RecordingCS};
+use bulletproofs::r1cs::{ConstraintSystem, Variable};
+use curve25519_dalek::scalar::Scalar;
+use std::fmt::Write as FmtWrite;
+use std::io::{self, Write};
+
+/// The constraint matrices of a synthesized circuit in sparse form.
+#[derive(Clone, Debug, PartialEq)]
+pub struct R1CSExport {
+    pub committed: usize,
+    pub multipliers: usize,
+    pub constraints: Vec<Vec<(Variable, Scalar)>>,
+}
+
+impl R1CSExport {
+    /// Synthesizes the circuit with a `RecordingCS` and collects its constraints.
+    /// The closure receives `committed` high-level variables.
+    pub fn from_circuit<F>(committed: usize, circuit: F) -> R1CSExport
+    where
+        F: FnOnce(&mut dyn ConstraintSystem, Vec<Variable>),
+    {
+        let mut cs = RecordingCS::new(b"R1CSExport");
+        let vars = (0..committed).map(|_| cs.commit(None)).collect();
+        circuit(&mut cs, vars);
+        R1CSExport::from_recording(&cs)
+    }
+
+    /// Collects the constraints of an already synthesized circuit.
+    pub fn from_recording(cs: &RecordingCS) -> R1CSExport {
+        R1CSExport {
+            committed: cs.committed(),
+            multipliers: cs.multipliers(),
+            constraints: cs.constraints().iter().map(lc_terms).collect(),
+        }
+    }
+
+    /// Writes the circuit in the text format described at the module level.
+    pub fn write<W: Write>(&self, mut w: W) -> io::Result<()> {
+        writeln!(w, "bulletproofs-r1cs v1")?;
+        writeln!(w, "committed {}", self.committed)?;
+        writeln!(w, "multipliers {}", self.multipliers)?;
+        writeln!(w, "constraints {}", self.constraints.len())?;
+        for constraint in &self.constraints {
+            let mut line = constraint.len().to_string();
+            for (var, coeff) in constraint {
+                write!(line, " {}:{}", var_name(var), to_hex(coeff)).unwrap();
+            }
+            writeln!(w, "{}", line)?;
+        }
+        Ok(())
+    }
+}
+
+fn to_hex(s: &Scalar) -> String {
+    s.as_bytes().iter().map(|b| format!("{:02x}", b)).collect()
+}
@@ -0,0 +1,227 @@
+//! Set membership through a polynomial accumulator, a lighter alternative to
+//! Merkle paths for moderate set sizes.
+//!
+//! The set `S = {s_1, ..., s_n}` is accumulated into `P(X) = prod(X - s_i)`.
+//! To prove that a committed `x` belongs to `S`, the prover commits to the
+//! coefficients of the witness polynomial `Q(X) = P(X) / (X - x)`, which only
+//! exists when `P(x) = 0`. The circuit then checks `P(z) = Q(z) * (z - x)` at a
+//! challenge `z` drawn from the transcript after every commitment, which takes a
+//! single multiplier regardless of the size of the set.
+//!
+//! Non-membership is shown by evaluating `P(x)` in-circuit and constraining it
+//! to be nonzero, which takes one multiplier per element of the set.
+use crate::errors::GadgetError;
+use crate::gadgets::scalar::nonzero_gadget;
+use crate::transcript::TranscriptBackend;
+use bulletproofs::r1cs::{ConstraintSystem as CS, LinearCombination as LC};
+use curve25519_dalek::scalar::Scalar;
+
+/// Public accumulator of a set of field elements.
+#[derive(Clone, Debug)]
+pub struct Accumulator {
+    elements: Vec<Scalar>,
+    /// Coefficients of `P`, lowest degree first.
+    polynomial: Vec<Scalar>,
+}
+
+fn poly_mul(a: &[Scalar], b: &[Scalar]) -> Vec<Scalar> {
+    let mut res = vec![Scalar::zero(); a.len() + b.len() - 1];
+    for (i, ai) in a.iter().enumerate() {
+        for (j, bj) in b.iter().enumerate() {
+            res[i + j] += ai * bj;
+        }
+    }
+    res
+}
+
+/// Multiplies the `(X - s_i)` factors pairwise, as a product tree.
+fn product_tree(mut layer: Vec<Vec<Scalar>>) -> Vec<Scalar> {
+    while layer.len() > 1 {
+        layer = layer
+            .chunks(2)
+            .map(|pair| match pair {
+                [a, b] => poly_mul(a, b),
+                [a] => a.clone(),
+                _ => unreachable!(),
+            })
+            .collect();
+    }
+    layer.pop().unwrap_or_else(|| vec![Scalar::one()])
+}
+
+fn eval(poly: &[Scalar], z: &Scalar) -> Scalar {
+    poly.iter().rev().fold(Scalar::zero(), |acc, c| acc * z + c)
+}
+
+impl Accumulator {
+    pub fn new(elements: Vec<Scalar>) -> Accumulator {
+        let polynomial = product_tree(elements.iter().map(|s| vec![-s, Scalar::one()]).collect());
+        Accumulator {
+            elements,
+            polynomial,
+        }
+    }
+
+    /// Number of accumulated elements, which is also the number of
+    /// coefficients of every witness.
+    pub fn len(&self) -> usize {
+        self.elements.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.elements.is_empty()
+    }
+
+    /// Coefficients of `P`, lowest degree first.
+    pub fn polynomial(&self) -> &[Scalar] {
+        &self.polynomial
+    }
+
+    pub fn contains(&self, x: &Scalar) -> bool {
+        self.elements.contains(x)
+    }
+
+    /// Returns the coefficients of `Q(X) = P(X) / (X - x)`, lowest degree first,
+    /// or `None` if `x` is not in the set.
+    pub fn witness(&self, x: &Scalar) -> Option<Vec<Scalar>> {
+        if !self.contains(x) {
+            return None;
+        }
+        // Synthetic division, from the highest degree down.
+        let n = self.len();
+        let mut q = vec![Scalar::zero(); n];
+        let mut carry = Scalar::zero();
+        for i in (0..n).rev() {
+            carry = self.polynomial[i + 1] + carry * x;
+            q[i] = carry;
+        }
+        Some(q)
+    }
+}
+
+/// Constrains the committed `x` to belong to the set accumulated in `acc`,
+/// given the committed coefficients of its witness polynomial (see `Accumulator::witness`).
+///
+/// Every commitment must have been made before calling the gadget, since the
+/// challenge is drawn from the transcript.
+pub fn membership_gadget(
+    cs: &mut dyn CS,
+    x: LC,
+    witness: &[LC],
+    acc: &Accumulator,
+) -> Result<(), GadgetError> {
+    if witness.len() != acc.len() {
+        return Err(GadgetError::InvalidLCsLength {
+            expected: acc.len(),
+            found: witness.len(),
+        });
+    }
+    let z = {
+        let transcript: &mut dyn TranscriptBackend = cs.transcript();
+        transcript.append_u64(b"accumulator-size", acc.len() as u64);
+        transcript.challenge_scalar(b"accumulator-challenge")
+    };
+    // Q(z) is linear in the committed coefficients.
+    let mut q_z = LC::default();
+    let mut z_pow = Scalar::one();
+    for q in witness {
+        q_z = q_z + q.clone() * z_pow;
+        z_pow *= z;
+    }
+    // Q(z) * (z - x) = P(z)
+    let (_, _, p_z) = cs.multiply(q_z, LC::from(z) - x);
+    cs.constrain(p_z - eval(acc.polynomial(), &z));
+    Ok(())
+}
+
+/// Constrains `x` not to belong to the set accumulated in `acc`, evaluating
+/// `P(x)` with Horner's rule and constraining it to be nonzero.
+///
+/// The prover must check that `x` is not in the set beforehand: the
+/// assignment of the inverse of `P(x)` does not exist otherwise.
+pub fn non_membership_gadget(cs: &mut dyn CS, x: LC, x_assign: Option<Scalar>, acc: &Accumulator) {
+    let poly = acc.polynomial();
+    let mut p_x = LC::from(poly[poly.len() - 1]);
+    for c in poly.iter().rev().skip(1) {
+        let (_, _, prod) = cs.multiply(p_x, x.clone());
+        p_x = LC::from(prod) + *c;
+    }
+    nonzero_gadget(p_x, x_assign.map(|x| eval(poly, &x)), cs);
+}
+
+mod test {
+    use super::*;
+    use crate::session;
+    use bulletproofs::r1cs::{Prover, R1CSError, Verifier};
+    use bulletproofs::{BulletproofGens, PedersenGens};
+
+    fn membership_roundtrip_helper(
+        acc: &Accumulator,
+        x: Scalar,
+        witness: Vec<Scalar>,
+    ) -> Result<(), R1CSError> {
+        let pc_gens = PedersenGens::default();
+        let bp_gens = BulletproofGens::new(8, 1);
+
+        let mut transcript = session::transcript(b"Accumulator membership");
+        let mut prover = Prover::new(&pc_gens, &mut transcript);
+        let (commitments, vars): (Vec<_>, Vec<_>) = std::iter::once(x)
+            .chain(witness)
+            .map(|v| prover.commit(v, Scalar::random(&mut rand::thread_rng())))
+            .unzip();
+        let lcs: Vec<LC> = vars.iter().map(|v| (*v).into()).collect();
+        membership_gadget(&mut prover, lcs[0].clone(), &lcs[1..], acc)?;
+        let proof = prover.prove(&bp_gens)?;
+
+        let mut transcript = session::transcript(b"Accumulator membership");
+        let mut verifier = Verifier::new(&mut transcript);
+        let lcs: Vec<LC> = commitments
+            .iter()
+            .map(|V| verifier.commit(*V).into())
+            .collect();
+        membership_gadget(&mut verifier, lcs[0].clone(), &lcs[1..], acc)?;
+        verify_proof!(verifier, &proof, &pc_gens, &bp_gens)
+    }
+
+    #[test]
+    fn accumulator_witness() {
+        let elements: Vec<Scalar> = (1..6u64).map(Scalar::from).collect();
+        let acc = Accumulator::new(elements.clone());
+        let z = Scalar::from(42u64);
+        for s in &elements {
+            assert_eq!(eval(acc.polynomial(), s), Scalar::zero());
+            let q = acc.witness(s).unwrap();
+            assert_eq!(eval(&q, &z) * (z - s), eval(acc.polynomial(), &z));
+        }
+        assert!(acc.witness(&Scalar::from(6u64)).is_none());
+    }
+
+    #[test]
+    fn membership() {
+        let elements: Vec<Scalar> = (0..7)
+            .map(|_| Scalar::random(&mut rand::thread_rng()))
+            .collect();
+        let acc = Accumulator::new(elements.clone());
+
+        let witness = acc.witness(&elements[3]).unwrap();
+        assert!(membership_roundtrip_helper(&acc, elements[3], witness.clone()).is_ok());
+        // Witness of another element.
+        assert!(membership_roundtrip_helper(&acc, elements[4], witness).is_err());
+    }
+
+    #[test]
+    fn non_membership() {
+        let acc = Accumulator::new((1..6u64).map(Scalar::from).collect());
+        // The assignment of `x` only serves to compute the inverse of `P(x)`.
+        let satisfied = |x: u64, x_assign: u64| {
+            let mut cs = crate::recording::RecordingCS::new(b"Accumulator");
+            let var = cs.commit(Some(Scalar::from(x)));
+            non_membership_gadget(&mut cs, var.into(), Some(Scalar::from(x_assign)), &acc);
+            cs.first_unsatisfied().is_none()
+        };
+        assert!(satisfied(6, 6));
+        assert!(!satisfied(3, 6));
+        assert!(!acc.contains(&Scalar::from(6u64)));
+        assert!(acc.contains(&Scalar::from(3u64)));
+    }
+}
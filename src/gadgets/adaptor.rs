@@ -0,0 +1,79 @@
+//! Adaptor statements of atomic swaps based on adaptor signatures.
+//!
+//! A pre-signature `(R, s)` on the swap transaction only becomes a valid
+//! signature `(R, s + t)` with the secret `t` of the adaptor point `T = t * B`
+//! (`B` being `signature::basepoint`). Publishing the completed signature thus
+//! reveals `t` to the counterparty, which uses it to claim the other leg of
+//! the swap.
+//!
+//! `adaptor_gadget` proves, for a committed `t` and a committed completed
+//! scalar `s'`, that `T = t * B` and `s' = s + t mod L`, so a swap can be
+//! audited inside a proof without revealing `t` or `s'`.
+use crate::gadgets::point::comb::{comb_mul_gadget, CombTable};
+use crate::gadgets::point::edwards_point::SonnyEdwardsPointGadget;
+use crate::gadgets::sonny_scalar::SonnyScalarGadget;
+use crate::gadgets::typed::Bit;
+use crate::helpers::sonny_scalar_to_scalar;
+use bulletproofs::r1cs::{ConstraintSystem as CS, LinearCombination as LC};
+use zerocaf::edwards::EdwardsPoint as SonnyEdwardsPoint;
+use zerocaf::scalar::Scalar as SonnyScalar;
+
+/// Constrains `T = t * B` and `s_completed = s_pre + t mod L`, for the public
+/// adaptor point `T` and pre-signature scalar `s_pre`.
+///
+/// `t` is range-checked below `L` (its bits are cached in the gadget), and
+/// `s_completed` is then bound to the reduced sum, so both are canonical.
+pub fn adaptor_gadget(
+    cs: &mut dyn CS,
+    T: &SonnyEdwardsPoint,
+    s_pre: &SonnyScalar,
+    t: &mut SonnyScalarGadget,
+    s_completed: &SonnyScalarGadget,
+) {
+    // T = t * B
+    t.range_check(cs);
+    let t_bits = Bit::variables(t.bits(cs));
+    let tB = comb_mul_gadget(cs, &CombTable::basepoint(), &t_bits);
+    SonnyEdwardsPointGadget::from_point(T).equal(&tB, cs);
+
+    // s' = s + t mod L
+    let s_pre = sonny_scalar_to_scalar(s_pre);
+    let sum = t.add(&SonnyScalarGadget::new(LC::from(s_pre), Some(s_pre)));
+    let reduced = sum.reduce(cs);
+    cs.constrain(reduced.lc() - s_completed.lc());
+}
+
+mod test {
+    use super::*;
+    use crate::gadgets::signature::public_key;
+    use crate::recording::RecordingCS;
+
+    fn satisfied(
+        T: &SonnyEdwardsPoint,
+        s_pre: &SonnyScalar,
+        t: &SonnyScalar,
+        s: &SonnyScalar,
+    ) -> bool {
+        let mut cs = RecordingCS::new(b"Adaptor");
+        let (t, s) = (sonny_scalar_to_scalar(t), sonny_scalar_to_scalar(s));
+        let mut t_gadget = SonnyScalarGadget::new(cs.commit(Some(t)).into(), Some(t));
+        let s_gadget = SonnyScalarGadget::new(cs.commit(Some(s)).into(), Some(s));
+        adaptor_gadget(&mut cs, T, s_pre, &mut t_gadget, &s_gadget);
+        cs.first_unsatisfied().is_none()
+    }
+
+    #[test]
+    fn adaptor_statement() {
+        let mut rng = rand::thread_rng();
+        let t = SonnyScalar::random(&mut rng);
+        let T = public_key(&t);
+        let s_pre = SonnyScalar::random(&mut rng);
+        let s = s_pre + t;
+        assert!(satisfied(&T, &s_pre, &t, &s));
+
+        // Another completion, or the secret of another adaptor point.
+        let other = SonnyScalar::random(&mut rng);
+        assert!(!satisfied(&T, &s_pre, &t, &(s_pre + other)));
+        assert!(!satisfied(&T, &s_pre, &other, &(s_pre + other)));
+    }
+}
@@ -0,0 +1,382 @@
+//! Conversions between the bit and byte representations of scalars.
+//!
+//! Three representations meet in this crate: `zerocaf::scalar::Scalar::into_bits`
+//! (one `u8` per bit), the bit variables the gadgets take (e.g.
+//! `SonnyEdwardsPointGadget::scalar_mul`) and byte arrays (`to_bytes`, wire
+//! formats). All of them are little-endian, but a `&[u8]` or a
+//! `&[Variable]` does not say whether it holds bits or bytes, nor in which
+//! order, and mixing them up only shows up as a failed verification.
+//!
+//! `LeBits` and `LeBitsVar` are only built from representations whose order
+//! they know. In-circuit, `LeBitsVar` is constrained to the value or to the
+//! bytes it was built from, and exposes the others as LCs.
+//!
+//! Hash gadgets working on big-endian words take `BeBits` / `BeBitsVar`,
+//! which hold the bits most significant first. Converting from and to the
+//! little-endian types only reorders the bits: in-circuit it adds no
+//! constraint. `reverse_byte_order` swaps the order of the bytes of a bit
+//! vector, keeping the order of the bits within every byte.
+//!
+//! `LeBitsVar::low_bits` constrains only the low bits of a short value, e.g.
+//! its parity, range-checking the rest without exposing its bits.
+use crate::gadgets::boolean::bit_decomposition_gadget;
+use crate::gadgets::typed::Bit;
+use crate::helpers::bits_to_bytes;
+use bulletproofs::r1cs::{ConstraintSystem as CS, LinearCombination as LC, Variable};
+use curve25519_dalek::scalar::Scalar;
+use zerocaf::scalar::Scalar as SonnyScalar;
+
+/// Little-endian bits of a scalar, one `u8` per bit.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct LeBits(Vec<u8>);
+
+impl LeBits {
+    /// The `n` low bits of `s`, as given by `into_bits`.
+    pub fn from_sonny_scalar(s: &SonnyScalar, n: usize) -> LeBits {
+        LeBits(s.into_bits()[..n].to_vec())
+    }
+
+    /// The `n` low bits of the canonical `s`.
+    pub fn from_scalar(s: &Scalar, n: usize) -> LeBits {
+        LeBits::from_bytes(s.as_bytes(), n)
+    }
+
+    /// The `n` low bits of the little-endian `bytes`.
+    pub fn from_bytes(bytes: &[u8], n: usize) -> LeBits {
+        assert!(n <= 8 * bytes.len());
+        LeBits((0..n).map(|i| (bytes[i / 8] >> (i % 8)) & 1).collect())
+    }
+
+    /// Little-endian bytes, the bits above `len` being zero.
+    pub fn to_bytes(&self) -> [u8; 32] {
+        bits_to_bytes(&self.0)
+    }
+
+    pub fn to_sonny_scalar(&self) -> SonnyScalar {
+        SonnyScalar::from_bytes(&self.to_bytes())
+    }
+
+    /// The scalar the bits represent, reduced.
+    pub fn to_scalar(&self) -> Scalar {
+        Scalar::from_bytes_mod_order(self.to_bytes())
+    }
+
+    /// The bits, e.g. for the native versions of the point gadgets.
+    pub fn as_slice(&self) -> &[u8] {
+        &self.0
+    }
+
+    pub fn len(&self) -> usize {
+        self.0.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    pub fn to_be(&self) -> BeBits {
+        BeBits(self.0.iter().rev().cloned().collect())
+    }
+}
+
+/// Big-endian bits, most significant first, one `u8` per bit.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct BeBits(Vec<u8>);
+
+impl BeBits {
+    /// The bits of the big-endian `bytes`, most significant bit of the first
+    /// byte first.
+    pub fn from_bytes(bytes: &[u8]) -> BeBits {
+        BeBits(
+            bytes
+                .iter()
+                .flat_map(|b| (0..8).rev().map(move |i| (b >> i) & 1))
+                .collect(),
+        )
+    }
+
+    pub fn to_le(&self) -> LeBits {
+        LeBits(self.0.iter().rev().cloned().collect())
+    }
+
+    pub fn as_slice(&self) -> &[u8] {
+        &self.0
+    }
+
+    pub fn len(&self) -> usize {
+        self.0.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+}
+
+/// Little-endian, boolean-constrained bit variables.
+#[derive(Clone, Debug)]
+pub struct LeBitsVar(Vec<Bit>);
+
+impl LeBitsVar {
+    /// Decomposes `value` into `n < 253` bits (see `Bit::decompose`).
+    pub fn from_value(
+        cs: &mut dyn CS,
+        value: LC,
+        value_assign: Option<&LeBits>,
+        n: usize,
+    ) -> LeBitsVar {
+        LeBitsVar(Bit::decompose(
+            cs,
+            value,
+            value_assign.map(LeBits::to_scalar),
+            n,
+        ))
+    }
+
+    /// The `k` low bits of `value`, which is constrained to `n < 253` bits,
+    /// e.g. its parity (`k = 1`) or a bucket index. `value_assign` holds its
+    /// `n` bits.
+    ///
+    /// The high part `value >> k` is allocated and range-checked to `n - k`
+    /// bits, so `low + 2^k * high` can not wrap around the field, and only the
+    /// low part is returned as bit variables. Takes about `n` multipliers: it
+    /// is cheap for values known to be short, such as amounts.
+    pub fn low_bits(
+        cs: &mut dyn CS,
+        value: LC,
+        value_assign: Option<&LeBits>,
+        k: usize,
+        n: usize,
+    ) -> LeBitsVar {
+        assert!(k <= n && n < 253, "invalid bit lengths");
+        let low_assign = value_assign.map(|v| LeBits(v.0[..k].to_vec()).to_scalar());
+        let high_assign = value_assign.map(|v| LeBits(v.0[k..].to_vec()).to_scalar());
+        let high = cs.allocate(high_assign).unwrap();
+        // 0 <= high < 2^(n - k)
+        bit_decomposition_gadget(cs, high.into(), high_assign, n - k);
+        // value - 2^k * high in [0, 2^k)
+        let mut shift = Scalar::one();
+        for _ in 0..k {
+            shift = shift + shift;
+        }
+        LeBitsVar(Bit::decompose(cs, value - high * shift, low_assign, k))
+    }
+
+    /// Decomposes every little-endian byte of `bytes` into 8 bits.
+    pub fn from_bytes(cs: &mut dyn CS, bytes: &[LC], bytes_assign: Option<&[u8]>) -> LeBitsVar {
+        let mut bits = Vec::with_capacity(8 * bytes.len());
+        for (i, byte) in bytes.iter().enumerate() {
+            let byte_assign = bytes_assign.map(|b| Scalar::from(b[i]));
+            bits.extend(Bit::decompose(cs, byte.clone(), byte_assign, 8));
+        }
+        LeBitsVar(bits)
+    }
+
+    /// The LC of the integer the bits represent (reduced in the field from
+    /// 253 bits on).
+    pub fn value(&self) -> LC {
+        weighted_sum(&self.0)
+    }
+
+    /// The LCs of the little-endian bytes, the last one possibly partial.
+    pub fn bytes(&self) -> Vec<LC> {
+        self.0.chunks(8).map(weighted_sum).collect()
+    }
+
+    pub fn bits(&self) -> &[Bit] {
+        &self.0
+    }
+
+    /// The variables of the bits, for the gadgets taking untyped bits.
+    pub fn variables(&self) -> Vec<Variable> {
+        Bit::variables(&self.0)
+    }
+
+    pub fn len(&self) -> usize {
+        self.0.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    /// The same bits, most significant first. Adds no constraint.
+    pub fn to_be(&self) -> BeBitsVar {
+        BeBitsVar(self.0.iter().rev().cloned().collect())
+    }
+}
+
+/// Big-endian, boolean-constrained bit variables, most significant first.
+#[derive(Clone, Debug)]
+pub struct BeBitsVar(Vec<Bit>);
+
+impl BeBitsVar {
+    /// The same bits, least significant first. Adds no constraint.
+    pub fn to_le(&self) -> LeBitsVar {
+        LeBitsVar(self.0.iter().rev().cloned().collect())
+    }
+
+    /// The LC of the integer the bits represent (reduced in the field from
+    /// 253 bits on).
+    pub fn value(&self) -> LC {
+        let le: Vec<Bit> = self.0.iter().rev().cloned().collect();
+        weighted_sum(&le)
+    }
+
+    /// The LCs of the big-endian bytes, the last one possibly partial. Every
+    /// byte is read most significant bit first.
+    pub fn bytes(&self) -> Vec<LC> {
+        self.0
+            .chunks(8)
+            .map(|byte| {
+                let le: Vec<Bit> = byte.iter().rev().cloned().collect();
+                weighted_sum(&le)
+            })
+            .collect()
+    }
+
+    pub fn bits(&self) -> &[Bit] {
+        &self.0
+    }
+
+    /// The variables of the bits, for the gadgets taking untyped bits.
+    pub fn variables(&self) -> Vec<Variable> {
+        Bit::variables(&self.0)
+    }
+
+    pub fn len(&self) -> usize {
+        self.0.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+}
+
+/// Reverses the order of the bytes of `bits`, keeping the order of the bits
+/// within every byte, e.g. to read the little-endian bits of a value as the
+/// bytes of a big-endian word. Works on native bits and on bit variables
+/// alike. Panics if the length is not a multiple of 8.
+pub fn reverse_byte_order<T: Clone>(bits: &[T]) -> Vec<T> {
+    assert!(bits.len() % 8 == 0, "not a whole number of bytes");
+    bits.chunks(8).rev().flatten().cloned().collect()
+}
+
+fn weighted_sum(bits: &[Bit]) -> LC {
+    let mut sum = LC::default();
+    let mut exp = Scalar::one();
+    for bit in bits {
+        sum = sum + LC::from(*bit) * exp;
+        exp = exp + exp;
+    }
+    sum
+}
+
+mod test {
+    use super::*;
+    use crate::recording::RecordingCS;
+
+    #[test]
+    fn native_conversions() {
+        let s = SonnyScalar::random(&mut rand::thread_rng());
+        let bits = LeBits::from_sonny_scalar(&s, 256);
+        assert_eq!(LeBits::from_bytes(&bits.to_bytes(), 256), bits);
+        assert_eq!(LeBits::from_scalar(&bits.to_scalar(), 256), bits);
+        assert_eq!(bits.to_sonny_scalar(), s);
+        assert_eq!(
+            LeBits::from_bytes(&[0b1000_0001, 1], 9).as_slice(),
+            &[1, 0, 0, 0, 0, 0, 0, 1, 1]
+        );
+    }
+
+    #[test]
+    fn endianness() {
+        let le = LeBits::from_bytes(&[0b1000_0001, 1], 9);
+        assert_eq!(le.to_be().as_slice(), &[1, 1, 0, 0, 0, 0, 0, 0, 1]);
+        assert_eq!(le.to_be().to_le(), le);
+        let be = BeBits::from_bytes(&[0x12, 0x34]);
+        assert_eq!(
+            BeBits::from_bytes(&[0x34, 0x12]).to_le(),
+            LeBits::from_bytes(&[0x12, 0x34], 16)
+        );
+        assert_eq!(
+            reverse_byte_order(be.as_slice()),
+            BeBits::from_bytes(&[0x34, 0x12]).as_slice()
+        );
+
+        let value = Scalar::from(0x1234u64);
+        let mut cs = RecordingCS::new(b"Bits");
+        let var = cs.commit(Some(value));
+        let le = LeBitsVar::from_value(
+            &mut cs,
+            var.into(),
+            Some(&LeBits::from_scalar(&value, 16)),
+            16,
+        );
+        let multipliers = cs.multipliers();
+        let be = le.to_be();
+        assert_eq!(cs.eval(&be.value()), Some(value));
+        let bytes: Vec<Option<Scalar>> = be.bytes().iter().map(|b| cs.eval(b)).collect();
+        assert_eq!(
+            bytes,
+            vec![Some(Scalar::from(0x12u64)), Some(Scalar::from(0x34u64))]
+        );
+        let swapped = LeBitsVar(reverse_byte_order(le.bits()));
+        assert_eq!(cs.eval(&swapped.value()), Some(Scalar::from(0x3412u64)));
+        assert_eq!(cs.eval(&be.to_le().value()), Some(value));
+        assert_eq!(cs.multipliers(), multipliers);
+        assert!(cs.first_unsatisfied().is_none());
+    }
+
+    #[test]
+    fn low_bits() {
+        let satisfied = |value: u64, k: usize, n: usize| {
+            let value = Scalar::from(value);
+            let mut cs = RecordingCS::new(b"Bits");
+            let var = cs.commit(Some(value));
+            let assign = LeBits::from_scalar(&value, n);
+            let low = LeBitsVar::low_bits(&mut cs, var.into(), Some(&assign), k, n);
+            let expected = LeBits(assign.as_slice()[..k].to_vec()).to_scalar();
+            assert_eq!(cs.eval(&low.value()), Some(expected));
+            cs.first_unsatisfied().is_none()
+        };
+        assert!(satisfied(0b1011_0110, 4, 16));
+        assert!(satisfied(0xffff, 1, 16));
+        assert!(satisfied(0, 1, 16));
+        // The value does not fit in `n` bits.
+        assert!(!satisfied(0x1_0005, 4, 16));
+    }
+
+    #[test]
+    fn bits_of_bytes() {
+        let s = Scalar::random(&mut rand::thread_rng());
+        let bytes = s.to_bytes();
+        let satisfied = |bytes: &[u8]| {
+            let mut cs = RecordingCS::new(b"Bits");
+            let value = cs.commit(Some(s));
+            let byte_lcs: Vec<LC> = bytes
+                .iter()
+                .map(|b| cs.commit(Some(Scalar::from(*b))).into())
+                .collect();
+            let bits = LeBitsVar::from_bytes(&mut cs, &byte_lcs, Some(bytes));
+            cs.constrain(bits.value() - value);
+            cs.first_unsatisfied().is_none()
+        };
+        assert!(satisfied(&bytes));
+        let mut reversed = bytes;
+        reversed.reverse();
+        assert!(!satisfied(&reversed));
+
+        let mut cs = RecordingCS::new(b"Bits");
+        let value = cs.commit(Some(s));
+        let bits = LeBitsVar::from_value(
+            &mut cs,
+            value.into(),
+            Some(&LeBits::from_scalar(&s, 252)),
+            252,
+        );
+        let evaluated: Vec<Option<Scalar>> = bits.bytes().iter().map(|b| cs.eval(b)).collect();
+        let expected: Vec<Option<Scalar>> = bytes.iter().map(|b| Some(Scalar::from(*b))).collect();
+        assert_eq!(evaluated, expected);
+        assert!(cs.first_unsatisfied().is_none());
+    }
+}
@@ -0,0 +1,104 @@
+//! Blinding of curve points, for sender privacy.
+//!
+//! A circuit using a sensitive point `P` (e.g. the public key of the sender)
+//! can output `P' = P + r * G` as a public value, for a committed blinding `r`:
+//! with `r` uniformly random below the order of the Sonny subgroup, `P'` reveals
+//! nothing about `P`, while the proof shows that `P'` is a blinding of the very
+//! `P` the circuit used. `G` is any fixed generator with a comb table, e.g.
+//! `CombTable::blinding()`; the same `P` blinded twice gives unlinkable points.
+use crate::gadgets::point::comb::{comb_mul_gadget, CombTable};
+use crate::gadgets::point::edwards_point::SonnyEdwardsPointGadget;
+use crate::gadgets::point::witness;
+use crate::gadgets::reduction::SONNY_ORDER_BITS;
+use crate::helpers::sonny_scalar_bits;
+use bulletproofs::r1cs::{ConstraintSystem as CS, LinearCombination as LC, Variable};
+use curve25519_dalek::scalar::Scalar;
+use zerocaf::edwards::EdwardsPoint as SonnyEdwardsPoint;
+use zerocaf::scalar::Scalar as SonnyScalar;
+
+/// Bits the blinding is decomposed into. Sonny scalars fit.
+pub const BLINDING_BITS: usize = SONNY_ORDER_BITS;
+
+/// Native version of `point_blinding_gadget`: `P + r * G`, `G` being the base of
+/// `table`.
+pub fn blind(table: &CombTable, p: &SonnyEdwardsPoint, r: &SonnyScalar) -> SonnyEdwardsPoint {
+    witness::edwards::add(p, &table.mul(&sonny_scalar_bits(r, BLINDING_BITS)))
+}
+
+/// Constrains the public `blinded` to be `P + r * G` for the committed
+/// blinding `r`, `G` being the base of `table`, and returns the gadget of
+/// `P + r * G`.
+///
+/// The bits of `r` are allocated here, so the prover must provide `r_assign`.
+/// Takes `BLINDING_BITS` multipliers for the bits, those of the comb
+/// multiplication, one addition and one equality.
+pub fn point_blinding_gadget(
+    cs: &mut dyn CS,
+    table: &CombTable,
+    p: &SonnyEdwardsPointGadget,
+    r: LC,
+    r_assign: Option<Scalar>,
+    blinded: &SonnyEdwardsPoint,
+) -> SonnyEdwardsPointGadget {
+    let r_bytes = r_assign.map(|r| r.to_bytes());
+    let mut sum = LC::default();
+    let mut exp = Scalar::one();
+    let bits: Vec<Variable> = (0..BLINDING_BITS)
+        .map(|i| {
+            let bit = cs
+                .allocate(r_bytes.map(|b| Scalar::from((b[i / 8] >> (i % 8)) & 1)))
+                .unwrap();
+            sum = sum.clone() + bit * exp;
+            exp = exp + exp;
+            bit
+        })
+        .collect();
+    // `comb_mul_gadget` boolean-constrains the bits.
+    let term = comb_mul_gadget(cs, table, &bits);
+    cs.constrain(r - sum);
+    let res = p.add(&term, cs);
+    SonnyEdwardsPointGadget::from_point(blinded).equal(&res, cs);
+    res
+}
+
+mod test {
+    use super::*;
+    use crate::helpers::sonny_scalar_to_scalar;
+    use crate::recording::RecordingCS;
+
+    fn blinding_satisfied(
+        p: &SonnyEdwardsPoint,
+        r: &SonnyScalar,
+        blinded: &SonnyEdwardsPoint,
+    ) -> bool {
+        let mut cs = RecordingCS::new(b"Blinding");
+        let r_assign = sonny_scalar_to_scalar(r);
+        let r_var = cs.commit(Some(r_assign));
+        let p_gadget = SonnyEdwardsPointGadget::from_point(p);
+        point_blinding_gadget(
+            &mut cs,
+            &CombTable::blinding(),
+            &p_gadget,
+            r_var.into(),
+            Some(r_assign),
+            blinded,
+        );
+        cs.first_unsatisfied().is_none()
+    }
+
+    #[test]
+    fn point_blinding() {
+        let mut rng = rand::thread_rng();
+        let table = CombTable::blinding();
+        let p = SonnyEdwardsPoint::new_random_point(&mut rng);
+        let r = SonnyScalar::random(&mut rng);
+        let blinded = blind(&table, &p, &r);
+        assert!(blinding_satisfied(&p, &r, &blinded));
+
+        let other = SonnyEdwardsPoint::new_random_point(&mut rng);
+        assert!(!blinding_satisfied(&other, &r, &blinded));
+        let r2 = SonnyScalar::random(&mut rng);
+        assert!(!blinding_satisfied(&p, &r2, &blinded));
+        assert!(blinding_satisfied(&p, &r2, &blind(&table, &p, &r2)));
+    }
+}
@@ -1,11 +1,10 @@
-use bulletproofs::r1cs::{
-    ConstraintSystem as CS, LinearCombination as LC, Prover, R1CSError, R1CSProof, Variable,
-    Verifier,
-};
+use crate::circuit_id::{circuit_id, ProofBundle};
+use crate::instrument;
+use bulletproofs::r1cs::{ConstraintSystem as CS, LinearCombination as LC, R1CSError, Variable};
 use bulletproofs::{BulletproofGens, PedersenGens};
 use curve25519_dalek::scalar::Scalar;
-use merlin::Transcript;
-use zerocaf::field::FieldElement;
+#[cfg(feature = "prover")]
+use rand::{CryptoRng, Rng};
 
 /// Adds a the classical boolean constrain `(1 - a) * a = 0` into the
 /// CS.
@@ -20,6 +19,186 @@ pub fn binary_constrain_gadget(cs: &mut CS, bit: Variable) {
     cs.constrain(res.into())
 }
 
+/// Allocates `n` bits, each of them boolean-constrained, and constrains
+/// `value = sum(2^i * bit_i)`. Bits are returned in little-endian order.
+///
+/// `n` must be small enough for the sum not to wrap around the field (`n < 253`),
+/// or the bits must additionally be checked with `le_constant_gadget`.
+pub fn bit_decomposition_gadget(
+    cs: &mut dyn CS,
+    value: LC,
+    value_assign: Option<Scalar>,
+    n: usize,
+) -> Vec<Variable> {
+    instrument::synthesis("bit_decomposition", cs, |cs| {
+        let assign_bytes = value_assign.map(|v| v.to_bytes());
+        let mut sum = LC::default();
+        let mut exp = Scalar::one();
+        let bits: Vec<Variable> = (0..n)
+            .map(|i| {
+                let bit = cs
+                    .allocate(assign_bytes.map(|b| Scalar::from((b[i / 8] >> (i % 8)) & 1)))
+                    .unwrap();
+                binary_constrain_gadget(cs, bit);
+                sum = sum.clone() + bit * exp;
+                exp = exp + exp;
+                bit
+            })
+            .collect();
+        cs.constrain(value - sum);
+        bits
+    })
+}
+
+/// Constrains the integer represented by `bits` (little-endian, each of them
+/// already boolean-constrained) to be lower or equal than the constant `c`.
+///
+/// Scanning from the most significant bit, it keeps the flag `eq` of "all
+/// the bits so far equal the ones of `c`". Where `c` has a `0`, `eq * bit = 0`
+/// is constrained; where it has a `1`, `eq` is updated to `eq * bit`.
+pub fn le_constant_gadget(cs: &mut dyn CS, bits: &[Variable], c: &Scalar) {
+    instrument::synthesis("le_constant", cs, |cs| {
+        let c_bytes = c.to_bytes();
+        let mut eq = LC::from(Scalar::one());
+        for (i, bit) in bits.iter().enumerate().rev() {
+            let c_bit = i < 256 && (c_bytes[i / 8] >> (i % 8)) & 1 == 1;
+            let (_, _, eq_and_bit) = cs.multiply(eq.clone(), (*bit).into());
+            if c_bit {
+                eq = eq_and_bit.into();
+            } else {
+                cs.constrain(eq_and_bit.into());
+            }
+        }
+    })
+}
+
+/// Label of the proofs of `prove_bit_decomposition`.
+pub const BIT_DECOMPOSITION_LABEL: &[u8] = b"Bit decomposition";
+/// Label of the proofs of `prove_le_constant`.
+pub const LE_CONSTANT_LABEL: &[u8] = b"Lower or equal than constant";
+
+/// Bits `le_constant_gadget` is applied to by `prove_le_constant`: every
+/// canonical scalar fits in them.
+const SCALAR_BITS: usize = 253;
+
+/// Committed value fitting in `n` bits.
+fn bit_decomposition_circuit(n: usize) -> impl Fn(&mut dyn CS, Vec<Variable>, Option<&[Scalar]>) {
+    move |cs, vars, witness| {
+        bit_decomposition_gadget(cs, vars[0].into(), witness.map(|w| w[0]), n);
+    }
+}
+
+/// Committed value lower or equal than the constant `c`.
+fn le_constant_circuit(c: Scalar) -> impl Fn(&mut dyn CS, Vec<Variable>, Option<&[Scalar]>) {
+    move |cs, vars, witness| {
+        let bits = bit_decomposition_gadget(cs, vars[0].into(), witness.map(|w| w[0]), SCALAR_BITS);
+        le_constant_gadget(cs, &bits, &c);
+    }
+}
+
+/// Commits to `value` and proves that it fits in `n` bits (`n < 253`).
+#[cfg(feature = "prover")]
+pub fn prove_bit_decomposition<R: Rng + CryptoRng>(
+    value: Scalar,
+    n: usize,
+    pc_gens: &PedersenGens,
+    bp_gens: &BulletproofGens,
+    rng: &mut R,
+) -> Result<ProofBundle, R1CSError> {
+    ProofBundle::prove_with_rng(
+        pc_gens,
+        bp_gens,
+        BIT_DECOMPOSITION_LABEL,
+        &[value],
+        bit_decomposition_circuit(n),
+        rng,
+    )
+}
+
+/// Verifies that the value committed in `bundle` fits in `n` bits.
+pub fn verify_bit_decomposition(
+    bundle: &ProofBundle,
+    n: usize,
+    pc_gens: &PedersenGens,
+    bp_gens: &BulletproofGens,
+) -> Result<(), R1CSError> {
+    let circuit = bit_decomposition_circuit(n);
+    let expected = circuit_id(BIT_DECOMPOSITION_LABEL, 1, |cs, vars| {
+        circuit(cs, vars, None)
+    });
+    bundle.verify(
+        pc_gens,
+        bp_gens,
+        BIT_DECOMPOSITION_LABEL,
+        &expected,
+        circuit,
+    )
+}
+
+/// Commits to `value` and proves that it is lower or equal than `c`.
+#[cfg(feature = "prover")]
+pub fn prove_le_constant<R: Rng + CryptoRng>(
+    value: Scalar,
+    c: &Scalar,
+    pc_gens: &PedersenGens,
+    bp_gens: &BulletproofGens,
+    rng: &mut R,
+) -> Result<ProofBundle, R1CSError> {
+    ProofBundle::prove_with_rng(
+        pc_gens,
+        bp_gens,
+        LE_CONSTANT_LABEL,
+        &[value],
+        le_constant_circuit(*c),
+        rng,
+    )
+}
+
+/// Verifies that the value committed in `bundle` is lower or equal than `c`.
+pub fn verify_le_constant(
+    bundle: &ProofBundle,
+    c: &Scalar,
+    pc_gens: &PedersenGens,
+    bp_gens: &BulletproofGens,
+) -> Result<(), R1CSError> {
+    let circuit = le_constant_circuit(*c);
+    let expected = circuit_id(LE_CONSTANT_LABEL, 1, |cs, vars| circuit(cs, vars, None));
+    bundle.verify(pc_gens, bp_gens, LE_CONSTANT_LABEL, &expected, circuit)
+}
+
+#[cfg(feature = "prover")]
 mod boolean_gadgets {
     use super::*;
+
+    #[test]
+    fn bit_decomposition_bundle() {
+        let pc_gens = PedersenGens::default();
+        let bp_gens = BulletproofGens::new(64, 1);
+        let mut rng = rand::thread_rng();
+        let bundle =
+            prove_bit_decomposition(Scalar::from(200u64), 8, &pc_gens, &bp_gens, &mut rng).unwrap();
+        assert!(verify_bit_decomposition(&bundle, 8, &pc_gens, &bp_gens).is_ok());
+        // The bundle is bound to the number of bits.
+        assert!(verify_bit_decomposition(&bundle, 7, &pc_gens, &bp_gens).is_err());
+
+        let bundle =
+            prove_bit_decomposition(Scalar::from(300u64), 8, &pc_gens, &bp_gens, &mut rng).unwrap();
+        assert!(verify_bit_decomposition(&bundle, 8, &pc_gens, &bp_gens).is_err());
+    }
+
+    #[test]
+    fn le_constant_bundle() {
+        let pc_gens = PedersenGens::default();
+        let bp_gens = BulletproofGens::new(1024, 1);
+        let mut rng = rand::thread_rng();
+        let c = Scalar::from(1000u64);
+        for (value, ok) in [(999u64, true), (1000, true), (1001, false)].iter() {
+            let bundle =
+                prove_le_constant(Scalar::from(*value), &c, &pc_gens, &bp_gens, &mut rng).unwrap();
+            assert_eq!(
+                verify_le_constant(&bundle, &c, &pc_gens, &bp_gens).is_ok(),
+                *ok
+            );
+        }
+    }
 }
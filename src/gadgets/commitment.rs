@@ -0,0 +1,328 @@
+//! Pedersen vector commitments over the Sonny curve and the gadget opening them.
+//!
+//! A commitment to `m_1, ..., m_n` with blinding `r` is
+//! `C = m_1 * G_1 + ... + m_n * G_n + r * H`. Unlike the commitments of the
+//! bulletproofs `Prover`, these live on the Sonny curve, so their opening can be
+//! checked inside the R1CS (e.g. for a commitment that someone else signed).
+//!
+//! Adding `r' * H` to a commitment re-randomizes it: `C' = C + r' * H` hides
+//! the same values as `C` with blinding `r + r'`, and `rerandomization_gadget`
+//! proves that relation for a committed `r'`. When `C` stays hidden in the
+//! circuit (e.g. it is proven to be a leaf of a public tree), `C'` can be
+//! re-posted without being linkable to it.
+use crate::errors::GadgetError;
+use crate::gadgets::point::comb::{comb_mul_gadget, CombTable};
+use crate::gadgets::point::edwards_point::SonnyEdwardsPointGadget;
+use crate::gadgets::point::fixed_base::fixed_base_mul_gadget;
+use crate::gadgets::point::witness;
+use crate::precomp::{FixedBaseTable, PrecompTables};
+use bulletproofs::r1cs::{ConstraintSystem as CS, Variable};
+use merlin::Transcript;
+use rand::rngs::StdRng;
+use rand::SeedableRng;
+use std::sync::Arc;
+use zerocaf::edwards::EdwardsPoint as SonnyEdwardsPoint;
+use zerocaf::ristretto::RistrettoPoint as SonnyRistrettoPoint;
+
+/// Generators of the Sonny Pedersen commitments.
+///
+/// They are sampled from an RNG seeded by a merlin transcript, so they are
+/// reproducible and nobody knows their discrete logs with respect to each other.
+#[derive(Clone, Debug)]
+pub struct SonnyPedersenGens {
+    pub value_gens: Vec<SonnyEdwardsPoint>,
+    pub blinding_gen: SonnyEdwardsPoint,
+}
+
+impl SonnyPedersenGens {
+    /// Creates the generators to commit to `n` values.
+    pub fn new(n: usize) -> SonnyPedersenGens {
+        let mut transcript = Transcript::new(b"bulletproofs_gadgets SonnyPedersenGens");
+        let mut seed = [0u8; 32];
+        transcript.challenge_bytes(b"seed", &mut seed);
+        let mut rng = StdRng::from_seed(seed);
+        let blinding_gen = SonnyRistrettoPoint::new_random_point(&mut rng).0;
+        let value_gens = (0..n)
+            .map(|_| SonnyRistrettoPoint::new_random_point(&mut rng).0)
+            .collect();
+        SonnyPedersenGens {
+            value_gens,
+            blinding_gen,
+        }
+    }
+
+    /// Precomputes the window tables of every generator, for scalars of up to `bits` bits.
+    pub fn tables(&self, window: usize, bits: usize) -> SonnyPedersenTables {
+        self.cached_tables(&PrecompTables::new(), window, bits)
+    }
+
+    /// Like `tables`, but reusing (and filling) the tables of `cache`.
+    pub fn cached_tables(
+        &self,
+        cache: &PrecompTables,
+        window: usize,
+        bits: usize,
+    ) -> SonnyPedersenTables {
+        SonnyPedersenTables {
+            value_tables: self
+                .value_gens
+                .iter()
+                .map(|g| cache.get(g, window, bits))
+                .collect(),
+            blinding_table: cache.get(&self.blinding_gen, window, bits),
+        }
+    }
+
+    /// Commits to `values` with `blinding`, every scalar given by its little-endian
+    /// bits. This is the native version of `opening_gadget` for the same bits.
+    /// Fails if there is not one value per generator.
+    pub fn commit(
+        &self,
+        values: &[Vec<u8>],
+        blinding: &[u8],
+    ) -> Result<SonnyEdwardsPoint, GadgetError> {
+        if values.len() != self.value_gens.len() {
+            return Err(GadgetError::InvalidLCsLength {
+                expected: self.value_gens.len(),
+                found: values.len(),
+            });
+        }
+        let mut acc = witness::edwards::identity();
+        for (gen, bits) in self
+            .value_gens
+            .iter()
+            .zip(values)
+            .chain(std::iter::once((&self.blinding_gen, &blinding.to_vec())))
+        {
+            acc = witness::edwards::add(&acc, &witness::edwards::scalar_mul(gen, bits));
+        }
+        Ok(acc)
+    }
+
+    /// Re-randomizes `commitment` with the additional `blinding` (given by its
+    /// little-endian bits). This is the native version of `rerandomization_gadget`.
+    pub fn rerandomize(
+        &self,
+        commitment: &SonnyEdwardsPoint,
+        blinding: &[u8],
+    ) -> SonnyEdwardsPoint {
+        witness::edwards::add(
+            commitment,
+            &witness::edwards::scalar_mul(&self.blinding_gen, blinding),
+        )
+    }
+}
+
+/// Window tables of the generators, see `SonnyPedersenGens::tables`.
+#[derive(Clone, Debug)]
+pub struct SonnyPedersenTables {
+    pub value_tables: Vec<Arc<FixedBaseTable>>,
+    pub blinding_table: Arc<FixedBaseTable>,
+}
+
+/// Computes the commitment to `values` with `blinding` in-circuit, every scalar
+/// given by its (already allocated) little-endian bits and their assignments.
+///
+/// The returned gadget can be constrained to be equal to a known commitment
+/// or used further, e.g. as a signed message. Fails if there is not one value
+/// per generator.
+pub fn opening_gadget(
+    cs: &mut dyn CS,
+    gens: &SonnyPedersenGens,
+    values: Vec<(Vec<Variable>, Option<Vec<u8>>)>,
+    blinding: (Vec<Variable>, Option<Vec<u8>>),
+) -> Result<(SonnyEdwardsPointGadget, Option<SonnyEdwardsPoint>), GadgetError> {
+    if values.len() != gens.value_gens.len() {
+        return Err(GadgetError::InvalidLCsLength {
+            expected: gens.value_gens.len(),
+            found: values.len(),
+        });
+    }
+    let points = gens
+        .value_gens
+        .iter()
+        .chain(std::iter::once(&gens.blinding_gen))
+        .map(|g| (SonnyEdwardsPointGadget::from_point(g), Some(*g)))
+        .collect();
+    let scalars = values
+        .into_iter()
+        .chain(std::iter::once(blinding))
+        .collect();
+    SonnyEdwardsPointGadget::multiscalar_mul(points, scalars, cs)
+}
+
+/// Constrains the public `commitment` to be the commitment to `values` with
+/// `blinding` (every scalar given by its already allocated little-endian bits),
+/// computed with fixed-base multiplications over the precomputed `tables`.
+///
+/// Compared to `opening_gadget` every generator takes one lookup and one point
+/// addition per window instead of a double-and-add step per bit.
+pub fn vector_commitment_gadget(
+    cs: &mut dyn CS,
+    tables: &SonnyPedersenTables,
+    values: &[Vec<Variable>],
+    blinding: &[Variable],
+    commitment: &SonnyEdwardsPoint,
+) -> Result<SonnyEdwardsPointGadget, GadgetError> {
+    if values.len() != tables.value_tables.len() {
+        return Err(GadgetError::InvalidLCsLength {
+            expected: tables.value_tables.len(),
+            found: values.len(),
+        });
+    }
+    let mut acc = fixed_base_mul_gadget(cs, &tables.blinding_table, blinding);
+    for (table, bits) in tables.value_tables.iter().zip(values) {
+        let term = fixed_base_mul_gadget(cs, table, bits);
+        acc = acc.add(&term, cs);
+    }
+    SonnyEdwardsPointGadget::from_point(commitment).equal(&acc, cs);
+    Ok(acc)
+}
+
+/// Constrains the public commitment `new` to be `old + r' * H`, for the
+/// additional blinding `r'` given by its allocated little-endian bits (boolean
+/// constrained here), and returns the in-circuit `old + r' * H`.
+///
+/// `old` may be a public commitment (`SonnyEdwardsPointGadget::from_point`) or
+/// one computed in-circuit, e.g. by `opening_gadget`. `r' * H` is computed with
+/// the embedded comb table of the blinding generator of `SonnyPedersenGens`.
+pub fn rerandomization_gadget(
+    cs: &mut dyn CS,
+    old: &SonnyEdwardsPointGadget,
+    new: &SonnyEdwardsPoint,
+    blinding: &[Variable],
+) -> SonnyEdwardsPointGadget {
+    let term = comb_mul_gadget(cs, &CombTable::blinding(), blinding);
+    let res = old.add(&term, cs);
+    SonnyEdwardsPointGadget::from_point(new).equal(&res, cs);
+    res
+}
+
+mod test {
+    use super::*;
+    use crate::helpers::affine_coords;
+    use crate::recording::RecordingCS;
+    use crate::session;
+    use bulletproofs::r1cs::{Prover, R1CSError, Verifier};
+    use bulletproofs::{BulletproofGens, PedersenGens};
+    use curve25519_dalek::scalar::Scalar;
+
+    const BITS: usize = 64;
+
+    fn bits(v: u64) -> Vec<u8> {
+        (0..BITS).map(|i| ((v >> i) & 1) as u8).collect()
+    }
+
+    fn vector_commitment_roundtrip_helper(
+        tables: &SonnyPedersenTables,
+        values: &[u64],
+        blinding: u64,
+        commitment: &SonnyEdwardsPoint,
+    ) -> Result<(), R1CSError> {
+        let pc_gens = PedersenGens::default();
+        let bp_gens = BulletproofGens::new(2048, 1);
+        let n = values.len() + 1;
+        let all_bits: Vec<u8> = values
+            .iter()
+            .chain(std::iter::once(&blinding))
+            .flat_map(|v| bits(*v))
+            .collect();
+
+        let mut transcript = session::transcript(b"Pedersen vector commitment");
+        let mut prover = Prover::new(&pc_gens, &mut transcript);
+        let (comms, vars): (Vec<_>, Vec<_>) = all_bits
+            .iter()
+            .map(|b| prover.commit(Scalar::from(*b), Scalar::random(&mut rand::thread_rng())))
+            .unzip();
+        let chunks: Vec<Vec<Variable>> = vars.chunks(BITS).map(|c| c.to_vec()).collect();
+        vector_commitment_gadget(
+            &mut prover,
+            tables,
+            &chunks[..n - 1],
+            &chunks[n - 1],
+            commitment,
+        )?;
+        let proof = prover.prove(&bp_gens)?;
+
+        let mut transcript = session::transcript(b"Pedersen vector commitment");
+        let mut verifier = Verifier::new(&mut transcript);
+        let vars: Vec<Variable> = comms.iter().map(|V| verifier.commit(*V)).collect();
+        let chunks: Vec<Vec<Variable>> = vars.chunks(BITS).map(|c| c.to_vec()).collect();
+        vector_commitment_gadget(
+            &mut verifier,
+            tables,
+            &chunks[..n - 1],
+            &chunks[n - 1],
+            commitment,
+        )?;
+        verify_proof!(verifier, &proof, &pc_gens, &bp_gens)
+    }
+
+    #[test]
+    fn vector_commitment() {
+        let gens = SonnyPedersenGens::new(2);
+        let tables = gens.tables(3, BITS);
+        let values = [7u64, 1_000_000];
+        let blinding = 0xdead_beef;
+        let commitment = gens
+            .commit(&[bits(values[0]), bits(values[1])], &bits(blinding))
+            .unwrap();
+
+        assert!(
+            vector_commitment_roundtrip_helper(&tables, &values, blinding, &commitment).is_ok()
+        );
+        assert!(
+            vector_commitment_roundtrip_helper(&tables, &[8, 1_000_000], blinding, &commitment)
+                .is_err()
+        );
+    }
+
+    #[test]
+    fn wrong_number_of_values() {
+        let gens = SonnyPedersenGens::new(2);
+        let expected = GadgetError::InvalidLCsLength {
+            expected: 2,
+            found: 1,
+        };
+        assert_eq!(
+            gens.commit(&[bits(42)], &bits(1234)).err(),
+            Some(expected.clone())
+        );
+
+        let mut cs = RecordingCS::new(b"Opening");
+        let opening = opening_gadget(&mut cs, &gens, vec![(vec![], None)], (vec![], None));
+        assert_eq!(opening.err(), Some(expected));
+    }
+
+    fn rerandomization_satisfied(
+        old: &SonnyEdwardsPoint,
+        new: &SonnyEdwardsPoint,
+        blinding: u64,
+    ) -> bool {
+        let mut cs = RecordingCS::new(b"Rerandomization");
+        let vars: Vec<Variable> = bits(blinding)
+            .iter()
+            .map(|b| cs.commit(Some(Scalar::from(*b))))
+            .collect();
+        let old = SonnyEdwardsPointGadget::from_point(old);
+        rerandomization_gadget(&mut cs, &old, new, &vars);
+        cs.first_unsatisfied().is_none()
+    }
+
+    #[test]
+    fn rerandomization() {
+        let gens = SonnyPedersenGens::new(1);
+        let old = gens.commit(&[bits(42)], &bits(1234)).unwrap();
+        let new = gens.rerandomize(&old, &bits(5678));
+        // Same value, blinding `1234 + 5678`.
+        assert_eq!(
+            affine_coords(&new),
+            affine_coords(&gens.commit(&[bits(42)], &bits(1234 + 5678)).unwrap())
+        );
+        assert!(rerandomization_satisfied(&old, &new, 5678));
+        assert!(!rerandomization_satisfied(&old, &new, 5679));
+        // A commitment to another value can not pass as a re-randomization.
+        let other = gens.commit(&[bits(43)], &bits(1234 + 5678)).unwrap();
+        assert!(!rerandomization_satisfied(&old, &other, 5678));
+    }
+}
@@ -0,0 +1,45 @@
+//! Fixed denominations: a committed amount must be an exact multiple of a public unit.
+use crate::gadgets::boolean::bit_decomposition_gadget;
+use crate::gadgets::range::RANGE_BITS;
+use bulletproofs::r1cs::{ConstraintSystem as CS, LinearCombination as LC};
+use curve25519_dalek::scalar::Scalar;
+
+/// Constrains `amount = q * unit` for some `q` in `[0, 2^RANGE_BITS)`.
+///
+/// The quotient is allocated and range-checked, so `q * unit` can not wrap
+/// around the field. The prover must provide `amount_assign`.
+pub fn denomination_gadget(
+    cs: &mut dyn CS,
+    amount: LC,
+    amount_assign: Option<u64>,
+    unit: u64,
+) {
+    assert!(unit != 0, "the unit must be non-zero");
+    let q_assign = amount_assign.map(|a| Scalar::from(a / unit));
+    let q = cs.allocate(q_assign).unwrap();
+    // q * unit = amount
+    cs.constrain(amount - q * Scalar::from(unit));
+    // 0 <= q < 2^RANGE_BITS
+    bit_decomposition_gadget(cs, q.into(), q_assign, RANGE_BITS);
+}
+
+mod test {
+    use super::*;
+    use crate::recording::RecordingCS;
+
+    fn satisfied(amount: u64, unit: u64) -> bool {
+        let mut cs = RecordingCS::new(b"Denomination");
+        let var = cs.commit(Some(Scalar::from(amount)));
+        denomination_gadget(&mut cs, var.into(), Some(amount), unit);
+        cs.first_unsatisfied().is_none()
+    }
+
+    #[test]
+    fn denomination() {
+        assert!(satisfied(0, 100));
+        assert!(satisfied(500, 100));
+        assert!(satisfied(100, 100));
+        assert!(!satisfied(550, 100));
+        assert!(!satisfied(99, 100));
+    }
+}
@@ -0,0 +1,180 @@
+//! ElGamal encryption of Sonny points and the gadget re-randomizing ciphertexts.
+//!
+//! Under the key `PK = sk * B` (see `gadgets::signature`), a point `M` encrypts
+//! with randomness `r` to `(A, C) = (r * B, M + r * PK)` and decrypts as
+//! `C - sk * A`. Adding an encryption of the identity with fresh randomness,
+//! `(A + r' * B, C + r' * PK)`, gives an unlinkable ciphertext of the same point.
+use crate::gadgets::point::edwards_point::SonnyEdwardsPointGadget;
+use crate::gadgets::point::fixed_base::fixed_base_mul_gadget;
+use crate::gadgets::point::witness;
+use crate::gadgets::signature::{basepoint, S_BITS};
+use crate::helpers::{sonny_scalar_bits, PointCoords};
+use crate::precomp::{FixedBaseTable, PrecompTables};
+use bulletproofs::r1cs::{ConstraintSystem as CS, Variable};
+use curve25519_dalek::scalar::Scalar;
+use std::sync::Arc;
+use zerocaf::edwards::EdwardsPoint as SonnyEdwardsPoint;
+use zerocaf::scalar::Scalar as SonnyScalar;
+
+/// Bits of the encryption randomness.
+pub const RANDOMNESS_BITS: usize = S_BITS;
+
+/// An ElGamal ciphertext `(A, C)`.
+#[derive(Clone, Copy, Debug)]
+pub struct Ciphertext {
+    pub A: SonnyEdwardsPoint,
+    pub C: SonnyEdwardsPoint,
+}
+
+impl Ciphertext {
+    /// Extended coordinates of `A` followed by those of `C`.
+    pub fn coords(&self) -> [Scalar; 8] {
+        let mut coords = [Scalar::zero(); 8];
+        coords[..4].copy_from_slice(&self.A.coords());
+        coords[4..].copy_from_slice(&self.C.coords());
+        coords
+    }
+}
+
+/// Encrypts `m` under `pk` with randomness `r`.
+pub fn encrypt(pk: &SonnyEdwardsPoint, m: &SonnyEdwardsPoint, r: &SonnyScalar) -> Ciphertext {
+    rerandomize(
+        pk,
+        &Ciphertext {
+            A: witness::edwards::identity(),
+            C: *m,
+        },
+        r,
+    )
+}
+
+/// Decrypts `ct` with the secret key `sk`.
+pub fn decrypt(sk: &SonnyScalar, ct: &Ciphertext) -> SonnyEdwardsPoint {
+    let shared = witness::edwards::scalar_mul(&ct.A, &sonny_scalar_bits(sk, S_BITS));
+    witness::edwards::add(&ct.C, &witness::edwards::neg(&shared))
+}
+
+/// Re-randomizes `ct` with the randomness `r`. This is the native version of
+/// `rerandomize_gadget`.
+pub fn rerandomize(pk: &SonnyEdwardsPoint, ct: &Ciphertext, r: &SonnyScalar) -> Ciphertext {
+    let bits = sonny_scalar_bits(r, RANDOMNESS_BITS);
+    Ciphertext {
+        A: witness::edwards::add(&ct.A, &witness::edwards::scalar_mul(&basepoint(), &bits)),
+        C: witness::edwards::add(&ct.C, &witness::edwards::scalar_mul(pk, &bits)),
+    }
+}
+
+/// Window tables of the basepoint and of a public key, used by `rerandomize_gadget`.
+#[derive(Clone, Debug)]
+pub struct ElGamalTables {
+    pub pk: SonnyEdwardsPoint,
+    pub basepoint_table: Arc<FixedBaseTable>,
+    pub pk_table: Arc<FixedBaseTable>,
+}
+
+impl ElGamalTables {
+    pub fn new(pk: &SonnyEdwardsPoint, window: usize) -> ElGamalTables {
+        ElGamalTables::cached(&PrecompTables::new(), pk, window)
+    }
+
+    /// Like `new`, but reusing (and filling) the tables of `cache`.
+    pub fn cached(cache: &PrecompTables, pk: &SonnyEdwardsPoint, window: usize) -> ElGamalTables {
+        ElGamalTables {
+            pk: *pk,
+            basepoint_table: cache.get(&basepoint(), window, RANDOMNESS_BITS),
+            pk_table: cache.get(pk, window, RANDOMNESS_BITS),
+        }
+    }
+}
+
+/// Ciphertext whose points are held by gadgets.
+#[derive(Clone, Debug)]
+pub struct CiphertextGadget {
+    pub A: SonnyEdwardsPointGadget,
+    pub C: SonnyEdwardsPointGadget,
+}
+
+impl CiphertextGadget {
+    pub fn from_ciphertext(ct: &Ciphertext) -> CiphertextGadget {
+        CiphertextGadget {
+            A: SonnyEdwardsPointGadget::from_point(&ct.A),
+            C: SonnyEdwardsPointGadget::from_point(&ct.C),
+        }
+    }
+
+    /// Constrains both ciphertexts to hold the same points.
+    pub fn equal(&self, other: &CiphertextGadget, cs: &mut dyn CS) {
+        self.A.equal(&other.A, cs);
+        self.C.equal(&other.C, cs);
+    }
+}
+
+/// Re-randomizes `ct` with the randomness given by `r_bits` (little-endian,
+/// boolean-constrained here) under the key of `tables`.
+pub fn rerandomize_gadget(
+    cs: &mut dyn CS,
+    tables: &ElGamalTables,
+    ct: &CiphertextGadget,
+    r_bits: &[Variable],
+) -> CiphertextGadget {
+    let rb = fixed_base_mul_gadget(cs, &tables.basepoint_table, r_bits);
+    let rpk = fixed_base_mul_gadget(cs, &tables.pk_table, r_bits);
+    CiphertextGadget {
+        A: ct.A.add(&rb, cs),
+        C: ct.C.add(&rpk, cs),
+    }
+}
+
+mod test {
+    use super::*;
+    use crate::gadgets::signature::public_key;
+    use crate::helpers::affine_coords;
+    use crate::recording::RecordingCS;
+
+    #[test]
+    fn encryption_roundtrip() {
+        let mut rng = rand::thread_rng();
+        let sk = SonnyScalar::random(&mut rng);
+        let pk = public_key(&sk);
+        let m = SonnyEdwardsPoint::new_random_point(&mut rng);
+
+        let ct = encrypt(&pk, &m, &SonnyScalar::random(&mut rng));
+        let ct2 = rerandomize(&pk, &ct, &SonnyScalar::random(&mut rng));
+        assert_ne!(affine_coords(&ct.A), affine_coords(&ct2.A));
+        assert_eq!(affine_coords(&decrypt(&sk, &ct2)), affine_coords(&m));
+    }
+
+    fn rerandomize_satisfied(
+        tables: &ElGamalTables,
+        ct: &Ciphertext,
+        r_bits: &[u8],
+        expected: &Ciphertext,
+    ) -> bool {
+        let mut cs = RecordingCS::new(b"ElGamal");
+        let r_bits: Vec<Variable> = r_bits
+            .iter()
+            .map(|b| cs.commit(Some(Scalar::from(*b))))
+            .collect();
+        let ct = CiphertextGadget::from_ciphertext(ct);
+        let out = rerandomize_gadget(&mut cs, tables, &ct, &r_bits);
+        out.equal(&CiphertextGadget::from_ciphertext(expected), &mut cs);
+        cs.first_unsatisfied().is_none()
+    }
+
+    #[test]
+    fn rerandomize_gadget_matches_native() {
+        let mut rng = rand::thread_rng();
+        let pk = public_key(&SonnyScalar::random(&mut rng));
+        let tables = ElGamalTables::new(&pk, 4);
+        let m = SonnyEdwardsPoint::new_random_point(&mut rng);
+        let ct = encrypt(&pk, &m, &SonnyScalar::random(&mut rng));
+        let r = SonnyScalar::random(&mut rng);
+        let expected = rerandomize(&pk, &ct, &r);
+
+        let mut r_bits = sonny_scalar_bits(&r, RANDOMNESS_BITS);
+        assert!(rerandomize_satisfied(&tables, &ct, &r_bits, &expected));
+        // Another randomness.
+        r_bits[0] ^= 1;
+        assert!(!rerandomize_satisfied(&tables, &ct, &r_bits, &expected));
+    }
+}
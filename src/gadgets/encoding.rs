@@ -0,0 +1,242 @@
+//! Gadgets binding wire-format encodings of points to their coordinates.
+//!
+//! Points received from outside the circuit go through
+//! `validate_external_point`, which also checks that they are on the curve and
+//! not of small order.
+use crate::errors::GadgetError;
+use crate::gadgets::boolean::{bit_decomposition_gadget, le_constant_gadget};
+use crate::gadgets::point::edwards_point::SonnyEdwardsPointGadget;
+use crate::gadgets::point::witness;
+use crate::helpers::{affine_coords, scalar_to_fe};
+use bulletproofs::r1cs::{ConstraintSystem as CS, LinearCombination as LC};
+use curve25519_dalek::scalar::Scalar;
+use zerocaf::edwards::EdwardsPoint as SonnyEdwardsPoint;
+use zerocaf::field::FieldElement;
+
+/// Number of bytes of a compressed point.
+pub const COMPRESSED_LEN: usize = 32;
+
+/// Compressed encoding of a point: the little-endian bytes of the affine `y`
+/// with the parity of the affine `x` stored in the most significant bit.
+pub fn compress(p: &SonnyEdwardsPoint) -> [u8; COMPRESSED_LEN] {
+    let (x, y) = affine_coords(p);
+    let mut bytes = y.to_bytes();
+    bytes[31] |= (x.to_bytes()[0] & 1) << 7;
+    bytes
+}
+
+/// Constrains the 32 `encoding` LCs to be the bytes of the canonical compressed
+/// encoding (see `compress`) of `point`.
+///
+/// It decomposes every byte into bits, checks that the 255 low bits are the
+/// canonical representation of the affine `y` and that the top bit is the parity
+/// of the (canonical) affine `x`. `point` is expected to be a valid curve point
+/// (see `satisfy_curve_eq`); only `Z != 0` is checked here (by `to_affine`).
+pub fn compressed_encoding_gadget(
+    cs: &mut dyn CS,
+    point: &SonnyEdwardsPointGadget,
+    point_assign: Option<SonnyEdwardsPoint>,
+    encoding: Vec<LC>,
+) -> Result<(), GadgetError> {
+    if encoding.len() != COMPRESSED_LEN {
+        return Err(GadgetError::InvalidLCsLength {
+            expected: COMPRESSED_LEN,
+            found: encoding.len(),
+        });
+    }
+    let affine_assign = point_assign.as_ref().map(affine_coords);
+    let encoding_assign = point_assign.as_ref().map(compress);
+    // l - 1, the biggest canonical value.
+    let max = -Scalar::one();
+
+    let (x, y) = point.to_affine(point_assign, cs);
+
+    // Canonical bits of x, its parity is the lowest one.
+    let x_bits = bit_decomposition_gadget(cs, x.into(), affine_assign.map(|(x, _)| x), 253);
+    le_constant_gadget(cs, &x_bits, &max);
+
+    // Bits of the encoding, 8 per byte.
+    let mut enc_bits = Vec::with_capacity(8 * COMPRESSED_LEN);
+    for (i, byte) in encoding.into_iter().enumerate() {
+        let byte_assign = encoding_assign.map(|e| Scalar::from(e[i]));
+        enc_bits.extend(bit_decomposition_gadget(cs, byte, byte_assign, 8));
+    }
+
+    // The 255 low bits must be the canonical representation of y.
+    let mut y_sum = LC::default();
+    let mut exp = Scalar::one();
+    for bit in &enc_bits[..255] {
+        y_sum = y_sum + *bit * exp;
+        exp = exp + exp;
+    }
+    cs.constrain(y_sum - y);
+    le_constant_gadget(cs, &enc_bits[..255], &max);
+
+    // The top bit must be the parity of x.
+    cs.constrain(enc_bits[255] - x_bits[0]);
+    Ok(())
+}
+
+/// Validates a point received in compressed form (e.g. a ciphertext point),
+/// allocating it from its assignment `point_assign` and constraining it to be
+/// the point the `encoding` decompresses to (see `compressed_encoding_gadget`),
+/// to satisfy the curve equation and not to be of small order (`8 * P` is not
+/// the identity). Skipping any of the three checks lets a malicious point in.
+///
+/// The point is allocated in affine form `(x, y, 1, x * y)`, and returned along
+/// with its assignment in these coordinates on the prover side. The prover
+/// panics if the point has small order.
+pub fn validate_external_point(
+    cs: &mut dyn CS,
+    encoding: Vec<LC>,
+    point_assign: Option<SonnyEdwardsPoint>,
+) -> Result<(SonnyEdwardsPointGadget, Option<SonnyEdwardsPoint>), GadgetError> {
+    let affine_assign = point_assign.as_ref().map(affine_coords);
+    let x = cs.allocate(affine_assign.map(|(x, _)| x)).unwrap();
+    let y = cs.allocate(affine_assign.map(|(_, y)| y)).unwrap();
+    let (_, _, t) = cs.multiply(x.into(), y.into());
+    let point = SonnyEdwardsPointGadget {
+        X: x.into(),
+        Y: y.into(),
+        Z: Scalar::one().into(),
+        T: t.into(),
+    };
+    let point_assign = affine_assign.map(|(x, y)| SonnyEdwardsPoint {
+        X: scalar_to_fe(&x),
+        Y: scalar_to_fe(&y),
+        Z: FieldElement::one(),
+        T: scalar_to_fe(&(x * y)),
+    });
+
+    compressed_encoding_gadget(cs, &point, point_assign, encoding)?;
+    point.satisfy_curve_eq(cs);
+    point.mul_by_pow2(3, cs).forbid_identity(
+        point_assign.map(|p| witness::edwards::mul_by_pow2(&p, 3)),
+        cs,
+    );
+    Ok((point, point_assign))
+}
+
+#[cfg(feature = "prover")]
+mod test {
+    use super::*;
+    use crate::helpers;
+    use crate::session;
+    use bulletproofs::r1cs::{Prover, R1CSError, Verifier};
+    use bulletproofs::{BulletproofGens, PedersenGens};
+    use curve25519_dalek::ristretto::CompressedRistretto;
+
+    fn encoding_roundtrip_helper(
+        point: SonnyEdwardsPoint,
+        encoding: [u8; COMPRESSED_LEN],
+    ) -> Result<(), R1CSError> {
+        let pc_gens = PedersenGens::default();
+        let bp_gens = BulletproofGens::new(2048, 1);
+
+        // Prover
+        let mut transcript = session::transcript(b"Compressed encoding");
+        let mut prover = Prover::new(&pc_gens, &mut transcript);
+        let (mut lcs, mut commitments) = helpers::prover_commit_points(&mut prover, &[point]);
+        let enc_lcs: Vec<LC> = encoding
+            .iter()
+            .map(|b| {
+                let (comm, var) =
+                    prover.commit(Scalar::from(*b), Scalar::random(&mut rand::thread_rng()));
+                commitments.push(comm);
+                var.into()
+            })
+            .collect();
+        let point_gadget: SonnyEdwardsPointGadget = lcs.remove(0).into();
+        compressed_encoding_gadget(&mut prover, &point_gadget, Some(point), enc_lcs)?;
+        let proof = prover.prove(&bp_gens)?;
+
+        // Verifier
+        let mut transcript = session::transcript(b"Compressed encoding");
+        let mut verifier = Verifier::new(&mut transcript);
+        let (point_comms, enc_comms): (&[CompressedRistretto], _) = commitments.split_at(4);
+        let point_gadget: SonnyEdwardsPointGadget =
+            helpers::verifier_commit_points(&mut verifier, point_comms, 1)?
+                .remove(0)
+                .into();
+        let enc_lcs = enc_comms
+            .iter()
+            .map(|V| verifier.commit(*V).into())
+            .collect();
+        compressed_encoding_gadget(&mut verifier, &point_gadget, None, enc_lcs)?;
+        verify_proof!(verifier, &proof, &pc_gens, &bp_gens)
+    }
+
+    #[test]
+    fn compressed_encoding() {
+        let p = SonnyEdwardsPoint::new_random_point(&mut rand::thread_rng());
+        let encoding = compress(&p);
+        assert!(encoding_roundtrip_helper(p, encoding).is_ok());
+
+        // Wrong sign bit.
+        let mut wrong = encoding;
+        wrong[31] ^= 0x80;
+        assert!(encoding_roundtrip_helper(p, wrong).is_err());
+        // Wrong y.
+        let mut wrong = encoding;
+        wrong[0] ^= 0x01;
+        assert!(encoding_roundtrip_helper(p, wrong).is_err());
+    }
+
+    fn validated(point: &SonnyEdwardsPoint, encoding: &[u8]) -> bool {
+        let mut cs = crate::recording::RecordingCS::new(b"External point");
+        let enc_lcs = encoding
+            .iter()
+            .map(|b| cs.commit(Some(Scalar::from(*b))).into())
+            .collect();
+        let (gadget, assign) = validate_external_point(&mut cs, enc_lcs, Some(*point)).unwrap();
+        assert_eq!(
+            cs.eval(&gadget.X),
+            Some(helpers::fe_to_scalar(&assign.unwrap().X))
+        );
+        cs.first_unsatisfied().is_none()
+    }
+
+    #[test]
+    fn external_point() {
+        let p = SonnyEdwardsPoint::new_random_point(&mut rand::thread_rng());
+        assert!(validated(&p, &compress(&p)));
+        let q = SonnyEdwardsPoint::new_random_point(&mut rand::thread_rng());
+        assert!(!validated(&p, &compress(&q)));
+
+        // (3, 5) is not on the curve.
+        let (x, y) = (Scalar::from(3u64), Scalar::from(5u64));
+        let off_curve = SonnyEdwardsPoint {
+            X: helpers::scalar_to_fe(&x),
+            Y: helpers::scalar_to_fe(&y),
+            Z: FieldElement::one(),
+            T: helpers::scalar_to_fe(&(x * y)),
+        };
+        assert!(!validated(&off_curve, &compress(&off_curve)));
+    }
+
+    #[test]
+    #[should_panic(expected = "divide by 0")]
+    fn small_order_external_point() {
+        let identity = witness::edwards::identity();
+        validated(&identity, &compress(&identity));
+    }
+
+    #[test]
+    fn compressed_encoding_rejects_wrong_length() {
+        let mut cs = crate::recording::RecordingCS::new(b"Compressed encoding");
+        let p = SonnyEdwardsPoint::new_random_point(&mut rand::thread_rng());
+        let res = compressed_encoding_gadget(
+            &mut cs,
+            &SonnyEdwardsPointGadget::from_point(&p),
+            Some(p),
+            vec![LC::default(); 31],
+        );
+        assert_eq!(
+            res,
+            Err(GadgetError::InvalidLCsLength {
+                expected: COMPRESSED_LEN,
+                found: 31
+            })
+        );
+    }
+}
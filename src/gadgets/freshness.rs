@@ -0,0 +1,167 @@
+//! Freshness of a committed timestamp with respect to a public epoch, and of a
+//! committed nonce with respect to the nonces already used in a session.
+use crate::errors::GadgetError;
+use crate::gadgets::range::range_gadget;
+use crate::statement::{encode_u64, Statement};
+use bulletproofs::r1cs::{
+    ConstraintSystem as CS, LinearCombination as LC, R1CSError, R1CSProof, Verifier,
+};
+#[cfg(feature = "prover")]
+use bulletproofs::r1cs::Prover;
+use bulletproofs::{BulletproofGens, PedersenGens};
+use curve25519_dalek::{ristretto::CompressedRistretto, scalar::Scalar};
+use merlin::Transcript;
+
+/// Public window `[start, end]` (inclusive) a timestamp must lie in.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Epoch {
+    pub start: u64,
+    pub end: u64,
+}
+
+impl Epoch {
+    /// Binds the epoch into the transcript, so a proof for one epoch can not be
+    /// replayed in another one.
+    pub fn append_to_transcript(&self, transcript: &mut Transcript) {
+        transcript.append_message(b"dom-sep", b"epoch");
+        transcript.append_u64(b"epoch-start", self.start);
+        transcript.append_u64(b"epoch-end", self.end);
+    }
+}
+
+impl Statement for Epoch {
+    const LABEL: &'static [u8] = b"Freshness";
+
+    fn encode(&self, out: &mut Vec<u8>) {
+        encode_u64(out, self.start);
+        encode_u64(out, self.end);
+    }
+}
+
+/// Constrains `epoch.start <= timestamp <= epoch.end`.
+/// Fails if the epoch is empty.
+pub fn freshness_gadget(
+    cs: &mut dyn CS,
+    timestamp: LC,
+    timestamp_assign: Option<u64>,
+    epoch: Epoch,
+) -> Result<(), GadgetError> {
+    range_gadget(cs, timestamp, timestamp_assign, epoch.start, epoch.end)
+}
+
+/// Constrains the committed `nonce` to differ from every nonce of the public
+/// list `used`, so a proof can not be replayed within a session without the
+/// nonce being revealed.
+///
+/// Instead of proving every difference `nonce - used[i]` nonzero, which takes
+/// an inverse each, their product is proven nonzero with a single inverse.
+/// Takes `used.len()` multipliers (none for an empty list).
+pub fn nonce_uniqueness_gadget(
+    cs: &mut dyn CS,
+    nonce: LC,
+    nonce_assign: Option<Scalar>,
+    used: &[Scalar],
+) {
+    if used.is_empty() {
+        return;
+    }
+    let mut product = nonce.clone() - used[0];
+    let mut product_assign = nonce_assign.map(|n| n - used[0]);
+    for u in &used[1..] {
+        let (_, _, o) = cs.multiply(product, nonce.clone() - *u);
+        product = o.into();
+        product_assign = product_assign.and_then(|p| nonce_assign.map(|n| p * (n - u)));
+    }
+    // product * inverse = 1. A replayed nonce leaves no valid inverse, the
+    // prover then assigns zero and the constraint is not satisfied.
+    let (p, _, one) = cs
+        .allocate_multiplier(product_assign.map(|p| (p, p.invert())))
+        .unwrap();
+    cs.constrain(product - p);
+    cs.constrain(one - Scalar::one());
+}
+
+/// Proves that the committed `timestamp` lies within `epoch`.
+#[cfg(feature = "prover")]
+pub fn prove_freshness(
+    pc_gens: &PedersenGens,
+    bp_gens: &BulletproofGens,
+    timestamp: u64,
+    epoch: Epoch,
+) -> Result<(R1CSProof, CompressedRistretto), R1CSError> {
+    let mut transcript = epoch.transcript();
+    let mut prover = Prover::new(pc_gens, &mut transcript);
+    let (commitment, var) = prover.commit(
+        Scalar::from(timestamp),
+        Scalar::random(&mut rand::thread_rng()),
+    );
+    freshness_gadget(&mut prover, var.into(), Some(timestamp), epoch)?;
+    let proof = prover.prove(bp_gens)?;
+    Ok((proof, commitment))
+}
+
+/// Verifies that the timestamp behind `commitment` lies within `epoch`.
+pub fn verify_freshness(
+    pc_gens: &PedersenGens,
+    bp_gens: &BulletproofGens,
+    commitment: CompressedRistretto,
+    epoch: Epoch,
+    proof: &R1CSProof,
+) -> Result<(), R1CSError> {
+    let mut transcript = epoch.transcript();
+    let mut verifier = Verifier::new(&mut transcript);
+    let var = verifier.commit(commitment);
+    freshness_gadget(&mut verifier, var.into(), None, epoch)?;
+    verify_proof!(verifier, proof, pc_gens, bp_gens)
+}
+
+#[cfg(feature = "prover")]
+mod test {
+    use super::*;
+    use crate::recording::RecordingCS;
+
+    #[test]
+    fn freshness() {
+        let pc_gens = PedersenGens::default();
+        let bp_gens = BulletproofGens::new(256, 1);
+        let epoch = Epoch {
+            start: 1_600_000_000,
+            end: 1_600_086_400,
+        };
+
+        let (proof, commitment) =
+            prove_freshness(&pc_gens, &bp_gens, 1_600_000_100, epoch).unwrap();
+        assert!(verify_freshness(&pc_gens, &bp_gens, commitment, epoch, &proof).is_ok());
+
+        // Replayed in the next epoch.
+        let next = Epoch {
+            start: epoch.end + 1,
+            end: epoch.end + 86_400,
+        };
+        assert!(verify_freshness(&pc_gens, &bp_gens, commitment, next, &proof).is_err());
+
+        // Stale timestamp.
+        let (proof, commitment) =
+            prove_freshness(&pc_gens, &bp_gens, 1_599_999_999, epoch).unwrap();
+        assert!(verify_freshness(&pc_gens, &bp_gens, commitment, epoch, &proof).is_err());
+    }
+
+    fn nonce_unique(nonce: u64, used: &[u64]) -> bool {
+        let mut cs = RecordingCS::new(b"NonceUniqueness");
+        let nonce = Scalar::from(nonce);
+        let var = cs.commit(Some(nonce));
+        let used: Vec<Scalar> = used.iter().map(|u| Scalar::from(*u)).collect();
+        nonce_uniqueness_gadget(&mut cs, var.into(), Some(nonce), &used);
+        assert_eq!(cs.multipliers(), used.len());
+        cs.first_unsatisfied().is_none()
+    }
+
+    #[test]
+    fn nonce_uniqueness() {
+        assert!(nonce_unique(7, &[]));
+        assert!(nonce_unique(7, &[1, 2, 3, 8]));
+        assert!(!nonce_unique(7, &[7]));
+        assert!(!nonce_unique(7, &[1, 2, 7, 8]));
+        assert!(!nonce_unique(8, &[1, 2, 7, 8]));
+    }
+}
@@ -0,0 +1,126 @@
+//! MiMC-based algebraic hash, cheap to arithmetize over the R1CS field.
+//!
+//! The permutation is `E_k(x) = r_n + k` with `r_0 = x` and
+//! `r_{i+1} = (r_i + k + c_i)^5` (`5` is the smallest exponent coprime with
+//! `l - 1`, since `3` divides it, so every round is a permutation). Inputs are
+//! absorbed with the Miyaguchi–Preneel construction `h' = E_h(m) + h + m`,
+//! starting from `h = 0`.
+//!
+//! The round constants are derived from a merlin transcript, so they are fixed
+//! and reproducible but nothing-up-my-sleeve.
+use crate::gadgets::boolean::{bit_decomposition_gadget, le_constant_gadget};
+use crate::gadgets::scalar::pow_const_gadget;
+use crate::instrument;
+use bulletproofs::r1cs::{ConstraintSystem as CS, LinearCombination as LC, Variable};
+use curve25519_dalek::scalar::Scalar;
+use merlin::Transcript;
+
+/// Number of rounds of the permutation, `ceil(log_5(l))`.
+pub const ROUNDS: usize = 109;
+
+/// Number of bits of a canonical scalar (`l < 2^253`).
+pub const SCALAR_BITS: usize = 253;
+
+/// Round constants of the permutation.
+pub fn round_constants() -> Vec<Scalar> {
+    let mut transcript = Transcript::new(b"bulletproofs_gadgets MiMC");
+    (0..ROUNDS)
+        .map(|_| {
+            let mut buf = [0u8; 64];
+            transcript.challenge_bytes(b"round-constant", &mut buf);
+            Scalar::from_bytes_mod_order_wide(&buf)
+        })
+        .collect()
+}
+
+fn pow5(x: Scalar) -> Scalar {
+    let x2 = x * x;
+    let x4 = x2 * x2;
+    x4 * x
+}
+
+/// Native version of the keyed permutation `E_k(x)`.
+pub fn mimc(x: Scalar, k: Scalar, constants: &[Scalar]) -> Scalar {
+    constants.iter().fold(x, |r, c| pow5(r + k + c)) + k
+}
+
+/// Native version of `mimc_hash_gadget`.
+pub fn mimc_hash(inputs: &[Scalar]) -> Scalar {
+    let constants = round_constants();
+    inputs
+        .iter()
+        .fold(Scalar::zero(), |h, m| mimc(*m, h, &constants) + h + m)
+}
+
+/// In-circuit keyed permutation `E_k(x)`. Takes `3 * ROUNDS` multipliers.
+pub(crate) fn mimc_gadget(cs: &mut dyn CS, x: LC, k: LC, constants: &[Scalar]) -> LC {
+    let r = constants.iter().fold(x, |r, c| {
+        pow_const_gadget(cs, r + k.clone() + LC::from(*c), 5)
+    });
+    r + k
+}
+
+/// Hashes `inputs` into a single field element (see the module docs).
+/// Every input takes `3 * ROUNDS` multipliers.
+pub fn mimc_hash_gadget(cs: &mut dyn CS, inputs: &[LC]) -> LC {
+    instrument::synthesis("mimc_hash", cs, |cs| {
+        let constants = round_constants();
+        inputs.iter().fold(LC::from(Scalar::zero()), |h, m| {
+            mimc_gadget(cs, m.clone(), h.clone(), &constants) + h + m.clone()
+        })
+    })
+}
+
+/// Hashes `inputs` and returns the canonical little-endian bits of the result,
+/// ready to be used as a scalar (e.g. by `SonnyEdwardsPointGadget::scalar_mul`).
+///
+/// Since the bits are allocated, the prover must provide `inputs_assign`.
+pub fn hash_to_scalar_gadget(
+    cs: &mut dyn CS,
+    inputs: &[LC],
+    inputs_assign: Option<&[Scalar]>,
+) -> Vec<Variable> {
+    instrument::synthesis("hash_to_scalar", cs, |cs| {
+        let hash = mimc_hash_gadget(cs, inputs);
+        let bits = bit_decomposition_gadget(cs, hash, inputs_assign.map(mimc_hash), SCALAR_BITS);
+        le_constant_gadget(cs, &bits, &-Scalar::one());
+        bits
+    })
+}
+
+/// Little-endian bits of a canonical scalar, as used by `hash_to_scalar_gadget`.
+pub fn scalar_bits(s: &Scalar) -> Vec<u8> {
+    let bytes = s.to_bytes();
+    (0..SCALAR_BITS)
+        .map(|i| (bytes[i / 8] >> (i % 8)) & 1)
+        .collect()
+}
+
+mod test {
+    use super::*;
+    use crate::recording::RecordingCS;
+
+    #[test]
+    fn mimc_hash_gadget_matches_native() {
+        let inputs: Vec<Scalar> = (0..3)
+            .map(|_| Scalar::random(&mut rand::thread_rng()))
+            .collect();
+        let mut cs = RecordingCS::new(b"MiMC");
+        let vars: Vec<LC> = inputs.iter().map(|i| cs.commit(Some(*i)).into()).collect();
+        let hash = mimc_hash_gadget(&mut cs, &vars);
+
+        assert_eq!(cs.eval(&hash), Some(mimc_hash(&inputs)));
+        assert_eq!(cs.multipliers(), 3 * 3 * ROUNDS);
+        assert_eq!(cs.first_unsatisfied(), None);
+    }
+
+    #[test]
+    fn hash_to_scalar_gadget_is_satisfied() {
+        let inputs = [Scalar::random(&mut rand::thread_rng())];
+        let mut cs = RecordingCS::new(b"MiMC");
+        let vars: Vec<LC> = inputs.iter().map(|i| cs.commit(Some(*i)).into()).collect();
+        hash_to_scalar_gadget(&mut cs, &vars, Some(&inputs));
+
+        assert_eq!(cs.first_unsatisfied(), None);
+    }
+}
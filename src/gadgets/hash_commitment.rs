@@ -0,0 +1,154 @@
+//! Hash commitments `C = H(m_1, ..., m_n, r)`, with the MiMC hash of
+//! `gadgets::hash`.
+//!
+//! Unlike Pedersen commitments, they are bound by the collision resistance of
+//! the hash rather than by discrete logarithms, which hedges the commitment
+//! layer against an adversary able to compute them (e.g. a quantum one). They
+//! hide the values as long as the blinding `r` is uniformly random, and a
+//! commitment is a single field element.
+//!
+//! `prove_opening` proves knowledge of an opening of a public commitment, the
+//! values and the blinding staying hidden behind Pedersen commitments so the
+//! proof can be combined with other gadgets over them.
+use crate::gadgets::hash::{mimc_hash, mimc_hash_gadget};
+use crate::statement::{encode_scalar, Statement};
+use bulletproofs::r1cs::{
+    ConstraintSystem as CS, LinearCombination as LC, R1CSError, R1CSProof, Variable, Verifier,
+};
+#[cfg(feature = "prover")]
+use bulletproofs::r1cs::Prover;
+use bulletproofs::{BulletproofGens, PedersenGens};
+use curve25519_dalek::{ristretto::CompressedRistretto, scalar::Scalar};
+
+/// A hash commitment to a sequence of values.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct HashCommitment(pub Scalar);
+
+impl HashCommitment {
+    /// Commits to `values` with `blinding`.
+    pub fn commit(values: &[Scalar], blinding: &Scalar) -> HashCommitment {
+        let mut inputs = values.to_vec();
+        inputs.push(*blinding);
+        HashCommitment(mimc_hash(&inputs))
+    }
+
+    /// Whether `(values, blinding)` opens the commitment.
+    pub fn verify(&self, values: &[Scalar], blinding: &Scalar) -> bool {
+        HashCommitment::commit(values, blinding) == *self
+    }
+}
+
+impl Statement for HashCommitment {
+    const LABEL: &'static [u8] = b"Hash commitment opening";
+
+    fn encode(&self, out: &mut Vec<u8>) {
+        encode_scalar(out, &self.0);
+    }
+}
+
+/// Computes in-circuit the commitment to `values` with `blinding`. Takes
+/// `3 * ROUNDS` multipliers per value, plus as many for the blinding.
+pub fn hash_commitment_gadget(cs: &mut dyn CS, values: &[LC], blinding: LC) -> LC {
+    let mut inputs = values.to_vec();
+    inputs.push(blinding);
+    mimc_hash_gadget(cs, &inputs)
+}
+
+/// Constrains `(values, blinding)` to open `commitment`.
+pub fn hash_opening_gadget(cs: &mut dyn CS, commitment: LC, values: &[LC], blinding: LC) {
+    let c = hash_commitment_gadget(cs, values, blinding);
+    cs.constrain(commitment - c);
+}
+
+/// Proves knowledge of the opening `(values, blinding)` of `commitment`. The
+/// Pedersen commitments returned hide the values, followed by the blinding.
+#[cfg(feature = "prover")]
+pub fn prove_opening(
+    pc_gens: &PedersenGens,
+    bp_gens: &BulletproofGens,
+    commitment: &HashCommitment,
+    values: &[Scalar],
+    blinding: &Scalar,
+) -> Result<(R1CSProof, Vec<CompressedRistretto>), R1CSError> {
+    let mut transcript = commitment.transcript();
+    let mut prover = Prover::new(pc_gens, &mut transcript);
+    let (commitments, vars): (Vec<CompressedRistretto>, Vec<Variable>) = values
+        .iter()
+        .chain(std::iter::once(blinding))
+        .map(|v| prover.commit(*v, Scalar::random(&mut rand::thread_rng())))
+        .unzip();
+    let mut lcs: Vec<LC> = vars.into_iter().map(LC::from).collect();
+    let blinding = lcs.pop().unwrap();
+    hash_opening_gadget(&mut prover, commitment.0.into(), &lcs, blinding);
+    let proof = prover.prove(bp_gens)?;
+    Ok((proof, commitments))
+}
+
+/// Verifies that the values and the blinding behind `commitments` open
+/// `commitment`.
+pub fn verify_opening(
+    pc_gens: &PedersenGens,
+    bp_gens: &BulletproofGens,
+    commitment: &HashCommitment,
+    commitments: &[CompressedRistretto],
+    proof: &R1CSProof,
+) -> Result<(), R1CSError> {
+    if commitments.is_empty() {
+        return Err(R1CSError::VerificationError);
+    }
+    let mut transcript = commitment.transcript();
+    let mut verifier = Verifier::new(&mut transcript);
+    let mut lcs: Vec<LC> = commitments
+        .iter()
+        .map(|V| verifier.commit(*V).into())
+        .collect();
+    let blinding = lcs.pop().unwrap();
+    hash_opening_gadget(&mut verifier, commitment.0.into(), &lcs, blinding);
+    verify_proof!(verifier, proof, pc_gens, bp_gens)
+}
+
+#[cfg(feature = "prover")]
+mod test {
+    use super::*;
+    use crate::gadgets::hash::ROUNDS;
+    use crate::recording::RecordingCS;
+
+    fn random_scalar() -> Scalar {
+        Scalar::random(&mut rand::thread_rng())
+    }
+
+    #[test]
+    fn hash_commitment() {
+        let values = [random_scalar(), random_scalar()];
+        let blinding = random_scalar();
+        let c = HashCommitment::commit(&values, &blinding);
+        assert!(c.verify(&values, &blinding));
+        assert!(!c.verify(&values, &random_scalar()));
+        assert!(!c.verify(&[values[1], values[0]], &blinding));
+
+        let satisfied = |values: &[Scalar], blinding: Scalar| {
+            let mut cs = RecordingCS::new(b"HashCommitment");
+            let lcs: Vec<LC> = values.iter().map(|v| cs.commit(Some(*v)).into()).collect();
+            let r = cs.commit(Some(blinding));
+            hash_opening_gadget(&mut cs, c.0.into(), &lcs, r.into());
+            assert_eq!(cs.multipliers(), 3 * ROUNDS * (values.len() + 1));
+            cs.first_unsatisfied().is_none()
+        };
+        assert!(satisfied(&values, blinding));
+        assert!(!satisfied(&values, blinding + Scalar::one()));
+        assert!(!satisfied(&values[..1], blinding));
+    }
+
+    #[test]
+    fn opening_proof() {
+        let pc_gens = PedersenGens::default();
+        let bp_gens = BulletproofGens::new(1024, 1);
+        let (value, blinding) = (random_scalar(), random_scalar());
+        let c = HashCommitment::commit(&[value], &blinding);
+        let (proof, commitments) =
+            prove_opening(&pc_gens, &bp_gens, &c, &[value], &blinding).unwrap();
+        assert!(verify_opening(&pc_gens, &bp_gens, &c, &commitments, &proof).is_ok());
+        let other = HashCommitment::commit(&[value], &random_scalar());
+        assert!(verify_opening(&pc_gens, &bp_gens, &other, &commitments, &proof).is_err());
+    }
+}
@@ -0,0 +1,101 @@
+//! Uniqueness of the key images of a proof.
+//!
+//! A key image `I = sk * H_point(m)` (see `hash_to_point_mul_gadget`) is
+//! deterministic, so spending the same input twice gives the same image and
+//! is caught by the set of spent images. A proof spending several inputs at
+//! once, one ring membership per input, must also show that its own images
+//! are pairwise distinct, or it could spend an input twice in a single
+//! transaction.
+//!
+//! Points are compared through their affine coordinates, with a challenge
+//! `z` drawn from the transcript: the differences
+//! `d_ij = (x_i - x_j) + z * (y_i - y_j)` are all nonzero for distinct points
+//! but with negligible probability, and their product is constrained to be
+//! nonzero with a single inversion. Comparing `y` alone would not do, since
+//! `P` and `-P` share it.
+use crate::gadgets::point::edwards_point::SonnyEdwardsPointGadget;
+use crate::gadgets::scalar::{grand_product_gadget, nonzero_gadget};
+use crate::helpers::affine_coords;
+use crate::transcript::TranscriptBackend;
+use bulletproofs::r1cs::{ConstraintSystem as CS, LinearCombination as LC};
+use curve25519_dalek::scalar::Scalar;
+use zerocaf::edwards::EdwardsPoint as SonnyEdwardsPoint;
+
+/// Constrains the key `images` to be pairwise distinct. The prover provides
+/// their assignments, and must check beforehand that they are distinct: the
+/// inverse of the product of the differences does not exist otherwise.
+///
+/// Every commitment must have been made before calling the gadget, since the
+/// challenge is drawn from the transcript. Takes 3 multipliers per image to
+/// get its affine coordinates, plus one per pair of images.
+pub fn distinct_key_images_gadget(
+    cs: &mut dyn CS,
+    images: &[SonnyEdwardsPointGadget],
+    images_assign: Option<&[SonnyEdwardsPoint]>,
+) {
+    let coords: Vec<(LC, LC)> = images
+        .iter()
+        .enumerate()
+        .map(|(i, image)| {
+            let (x, y) = image.to_affine(images_assign.map(|a| a[i]), cs);
+            (x.into(), y.into())
+        })
+        .collect();
+    let coords_assign: Option<Vec<(Scalar, Scalar)>> =
+        images_assign.map(|a| a.iter().map(affine_coords).collect());
+
+    let z = {
+        let transcript: &mut dyn TranscriptBackend = cs.transcript();
+        transcript.append_u64(b"key-images", images.len() as u64);
+        transcript.challenge_scalar(b"key-images-challenge")
+    };
+    let mut diffs = Vec::new();
+    let mut product_assign = coords_assign.as_ref().map(|_| Scalar::one());
+    for i in 0..images.len() {
+        for j in i + 1..images.len() {
+            let (xi, yi) = coords[i].clone();
+            let (xj, yj) = coords[j].clone();
+            diffs.push(xi - xj + (yi - yj) * z);
+            if let (Some(p), Some(c)) = (product_assign.as_mut(), coords_assign.as_ref()) {
+                *p *= c[i].0 - c[j].0 + (c[i].1 - c[j].1) * z;
+            }
+        }
+    }
+    let product = grand_product_gadget(cs, &diffs);
+    nonzero_gadget(product, product_assign, cs);
+}
+
+mod test {
+    use super::*;
+    use crate::gadgets::point::witness;
+    use crate::recording::RecordingCS;
+
+    fn satisfied(images: &[SonnyEdwardsPoint]) -> bool {
+        let mut cs = RecordingCS::new(b"Key images");
+        let gadgets: Vec<SonnyEdwardsPointGadget> = images
+            .iter()
+            .map(SonnyEdwardsPointGadget::from_point)
+            .collect();
+        distinct_key_images_gadget(&mut cs, &gadgets, Some(images));
+        cs.first_unsatisfied().is_none()
+    }
+
+    #[test]
+    fn distinct_key_images() {
+        let mut rng = rand::thread_rng();
+        let images: Vec<SonnyEdwardsPoint> = (0..3)
+            .map(|_| SonnyEdwardsPoint::new_random_point(&mut rng))
+            .collect();
+        assert!(satisfied(&images));
+        // Opposite points share their y-coordinate.
+        assert!(satisfied(&[images[0], witness::edwards::neg(&images[0])]));
+    }
+
+    #[test]
+    #[should_panic(expected = "divide by 0")]
+    fn repeated_key_image() {
+        let image = SonnyEdwardsPoint::new_random_point(&mut rand::thread_rng());
+        let other = SonnyEdwardsPoint::new_random_point(&mut rand::thread_rng());
+        satisfied(&[image, other, image]);
+    }
+}
@@ -0,0 +1,109 @@
+//! Linear operations. Linear combinations are free in the R1CS: they are folded
+//! into the constraints that use them, so none of these take multipliers
+//! unless some of the operands are committed matrices.
+use bulletproofs::r1cs::{ConstraintSystem as CS, LinearCombination as LC};
+use curve25519_dalek::scalar::Scalar;
+
+/// Returns the LC of `sum(weights[i] * values[i])`. Takes no multipliers.
+///
+/// Panics if `values` and `weights` have different lengths.
+pub fn weighted_sum(_cs: &mut dyn CS, values: &[LC], weights: &[Scalar]) -> LC {
+    assert_eq!(values.len(), weights.len(), "one weight per value");
+    values
+        .iter()
+        .zip(weights)
+        .fold(LC::default(), |acc, (v, w)| acc + v.clone() * *w)
+}
+
+/// Constrains `sum(weights[i] * values[i]) = expected` with a single linear
+/// constraint.
+pub fn assert_weighted_sum(cs: &mut dyn CS, values: &[LC], weights: &[Scalar], expected: LC) {
+    let sum = weighted_sum(cs, values, weights);
+    cs.constrain(sum - expected);
+}
+
+/// Returns the LCs of `matrix * vector` for a public matrix, given by rows.
+/// Takes no multipliers.
+pub fn mat_vec_mul_gadget(cs: &mut dyn CS, matrix: &[&[Scalar]], vector: &[LC]) -> Vec<LC> {
+    matrix
+        .iter()
+        .map(|row| weighted_sum(cs, vector, row))
+        .collect()
+}
+
+/// Returns the LCs of `matrix * vector` for a committed matrix, given by rows.
+/// Takes one multiplier per entry of the matrix.
+///
+/// Panics if a row and `vector` have different lengths.
+pub fn committed_mat_vec_mul_gadget(
+    cs: &mut dyn CS,
+    matrix: &[Vec<LC>],
+    vector: &[LC],
+) -> Vec<LC> {
+    matrix
+        .iter()
+        .map(|row| {
+            assert_eq!(row.len(), vector.len(), "one entry per element of the vector");
+            row.iter().zip(vector).fold(LC::default(), |acc, (m, v)| {
+                let (_, _, prod) = cs.multiply(m.clone(), v.clone());
+                acc + prod
+            })
+        })
+        .collect()
+}
+
+mod test {
+    use super::*;
+    use crate::recording::RecordingCS;
+
+    #[test]
+    fn weighted_sum_is_free() {
+        let values: Vec<Scalar> = (1..4u64).map(Scalar::from).collect();
+        let weights: Vec<Scalar> = (4..7u64).map(Scalar::from).collect();
+        let mut cs = RecordingCS::new(b"Weighted sum");
+        let lcs: Vec<LC> = values.iter().map(|v| cs.commit(Some(*v)).into()).collect();
+
+        let sum = weighted_sum(&mut cs, &lcs, &weights);
+        // 1*4 + 2*5 + 3*6
+        assert_eq!(cs.eval(&sum), Some(Scalar::from(32u64)));
+        assert_weighted_sum(&mut cs, &lcs, &weights, Scalar::from(32u64).into());
+        assert_eq!(cs.multipliers(), 0);
+        assert_eq!(cs.constraints().len(), 1);
+        assert_eq!(cs.first_unsatisfied(), None);
+
+        assert_weighted_sum(&mut cs, &lcs, &weights, Scalar::from(33u64).into());
+        assert_eq!(cs.first_unsatisfied(), Some(1));
+    }
+
+    #[test]
+    fn mat_vec_mul() {
+        // [[1, 2], [3, 4]] * [5, 6] = [17, 39]
+        let matrix: Vec<Vec<Scalar>> = vec![
+            vec![Scalar::from(1u64), Scalar::from(2u64)],
+            vec![Scalar::from(3u64), Scalar::from(4u64)],
+        ];
+        let rows: Vec<&[Scalar]> = matrix.iter().map(|r| &r[..]).collect();
+        let mut cs = RecordingCS::new(b"Mat-vec mul");
+        let vector: Vec<LC> = [5u64, 6]
+            .iter()
+            .map(|v| cs.commit(Some(Scalar::from(*v))).into())
+            .collect();
+
+        let public = mat_vec_mul_gadget(&mut cs, &rows, &vector);
+        assert_eq!(cs.multipliers(), 0);
+
+        let committed: Vec<Vec<LC>> = matrix
+            .iter()
+            .map(|r| r.iter().map(|m| cs.commit(Some(*m)).into()).collect())
+            .collect();
+        let res = committed_mat_vec_mul_gadget(&mut cs, &committed, &vector);
+        assert_eq!(cs.multipliers(), 4);
+
+        for (p, c) in public.iter().zip(&res) {
+            assert_eq!(cs.eval(p), cs.eval(c));
+        }
+        assert_eq!(cs.eval(&public[0]), Some(Scalar::from(17u64)));
+        assert_eq!(cs.eval(&public[1]), Some(Scalar::from(39u64)));
+        assert_eq!(cs.first_unsatisfied(), None);
+    }
+}
@@ -0,0 +1,273 @@
+//! Merkle trees hashed with the MiMC hash of `gadgets::hash`.
+//!
+//! Leaves are `H(v)` and inner nodes `H(left, right)`. The tree is complete: the
+//! values are padded with zeros up to `2^depth`.
+use crate::errors::GadgetError;
+use crate::gadgets::hash::{mimc_hash, mimc_hash_gadget};
+use crate::gadgets::mux::{conditional_swap_gadget, mux_gadget};
+use crate::gadgets::typed::Bit;
+use bulletproofs::r1cs::{ConstraintSystem as CS, LinearCombination as LC};
+use curve25519_dalek::scalar::Scalar;
+use std::collections::HashMap;
+
+#[cfg(feature = "parallel")]
+use rayon::prelude::*;
+
+/// A complete Merkle tree over field elements.
+#[derive(Clone, Debug)]
+pub struct MerkleTree {
+    /// Every level of the tree, from the leaves up to the root.
+    levels: Vec<Vec<Scalar>>,
+}
+
+impl MerkleTree {
+    /// Builds a tree of the given depth. Panics if `values` does not fit in it.
+    /// With the `parallel` feature the nodes of every level are hashed
+    /// concurrently.
+    pub fn new(values: &[Scalar], depth: usize) -> MerkleTree {
+        assert!(values.len() <= 1 << depth, "too many values for the depth");
+        let leaf = |v: &Scalar| mimc_hash(&[*v]);
+        #[cfg(feature = "parallel")]
+        let mut leaves: Vec<Scalar> = values.par_iter().map(leaf).collect();
+        #[cfg(not(feature = "parallel"))]
+        let mut leaves: Vec<Scalar> = values.iter().map(leaf).collect();
+        leaves.resize(1 << depth, mimc_hash(&[Scalar::zero()]));
+        let mut levels = vec![leaves];
+        for _ in 0..depth {
+            let level = levels.last().unwrap();
+            #[cfg(feature = "parallel")]
+            let next = level.par_chunks(2).map(mimc_hash).collect();
+            #[cfg(not(feature = "parallel"))]
+            let next = level.chunks(2).map(mimc_hash).collect();
+            levels.push(next);
+        }
+        MerkleTree { levels }
+    }
+
+    pub fn depth(&self) -> usize {
+        self.levels.len() - 1
+    }
+
+    pub fn root(&self) -> Scalar {
+        self.levels[self.depth()][0]
+    }
+
+    /// Siblings of the leaf at `index`, from the leaves up.
+    pub fn path(&self, index: usize) -> Vec<Scalar> {
+        (0..self.depth())
+            .map(|level| self.levels[level][(index >> level) ^ 1])
+            .collect()
+    }
+}
+
+/// A sparse Merkle tree: a complete tree of the given depth storing only its
+/// non-empty nodes, so trees with far more leaves than could fit in memory
+/// (e.g. indexed by 64-bit keys) can be maintained.
+///
+/// Empty leaves hold zero like the padding of `MerkleTree`, so both trees have
+/// the same root for the same contents and share `merkle_root_gadget`.
+#[derive(Clone, Debug)]
+pub struct SparseMerkleTree {
+    /// Roots of the empty subtrees of every height, from the leaves up.
+    empty: Vec<Scalar>,
+    /// Non-empty nodes of every level, from the leaves up.
+    nodes: Vec<HashMap<usize, Scalar>>,
+}
+
+impl SparseMerkleTree {
+    /// Builds an empty tree of the given depth (at most 63).
+    pub fn new(depth: usize) -> SparseMerkleTree {
+        assert!(depth < 64, "the depth must fit the leaf indices");
+        let mut empty = vec![mimc_hash(&[Scalar::zero()])];
+        for level in 0..depth {
+            empty.push(mimc_hash(&[empty[level], empty[level]]));
+        }
+        SparseMerkleTree {
+            empty,
+            nodes: vec![HashMap::new(); depth + 1],
+        }
+    }
+
+    pub fn depth(&self) -> usize {
+        self.empty.len() - 1
+    }
+
+    fn node(&self, level: usize, index: usize) -> Scalar {
+        self.nodes[level]
+            .get(&index)
+            .cloned()
+            .unwrap_or(self.empty[level])
+    }
+
+    pub fn root(&self) -> Scalar {
+        self.node(self.depth(), 0)
+    }
+
+    /// Whether the leaf at `index` is non-empty.
+    pub fn contains(&self, index: usize) -> bool {
+        self.nodes[0].contains_key(&index)
+    }
+
+    /// Siblings of the leaf at `index`, from the leaves up.
+    pub fn path(&self, index: usize) -> Vec<Scalar> {
+        (0..self.depth())
+            .map(|level| self.node(level, (index >> level) ^ 1))
+            .collect()
+    }
+
+    /// Sets the leaf at `index` to `value`, unless it is already non-empty.
+    /// Returns whether the value was inserted.
+    pub fn insert(&mut self, index: usize, value: Scalar) -> bool {
+        assert!(index >> self.depth() == 0, "index out of the tree");
+        if self.contains(index) {
+            return false;
+        }
+        let mut node = mimc_hash(&[value]);
+        for level in 0..self.depth() {
+            let position = index >> level;
+            self.nodes[level].insert(position, node);
+            let sibling = self.node(level, position ^ 1);
+            node = match position & 1 {
+                0 => mimc_hash(&[node, sibling]),
+                _ => mimc_hash(&[sibling, node]),
+            };
+        }
+        self.nodes[self.depth()].insert(0, node);
+        true
+    }
+}
+
+/// Computes in-circuit the root of the tree holding `value` at the position
+/// given by `index_bits` (little-endian), with `siblings` from the leaves up.
+pub fn merkle_root_gadget(
+    cs: &mut dyn CS,
+    value: LC,
+    index_bits: &[Bit],
+    siblings: &[LC],
+) -> Result<LC, GadgetError> {
+    if siblings.len() != index_bits.len() {
+        return Err(GadgetError::InvalidLCsLength {
+            expected: index_bits.len(),
+            found: siblings.len(),
+        });
+    }
+    let mut node = mimc_hash_gadget(cs, &[value]);
+    for (bit, sibling) in index_bits.iter().zip(siblings) {
+        // bit = 0: the node is the left child.
+        let (left, right) = conditional_swap_gadget(cs, *bit, node, sibling.clone());
+        node = mimc_hash_gadget(cs, &[left, right]);
+    }
+    Ok(node)
+}
+
+/// Selects `roots[index]` with a tree of MUXes over the bits of the hidden
+/// `index`. The roots are padded to a power of two by repeating the last one.
+fn select_root_gadget(
+    cs: &mut dyn CS,
+    roots: &[Scalar],
+    index: LC,
+    index_assign: Option<usize>,
+) -> LC {
+    let n_bits = (roots.len() as u64).next_power_of_two().trailing_zeros() as usize;
+    let bits = Bit::decompose(
+        cs,
+        index,
+        index_assign.map(|i| Scalar::from(i as u64)),
+        n_bits,
+    );
+    let mut layer: Vec<LC> = (0..1 << n_bits)
+        .map(|i| LC::from(roots[i.min(roots.len() - 1)]))
+        .collect();
+    for bit in bits {
+        layer = layer
+            .chunks(2)
+            .map(|pair| mux_gadget(cs, bit, pair[0].clone(), pair[1].clone()))
+            .collect();
+    }
+    layer.pop().unwrap()
+}
+
+/// Constrains `value` to be in the tree of one of the public `roots` (e.g. the
+/// state snapshots of recent blocks) without revealing which one: the root is
+/// selected by the hidden `root_index`, and the leaf position by `index_bits`.
+pub fn merkle_forest_gadget(
+    cs: &mut dyn CS,
+    roots: &[Scalar],
+    root_index: LC,
+    root_index_assign: Option<usize>,
+    value: LC,
+    index_bits: &[Bit],
+    siblings: &[LC],
+) -> Result<(), GadgetError> {
+    assert!(!roots.is_empty(), "at least one root is needed");
+    let root = merkle_root_gadget(cs, value, index_bits, siblings)?;
+    let selected = select_root_gadget(cs, roots, root_index, root_index_assign);
+    cs.constrain(root - selected);
+    Ok(())
+}
+
+mod test {
+    use super::*;
+    use crate::recording::RecordingCS;
+
+    fn forest_satisfied(
+        trees: &[MerkleTree],
+        root_index: usize,
+        leaf_index: usize,
+        value: Scalar,
+        path: &[Scalar],
+    ) -> bool {
+        let roots: Vec<Scalar> = trees.iter().map(|t| t.root()).collect();
+        let mut cs = RecordingCS::new(b"Merkle forest");
+        let root_index_lc: LC = cs.commit(Some(Scalar::from(root_index as u64))).into();
+        let value_lc: LC = cs.commit(Some(value)).into();
+        let index_bits: Vec<Bit> = (0..path.len())
+            .map(|i| {
+                let bit = cs.commit(Some(Scalar::from(((leaf_index >> i) & 1) as u64)));
+                Bit::constrain(&mut cs, bit)
+            })
+            .collect();
+        let siblings: Vec<LC> = path.iter().map(|s| cs.commit(Some(*s)).into()).collect();
+        merkle_forest_gadget(
+            &mut cs,
+            &roots,
+            root_index_lc,
+            Some(root_index),
+            value_lc,
+            &index_bits,
+            &siblings,
+        )
+        .unwrap();
+        cs.first_unsatisfied().is_none()
+    }
+
+    #[test]
+    fn sparse_tree_matches_complete_tree() {
+        let values: Vec<Scalar> = (1..6).map(|v| Scalar::from(v as u64)).collect();
+        let tree = MerkleTree::new(&values, 3);
+        let mut sparse = SparseMerkleTree::new(3);
+        assert_eq!(sparse.root(), MerkleTree::new(&[], 3).root());
+        for (index, value) in values.iter().enumerate().rev() {
+            assert!(sparse.insert(index, *value));
+        }
+        assert!(!sparse.insert(2, Scalar::one()));
+        assert_eq!(sparse.root(), tree.root());
+        assert_eq!(sparse.path(6), tree.path(6));
+    }
+
+    #[test]
+    fn merkle_forest() {
+        let values: Vec<Vec<Scalar>> = (0..3)
+            .map(|t| (0..4).map(|i| Scalar::from(10 * t + i as u64)).collect())
+            .collect();
+        let trees: Vec<MerkleTree> = values.iter().map(|v| MerkleTree::new(v, 2)).collect();
+
+        assert!(forest_satisfied(&trees, 1, 2, values[1][2], &trees[1].path(2)));
+        assert!(forest_satisfied(&trees, 2, 0, values[2][0], &trees[2].path(0)));
+        // Right path, wrong root.
+        assert!(!forest_satisfied(&trees, 0, 2, values[1][2], &trees[1].path(2)));
+        // The padding slot holds the last root again.
+        assert!(forest_satisfied(&trees, 3, 0, values[2][0], &trees[2].path(0)));
+        assert!(!forest_satisfied(&trees, 3, 2, values[1][2], &trees[1].path(2)));
+    }
+}
@@ -1,4 +1,32 @@
+pub mod accumulator;
+pub mod adaptor;
+pub mod bits;
+pub mod blinding;
 pub mod boolean;
+pub mod commitment;
+pub mod denomination;
+pub mod elgamal;
+pub mod encoding;
+pub mod freshness;
+pub mod hash;
+pub mod hash_commitment;
+pub mod key_image;
+pub mod linear;
+pub mod merkle;
+pub mod musig;
+pub mod mux;
+pub mod nonce;
+pub mod output_digest;
+pub mod pedersen_hash;
+pub mod permutation;
 pub mod point;
+pub mod range;
+pub mod reduction;
 pub mod scalar;
+pub mod sigma;
+pub mod signature;
 pub mod sk_knowledge;
+pub mod sonny_scalar;
+pub mod split;
+pub mod typed;
+pub mod vector_commitment;
@@ -0,0 +1,168 @@
+//! MuSig-style key aggregation.
+//!
+//! For the cosigner keys `PK_1, ..., PK_n` the aggregated key is
+//! `PK_agg = sum(a_i * PK_i)` with `a_i = H(L, x_i, y_i)` and
+//! `L = H(x_1, y_1, ..., x_n, y_n)`, where `(x_i, y_i)` are the affine
+//! coordinates of `PK_i` and `H` is the MiMC hash of `gadgets::hash`.
+use crate::errors::GadgetError;
+use crate::gadgets::hash::{hash_to_scalar_gadget, mimc_hash, mimc_hash_gadget, scalar_bits};
+use crate::gadgets::point::edwards_point::SonnyEdwardsPointGadget;
+use crate::gadgets::point::witness;
+use crate::helpers::affine_coords;
+use bulletproofs::r1cs::{ConstraintSystem as CS, LinearCombination as LC};
+use curve25519_dalek::scalar::Scalar;
+use zerocaf::edwards::EdwardsPoint as SonnyEdwardsPoint;
+
+/// Returns the coefficients `a_i` of every key.
+pub fn key_coefficients(keys: &[SonnyEdwardsPoint]) -> Vec<Scalar> {
+    let coords: Vec<(Scalar, Scalar)> = keys.iter().map(affine_coords).collect();
+    let all: Vec<Scalar> = coords.iter().flat_map(|(x, y)| vec![*x, *y]).collect();
+    let L = mimc_hash(&all);
+    coords.iter().map(|(x, y)| mimc_hash(&[L, *x, *y])).collect()
+}
+
+/// Native version of `aggregated_key_gadget`, returning `PK_agg`.
+pub fn aggregate_keys(keys: &[SonnyEdwardsPoint]) -> SonnyEdwardsPoint {
+    let mut acc = witness::edwards::identity();
+    for (key, a) in keys.iter().zip(key_coefficients(keys)) {
+        let term = witness::edwards::scalar_mul(key, &scalar_bits(&a));
+        acc = witness::edwards::add(&acc, &term);
+    }
+    acc
+}
+
+/// Constrains `pk_agg` to be the aggregation of `keys` (see the module docs).
+/// The prover must provide `keys_assign`, since the coefficients are allocated.
+/// Fails if `keys_assign` does not hold one point per key.
+pub fn aggregated_key_gadget(
+    cs: &mut dyn CS,
+    keys: &[SonnyEdwardsPointGadget],
+    keys_assign: Option<&[SonnyEdwardsPoint]>,
+    pk_agg: &SonnyEdwardsPointGadget,
+) -> Result<(), GadgetError> {
+    if let Some(k) = keys_assign {
+        if k.len() != keys.len() {
+            return Err(GadgetError::InvalidLCsLength {
+                expected: keys.len(),
+                found: k.len(),
+            });
+        }
+    }
+    let key_assign = |i: usize| keys_assign.map(|k| k[i]);
+    let coords: Vec<(LC, LC)> = keys
+        .iter()
+        .enumerate()
+        .map(|(i, key)| {
+            let (x, y) = key.to_affine(key_assign(i), cs);
+            (x.into(), y.into())
+        })
+        .collect();
+    let coords_assign: Option<Vec<(Scalar, Scalar)>> =
+        keys_assign.map(|k| k.iter().map(affine_coords).collect());
+
+    // L = H(x_1, y_1, ..., x_n, y_n)
+    let all: Vec<LC> = coords
+        .iter()
+        .flat_map(|(x, y)| vec![x.clone(), y.clone()])
+        .collect();
+    let all_assign: Option<Vec<Scalar>> = coords_assign
+        .as_ref()
+        .map(|c| c.iter().flat_map(|(x, y)| vec![*x, *y]).collect());
+    let L = mimc_hash_gadget(cs, &all);
+    let L_assign = all_assign.as_ref().map(|a| mimc_hash(a));
+
+    // a_i = H(L, x_i, y_i)
+    let mut points = Vec::with_capacity(keys.len());
+    let mut scalars = Vec::with_capacity(keys.len());
+    for (i, (x, y)) in coords.into_iter().enumerate() {
+        let inputs_assign = L_assign.and_then(|L| {
+            coords_assign
+                .as_ref()
+                .map(|c| vec![L, c[i].0, c[i].1])
+        });
+        let bits = hash_to_scalar_gadget(cs, &[L.clone(), x, y], inputs_assign.as_deref());
+        let bits_assign = inputs_assign.map(|inputs| scalar_bits(&mimc_hash(&inputs)));
+        points.push((keys[i].clone(), key_assign(i)));
+        scalars.push((bits, bits_assign));
+    }
+
+    let (agg, _) = SonnyEdwardsPointGadget::multiscalar_mul(points, scalars, cs)?;
+    pk_agg.equal(&agg, cs);
+    Ok(())
+}
+
+#[cfg(feature = "prover")]
+mod test {
+    use super::*;
+    use crate::helpers;
+    use crate::recording::RecordingCS;
+    use crate::session;
+    use bulletproofs::r1cs::{Prover, R1CSError, Verifier};
+    use bulletproofs::{BulletproofGens, PedersenGens};
+
+    fn aggregated_key_roundtrip_helper(
+        keys: &[SonnyEdwardsPoint],
+        pk_agg: SonnyEdwardsPoint,
+    ) -> Result<(), R1CSError> {
+        let pc_gens = PedersenGens::default();
+        let bp_gens = BulletproofGens::new(32768, 1);
+
+        // Prover
+        let mut transcript = session::transcript(b"MuSig key aggregation");
+        let mut prover = Prover::new(&pc_gens, &mut transcript);
+        let (lcs, commitments) = helpers::prover_commit_points(&mut prover, keys);
+        let key_gadgets: Vec<SonnyEdwardsPointGadget> =
+            lcs.into_iter().map(|p| p.into()).collect();
+        let pk_agg_gadget = SonnyEdwardsPointGadget::from_point(&pk_agg);
+        aggregated_key_gadget(&mut prover, &key_gadgets, Some(keys), &pk_agg_gadget)?;
+        let proof = prover.prove(&bp_gens)?;
+
+        // Verifier
+        let mut transcript = session::transcript(b"MuSig key aggregation");
+        let mut verifier = Verifier::new(&mut transcript);
+        let key_gadgets: Vec<SonnyEdwardsPointGadget> =
+            helpers::verifier_commit_points(&mut verifier, &commitments, keys.len())?
+                .into_iter()
+                .map(|p| p.into())
+                .collect();
+        let pk_agg_gadget = SonnyEdwardsPointGadget::from_point(&pk_agg);
+        aggregated_key_gadget(&mut verifier, &key_gadgets, None, &pk_agg_gadget)?;
+        verify_proof!(verifier, &proof, &pc_gens, &bp_gens)
+    }
+
+    #[test]
+    fn aggregated_key() {
+        let keys: Vec<SonnyEdwardsPoint> = (0..2)
+            .map(|_| SonnyEdwardsPoint::new_random_point(&mut rand::thread_rng()))
+            .collect();
+        let pk_agg = aggregate_keys(&keys);
+        assert!(aggregated_key_roundtrip_helper(&keys, pk_agg).is_ok());
+        // Plain sum of the keys.
+        let sum = witness::edwards::add(&keys[0], &keys[1]);
+        assert!(aggregated_key_roundtrip_helper(&keys, sum).is_err());
+    }
+
+    #[test]
+    fn wrong_number_of_assignments() {
+        let keys: Vec<SonnyEdwardsPoint> = (0..2)
+            .map(|_| SonnyEdwardsPoint::new_random_point(&mut rand::thread_rng()))
+            .collect();
+        let key_gadgets: Vec<SonnyEdwardsPointGadget> = keys
+            .iter()
+            .map(SonnyEdwardsPointGadget::from_point)
+            .collect();
+        let mut cs = RecordingCS::new(b"MuSig key aggregation");
+        assert_eq!(
+            aggregated_key_gadget(
+                &mut cs,
+                &key_gadgets,
+                Some(&keys[..1]),
+                &SonnyEdwardsPointGadget::from_point(&aggregate_keys(&keys)),
+            ),
+            Err(GadgetError::InvalidLCsLength {
+                expected: 2,
+                found: 1
+            })
+        );
+    }
+}
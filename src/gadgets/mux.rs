@@ -0,0 +1,43 @@
+//! Selection gadgets driven by a (boolean-constrained) bit.
+use crate::gadgets::typed::Bit;
+use bulletproofs::r1cs::{ConstraintSystem as CS, LinearCombination as LC};
+
+/// Returns `a` if `bit = 0` and `b` if `bit = 1`, as `a + bit * (b - a)`.
+/// Takes a single multiplier.
+pub fn mux_gadget(cs: &mut dyn CS, bit: Bit, a: LC, b: LC) -> LC {
+    let (_, _, bit_t_diff) = cs.multiply(bit.into(), b - a.clone());
+    a + bit_t_diff
+}
+
+/// Returns `(a, b)` if `bit = 0` and `(b, a)` if `bit = 1`.
+/// Takes a single multiplier.
+pub fn conditional_swap_gadget(cs: &mut dyn CS, bit: Bit, a: LC, b: LC) -> (LC, LC) {
+    // Both outputs move by the same `bit * (b - a)`, in opposite directions.
+    let (_, _, delta) = cs.multiply(bit.into(), b.clone() - a.clone());
+    (a + delta, b - delta)
+}
+
+mod test {
+    use super::*;
+    use crate::recording::RecordingCS;
+    use curve25519_dalek::scalar::Scalar;
+
+    #[test]
+    fn mux_and_swap() {
+        let (a, b) = (Scalar::from(3u64), Scalar::from(5u64));
+        for bit in 0..2u64 {
+            let mut cs = RecordingCS::new(b"Mux");
+            let bit_var = cs.commit(Some(Scalar::from(bit)));
+            let a_lc: LC = cs.commit(Some(a)).into();
+            let b_lc: LC = cs.commit(Some(b)).into();
+            let bit_var = Bit::constrain(&mut cs, bit_var);
+
+            let out = mux_gadget(&mut cs, bit_var, a_lc.clone(), b_lc.clone());
+            let (l, r) = conditional_swap_gadget(&mut cs, bit_var, a_lc, b_lc);
+            let (expected_l, expected_r) = if bit == 0 { (a, b) } else { (b, a) };
+            assert_eq!(cs.eval(&out), Some(expected_r));
+            assert_eq!(cs.eval(&l), Some(expected_l));
+            assert_eq!(cs.eval(&r), Some(expected_r));
+        }
+    }
+}
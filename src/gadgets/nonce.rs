@@ -0,0 +1,135 @@
+//! Deterministic Schnorr nonces and the gadget proving their derivation.
+//!
+//! Instead of sampling it, the signer derives the nonce from its key and the
+//! message, RFC6979-style, with the MiMC hash of `gadgets::hash` as PRF:
+//! `k = H(sk, 0, m) + 2^253 * H(sk, 1, m) mod q`, with both hashes taken as
+//! canonical 253-bit integers. The double width makes the bias of `k` modulo
+//! the order `q` of the Sonny subgroup negligible, which matters for nonces.
+//!
+//! `nonce_derivation_gadget` proves that the `R` of a signature is `k * B` for
+//! that `k` and a committed `sk`, so auditors know that signing the same message
+//! twice can not reuse a nonce, without learning the key.
+use crate::gadgets::hash::{hash_to_scalar_gadget, mimc_hash, scalar_bits, SCALAR_BITS};
+use crate::gadgets::point::edwards_point::SonnyEdwardsPointGadget;
+use crate::gadgets::point::fixed_base::fixed_base_mul_gadget;
+use crate::gadgets::point::witness;
+use crate::gadgets::signature::{
+    basepoint, challenge, public_key, Signature, CHALLENGE_BITS, S_BITS,
+};
+use crate::helpers::{bits_to_bytes, sonny_scalar_bits, sonny_scalar_to_scalar};
+use crate::precomp::PrecompTables;
+use bulletproofs::r1cs::{ConstraintSystem as CS, LinearCombination as LC, Variable};
+use curve25519_dalek::scalar::Scalar;
+use zerocaf::edwards::EdwardsPoint as SonnyEdwardsPoint;
+use zerocaf::scalar::Scalar as SonnyScalar;
+
+/// Bits of the integer reduced into the nonce.
+pub const NONCE_BITS: usize = 2 * SCALAR_BITS;
+
+/// Window of the basepoint table used by `nonce_derivation_gadget`.
+pub const NONCE_WINDOW: usize = 4;
+
+fn prf_inputs(sk: Scalar, i: u64, message: &[Scalar]) -> Vec<Scalar> {
+    let mut inputs = vec![sk, Scalar::from(i)];
+    inputs.extend_from_slice(message);
+    inputs
+}
+
+/// Little-endian bits of the integer `H(sk, 0, m) + 2^253 * H(sk, 1, m)`.
+pub fn nonce_bits(sk: &SonnyScalar, message: &[Scalar]) -> Vec<u8> {
+    let sk = sonny_scalar_to_scalar(sk);
+    (0..2)
+        .flat_map(|i| scalar_bits(&mimc_hash(&prf_inputs(sk, i, message))))
+        .collect()
+}
+
+/// Derives the nonce of the signature of `message` under `sk`.
+pub fn derive_nonce(sk: &SonnyScalar, message: &[Scalar]) -> SonnyScalar {
+    // Horner over chunks of `CHALLENGE_BITS` bits, which are all below `q`.
+    let mut shift_bits = vec![0u8; CHALLENGE_BITS + 1];
+    shift_bits[CHALLENGE_BITS] = 1;
+    let shift = SonnyScalar::from_bytes(&bits_to_bytes(&shift_bits));
+    nonce_bits(sk, message)
+        .chunks(CHALLENGE_BITS)
+        .rev()
+        .fold(SonnyScalar::zero(), |k, chunk| {
+            k * shift + SonnyScalar::from_bytes(&bits_to_bytes(chunk))
+        })
+}
+
+/// Signs `message` with `sk` and the nonce of `derive_nonce`: signing the
+/// same message twice gives the same signature.
+pub fn sign_deterministic(sk: &SonnyScalar, message: &[Scalar]) -> Signature {
+    let k = derive_nonce(sk, message);
+    let R = witness::edwards::scalar_mul(&basepoint(), &sonny_scalar_bits(&k, S_BITS));
+    let c = SonnyScalar::from_bytes(&bits_to_bytes(&challenge(&R, &public_key(sk), message)));
+    Signature { R, s: k + c * *sk }
+}
+
+/// Constrains `R` to be `k * B` for the nonce `k` derived from the secret key
+/// `sk` (as a field element, see `helpers::sonny_scalar_to_scalar`) and
+/// `message`. The basepoint table is taken from `cache`.
+///
+/// Along with `schnorr_verify_gadget` on the same `R` and a proof that `sk`
+/// is the key of `PK`, this shows that the signature used a deterministic nonce.
+pub fn nonce_derivation_gadget(
+    cs: &mut dyn CS,
+    cache: &PrecompTables,
+    sk: LC,
+    sk_assign: Option<Scalar>,
+    message: &[LC],
+    message_assign: Option<&[Scalar]>,
+    R: &SonnyEdwardsPointGadget,
+) {
+    let mut k_bits: Vec<Variable> = Vec::with_capacity(NONCE_BITS);
+    for i in 0..2 {
+        let mut inputs = vec![sk.clone(), LC::from(Scalar::from(i))];
+        inputs.extend_from_slice(message);
+        let inputs_assign = sk_assign.and_then(|sk| message_assign.map(|m| prf_inputs(sk, i, m)));
+        k_bits.extend(hash_to_scalar_gadget(cs, &inputs, inputs_assign.as_deref()));
+    }
+    // The multiplication reduces the integer modulo the order of B.
+    let table = cache.get(&basepoint(), NONCE_WINDOW, NONCE_BITS);
+    let kB = fixed_base_mul_gadget(cs, &table, &k_bits);
+    kB.equal(R, cs);
+}
+
+mod test {
+    use super::*;
+    use crate::gadgets::signature::sign;
+    use crate::recording::RecordingCS;
+
+    fn derivation_satisfied(sk: &SonnyScalar, message: &[Scalar], R: &SonnyEdwardsPoint) -> bool {
+        let mut cs = RecordingCS::new(b"Nonce");
+        let sk_assign = sonny_scalar_to_scalar(sk);
+        let sk_lc: LC = cs.commit(Some(sk_assign)).into();
+        let message_lcs: Vec<LC> = message.iter().map(|m| cs.commit(Some(*m)).into()).collect();
+        nonce_derivation_gadget(
+            &mut cs,
+            &PrecompTables::new(),
+            sk_lc,
+            Some(sk_assign),
+            &message_lcs,
+            Some(message),
+            &SonnyEdwardsPointGadget::from_point(R),
+        );
+        cs.first_unsatisfied().is_none()
+    }
+
+    #[test]
+    fn deterministic_nonce() {
+        let mut rng = rand::thread_rng();
+        let sk = SonnyScalar::random(&mut rng);
+        let message = [Scalar::from(1u64), Scalar::from(2u64)];
+
+        let sig = sign_deterministic(&sk, &message);
+        assert_eq!(sig.s, sign_deterministic(&sk, &message).s);
+        assert!(derivation_satisfied(&sk, &message, &sig.R));
+
+        // The nonce of another message, and a random nonce.
+        let other = sign_deterministic(&sk, &[Scalar::from(3u64)]);
+        assert!(!derivation_satisfied(&sk, &message, &other.R));
+        let random = sign(&sk, &message, &mut rng);
+        assert!(!derivation_satisfied(&sk, &message, &random.R));
+    }
+}
@@ -0,0 +1,121 @@
+//! Digest of the public outputs of a transaction, binding a proof to them.
+//!
+//! Every output is described by the commitment to its amount and the address
+//! (a Sonny point) it pays to. The digest is the MiMC hash (see
+//! `gadgets::hash`) of the number of outputs followed by, for every output,
+//! the two 128-bit halves of its commitment and the affine coordinates of its
+//! address. `output_digest_gadget` recomputes it in-circuit and constrains it
+//! to the public digest, so a proof made for some outputs does not verify
+//! against altered, added, removed or reordered ones.
+use crate::gadgets::hash::{mimc_hash, mimc_hash_gadget};
+use crate::helpers::affine_coords;
+use bulletproofs::r1cs::{ConstraintSystem as CS, LinearCombination as LC};
+use curve25519_dalek::{ristretto::CompressedRistretto, scalar::Scalar};
+use zerocaf::edwards::EdwardsPoint as SonnyEdwardsPoint;
+
+/// A public output of a transaction.
+#[derive(Clone, Copy, Debug)]
+pub struct OutputDescriptor {
+    pub commitment: CompressedRistretto,
+    pub address: SonnyEdwardsPoint,
+}
+
+impl OutputDescriptor {
+    /// Field elements the output is hashed as.
+    pub fn to_scalars(&self) -> [Scalar; 4] {
+        let bytes = self.commitment.as_bytes();
+        let half = |h: &[u8]| {
+            let mut buf = [0u8; 32];
+            buf[..16].copy_from_slice(h);
+            Scalar::from_bytes_mod_order(buf)
+        };
+        let (x, y) = affine_coords(&self.address);
+        [half(&bytes[..16]), half(&bytes[16..]), x, y]
+    }
+}
+
+/// The output in-circuit, as the LCs of `OutputDescriptor::to_scalars`. Public
+/// outputs are constants; an address can also be the affine coordinates of a
+/// point of the circuit (see `SonnyEdwardsPointGadget::to_affine`).
+#[derive(Clone, Debug)]
+pub struct OutputDescriptorGadget {
+    pub commitment: [LC; 2],
+    pub address: (LC, LC),
+}
+
+impl OutputDescriptorGadget {
+    pub fn from_descriptor(output: &OutputDescriptor) -> OutputDescriptorGadget {
+        let [c0, c1, x, y] = output.to_scalars();
+        OutputDescriptorGadget {
+            commitment: [c0.into(), c1.into()],
+            address: (x.into(), y.into()),
+        }
+    }
+}
+
+/// Native digest of `outputs`.
+pub fn output_digest(outputs: &[OutputDescriptor]) -> Scalar {
+    let mut inputs = vec![Scalar::from(outputs.len() as u64)];
+    for output in outputs {
+        inputs.extend_from_slice(&output.to_scalars());
+    }
+    mimc_hash(&inputs)
+}
+
+/// Computes the digest of `outputs` in-circuit and constrains it to be the
+/// public `digest`. Returns the LC of the digest, e.g. to be signed.
+pub fn output_digest_gadget(
+    cs: &mut dyn CS,
+    outputs: &[OutputDescriptorGadget],
+    digest: Scalar,
+) -> LC {
+    let mut inputs = vec![LC::from(Scalar::from(outputs.len() as u64))];
+    for output in outputs {
+        inputs.extend_from_slice(&output.commitment);
+        inputs.push(output.address.0.clone());
+        inputs.push(output.address.1.clone());
+    }
+    let res = mimc_hash_gadget(cs, &inputs);
+    cs.constrain(res.clone() - digest);
+    res
+}
+
+mod test {
+    use super::*;
+    use crate::recording::RecordingCS;
+    use bulletproofs::PedersenGens;
+
+    fn random_output() -> OutputDescriptor {
+        let mut rng = rand::thread_rng();
+        OutputDescriptor {
+            commitment: PedersenGens::default()
+                .commit(Scalar::random(&mut rng), Scalar::random(&mut rng))
+                .compress(),
+            address: SonnyEdwardsPoint::new_random_point(&mut rng),
+        }
+    }
+
+    fn satisfied(outputs: &[OutputDescriptor], digest: Scalar) -> bool {
+        let mut cs = RecordingCS::new(b"Output digest");
+        let gadgets: Vec<OutputDescriptorGadget> = outputs
+            .iter()
+            .map(OutputDescriptorGadget::from_descriptor)
+            .collect();
+        output_digest_gadget(&mut cs, &gadgets, digest);
+        cs.first_unsatisfied().is_none()
+    }
+
+    #[test]
+    fn binding_digest() {
+        let outputs = vec![random_output(), random_output()];
+        let digest = output_digest(&outputs);
+        assert!(satisfied(&outputs, digest));
+
+        let mut altered = outputs.clone();
+        altered[1].address = random_output().address;
+        assert!(!satisfied(&altered, digest));
+        let reordered = vec![outputs[1], outputs[0]];
+        assert!(!satisfied(&reordered, digest));
+        assert!(!satisfied(&outputs[..1], digest));
+    }
+}
@@ -0,0 +1,146 @@
+//! Windowed Pedersen hash over the Sonny curve, with personalization.
+//!
+//! The message bits are split into segments of `SEGMENT_BITS` bits, and the
+//! hash is `H(m) = m_0 * G_0 + m_1 * G_1 + ...`, where `m_i` is the `i`-th
+//! segment read as a little-endian scalar. Every term is a windowed fixed-base
+//! multiplication (see `gadgets::point::fixed_base`), so the message bits
+//! only cost their window lookups and one point addition per window.
+//!
+//! The generators are sampled from an RNG seeded by the personalization, as
+//! `SonnyPedersenGens` does. Hashers with different personalizations use
+//! independent generators: a collision across domains, like one within a
+//! domain, would give a discrete-log relation between them.
+//!
+//! Trailing zero bits do not change the hash, so within a domain either the
+//! messages have a fixed length or their length is part of the message.
+use crate::gadgets::point::edwards_point::SonnyEdwardsPointGadget;
+use crate::gadgets::point::fixed_base::fixed_base_mul_gadget;
+use crate::gadgets::point::witness;
+use crate::precomp::FixedBaseTable;
+use bulletproofs::r1cs::{ConstraintSystem as CS, Variable};
+use merlin::Transcript;
+use rand::rngs::StdRng;
+use rand::SeedableRng;
+use zerocaf::edwards::EdwardsPoint as SonnyEdwardsPoint;
+use zerocaf::ristretto::RistrettoPoint as SonnyRistrettoPoint;
+
+/// Bits of every segment. Lower than the bits of the order of the generators,
+/// so distinct segments give distinct multiples.
+pub const SEGMENT_BITS: usize = 248;
+
+/// Pedersen hash of a domain, for messages of up to a fixed number of bits.
+#[derive(Clone, Debug)]
+pub struct PedersenHash {
+    personalization: Vec<u8>,
+    generators: Vec<SonnyEdwardsPoint>,
+    tables: Vec<FixedBaseTable>,
+}
+
+impl PedersenHash {
+    /// Creates the hash of the domain `personalization` for messages of up to
+    /// `max_bits` bits, precomputing the tables of its generators with windows
+    /// of `window` bits.
+    pub fn new(personalization: &[u8], max_bits: usize, window: usize) -> PedersenHash {
+        let mut transcript = Transcript::new(b"bulletproofs_gadgets PedersenHash");
+        transcript.append_message(b"personalization", personalization);
+        let mut seed = [0u8; 32];
+        transcript.challenge_bytes(b"seed", &mut seed);
+        let mut rng = StdRng::from_seed(seed);
+        let segments = (max_bits + SEGMENT_BITS - 1) / SEGMENT_BITS;
+        let generators: Vec<SonnyEdwardsPoint> = (0..segments)
+            .map(|_| SonnyRistrettoPoint::new_random_point(&mut rng).0)
+            .collect();
+        let tables = generators
+            .iter()
+            .map(|g| FixedBaseTable::new(g, window, SEGMENT_BITS))
+            .collect();
+        PedersenHash {
+            personalization: personalization.to_vec(),
+            generators,
+            tables,
+        }
+    }
+
+    pub fn personalization(&self) -> &[u8] {
+        &self.personalization
+    }
+
+    /// Generators of the segments, in order.
+    pub fn generators(&self) -> &[SonnyEdwardsPoint] {
+        &self.generators
+    }
+
+    /// Maximum number of bits of the messages.
+    pub fn max_bits(&self) -> usize {
+        self.generators.len() * SEGMENT_BITS
+    }
+
+    /// Hashes the little-endian `bits`. This is the native version of
+    /// `hash_gadget`, giving the same extended coordinates.
+    pub fn hash(&self, bits: &[u8]) -> SonnyEdwardsPoint {
+        assert!(bits.len() <= self.max_bits(), "message too long");
+        let mut terms = bits
+            .chunks(SEGMENT_BITS)
+            .zip(&self.tables)
+            .map(|(segment, table)| witness::edwards::fixed_base_mul(table, segment));
+        let first = terms.next().unwrap_or_else(witness::edwards::identity);
+        terms.fold(first, |acc, term| witness::edwards::add(&acc, &term))
+    }
+
+    /// Computes in-circuit the hash of the message given by its allocated
+    /// little-endian bits, which are boolean-constrained here.
+    pub fn hash_gadget(&self, cs: &mut dyn CS, bits: &[Variable]) -> SonnyEdwardsPointGadget {
+        assert!(bits.len() <= self.max_bits(), "message too long");
+        let mut acc: Option<SonnyEdwardsPointGadget> = None;
+        for (segment, table) in bits.chunks(SEGMENT_BITS).zip(&self.tables) {
+            let term = fixed_base_mul_gadget(cs, table, segment);
+            acc = Some(match acc {
+                None => term,
+                Some(acc) => acc.add(&term, cs),
+            });
+        }
+        acc.unwrap_or_else(SonnyEdwardsPointGadget::identity)
+    }
+}
+
+mod test {
+    use super::*;
+    use crate::helpers::{affine_coords, PointCoords};
+    use crate::recording::RecordingCS;
+    use curve25519_dalek::scalar::Scalar;
+
+    #[test]
+    fn hash_gadget_matches_native() {
+        let hasher = PedersenHash::new(b"test domain", 300, 4);
+        let bits: Vec<u8> = (0..300).map(|_| rand::random::<bool>() as u8).collect();
+        let expected = hasher.hash(&bits);
+
+        let mut cs = RecordingCS::new(b"Pedersen hash");
+        let vars: Vec<Variable> = bits
+            .iter()
+            .map(|b| cs.commit(Some(Scalar::from(*b))))
+            .collect();
+        let res = hasher.hash_gadget(&mut cs, &vars);
+        assert_eq!(cs.eval(&res.X), Some(expected.coords()[0]));
+        assert_eq!(cs.eval(&res.Y), Some(expected.coords()[1]));
+        assert_eq!(cs.eval(&res.Z), Some(expected.coords()[2]));
+        assert_eq!(cs.eval(&res.T), Some(expected.coords()[3]));
+        assert_eq!(cs.first_unsatisfied(), None);
+    }
+
+    #[test]
+    fn personalization() {
+        let bits: Vec<u8> = (0..64).map(|i| (i % 3 == 0) as u8).collect();
+        let a = PedersenHash::new(b"domain a", 64, 4);
+        let b = PedersenHash::new(b"domain b", 64, 4);
+        assert_eq!(
+            affine_coords(&a.hash(&bits)),
+            affine_coords(&PedersenHash::new(b"domain a", 64, 2).hash(&bits))
+        );
+        assert_ne!(affine_coords(&a.hash(&bits)), affine_coords(&b.hash(&bits)));
+        assert_ne!(
+            affine_coords(&a.generators()[0]),
+            affine_coords(&b.generators()[0])
+        );
+    }
+}
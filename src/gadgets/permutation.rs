@@ -0,0 +1,110 @@
+//! Permutation argument: constrains two lists of tuples to hold the same
+//! elements, in any order.
+//!
+//! Every tuple `t` is compressed into `f(t) = t_0 + w * t_1 + w^2 * t_2 + ...`
+//! and the lists are compared through `prod(z - f(a_i)) = prod(z - f(b_i))`,
+//! with `w` and `z` drawn from the transcript after every commitment. By the
+//! Schwartz–Zippel lemma both products only match for distinct multisets with
+//! probability `~ n * k / l` (`n` tuples of `k` elements). The check takes
+//! `2 * (n - 1)` multipliers.
+use crate::errors::GadgetError;
+use crate::gadgets::scalar::grand_product_gadget;
+use crate::transcript::TranscriptBackend;
+use bulletproofs::r1cs::{ConstraintSystem as CS, LinearCombination as LC};
+use curve25519_dalek::scalar::Scalar;
+
+/// Constrains `b` to be a permutation of `a`. Every tuple must have the same
+/// number of elements.
+///
+/// Every commitment must have been made before calling the gadget, since the
+/// challenges are drawn from the transcript.
+pub fn permutation_gadget(
+    cs: &mut dyn CS,
+    a: &[Vec<LC>],
+    b: &[Vec<LC>],
+) -> Result<(), GadgetError> {
+    if b.len() != a.len() {
+        return Err(GadgetError::InvalidLCsLength {
+            expected: a.len(),
+            found: b.len(),
+        });
+    }
+    let width = a.first().map_or(0, |t| t.len());
+    if let Some(t) = a.iter().chain(b).find(|t| t.len() != width) {
+        return Err(GadgetError::InvalidLCsLength {
+            expected: width,
+            found: t.len(),
+        });
+    }
+
+    let transcript: &mut dyn TranscriptBackend = cs.transcript();
+    transcript.append_u64(b"permutation-size", a.len() as u64);
+    transcript.append_u64(b"permutation-width", width as u64);
+    let w = transcript.challenge_scalar(b"permutation-compression");
+    let z = transcript.challenge_scalar(b"permutation-challenge");
+
+    let factors = |tuples: &[Vec<LC>]| -> Vec<LC> {
+        tuples
+            .iter()
+            .map(|t| {
+                let mut f = LC::from(z);
+                let mut w_pow = Scalar::one();
+                for x in t {
+                    f = f - x.clone() * w_pow;
+                    w_pow *= w;
+                }
+                f
+            })
+            .collect()
+    };
+    let (a_factors, b_factors) = (factors(a), factors(b));
+    let a_product = grand_product_gadget(cs, &a_factors);
+    let b_product = grand_product_gadget(cs, &b_factors);
+    cs.constrain(a_product - b_product);
+    Ok(())
+}
+
+mod test {
+    use super::*;
+    use crate::recording::RecordingCS;
+
+    fn is_permutation(a: &[[u64; 2]], b: &[[u64; 2]]) -> bool {
+        let mut cs = RecordingCS::new(b"Permutation");
+        let mut commit = |tuples: &[[u64; 2]]| -> Vec<Vec<LC>> {
+            tuples
+                .iter()
+                .map(|t| {
+                    t.iter()
+                        .map(|x| cs.commit(Some(Scalar::from(*x))).into())
+                        .collect()
+                })
+                .collect()
+        };
+        let (a, b) = (commit(a), commit(b));
+        permutation_gadget(&mut cs, &a, &b).unwrap();
+        cs.first_unsatisfied().is_none()
+    }
+
+    #[test]
+    fn permutation() {
+        let a = [[1, 2], [3, 4], [5, 6], [3, 4]];
+        assert!(is_permutation(&a, &[[3, 4], [5, 6], [3, 4], [1, 2]]));
+        // Same elements, split differently into tuples.
+        assert!(!is_permutation(&a, &[[2, 1], [3, 4], [5, 6], [3, 4]]));
+        // Same set, different multiplicities.
+        assert!(!is_permutation(&a, &[[1, 2], [3, 4], [5, 6], [5, 6]]));
+    }
+
+    #[test]
+    fn permutation_lengths() {
+        let mut cs = RecordingCS::new(b"Permutation");
+        let one = || vec![LC::from(Scalar::one())];
+        assert_eq!(
+            permutation_gadget(&mut cs, &[one(), one()], &[one()]),
+            Err(GadgetError::InvalidLCsLength {
+                expected: 2,
+                found: 1
+            })
+        );
+    }
+}
@@ -0,0 +1,166 @@
+//! Double-and-add chains for multiplications by public constant scalars.
+//!
+//! Multiplying a point by a public constant (the cofactor, a small constant)
+//! does not need a scalar-multiplication ladder over allocated bits: the
+//! constant is known when the circuit is built, so the sequence of doublings
+//! and additions can be fixed then, and chosen to be cheap.
+//!
+//! A run of `n` doublings takes `7n + 1` multipliers (see `mul_by_pow2`), an
+//! addition 12, and negating a point is free, so subtracting the base point
+//! costs as much as adding it. The chain is built over signed binary digits:
+//! `15` is better computed as `16P - P` than as `8P + 4P + 2P + P`. A tripling
+//! costs as much as a doubling followed by an addition with these formulas, so
+//! base 3 steps would not make a chain any cheaper.
+
+/// Multipliers of a doubling within a run, and of starting a run.
+const DOUBLING_MULTIPLIERS: usize = 7;
+const RUN_MULTIPLIERS: usize = 1;
+
+/// Multipliers of an addition.
+const ADDITION_MULTIPLIERS: usize = 12;
+
+/// A step of a chain, applied to an accumulator starting at the base point.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ChainStep {
+    /// Doubles the accumulator the given number of times.
+    Double(usize),
+    /// Adds the base point to the accumulator.
+    Add,
+    /// Subtracts the base point from the accumulator.
+    Sub,
+}
+
+/// Returns the chain computing `[k]P` from `P` (`k >= 1`) that takes the fewest
+/// multipliers among the signed binary chains.
+///
+/// The signed digits are chosen from the least significant one, keeping the
+/// cheapest digits for each of the (at most two) values left to represent.
+pub fn double_add_chain(k: u64) -> Vec<ChainStep> {
+    assert!(k >= 1);
+    // Values left to represent, with the cost and the digits used so far.
+    let mut states: Vec<(u128, usize, Vec<i8>)> = vec![(k as u128, 0, Vec::new())];
+    let mut best: Option<(usize, Vec<i8>)> = None;
+    while !states.is_empty() {
+        let mut next: Vec<(u128, usize, Vec<i8>)> = Vec::new();
+        for (n, cost, digits) in states {
+            let i = digits.len();
+            let starts_run = if i > 0 { RUN_MULTIPLIERS } else { 0 };
+            if n == 1 {
+                // The leading digit, followed by the first run of doublings.
+                let cost = cost + starts_run;
+                if best.as_ref().map_or(true, |(c, _)| cost < *c) {
+                    let mut digits = digits;
+                    digits.push(1);
+                    best = Some((cost, digits));
+                }
+                continue;
+            }
+            let candidates: &[i8] = if n % 2 == 0 { &[0] } else { &[1, -1] };
+            for d in candidates {
+                let m = match *d {
+                    0 => n / 2,
+                    1 => (n - 1) / 2,
+                    _ => (n + 1) / 2,
+                };
+                let mut cost = cost + DOUBLING_MULTIPLIERS;
+                if *d != 0 {
+                    // The addition is followed by a new run of doublings.
+                    cost += ADDITION_MULTIPLIERS + starts_run;
+                }
+                match next.iter_mut().find(|(v, _, _)| *v == m) {
+                    Some(state) if state.1 <= cost => {}
+                    Some(state) => {
+                        let mut digits = digits.clone();
+                        digits.push(*d);
+                        *state = (m, cost, digits);
+                    }
+                    None => {
+                        let mut digits = digits.clone();
+                        digits.push(*d);
+                        next.push((m, cost, digits));
+                    }
+                }
+            }
+        }
+        states = next;
+    }
+
+    let (_, digits) = best.unwrap();
+    let mut steps = Vec::new();
+    let mut doublings = 0;
+    for d in digits.iter().rev().skip(1) {
+        doublings += 1;
+        if *d != 0 {
+            steps.push(ChainStep::Double(doublings));
+            steps.push(if *d == 1 {
+                ChainStep::Add
+            } else {
+                ChainStep::Sub
+            });
+            doublings = 0;
+        }
+    }
+    if doublings > 0 {
+        steps.push(ChainStep::Double(doublings));
+    }
+    steps
+}
+
+/// Multipliers the chain takes in-circuit.
+pub fn chain_multipliers(steps: &[ChainStep]) -> usize {
+    steps
+        .iter()
+        .map(|step| match step {
+            ChainStep::Double(n) => DOUBLING_MULTIPLIERS * n + RUN_MULTIPLIERS,
+            ChainStep::Add | ChainStep::Sub => ADDITION_MULTIPLIERS,
+        })
+        .sum()
+}
+
+mod test {
+    use super::*;
+
+    fn eval(steps: &[ChainStep]) -> u128 {
+        steps.iter().fold(1, |acc, step| match step {
+            ChainStep::Double(n) => acc << n,
+            ChainStep::Add => acc + 1,
+            ChainStep::Sub => acc - 1,
+        })
+    }
+
+    /// The chain of the unsigned binary digits of `k`.
+    fn binary_chain(k: u64) -> Vec<ChainStep> {
+        let mut steps = Vec::new();
+        let mut doublings = 0;
+        for i in (0..63 - k.leading_zeros() as usize).rev() {
+            doublings += 1;
+            if (k >> i) & 1 == 1 {
+                steps.push(ChainStep::Double(doublings));
+                steps.push(ChainStep::Add);
+                doublings = 0;
+            }
+        }
+        if doublings > 0 {
+            steps.push(ChainStep::Double(doublings));
+        }
+        steps
+    }
+
+    #[test]
+    fn chains() {
+        for k in (1..300).chain(vec![u64::max_value() - 1, u64::max_value()]) {
+            let chain = double_add_chain(k);
+            assert_eq!(eval(&chain), k as u128);
+            assert_eq!(eval(&binary_chain(k)), k as u128);
+            assert!(chain_multipliers(&chain) <= chain_multipliers(&binary_chain(k)));
+        }
+        assert_eq!(double_add_chain(1), vec![]);
+        assert_eq!(double_add_chain(8), vec![ChainStep::Double(3)]);
+        assert_eq!(
+            double_add_chain(15),
+            vec![ChainStep::Double(4), ChainStep::Sub]
+        );
+        assert_eq!(chain_multipliers(&double_add_chain(15)), 41);
+        assert_eq!(chain_multipliers(&binary_chain(15)), 60);
+    }
+}
@@ -0,0 +1,227 @@
+//! Fixed-base multiplication with multi-comb tables, including tables of the
+//! Sonny basepoint and of the Pedersen blinding generator `H` that are
+//! precomputed by the build script and embedded in the crate.
+//!
+//! A comb table with `t` teeth and spacing `d` splits the scalar bits into
+//! combs of `t` bits, `d` positions apart: entry `j` of comb `c` is
+//! `sum_i j_i * 2^((c * t + i) * d) * P`. The multiplication runs over the `d`
+//! columns, doubling once per column and adding one entry per comb, selected
+//! in-circuit as in `fixed_base` (`2^t - t - 1` multipliers per lookup).
+//!
+//! Inside the circuit every doubling costs multipliers, while the number of
+//! lookups does not depend on `d`, so the embedded tables use spacing 1 (no
+//! doublings at all). Larger spacings build smaller tables, which may pay off
+//! for tables created at runtime with `CombTable::new`.
+use crate::gadgets::boolean::binary_constrain_gadget;
+use crate::gadgets::point::edwards_point::SonnyEdwardsPointGadget;
+use crate::gadgets::point::fixed_base::lookup_gadget;
+use crate::precomp::window_index;
+use bulletproofs::r1cs::{ConstraintSystem as CS, Variable};
+use zerocaf::edwards::EdwardsPoint as SonnyEdwardsPoint;
+use zerocaf::field::FieldElement;
+use zerocaf::traits::ops::Double;
+use zerocaf::traits::Identity;
+
+include!(concat!(env!("OUT_DIR"), "/comb_tables.rs"));
+
+/// Comb table of a fixed base point.
+#[derive(Clone, Debug)]
+pub struct CombTable {
+    pub teeth: usize,
+    pub spacing: usize,
+    /// `combs[c][j] = sum_i j_i * 2^((c * teeth + i) * spacing) * P`.
+    pub combs: Vec<Vec<SonnyEdwardsPoint>>,
+}
+
+impl CombTable {
+    /// Precomputes the table of `base` for scalars of up to `bits` bits.
+    pub fn new(base: &SonnyEdwardsPoint, teeth: usize, spacing: usize, bits: usize) -> CombTable {
+        assert!(teeth > 0 && teeth < 16 && spacing > 0);
+        let n_combs = (bits + teeth * spacing - 1) / (teeth * spacing);
+        let mut tooth_bases = Vec::with_capacity(n_combs * teeth);
+        let mut tooth_base = *base;
+        for _ in 0..n_combs * teeth {
+            tooth_bases.push(tooth_base);
+            for _ in 0..spacing {
+                tooth_base = tooth_base.double();
+            }
+        }
+        let combs = tooth_bases
+            .chunks(teeth)
+            .map(|bases| {
+                let mut comb = vec![SonnyEdwardsPoint::identity(); 1 << teeth];
+                for j in 1..(1usize << teeth) {
+                    let top = (usize::BITS - 1 - j.leading_zeros()) as usize;
+                    comb[j] = comb[j ^ (1 << top)] + bases[top];
+                }
+                comb
+            })
+            .collect();
+        CombTable {
+            teeth,
+            spacing,
+            combs,
+        }
+    }
+
+    /// The embedded table of the Sonny basepoint (see `signature::basepoint`).
+    pub fn basepoint() -> CombTable {
+        CombTable::embedded(&BASEPOINT_COMB)
+    }
+
+    /// The embedded table of the blinding generator of `SonnyPedersenGens`.
+    pub fn blinding() -> CombTable {
+        CombTable::embedded(&BLINDING_COMB)
+    }
+
+    fn embedded(combs: &[[[[u8; 32]; 4]; COMB_ENTRIES]]) -> CombTable {
+        let point = |p: &[[u8; 32]; 4]| SonnyEdwardsPoint {
+            X: FieldElement::from_bytes(&p[0]),
+            Y: FieldElement::from_bytes(&p[1]),
+            Z: FieldElement::from_bytes(&p[2]),
+            T: FieldElement::from_bytes(&p[3]),
+        };
+        CombTable {
+            teeth: COMB_TEETH,
+            spacing: COMB_SPACING,
+            combs: combs
+                .iter()
+                .map(|comb| comb.iter().map(point).collect())
+                .collect(),
+        }
+    }
+
+    /// Number of scalar bits the table covers.
+    pub fn bits(&self) -> usize {
+        self.combs.len() * self.teeth * self.spacing
+    }
+
+    /// Position of the scalar bit of tooth `i` of comb `c` in column `col`.
+    fn tooth(&self, c: usize, i: usize, col: usize) -> usize {
+        (c * self.teeth + i) * self.spacing + col
+    }
+
+    /// Multiplies the base by the scalar given as bits (least significant bit
+    /// first). Native version of `comb_mul_gadget`.
+    pub fn mul(&self, bits: &[u8]) -> SonnyEdwardsPoint {
+        assert!(bits.len() <= self.bits());
+        let mut acc = SonnyEdwardsPoint::identity();
+        for col in (0..self.spacing).rev() {
+            acc = acc.double();
+            for (c, comb) in self.combs.iter().enumerate() {
+                let index: Vec<u8> = (0..self.teeth)
+                    .map(|i| *bits.get(self.tooth(c, i, col)).unwrap_or(&0))
+                    .collect();
+                acc = acc + comb[window_index(&index)];
+            }
+        }
+        acc
+    }
+}
+
+/// Multiplies the base of `table` by the scalar given by `bits` (least
+/// significant bit first), which are boolean-constrained here.
+pub fn comb_mul_gadget(
+    cs: &mut dyn CS,
+    table: &CombTable,
+    bits: &[Variable],
+) -> SonnyEdwardsPointGadget {
+    assert!(bits.len() <= table.bits());
+    for bit in bits {
+        binary_constrain_gadget(cs, *bit);
+    }
+    let mut acc: Option<SonnyEdwardsPointGadget> = None;
+    for col in (0..table.spacing).rev() {
+        // Doubling the identity is skipped.
+        acc = acc.map(|acc| acc.double(cs));
+        for (c, comb) in table.combs.iter().enumerate() {
+            let teeth: Vec<Variable> = (0..table.teeth)
+                .map(|i| table.tooth(c, i, col))
+                .take_while(|k| *k < bits.len())
+                .map(|k| bits[k])
+                .collect();
+            if teeth.is_empty() {
+                continue;
+            }
+            let term = lookup_gadget(cs, &teeth, comb);
+            acc = Some(match acc {
+                None => term,
+                Some(acc) => acc.add(&term, cs),
+            });
+        }
+    }
+    acc.unwrap_or_else(SonnyEdwardsPointGadget::identity)
+}
+
+mod test {
+    use super::*;
+    use crate::gadgets::commitment::SonnyPedersenGens;
+    use crate::gadgets::point::witness;
+    use crate::gadgets::signature::basepoint;
+    use crate::helpers::affine_coords;
+    use crate::recording::RecordingCS;
+    use curve25519_dalek::scalar::Scalar;
+    use zerocaf::scalar::Scalar as SonnyScalar;
+
+    fn same_tables(a: &CombTable, b: &CombTable) -> bool {
+        a.combs.len() == b.combs.len()
+            && a.combs
+                .iter()
+                .flatten()
+                .zip(b.combs.iter().flatten())
+                .all(|(p, q)| affine_coords(p) == affine_coords(q))
+    }
+
+    #[test]
+    fn embedded_tables() {
+        let runtime =
+            |base: &SonnyEdwardsPoint| CombTable::new(base, COMB_TEETH, COMB_SPACING, COMB_BITS);
+        assert!(same_tables(&CombTable::basepoint(), &runtime(&basepoint())));
+        let h = SonnyPedersenGens::new(0).blinding_gen;
+        assert!(same_tables(&CombTable::blinding(), &runtime(&h)));
+    }
+
+    fn comb_mul_satisfied(table: &CombTable, base: &SonnyEdwardsPoint, bits: &[u8]) -> bool {
+        let expected = witness::edwards::scalar_mul(base, bits);
+        assert_eq!(affine_coords(&table.mul(bits)), affine_coords(&expected));
+
+        let mut cs = RecordingCS::new(b"CombMul");
+        let vars: Vec<Variable> = bits
+            .iter()
+            .map(|b| cs.commit(Some(Scalar::from(*b))))
+            .collect();
+        let res = comb_mul_gadget(&mut cs, table, &vars);
+        res.equal(&SonnyEdwardsPointGadget::from_point(&expected), &mut cs);
+        cs.first_unsatisfied().is_none()
+    }
+
+    #[test]
+    fn comb_mul() {
+        let mut rng = rand::thread_rng();
+        let bits = SonnyScalar::random(&mut rng).into_bits()[..COMB_BITS].to_vec();
+        assert!(comb_mul_satisfied(
+            &CombTable::basepoint(),
+            &basepoint(),
+            &bits
+        ));
+
+        // A runtime table with spacing, over a scalar shorter than it covers.
+        let base = SonnyEdwardsPoint::new_random_point(&mut rng);
+        let table = CombTable::new(&base, 3, 4, 40);
+        assert!(comb_mul_satisfied(&table, &base, &bits[..37]));
+    }
+
+    #[test]
+    fn comb_mul_rejects_wrong_result() {
+        let bits = [1u8, 1, 0, 1];
+        let mut cs = RecordingCS::new(b"CombMul");
+        let vars: Vec<Variable> = bits
+            .iter()
+            .map(|b| cs.commit(Some(Scalar::from(*b))))
+            .collect();
+        let res = comb_mul_gadget(&mut cs, &CombTable::basepoint(), &vars);
+        let wrong = witness::edwards::scalar_mul(&basepoint(), &[0, 1, 0, 1]);
+        res.equal(&SonnyEdwardsPointGadget::from_point(&wrong), &mut cs);
+        assert!(cs.first_unsatisfied().is_some());
+    }
+}
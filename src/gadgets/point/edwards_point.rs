@@ -1,9 +1,27 @@
+use crate::circuit_id::{circuit_id, ProofBundle};
+use crate::errors::GadgetError;
 use crate::gadgets::boolean::binary_constrain_gadget;
+use crate::gadgets::point::chain::{double_add_chain, ChainStep};
+use crate::gadgets::point::ristretto_point::SonnyRistrettoPointGadget;
+use crate::gadgets::point::witness;
+use crate::gadgets::scalar::nonzero_gadget;
+use crate::helpers;
+#[cfg(feature = "prover")]
+use crate::helpers::{PointCoords, PointLCs};
+use crate::instrument;
+use crate::recording::format_lc;
 use bulletproofs::r1cs::{
-    ConstraintSystem as CS, LinearCombination as LC, Prover, Variable, Verifier,
+    ConstraintSystem as CS, LinearCombination as LC, R1CSError, Variable, Verifier,
 };
+#[cfg(feature = "prover")]
+use bulletproofs::r1cs::Prover;
+use bulletproofs::{BulletproofGens, PedersenGens};
 use curve25519_dalek::{ristretto::CompressedRistretto, scalar::Scalar};
+#[cfg(feature = "prover")]
+use rand::{CryptoRng, Rng};
+use std::fmt;
 use zerocaf::edwards::EdwardsPoint as SonnyEdwardsPoint;
+use zerocaf::ristretto::RistrettoPoint as SonnyRistrettoPoint;
 
 #[derive(Clone)]
 // Represents a Sonny Edwards Point using Twisted Edwards Extended Coordinates
@@ -14,7 +32,36 @@ pub struct SonnyEdwardsPointGadget {
     pub T: LC,
 }
 
+impl fmt::Debug for SonnyEdwardsPointGadget {
+    /// Prints the LC of every coordinate symbolically (see `recording::format_lc`).
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("SonnyEdwardsPointGadget")
+            .field("X", &format_args!("{}", format_lc(&self.X)))
+            .field("Y", &format_args!("{}", format_lc(&self.Y)))
+            .field("Z", &format_args!("{}", format_lc(&self.Z)))
+            .field("T", &format_args!("{}", format_lc(&self.T)))
+            .finish()
+    }
+}
+
+/// Whether the identity may occur in a point operation.
+///
+/// `Allow` uses the complete formulas of `add`, correct on any input. `Forbid`
+/// uses the cheaper dedicated formulas of `add_dedicated`, which do not handle
+/// doublings (`P + P`) and some sums involving points of small order, the
+/// identity among them. On those inputs their `Z` is zero, which `Forbid`
+/// constrains against: a circuit hitting them can not be proven, instead of
+/// silently computing a wrong point.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum IdentityPolicy {
+    Forbid,
+    Allow,
+}
+
 impl SonnyEdwardsPointGadget {
+    /// Number of commitments (one per coordinate) a committed point takes.
+    pub const COMMITMENTS: usize = 4;
+
     /// Creates LCs from the point coordinates, and returns a new `SonnyEdwardsPointGadget`.
     pub fn from_point(point: &SonnyEdwardsPoint) -> SonnyEdwardsPointGadget {
         SonnyEdwardsPointGadget {
@@ -25,267 +72,725 @@ impl SonnyEdwardsPointGadget {
         }
     }
 
+    /// The identity point `(0, 1, 1, 0)`, as a constant.
+    pub fn identity() -> SonnyEdwardsPointGadget {
+        SonnyEdwardsPointGadget {
+            X: LC::from(Scalar::zero()),
+            Y: LC::from(Scalar::one()),
+            Z: LC::from(Scalar::one()),
+            T: LC::from(Scalar::zero()),
+        }
+    }
+
+    /// The basepoint of the Sonny Ristretto group, as a constant.
+    pub fn basepoint() -> SonnyEdwardsPointGadget {
+        SonnyEdwardsPointGadget::from_point(&zerocaf::constants::RISTRETTO_BASEPOINT.0)
+    }
+
     pub fn add(&self, other: &SonnyEdwardsPointGadget, cs: &mut dyn CS) -> SonnyEdwardsPointGadget {
-        // XXX: public constants should be defined at a higher level
-        let a: Scalar = Scalar::from_bytes_mod_order(zerocaf::constants::EDWARDS_A.to_bytes());
-        let d: Scalar = Scalar::from_bytes_mod_order(zerocaf::constants::EDWARDS_D.to_bytes());
-
-        // Point addition impl
-        // A = p1_x * p2_x
-        // B = p1_y * p2_y
-        // C = d*(p1_t * p2_t)
-        // D = p1_z * p2_z
-        // E = (p1_x + p1_y) * (p2_x + p2_y) + a*A + a*B
-        // F = D - C
-        // G = D + C
-        // H = B + A
-        // X3 = E * F , Y3 = G * H, Z3 = F * G, T3 = E * H
-        //
-        // Compute A
-        let (X, other_x, A) = cs.multiply(self.X.clone(), other.X.clone());
-        // Compute B
-        let (Y, other_y, B) = cs.multiply(self.Y.clone(), other.Y.clone());
-        // Compute C
-        let (_, _, pt) = cs.multiply(self.T.clone(), other.T.clone());
-        let (_, _, C) = cs.multiply(pt.into(), d.into());
-        // Compute D
-        let (_, _, D) = cs.multiply(self.Z.clone(), other.Z.clone());
-        // Compute E
-        let E = {
-            let E1 = self.X.clone() + Y.clone();
-            cs.constrain(E1.clone() - X - Y);
-
-            let E2 = other.X.clone() + other.Y.clone();
-            cs.constrain(E2.clone() - other_x - other_y);
-
-            let (_, _, E12) = cs.multiply(E1, E2);
-
-            let (_, _, aA) = cs.multiply(a.into(), A.into());
-            let (_, _, bB) = cs.multiply(a.into(), B.into());
-
-            let E = aA + bB + E12;
-            cs.constrain(E.clone() - aA - bB - E12);
-
-            E
-        };
-        // Compute F
-        let F = D - C;
-        cs.constrain(F.clone() - D + C);
-        // Compute G
-        let G = D + C;
-        cs.constrain(G.clone() - D - C);
-        // Compute H
-        let H = B + A;
-        cs.constrain(H.clone() - B - A);
-
-        // Compute new point
-        let (E, F, X) = cs.multiply(E, F);
-        let (G, H, Y) = cs.multiply(G, H);
-        let (_, _, Z) = cs.multiply(F.into(), G.into());
-        let (_, _, T) = cs.multiply(E.into(), H.into());
+        instrument::synthesis("addition", cs, |cs| {
+            // XXX: public constants should be defined at a higher level
+            let a: Scalar = Scalar::from_bytes_mod_order(zerocaf::constants::EDWARDS_A.to_bytes());
+            let d: Scalar = Scalar::from_bytes_mod_order(zerocaf::constants::EDWARDS_D.to_bytes());
+
+            // Point addition impl
+            // A = p1_x * p2_x
+            // B = p1_y * p2_y
+            // C = d*(p1_t * p2_t)
+            // D = p1_z * p2_z
+            // E = (p1_x + p1_y) * (p2_x + p2_y) + a*A + a*B
+            // F = D - C
+            // G = D + C
+            // H = B + A
+            // X3 = E * F , Y3 = G * H, Z3 = F * G, T3 = E * H
+            //
+            // Compute A
+            let (X, other_x, A) = cs.multiply(self.X.clone(), other.X.clone());
+            // Compute B
+            let (Y, other_y, B) = cs.multiply(self.Y.clone(), other.Y.clone());
+            // Compute C
+            let (_, _, pt) = cs.multiply(self.T.clone(), other.T.clone());
+            let (_, _, C) = cs.multiply(pt.into(), d.into());
+            // Compute D
+            let (_, _, D) = cs.multiply(self.Z.clone(), other.Z.clone());
+            // Compute E
+            let E = {
+                let E1 = self.X.clone() + Y.clone();
+                cs.constrain(E1.clone() - X - Y);
+
+                let E2 = other.X.clone() + other.Y.clone();
+                cs.constrain(E2.clone() - other_x - other_y);
+
+                let (_, _, E12) = cs.multiply(E1, E2);
+
+                let (_, _, aA) = cs.multiply(a.into(), A.into());
+                let (_, _, bB) = cs.multiply(a.into(), B.into());
+
+                let E = aA + bB + E12;
+                cs.constrain(E.clone() - aA - bB - E12);
 
+                E
+            };
+            // Compute F
+            let F = D - C;
+            cs.constrain(F.clone() - D + C);
+            // Compute G
+            let G = D + C;
+            cs.constrain(G.clone() - D - C);
+            // Compute H
+            let H = B + A;
+            cs.constrain(H.clone() - B - A);
+
+            // Compute new point
+            let (E, F, X) = cs.multiply(E, F);
+            let (G, H, Y) = cs.multiply(G, H);
+            let (_, _, Z) = cs.multiply(F.into(), G.into());
+            let (_, _, T) = cs.multiply(E.into(), H.into());
+
+            SonnyEdwardsPointGadget {
+                X: X.into(),
+                Y: Y.into(),
+                Z: Z.into(),
+                T: T.into(),
+            }
+        })
+    }
+
+    /// Adds the points with the dedicated formulas (Hisil et al., "Twisted
+    /// Edwards Curves Revisited", section 3.2), which take 9 multipliers
+    /// instead of 12 but are incomplete: see `IdentityPolicy`. The output is
+    /// not checked, use `add_with_policy` unless the inputs are known to be
+    /// handled.
+    pub fn add_dedicated(
+        &self,
+        other: &SonnyEdwardsPointGadget,
+        cs: &mut dyn CS,
+    ) -> SonnyEdwardsPointGadget {
+        instrument::synthesis("dedicated addition", cs, |cs| {
+            let a = Scalar::from_bytes_mod_order(zerocaf::constants::EDWARDS_A.to_bytes());
+
+            // A = p1_x * p2_x
+            // B = p1_y * p2_y
+            // C = p1_z * p2_t
+            // D = p1_t * p2_z
+            // E = D + C
+            // F = (p1_x - p1_y) * (p2_x + p2_y) + B - A
+            // G = B + a*A
+            // H = D - C
+            // X3 = E * F , Y3 = G * H, Z3 = F * G, T3 = E * H
+            let (_, _, A) = cs.multiply(self.X.clone(), other.X.clone());
+            let (_, _, B) = cs.multiply(self.Y.clone(), other.Y.clone());
+            let (_, _, C) = cs.multiply(self.Z.clone(), other.T.clone());
+            let (_, _, D) = cs.multiply(self.T.clone(), other.Z.clone());
+            let (_, _, xy) = cs.multiply(
+                self.X.clone() - self.Y.clone(),
+                other.X.clone() + other.Y.clone(),
+            );
+            let E = D + C;
+            let F = xy + B - A;
+            let G = B + A * a;
+            let H = D - C;
+
+            let (E, F, X) = cs.multiply(E, F);
+            let (G, H, Y) = cs.multiply(G, H);
+            let (_, _, Z) = cs.multiply(F.into(), G.into());
+            let (_, _, T) = cs.multiply(E.into(), H.into());
+
+            SonnyEdwardsPointGadget {
+                X: X.into(),
+                Y: Y.into(),
+                Z: Z.into(),
+                T: T.into(),
+            }
+        })
+    }
+
+    /// Adds the points following `policy`: `add` for `Allow` (12 multipliers),
+    /// `add_dedicated` with its `Z` constrained to be nonzero for `Forbid` (11
+    /// multipliers).
+    ///
+    /// With `Forbid`, the prover must provide the assignments of both points,
+    /// in the extended coordinates the gadgets hold (see `witness`). It panics
+    /// if the points are not handled by the dedicated formulas.
+    pub fn add_with_policy(
+        &self,
+        other: &SonnyEdwardsPointGadget,
+        policy: IdentityPolicy,
+        assign: Option<(SonnyEdwardsPoint, SonnyEdwardsPoint)>,
+        cs: &mut dyn CS,
+    ) -> SonnyEdwardsPointGadget {
+        instrument::synthesis("add_with_policy", cs, |cs| match policy {
+            IdentityPolicy::Allow => self.add(other, cs),
+            IdentityPolicy::Forbid => {
+                let sum = self.add_dedicated(other, cs);
+                let z_assign = assign.map(|(p, q)| {
+                    let z = witness::edwards::add_dedicated(&p, &q).Z;
+                    Scalar::from_bytes_mod_order(z.to_bytes())
+                });
+                nonzero_gadget(sum.Z.clone(), z_assign, cs);
+                sum
+            }
+        })
+    }
+
+    /// Constrains the point not to be the identity, e.g. a committed point the
+    /// circuit then adds with `IdentityPolicy::Forbid`. It constrains `X` to be
+    /// nonzero, which also excludes the point `(0, -1)` of order 2. Takes 2
+    /// multipliers.
+    pub fn forbid_identity(&self, point_assign: Option<SonnyEdwardsPoint>, cs: &mut dyn CS) {
+        instrument::synthesis("forbid_identity", cs, |cs| {
+            let x_assign = point_assign.map(|p| Scalar::from_bytes_mod_order(p.X.to_bytes()));
+            nonzero_gadget(self.X.clone(), x_assign, cs);
+        })
+    }
+
+    /// Converts the gadget into a `SonnyRistrettoPointGadget`, adding the constraints
+    /// that check that the point satisfies the curve equation and that it does not
+    /// lie on a small order subgroup.
+    ///
+    /// The prover must provide the assignment of the point, the verifier passes `None`.
+    pub fn into_ristretto(
+        self,
+        cs: &mut dyn CS,
+        point_assign: Option<SonnyEdwardsPoint>,
+    ) -> SonnyRistrettoPointGadget {
+        instrument::synthesis("into_ristretto", cs, |cs| {
+            self.satisfy_curve_eq(cs);
+            let ristretto = SonnyRistrettoPointGadget {
+                X: self.X,
+                Y: self.Y,
+                Z: self.Z,
+                T: self.T,
+            };
+            ristretto.ristretto_gadget(cs, point_assign.map(SonnyRistrettoPoint));
+            ristretto
+        })
+    }
+
+    /// Returns the negation of the point `(-X, Y, Z, -T)`.
+    /// It is a linear operation, so no constraints are added.
+    pub fn neg(&self) -> SonnyEdwardsPointGadget {
         SonnyEdwardsPointGadget {
-            X: X.into(),
-            Y: Y.into(),
-            Z: Z.into(),
-            T: T.into(),
+            X: -self.X.clone(),
+            Y: self.Y.clone(),
+            Z: self.Z.clone(),
+            T: -self.T.clone(),
         }
     }
 
     /// Builds and adds to the CS the circuit that corresponds to the
     /// doubling of a Twisted Edwards point in Extended Coordinates.
     pub fn double(&self, cs: &mut dyn CS) -> SonnyEdwardsPointGadget {
-        // Point doubling impl
-        // A = p1_x²
-        // B = p1_y²
-        // C = 2*p1_z²
-        // D = a*A
-        // E = (p1_x + p1_y)² - A - B
-        // G = D + B
-        // F = G - C
-        // H = D - B
-        // X3 = E * F,  Y3 = G * H, Z3 = F * G, T3 = E * H
-        let a = LC::from(Scalar::from_bytes_mod_order(
-            zerocaf::constants::EDWARDS_A.to_bytes(),
-        ));
-        let (X, _, A) = cs.multiply(self.X.clone(), self.X.clone());
-        let (Y, _, B) = cs.multiply(self.Y.clone(), self.Y.clone());
-        let C = {
-            let z_sq = cs.multiply(self.Z.clone(), self.Z.clone()).2;
-            cs.multiply(Scalar::from(2u8).into(), z_sq.into()).2
-        };
-        let D = cs.multiply(a, A.into()).2;
-        let E = {
-            let p1xy_sq = cs.multiply(X + Y, X + Y).2;
-            let E = p1xy_sq - A - B;
-            cs.constrain(E.clone() - p1xy_sq + A + B);
-            E
-        };
-        let G = D + B;
-        cs.constrain(G.clone() - D - B);
-        let F = G.clone() - C;
-        cs.constrain(F.clone() - G.clone() + C);
-        let H = D - B;
-        cs.constrain(H.clone() - D + B);
+        instrument::synthesis("doubling", cs, |cs| {
+            // Point doubling impl
+            // A = p1_x²
+            // B = p1_y²
+            // C = 2*p1_z²
+            // D = a*A
+            // E = (p1_x + p1_y)² - A - B
+            // G = D + B
+            // F = G - C
+            // H = D - B
+            // X3 = E * F,  Y3 = G * H, Z3 = F * G, T3 = E * H
+            let a = LC::from(Scalar::from_bytes_mod_order(
+                zerocaf::constants::EDWARDS_A.to_bytes(),
+            ));
+            let (X, _, A) = cs.multiply(self.X.clone(), self.X.clone());
+            let (Y, _, B) = cs.multiply(self.Y.clone(), self.Y.clone());
+            let C = {
+                let z_sq = cs.multiply(self.Z.clone(), self.Z.clone()).2;
+                cs.multiply(Scalar::from(2u8).into(), z_sq.into()).2
+            };
+            let D = cs.multiply(a, A.into()).2;
+            let E = {
+                let p1xy_sq = cs.multiply(X + Y, X + Y).2;
+                let E = p1xy_sq - A - B;
+                cs.constrain(E.clone() - p1xy_sq + A + B);
+                E
+            };
+            let G = D + B;
+            cs.constrain(G.clone() - D - B);
+            let F = G.clone() - C;
+            cs.constrain(F.clone() - G.clone() + C);
+            let H = D - B;
+            cs.constrain(H.clone() - D + B);
 
-        SonnyEdwardsPointGadget {
-            X: LC::from(cs.multiply(E.clone(), F.clone()).2),
-            Y: LC::from(cs.multiply(G.clone(), H.clone()).2),
-            Z: LC::from(cs.multiply(F, G).2),
-            T: LC::from(cs.multiply(E, H).2),
-        }
+            SonnyEdwardsPointGadget {
+                X: LC::from(cs.multiply(E.clone(), F.clone()).2),
+                Y: LC::from(cs.multiply(G.clone(), H.clone()).2),
+                Z: LC::from(cs.multiply(F, G).2),
+                T: LC::from(cs.multiply(E, H).2),
+            }
+        })
     }
 
-    /// Multiplies a SonnyEdwardsPointGadget by a SonnyScalar
+    /// Computes `[2^k]P` with `k` chained doublings.
+    ///
+    /// It uses the formulas of `double`, but the constants are applied
+    /// linearly, the definitions of `E`, `F`, `G` and `H` are not constrained
+    /// (they are linear in the squarings `A`, `B` and `Z²` they reuse), and since
+    /// doubling does not read `T`, it is only computed for the last point. It
+    /// takes `7k + 1` multipliers instead of the `10k` of `k` calls to `double`.
+    pub fn mul_by_pow2(&self, k: usize, cs: &mut dyn CS) -> SonnyEdwardsPointGadget {
+        instrument::synthesis("mul_by_pow2", cs, |cs| {
+            if k == 0 {
+                return self.clone();
+            }
+            let a = Scalar::from_bytes_mod_order(zerocaf::constants::EDWARDS_A.to_bytes());
+            let (mut X, mut Y, mut Z) = (self.X.clone(), self.Y.clone(), self.Z.clone());
+            let (mut E, mut H) = (LC::default(), LC::default());
+            for _ in 0..k {
+                let (x, _, A) = cs.multiply(X.clone(), X);
+                let (y, _, B) = cs.multiply(Y.clone(), Y);
+                let (_, _, z_sq) = cs.multiply(Z.clone(), Z);
+                let (_, _, xy_sq) = cs.multiply(x + y, x + y);
+                let C = z_sq * Scalar::from(2u8);
+                let D = A * a;
+                E = xy_sq - A - B;
+                let G = D.clone() + B;
+                let F = G.clone() - C;
+                H = D - B;
+
+                let (e, f, x) = cs.multiply(E, F);
+                let (g, h, y) = cs.multiply(G, H);
+                let (_, _, z) = cs.multiply(f.into(), g.into());
+                X = x.into();
+                Y = y.into();
+                Z = z.into();
+                E = e.into();
+                H = h.into();
+            }
+            let (_, _, T) = cs.multiply(E, H);
+            SonnyEdwardsPointGadget {
+                X,
+                Y,
+                Z,
+                T: T.into(),
+            }
+        })
+    }
+
+    /// Computes `[k]P` for a public constant `k` (e.g. the cofactor), following
+    /// `chain::double_add_chain(k)`. It allocates no bits and takes the
+    /// `chain::chain_multipliers` of the chain.
+    pub fn mul_by_const_scalar(&self, k: u64, cs: &mut dyn CS) -> SonnyEdwardsPointGadget {
+        instrument::synthesis("mul_by_const_scalar", cs, |cs| {
+            if k == 0 {
+                return SonnyEdwardsPointGadget::identity();
+            }
+            double_add_chain(k)
+                .into_iter()
+                .fold(self.clone(), |acc, step| match step {
+                    ChainStep::Double(n) => acc.mul_by_pow2(n, cs),
+                    ChainStep::Add => acc.add(self, cs),
+                    ChainStep::Sub => acc.add(&self.neg(), cs),
+                })
+        })
+    }
+
+    /// Multiplies a SonnyEdwardsPointGadget by a SonnyScalar given as bits
+    /// (least significant bit first).
+    ///
+    /// When the assignments of the point and the scalar bits are provided (prover side),
+    /// it also returns the natively computed point that the circuit outputs.
     pub fn scalar_mul(
         point: SonnyEdwardsPointGadget,
-        mut sk: Vec<Variable>,
+        point_assign: Option<SonnyEdwardsPoint>,
+        sk: Vec<Variable>,
+        sk_assign: Option<&[u8]>,
         cs: &mut dyn CS,
-    ) -> SonnyEdwardsPointGadget {
-        // Generate Identity point without the ristretto constraint
-        let mut Q = SonnyEdwardsPointGadget {
-            X: LC::from(Scalar::zero()),
-            Y: LC::from(Scalar::one()),
-            Z: LC::from(Scalar::one()),
-            T: LC::from(Scalar::zero()),
-        };
-        // Compute pk'
-        sk.reverse();
-        for var in sk {
-            // Check that var is either `0` or `1`
-            binary_constrain_gadget(cs, var);
-            Q = Q.double(cs);
-            // If bit == 1 -> Q = Q + point
-            let point_or_id = point.conditionally_select(LC::from(var), cs);
-            Q = Q.add(&point_or_id, cs);
+    ) -> (SonnyEdwardsPointGadget, Option<SonnyEdwardsPoint>) {
+        let identity_assign = point_assign
+            .and(sk_assign)
+            .map(|_| witness::edwards::identity());
+        SonnyEdwardsPointGadget::scalar_mul_prefixes(point, point_assign, sk, sk_assign, cs)
+            .pop()
+            .unwrap_or_else(|| (SonnyEdwardsPointGadget::identity(), identity_assign))
+    }
+
+    /// Same as `scalar_mul`, returning every intermediate accumulator of the
+    /// ladder: for an `n`-bit scalar `k`, the `i`-th one is
+    /// `(k >> (n - 1 - i)) * point`, the multiple by the `i + 1` most
+    /// significant bits of `k`, and the last one is `k * point`. Statements about a prefix of the scalar
+    /// can use them without running the ladder again.
+    ///
+    /// The assignments are returned along with the gadgets on the prover side.
+    pub fn scalar_mul_prefixes(
+        point: SonnyEdwardsPointGadget,
+        point_assign: Option<SonnyEdwardsPoint>,
+        sk: Vec<Variable>,
+        sk_assign: Option<&[u8]>,
+        cs: &mut dyn CS,
+    ) -> Vec<(SonnyEdwardsPointGadget, Option<SonnyEdwardsPoint>)> {
+        instrument::synthesis("scalar_mul", cs, |cs| {
+            let expected = match (point_assign, sk_assign) {
+                (Some(p), Some(bits)) => Some(witness::edwards::scalar_mul_prefixes(&p, bits)),
+                _ => None,
+            };
+            let mut Q = SonnyEdwardsPointGadget::identity();
+            let mut prefixes = Vec::with_capacity(sk.len());
+            // Compute pk'
+            for (i, var) in sk.into_iter().enumerate().rev() {
+                let _bit = annotate!("bit {}", i);
+                // Check that var is either `0` or `1`
+                binary_constrain_gadget(cs, var);
+                Q = Q.double(cs);
+                // If bit == 1 -> Q = Q + point
+                let point_or_id = point.conditionally_select(LC::from(var), cs);
+                Q = Q.add(&point_or_id, cs);
+                let prefix_assign = expected.as_ref().map(|e| e[prefixes.len()]);
+                prefixes.push((Q.clone(), prefix_assign));
+            }
+            prefixes
+        })
+    }
+
+    /// Computes `sum(scalar_i * point_i)`, where every scalar is given by its
+    /// little-endian bits (see `scalar_mul`). The witness is only returned when
+    /// every point and scalar assignment is provided, and is computed by
+    /// `witness::edwards::multiscalar_mul` (concurrently with the `parallel`
+    /// feature). Fails if there is not one scalar per point.
+    pub fn multiscalar_mul(
+        points: Vec<(SonnyEdwardsPointGadget, Option<SonnyEdwardsPoint>)>,
+        scalars: Vec<(Vec<Variable>, Option<Vec<u8>>)>,
+        cs: &mut dyn CS,
+    ) -> Result<(SonnyEdwardsPointGadget, Option<SonnyEdwardsPoint>), GadgetError> {
+        if scalars.len() != points.len() {
+            return Err(GadgetError::InvalidLCsLength {
+                expected: points.len(),
+                found: scalars.len(),
+            });
         }
-        Q
+        Ok(instrument::synthesis("multiscalar_mul", cs, |cs| {
+            let points_assign: Option<Vec<SonnyEdwardsPoint>> =
+                points.iter().map(|p| p.1).collect();
+            let scalars_assign: Option<Vec<Vec<u8>>> =
+                scalars.iter().map(|s| s.1.clone()).collect();
+            let acc_assign = points_assign
+                .and_then(|p| scalars_assign.map(|s| witness::edwards::multiscalar_mul(&p, &s)));
+
+            let mut acc = SonnyEdwardsPointGadget::identity();
+            for ((point, _), (bits, _)) in points.into_iter().zip(scalars) {
+                let (term, _) = SonnyEdwardsPointGadget::scalar_mul(point, None, bits, None, cs);
+                acc = acc.add(&term, cs);
+            }
+            (acc, acc_assign)
+        }))
+    }
+
+    /// Allocates the affine coordinates `(x, y)` of the point, constraining
+    /// `Z != 0`, `x * Z = X` and `y * Z = Y`.
+    pub fn to_affine(
+        &self,
+        point_assign: Option<SonnyEdwardsPoint>,
+        cs: &mut dyn CS,
+    ) -> (Variable, Variable) {
+        instrument::synthesis("to_affine", cs, |cs| {
+            let z_assign = point_assign.map(|p| helpers::fe_to_scalar(&p.Z));
+            let affine_assign = point_assign.as_ref().map(helpers::affine_coords);
+            // The affine coordinates are only defined for Z != 0.
+            nonzero_gadget(self.Z.clone(), z_assign, cs);
+
+            let mut quotient = |num: LC, value_assign: Option<Scalar>| {
+                let (value, den, prod) = cs
+                    .allocate_multiplier(value_assign.and_then(|v| z_assign.map(|z| (v, z))))
+                    .unwrap();
+                cs.constrain(self.Z.clone() - den);
+                cs.constrain(num - prod);
+                value
+            };
+            let x = quotient(self.X.clone(), affine_assign.map(|(x, _)| x));
+            let y = quotient(self.Y.clone(), affine_assign.map(|(_, y)| y));
+            (x, y)
+        })
+    }
+
+    /// Constrains `scalar` to be the affine x-coordinate `X / Z` of the point
+    /// (see `to_affine`), linking a committed scalar to a committed point,
+    /// e.g. for protocols signing or hashing the coordinate.
+    pub fn equal_affine_x(
+        &self,
+        scalar: LC,
+        point_assign: Option<SonnyEdwardsPoint>,
+        cs: &mut dyn CS,
+    ) {
+        instrument::synthesis("equal_affine_x", cs, |cs| {
+            let (x, _) = self.to_affine(point_assign, cs);
+            cs.constrain(scalar - x);
+        })
     }
 
     // self.x * other.z = other.x * self.z AND self.y * other.z == other.y * self.z
+    //
+    // The T coordinates are not compared: a gadget with an incoherent T passes
+    // as equal to a valid point, and additions involving it produce garbage.
+    // Use `equal_extended` when T is not otherwise known to be coherent.
     pub fn equal(&self, other: &SonnyEdwardsPointGadget, cs: &mut dyn CS) {
-        let (_, other_z, a) = cs.multiply(self.X.clone(), other.Z.clone());
-        let (_, Z, b) = cs.multiply(other.X.clone(), self.Z.clone());
-        cs.constrain(a - b);
+        instrument::synthesis("equal", cs, |cs| {
+            let (_, other_z, a) = cs.multiply(self.X.clone(), other.Z.clone());
+            let (_, Z, b) = cs.multiply(other.X.clone(), self.Z.clone());
+            cs.constrain(a - b);
+
+            let (_, _, c) = cs.multiply(self.Y.clone(), other_z.into());
+            let (_, _, d) = cs.multiply(other.Y.clone(), Z.into());
+            cs.constrain(c - d);
+        })
+    }
 
-        let (_, _, c) = cs.multiply(self.Y.clone(), other_z.into());
-        let (_, _, d) = cs.multiply(other.Y.clone(), Z.into());
-        cs.constrain(c - d);
+    /// Like `equal`, but also constrains `self.t * other.z = other.t * self.z`,
+    /// so both points have the same T coordinate up to the projective factor.
+    /// When `other` is coherent (e.g. it is the output of `add` or `double` on
+    /// coherent points, or a constant), so is `self`.
+    pub fn equal_extended(&self, other: &SonnyEdwardsPointGadget, cs: &mut dyn CS) {
+        instrument::synthesis("equal_extended", cs, |cs| {
+            self.equal(other, cs);
+            let (_, _, a) = cs.multiply(self.T.clone(), other.Z.clone());
+            let (_, _, b) = cs.multiply(other.T.clone(), self.Z.clone());
+            cs.constrain(a - b);
+        })
+    }
+
+    /// Constrains the coordinates to be coherent, i.e. `X * Y = Z * T`.
+    pub fn satisfy_coherence(&self, cs: &mut dyn CS) {
+        instrument::synthesis("satisfy_coherence", cs, |cs| {
+            let (_, _, xy) = cs.multiply(self.X.clone(), self.Y.clone());
+            let (_, _, zt) = cs.multiply(self.Z.clone(), self.T.clone());
+            cs.constrain(xy - zt);
+        })
     }
 
     /// Adds constraints to ensure that the point satisfies the Sonny curve eq
     /// by verifying `(aX^{2}+Y^{2})Z^{2} = Z^{4}+d(X^{2})Y^{2}`
     pub fn satisfy_curve_eq(&self, cs: &mut dyn CS) {
-        let a = LC::from(Scalar::from_bytes_mod_order(
-            zerocaf::constants::EDWARDS_A.to_bytes(),
-        ));
-        let d = LC::from(Scalar::from_bytes_mod_order(
-            zerocaf::constants::EDWARDS_D.to_bytes(),
-        ));
-        // Compute X²
-        let (_, _, x_sq) = cs.multiply(self.X.clone(), self.X.clone());
-        // Compute a * X²
-        let (_, _, aX_sq) = cs.multiply(a.into(), x_sq.into());
-        // Compute Y²
-        let (_, _, y_sq) = cs.multiply(self.Y.clone(), self.Y.clone());
-        // Compute a*X² + Y²
-        let ax_sq_y_sq = aX_sq + y_sq.clone();
-        cs.constrain(ax_sq_y_sq.clone() - aX_sq - y_sq);
-        // Compute Z²
-        let (_, _, z_sq) = cs.multiply(self.Z.clone(), self.Z.clone());
-        // Compute left assigment
-        let (_, _, left_assigm) = cs.multiply(ax_sq_y_sq, z_sq.into());
-
-        // Compute Z⁴
-        let (_, _, z_s_s) = cs.multiply(z_sq.into(), z_sq.into());
-        // Compute d*(X)²
-        let (_, _, dx_sq) = cs.multiply(d, x_sq.into());
-        // Compute d*(X²) * Y²
-        let (_, _, dx_sq_y_sq) = cs.multiply(dx_sq.into(), y_sq.into());
-        // Compute right assigment
-        let right_assigm = z_s_s + dx_sq_y_sq;
-        cs.constrain(right_assigm.clone() - z_s_s - dx_sq_y_sq);
-
-        // Constrain left assigment = right assigment
-        cs.constrain(right_assigm - left_assigm);
+        instrument::synthesis("satisfy_curve_eq", cs, |cs| {
+            let a = LC::from(Scalar::from_bytes_mod_order(
+                zerocaf::constants::EDWARDS_A.to_bytes(),
+            ));
+            let d = LC::from(Scalar::from_bytes_mod_order(
+                zerocaf::constants::EDWARDS_D.to_bytes(),
+            ));
+            // Compute X²
+            let (_, _, x_sq) = cs.multiply(self.X.clone(), self.X.clone());
+            // Compute a * X²
+            let (_, _, aX_sq) = cs.multiply(a.into(), x_sq.into());
+            // Compute Y²
+            let (_, _, y_sq) = cs.multiply(self.Y.clone(), self.Y.clone());
+            // Compute a*X² + Y²
+            let ax_sq_y_sq = aX_sq + y_sq.clone();
+            cs.constrain(ax_sq_y_sq.clone() - aX_sq - y_sq);
+            // Compute Z²
+            let (_, _, z_sq) = cs.multiply(self.Z.clone(), self.Z.clone());
+            // Compute left assigment
+            let (_, _, left_assigm) = cs.multiply(ax_sq_y_sq, z_sq.into());
+
+            // Compute Z⁴
+            let (_, _, z_s_s) = cs.multiply(z_sq.into(), z_sq.into());
+            // Compute d*(X)²
+            let (_, _, dx_sq) = cs.multiply(d, x_sq.into());
+            // Compute d*(X²) * Y²
+            let (_, _, dx_sq_y_sq) = cs.multiply(dx_sq.into(), y_sq.into());
+            // Compute right assigment
+            let right_assigm = z_s_s + dx_sq_y_sq;
+            cs.constrain(right_assigm.clone() - z_s_s - dx_sq_y_sq);
+
+            // Constrain left assigment = right assigment
+            cs.constrain(right_assigm - left_assigm);
+        })
     }
 
     /// If `bit = 0` assigns the Identity point coordinates (0, 1, 1, 0)
     /// to the point, otherways, leaves the point as it is.
     pub fn conditionally_select(&self, bit: LC, cs: &mut dyn CS) -> Self {
-        let one = LC::from(Scalar::one());
-
-        // x' = x if bit = 1
-        // x' = 0 if bit = 0 =>
-        // x' = x * bit
-        let (_, bit, x_prime) = cs.multiply(self.X.clone(), bit);
-
-        // y' = y if bit = 1
-        // y' = 1 if bit = 0 =>
-        // y' = bit * y + (1 - bit)
-        let y_prime = {
-            let (bit, _, bit_t_y) = cs.multiply(bit.into(), self.Y.clone());
-            let y_prime = bit_t_y + one.clone() - bit;
-            cs.constrain(y_prime.clone() - bit_t_y - one.clone() + bit);
-            y_prime
-        };
-
-        // z' = z if bit = 1
-        // z' = 1 if bit = 0 =>
-        // z' = bit * z + (1 - bit)
-        let z_prime = {
-            let (bit, _, bit_t_z) = cs.multiply(bit.into(), self.Z.clone());
-            let z_prime = bit_t_z + one.clone() - bit;
-            cs.constrain(z_prime.clone() - bit_t_z - one + bit);
-            z_prime
-        };
-
-        // t' = t if bit = 1
-        // t' = 0 if bit = 0 =>
-        // t' = t * bit
-        let (_, _, t_prime) = cs.multiply(self.T.clone(), bit.into());
-        SonnyEdwardsPointGadget {
-            X: x_prime.into(),
-            Y: y_prime.into(),
-            Z: z_prime.into(),
-            T: t_prime.into(),
-        }
+        instrument::synthesis("conditionally_select", cs, |cs| {
+            let one = LC::from(Scalar::one());
+
+            // x' = x if bit = 1
+            // x' = 0 if bit = 0 =>
+            // x' = x * bit
+            let (_, bit, x_prime) = cs.multiply(self.X.clone(), bit);
+
+            // y' = y if bit = 1
+            // y' = 1 if bit = 0 =>
+            // y' = bit * y + (1 - bit)
+            let y_prime = {
+                let (bit, _, bit_t_y) = cs.multiply(bit.into(), self.Y.clone());
+                let y_prime = bit_t_y + one.clone() - bit;
+                cs.constrain(y_prime.clone() - bit_t_y - one.clone() + bit);
+                y_prime
+            };
+
+            // z' = z if bit = 1
+            // z' = 1 if bit = 0 =>
+            // z' = bit * z + (1 - bit)
+            let z_prime = {
+                let (bit, _, bit_t_z) = cs.multiply(bit.into(), self.Z.clone());
+                let z_prime = bit_t_z + one.clone() - bit;
+                cs.constrain(z_prime.clone() - bit_t_z - one + bit);
+                z_prime
+            };
+
+            // t' = t if bit = 1
+            // t' = 0 if bit = 0 =>
+            // t' = t * bit
+            let (_, _, t_prime) = cs.multiply(self.T.clone(), bit.into());
+            SonnyEdwardsPointGadget {
+                X: x_prime.into(),
+                Y: y_prime.into(),
+                Z: z_prime.into(),
+                T: t_prime.into(),
+            }
+        })
     }
 
+    /// Commits the coordinates of a point as the prover.
+    #[cfg(feature = "prover")]
     pub fn prover_commit_to_sonny_edwards_point(
         prover: &mut Prover,
         p: &SonnyEdwardsPoint,
     ) -> (SonnyEdwardsPointGadget, Vec<CompressedRistretto>) {
-        let scalars = vec![
-            Scalar::from_bytes_mod_order(p.X.to_bytes()),
-            Scalar::from_bytes_mod_order(p.Y.to_bytes()),
-            Scalar::from_bytes_mod_order(p.Z.to_bytes()),
-            Scalar::from_bytes_mod_order(p.T.to_bytes()),
-        ];
-        let (commitments, vars): (Vec<_>, Vec<_>) = scalars
-            .into_iter()
-            .map(|x| prover.commit(Scalar::from(x), Scalar::random(&mut rand::thread_rng())))
-            .unzip();
-        let gadget_p = SonnyEdwardsPointGadget {
-            X: vars[0].into(),
-            Y: vars[1].into(),
-            Z: vars[2].into(),
-            T: vars[3].into(),
-        };
-        (gadget_p, commitments)
+        let (mut lcs, commitments) = helpers::prover_commit_points(prover, &[*p]);
+        (lcs.remove(0).into(), commitments)
     }
 
+    /// Commits the coordinates of a point as the verifier.
+    /// Fails if `commitments` does not contain exactly `COMMITMENTS` elements.
     pub fn verifier_commit_to_sonny_edwards_point(
         verifier: &mut Verifier,
         commitments: &[CompressedRistretto],
-    ) -> SonnyEdwardsPointGadget {
-        assert_eq!(commitments.len(), 4);
-        let vars: Vec<_> = commitments.iter().map(|V| verifier.commit(*V)).collect();
-        SonnyEdwardsPointGadget {
-            X: vars[0].into(),
-            Y: vars[1].into(),
-            Z: vars[2].into(),
-            T: vars[3].into(),
-        }
+    ) -> Result<SonnyEdwardsPointGadget, GadgetError> {
+        let mut lcs = helpers::verifier_commit_points(verifier, commitments, 1)?;
+        Ok(lcs.remove(0).into())
+    }
+
+    /// Commits the coordinates of `n` points as the verifier.
+    /// Fails if `commitments` does not contain exactly `n * COMMITMENTS` elements.
+    pub fn verifier_commit_to_sonny_edwards_points(
+        verifier: &mut Verifier,
+        commitments: &[CompressedRistretto],
+        n: usize,
+    ) -> Result<Vec<SonnyEdwardsPointGadget>, GadgetError> {
+        Ok(helpers::verifier_commit_points(verifier, commitments, n)?
+            .into_iter()
+            .map(SonnyEdwardsPointGadget::from)
+            .collect())
+    }
+}
+
+/// Label of the proofs of `prove_addition`.
+pub const ADDITION_LABEL: &[u8] = b"Sonny point addition";
+/// Label of the proofs of `prove_doubling`.
+pub const DOUBLING_LABEL: &[u8] = b"Sonny point doubling";
+
+/// Turns the committed coordinates of `vars` into points, constraining each of
+/// them to lie on the curve with a nonzero `Z`.
+fn committed_points(
+    cs: &mut dyn CS,
+    vars: &[Variable],
+    witness: Option<&[Scalar]>,
+) -> Vec<SonnyEdwardsPointGadget> {
+    vars.chunks_exact(4)
+        .enumerate()
+        .map(|(i, c)| {
+            let point: SonnyEdwardsPointGadget =
+                PointLCs::from_coords([c[0], c[1], c[2], c[3]], LC::from).into();
+            point.satisfy_curve_eq(cs);
+            nonzero_gadget(point.Z.clone(), witness.map(|w| w[4 * i + 2]), cs);
+            point
+        })
+        .collect()
+}
+
+/// Committed points `P` and `Q` whose sum is the public `sum`.
+fn addition_circuit(
+    sum: SonnyEdwardsPoint,
+) -> impl Fn(&mut dyn CS, Vec<Variable>, Option<&[Scalar]>) {
+    move |cs, vars, witness| {
+        let points = committed_points(cs, &vars, witness);
+        let res = points[0].add(&points[1], cs);
+        SonnyEdwardsPointGadget::from_point(&sum).equal(&res, cs);
     }
 }
+
+/// Committed point `P` whose double is the public `double`.
+fn doubling_circuit(
+    double: SonnyEdwardsPoint,
+) -> impl Fn(&mut dyn CS, Vec<Variable>, Option<&[Scalar]>) {
+    move |cs, vars, witness| {
+        let points = committed_points(cs, &vars, witness);
+        let res = points[0].double(cs);
+        SonnyEdwardsPointGadget::from_point(&double).equal(&res, cs);
+    }
+}
+
+/// Commits to the points `p` and `q` and proves that they add up to `p + q`,
+/// which the verifier knows.
+#[cfg(feature = "prover")]
+pub fn prove_addition<R: Rng + CryptoRng>(
+    p: &SonnyEdwardsPoint,
+    q: &SonnyEdwardsPoint,
+    pc_gens: &PedersenGens,
+    bp_gens: &BulletproofGens,
+    rng: &mut R,
+) -> Result<ProofBundle, R1CSError> {
+    let mut witness = p.coords().to_vec();
+    witness.extend_from_slice(&q.coords());
+    ProofBundle::prove_with_rng(
+        pc_gens,
+        bp_gens,
+        ADDITION_LABEL,
+        &witness,
+        addition_circuit(*p + *q),
+        rng,
+    )
+}
+
+/// Verifies that the two points committed in `bundle` add up to `sum`.
+pub fn verify_addition(
+    bundle: &ProofBundle,
+    sum: &SonnyEdwardsPoint,
+    pc_gens: &PedersenGens,
+    bp_gens: &BulletproofGens,
+) -> Result<(), R1CSError> {
+    let circuit = addition_circuit(*sum);
+    let expected = circuit_id(
+        ADDITION_LABEL,
+        2 * SonnyEdwardsPointGadget::COMMITMENTS,
+        |cs, vars| circuit(cs, vars, None),
+    );
+    bundle.verify(pc_gens, bp_gens, ADDITION_LABEL, &expected, circuit)
+}
+
+/// Commits to the point `p` and proves that its double is `2 * p`, which the
+/// verifier knows.
+#[cfg(feature = "prover")]
+pub fn prove_doubling<R: Rng + CryptoRng>(
+    p: &SonnyEdwardsPoint,
+    pc_gens: &PedersenGens,
+    bp_gens: &BulletproofGens,
+    rng: &mut R,
+) -> Result<ProofBundle, R1CSError> {
+    ProofBundle::prove_with_rng(
+        pc_gens,
+        bp_gens,
+        DOUBLING_LABEL,
+        &p.coords(),
+        doubling_circuit(*p + *p),
+        rng,
+    )
+}
+
+/// Verifies that the double of the point committed in `bundle` is `double`.
+pub fn verify_doubling(
+    bundle: &ProofBundle,
+    double: &SonnyEdwardsPoint,
+    pc_gens: &PedersenGens,
+    bp_gens: &BulletproofGens,
+) -> Result<(), R1CSError> {
+    let circuit = doubling_circuit(*double);
+    let expected = circuit_id(
+        DOUBLING_LABEL,
+        SonnyEdwardsPointGadget::COMMITMENTS,
+        |cs, vars| circuit(cs, vars, None),
+    );
+    bundle.verify(pc_gens, bp_gens, DOUBLING_LABEL, &expected, circuit)
+}
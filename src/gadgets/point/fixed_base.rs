@@ -0,0 +1,138 @@
+//! Multiplication of a fixed (public) base point by a committed scalar, using
+//! the window tables of `precomp::FixedBaseTable`.
+//!
+//! Every window of `w` bits selects one of the `2^w` precomputed points of its
+//! row. The selection is the multilinear interpolation of the row over the bits,
+//! so it takes `2^w - w - 1` multipliers (the products of two or more bits) and
+//! no point operations. The selected points are then added up, taking a single
+//! addition per window instead of a double-and-add step per bit.
+use crate::gadgets::boolean::binary_constrain_gadget;
+use crate::gadgets::point::edwards_point::SonnyEdwardsPointGadget;
+use crate::gadgets::point::witness;
+use crate::helpers::PointCoords;
+use crate::precomp::FixedBaseTable;
+use bulletproofs::r1cs::{ConstraintSystem as CS, LinearCombination as LC, Variable};
+use curve25519_dalek::scalar::Scalar;
+use zerocaf::edwards::EdwardsPoint as SonnyEdwardsPoint;
+
+/// Selects `row[index]`, where `index` is given by `bits` (least significant bit first).
+pub(crate) fn lookup_gadget(
+    cs: &mut dyn CS,
+    bits: &[Variable],
+    row: &[SonnyEdwardsPoint],
+) -> SonnyEdwardsPointGadget {
+    let size = 1 << bits.len();
+    // monomials[s] = product of the bits in the set `s`.
+    let mut monomials: Vec<LC> = vec![LC::from(Scalar::one()); size];
+    for s in 1..size {
+        let low = s.trailing_zeros() as usize;
+        let rest = s & (s - 1);
+        monomials[s] = if rest == 0 {
+            bits[low].into()
+        } else {
+            let (_, _, prod) = cs.multiply(monomials[rest].clone(), bits[low].into());
+            prod.into()
+        };
+    }
+    // Coefficients of the monomials, by the Möbius transform of the row.
+    let mut coeffs: Vec<[Scalar; 4]> = row[..size].iter().map(|p| p.coords()).collect();
+    for i in 0..bits.len() {
+        for s in 0..size {
+            if s & (1 << i) != 0 {
+                for c in 0..4 {
+                    coeffs[s][c] = coeffs[s][c] - coeffs[s ^ (1 << i)][c];
+                }
+            }
+        }
+    }
+    let coord = |c: usize| {
+        monomials
+            .iter()
+            .zip(&coeffs)
+            .fold(LC::default(), |acc, (m, a)| acc + m.clone() * a[c])
+    };
+    SonnyEdwardsPointGadget {
+        X: coord(0),
+        Y: coord(1),
+        Z: coord(2),
+        T: coord(3),
+    }
+}
+
+/// Multiplies the base of `table` by the scalar given by `bits` (least
+/// significant bit first), which are boolean-constrained here.
+/// `witness::edwards::fixed_base_mul` computes the matching witness.
+pub fn fixed_base_mul_gadget(
+    cs: &mut dyn CS,
+    table: &FixedBaseTable,
+    bits: &[Variable],
+) -> SonnyEdwardsPointGadget {
+    windowed_mul(cs, table, bits, true)
+}
+
+/// `fixed_base_mul_gadget` over bits that are already boolean-constrained, e.g.
+/// because several multiplications share them.
+pub(crate) fn fixed_base_mul_unchecked(
+    cs: &mut dyn CS,
+    table: &FixedBaseTable,
+    bits: &[Variable],
+) -> SonnyEdwardsPointGadget {
+    windowed_mul(cs, table, bits, false)
+}
+
+fn windowed_mul(
+    cs: &mut dyn CS,
+    table: &FixedBaseTable,
+    bits: &[Variable],
+    constrain_bits: bool,
+) -> SonnyEdwardsPointGadget {
+    assert!(bits.len() <= table.rows.len() * table.window);
+    let mut acc: Option<SonnyEdwardsPointGadget> = None;
+    for (chunk, row) in bits.chunks(table.window).zip(&table.rows) {
+        if constrain_bits {
+            for bit in chunk {
+                binary_constrain_gadget(cs, *bit);
+            }
+        }
+        let term = lookup_gadget(cs, chunk, row);
+        acc = Some(match acc {
+            None => term,
+            Some(acc) => acc.add(&term, cs),
+        });
+    }
+    acc.unwrap_or_else(SonnyEdwardsPointGadget::identity)
+}
+
+mod test {
+    use super::*;
+    use crate::helpers::affine_coords;
+    use crate::recording::RecordingCS;
+    use zerocaf::scalar::Scalar as SonnyScalar;
+
+    #[test]
+    fn fixed_base_mul_matches_witness() {
+        let base = SonnyEdwardsPoint::new_random_point(&mut rand::thread_rng());
+        let bits = SonnyScalar::random(&mut rand::thread_rng()).into_bits()[..32].to_vec();
+        for window in 1..5 {
+            let table = FixedBaseTable::new(&base, window, bits.len());
+            let mut cs = RecordingCS::new(b"Fixed base");
+            let vars: Vec<Variable> = bits
+                .iter()
+                .map(|b| cs.commit(Some(Scalar::from(*b))))
+                .collect();
+            let res = fixed_base_mul_gadget(&mut cs, &table, &vars);
+
+            let expected = witness::edwards::fixed_base_mul(&table, &bits);
+            assert_eq!(cs.eval(&res.X), Some(expected.coords()[0]));
+            assert_eq!(cs.eval(&res.Y), Some(expected.coords()[1]));
+            assert_eq!(cs.eval(&res.Z), Some(expected.coords()[2]));
+            assert_eq!(cs.eval(&res.T), Some(expected.coords()[3]));
+            assert_eq!(cs.first_unsatisfied(), None);
+            // Same point as the double-and-add ladder.
+            assert_eq!(
+                affine_coords(&expected),
+                affine_coords(&witness::edwards::scalar_mul(&base, &bits))
+            );
+        }
+    }
+}
@@ -0,0 +1,260 @@
+//! Hashing committed messages to Sonny points, and the composed `sk * H_point(m)`
+//! gadget at the core of key images, VRFs and PRFs to the group.
+//!
+//! The message is hashed to a field element `t` (see `gadgets::hash`) which is
+//! mapped to the curve with Elligator 2, working on the Montgomery form
+//! `B*v^2 = u^3 + A*u^2 + u` of the curve:
+//!
+//! - `u1 = -A / (1 + Z*t^2)`, `u2 = -u1 - A` with `Z = 2` a non-square. Exactly
+//!   one of `g(u1)`, `g(u2)` is a square (`g(u) = (u^3 + A*u^2 + u) / B`), so the
+//!   choice `e` between them is forced by providing `v = sqrt(g(u_e))`.
+//! - The root with even canonical representation is taken, so the map is a function.
+//! - The Edwards point is `(x, y) = (u / v, (u - 1) / (u + 1))`, multiplied by the
+//!   cofactor `8` to land in the prime order subgroup.
+//!
+//! Unlike try-and-increment the map is deterministic, so every message has a single
+//! point (as key images require) and nobody knows its discrete log.
+use crate::gadgets::boolean::{
+    binary_constrain_gadget, bit_decomposition_gadget, le_constant_gadget,
+};
+use crate::gadgets::hash::{mimc_hash, mimc_hash_gadget, SCALAR_BITS};
+use crate::gadgets::point::edwards_point::SonnyEdwardsPointGadget;
+use crate::gadgets::point::witness;
+use crate::helpers::{fe_to_scalar, scalar_to_fe};
+use bulletproofs::r1cs::{ConstraintSystem as CS, LinearCombination as LC, Variable};
+use curve25519_dalek::scalar::Scalar;
+use zerocaf::edwards::EdwardsPoint as SonnyEdwardsPoint;
+
+/// Non-square used by the map.
+const Z: u64 = 2;
+
+/// `x^exp`, with `exp` given by its little-endian bytes.
+fn pow(x: &Scalar, exp: &[u8; 32]) -> Scalar {
+    (0..256).rev().fold(Scalar::one(), |acc, i| {
+        let acc = acc * acc;
+        if (exp[i / 8] >> (i % 8)) & 1 == 1 {
+            acc * x
+        } else {
+            acc
+        }
+    })
+}
+
+/// Shifts the little-endian bytes of an integer `n` bits to the right.
+fn shr(bytes: &[u8; 32], n: usize) -> [u8; 32] {
+    let mut res = [0u8; 32];
+    for i in 0..256 - n {
+        let bit = (bytes[(i + n) / 8] >> ((i + n) % 8)) & 1;
+        res[i / 8] |= bit << (i % 8);
+    }
+    res
+}
+
+fn is_square(x: &Scalar) -> bool {
+    // Euler's criterion, with exponent (l - 1) / 2.
+    let exp = shr(&(-Scalar::one()).to_bytes(), 1);
+    *x == Scalar::zero() || pow(x, &exp) == Scalar::one()
+}
+
+/// Square root of `x`, if any. Since `l = 5 mod 8` it is computed as
+/// `x * s * (i - 1)` with `s = (2x)^((l - 5) / 8)` and `i = 2x * s^2`.
+fn sqrt(x: &Scalar) -> Option<Scalar> {
+    let two_x = Scalar::from(2u64) * x;
+    let s = pow(&two_x, &shr(&(-Scalar::from(5u64)).to_bytes(), 3));
+    let i = two_x * s * s;
+    let root = x * s * (i - Scalar::one());
+    if root * root == *x {
+        Some(root)
+    } else {
+        None
+    }
+}
+
+/// Coefficients `(A, B)` of the Montgomery form of the curve:
+/// `A = 2(a + d) / (a - d)` and `B = 4 / (a - d)`.
+fn montgomery_coefficients() -> (Scalar, Scalar) {
+    let a = fe_to_scalar(&zerocaf::constants::EDWARDS_A);
+    let d = fe_to_scalar(&zerocaf::constants::EDWARDS_D);
+    let inv = (a - d).invert();
+    (Scalar::from(2u64) * (a + d) * inv, Scalar::from(4u64) * inv)
+}
+
+/// Values the map goes through, which the prover needs as witnesses.
+#[derive(Clone, Copy, Debug)]
+struct MapWitness {
+    u1: Scalar,
+    e: Scalar,
+    v: Scalar,
+    x: Scalar,
+    y: Scalar,
+}
+
+fn map_witness(t: &Scalar) -> MapWitness {
+    let (A, B) = montgomery_coefficients();
+    let g = |u: &Scalar| (u * u * u + A * u * u + u) * B.invert();
+
+    let u1 = -A * (Scalar::one() + Scalar::from(Z) * t * t).invert();
+    let (e, u) = if is_square(&g(&u1)) {
+        (Scalar::one(), u1)
+    } else {
+        (Scalar::zero(), -u1 - A)
+    };
+    let mut v = sqrt(&g(&u)).expect("exactly one of g(u1), g(u2) is a square");
+    if v.to_bytes()[0] & 1 == 1 {
+        v = -v;
+    }
+    MapWitness {
+        u1,
+        e,
+        v,
+        x: u * v.invert(),
+        y: (u - Scalar::one()) * (u + Scalar::one()).invert(),
+    }
+}
+
+/// Multiplies the affine point `(x, y)` by the cofactor, mirroring the gadget.
+fn clear_cofactor(x: &Scalar, y: &Scalar) -> SonnyEdwardsPoint {
+    let p = SonnyEdwardsPoint {
+        X: scalar_to_fe(x),
+        Y: scalar_to_fe(y),
+        Z: scalar_to_fe(&Scalar::one()),
+        T: scalar_to_fe(&(x * y)),
+    };
+    witness::edwards::double(&witness::edwards::double(&witness::edwards::double(&p)))
+}
+
+/// Native version of `hash_to_point_gadget`.
+pub fn hash_to_point(message: &[Scalar]) -> SonnyEdwardsPoint {
+    let w = map_witness(&mimc_hash(message));
+    clear_cofactor(&w.x, &w.y)
+}
+
+/// Allocates `num / den` given its assignment, constraining `value * den = num`.
+fn quotient(cs: &mut dyn CS, num: LC, den: LC, assign: Option<(Scalar, Scalar)>) -> Variable {
+    let (value, den_var, prod) = cs.allocate_multiplier(assign).unwrap();
+    cs.constrain(den - den_var);
+    cs.constrain(num - prod);
+    value
+}
+
+/// Hashes `message` to a point of the prime order subgroup (see the module docs).
+/// The prover must provide `message_assign`, since the map allocates witnesses.
+pub fn hash_to_point_gadget(
+    cs: &mut dyn CS,
+    message: &[LC],
+    message_assign: Option<&[Scalar]>,
+) -> (SonnyEdwardsPointGadget, Option<SonnyEdwardsPoint>) {
+    let (A, B) = montgomery_coefficients();
+    let one = LC::from(Scalar::one());
+    let t = mimc_hash_gadget(cs, message);
+    let t_assign = message_assign.map(mimc_hash);
+    let w = t_assign.as_ref().map(map_witness);
+
+    // u1 = -A / (1 + Z*t^2)
+    let (_, _, t_sq) = cs.multiply(t.clone(), t);
+    let den = one.clone() + t_sq * Scalar::from(Z);
+    let den_assign = t_assign.map(|t| Scalar::one() + Scalar::from(Z) * t * t);
+    let u1 = quotient(
+        cs,
+        LC::from(-A),
+        den,
+        w.and_then(|w| den_assign.map(|d| (w.u1, d))),
+    );
+
+    // u = e*u1 + (1 - e)*u2 = e*(2*u1 + A) - u1 - A
+    let e = cs.allocate(w.map(|w| w.e)).unwrap();
+    binary_constrain_gadget(cs, e);
+    let (_, _, e_t) = cs.multiply(e.into(), u1 * Scalar::from(2u64) + A);
+    let u: LC = e_t - u1 - A;
+
+    // B*v^2 = u^3 + A*u^2 + u
+    let v = cs.allocate(w.map(|w| w.v)).unwrap();
+    let (_, _, v_sq) = cs.multiply(v.into(), v.into());
+    let (_, _, u_sq) = cs.multiply(u.clone(), u.clone());
+    let (_, _, u_cb) = cs.multiply(u_sq.into(), u.clone());
+    cs.constrain(v_sq * B - u_cb - u_sq * A - u.clone());
+
+    // v has an even canonical representation.
+    let v_bits = bit_decomposition_gadget(cs, v.into(), w.map(|w| w.v), SCALAR_BITS);
+    le_constant_gadget(cs, &v_bits, &-Scalar::one());
+    cs.constrain(v_bits[0].into());
+
+    // (x, y) = (u / v, (u - 1) / (u + 1))
+    let u_assign = w.map(|w| w.x * w.v);
+    let x = quotient(cs, u.clone(), v.into(), w.map(|w| (w.x, w.v)));
+    let y = quotient(
+        cs,
+        u.clone() - Scalar::one(),
+        u + Scalar::one(),
+        w.and_then(|w| u_assign.map(|u| (w.y, u + Scalar::one()))),
+    );
+    let (_, _, xy) = cs.multiply(x.into(), y.into());
+
+    let p = SonnyEdwardsPointGadget {
+        X: x.into(),
+        Y: y.into(),
+        Z: one,
+        T: xy.into(),
+    };
+    let p8 = p.double(cs).double(cs).double(cs);
+    (p8, w.map(|w| clear_cofactor(&w.x, &w.y)))
+}
+
+/// Computes `sk * H_point(message)`, with `sk` given by its little-endian bits.
+/// The prover must provide every assignment.
+pub fn hash_to_point_mul_gadget(
+    cs: &mut dyn CS,
+    message: &[LC],
+    message_assign: Option<&[Scalar]>,
+    sk: Vec<Variable>,
+    sk_assign: Option<&[u8]>,
+) -> (SonnyEdwardsPointGadget, Option<SonnyEdwardsPoint>) {
+    let (h, h_assign) = hash_to_point_gadget(cs, message, message_assign);
+    SonnyEdwardsPointGadget::scalar_mul(h, h_assign, sk, sk_assign, cs)
+}
+
+mod test {
+    use super::*;
+    use crate::helpers::{affine_coords, PointCoords};
+    use crate::recording::RecordingCS;
+    use zerocaf::scalar::Scalar as SonnyScalar;
+
+    #[test]
+    fn sqrt_and_squares() {
+        let x = Scalar::random(&mut rand::thread_rng());
+        let root = sqrt(&(x * x)).unwrap();
+        assert!(root == x || root == -x);
+        assert!(!is_square(&Scalar::from(Z)));
+        assert!(sqrt(&Scalar::from(Z)).is_none());
+    }
+
+    #[test]
+    fn hash_to_point_is_on_curve() {
+        let message = [Scalar::random(&mut rand::thread_rng())];
+        let w = map_witness(&mimc_hash(&message));
+        let a = fe_to_scalar(&zerocaf::constants::EDWARDS_A);
+        let d = fe_to_scalar(&zerocaf::constants::EDWARDS_D);
+        let (x_sq, y_sq) = (w.x * w.x, w.y * w.y);
+        assert_eq!(a * x_sq + y_sq, Scalar::one() + d * x_sq * y_sq);
+    }
+
+    #[test]
+    fn hash_to_point_mul_matches_native() {
+        let message = [Scalar::random(&mut rand::thread_rng())];
+        let sk_bits = SonnyScalar::random(&mut rand::thread_rng()).into_bits()[..16].to_vec();
+
+        let mut cs = RecordingCS::new(b"Hash to point");
+        let m: Vec<LC> = message.iter().map(|m| cs.commit(Some(*m)).into()).collect();
+        let sk: Vec<Variable> = sk_bits
+            .iter()
+            .map(|b| cs.commit(Some(Scalar::from(*b))))
+            .collect();
+        let (res, res_assign) =
+            hash_to_point_mul_gadget(&mut cs, &m, Some(&message), sk, Some(&sk_bits));
+        assert_eq!(cs.first_unsatisfied(), None);
+
+        let expected = witness::edwards::scalar_mul(&hash_to_point(&message), &sk_bits);
+        assert_eq!(affine_coords(&res_assign.unwrap()), affine_coords(&expected));
+        assert_eq!(cs.eval(&res.X), Some(expected.coords()[0]));
+    }
+}
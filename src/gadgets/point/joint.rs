@@ -0,0 +1,178 @@
+//! Joint double-scalar multiplication and half-length scalar decomposition.
+//!
+//! GLV splits a scalar into two half-length ones through an efficiently
+//! computable endomorphism `phi(P) = [lambda]P`. The Sonny curve has no such
+//! endomorphism (its only cheap automorphism is the negation), so GLV does not
+//! apply. What does apply is Shamir's trick: `k1 * P + k2 * Q` is computed with
+//! a single doubling ladder, adding at every step one of `O`, `P`, `Q` or
+//! `P + Q` selected by the bits of both scalars. Boolean checks aside, a step
+//! takes 35 multipliers instead of the 52 of two `scalar_mul` ladders, which
+//! would also help the `s * B - c * PK` of signature verification.
+//!
+//! Combined with a decomposition `k = k_lo + 2^h * k_hi` with a proven
+//! recomposition, a single scalar multiplication becomes the joint one of
+//! `(k_lo, k_hi)` over `(P, [2^h]P)`, halving the doublings of the ladder.
+use crate::gadgets::boolean::bit_decomposition_gadget;
+use crate::gadgets::point::edwards_point::SonnyEdwardsPointGadget;
+use bulletproofs::r1cs::{ConstraintSystem as CS, LinearCombination as LC, Variable};
+use curve25519_dalek::scalar::Scalar;
+
+/// Decomposes `k` into `n` bits and splits them into the `(n + 1) / 2` low bits
+/// and the remaining high ones, so `k = k_lo + 2^((n + 1) / 2) * k_hi`. Every
+/// bit is boolean-constrained and the recomposition is constrained to be `k`.
+///
+/// `n` must be lower than 253, so the decomposition of `k` is unique.
+pub fn decompose_scalar_gadget(
+    cs: &mut dyn CS,
+    k: LC,
+    k_assign: Option<Scalar>,
+    n: usize,
+) -> (Vec<Variable>, Vec<Variable>) {
+    assert!(n < 253, "the decomposition must not wrap around the field");
+    let mut lo = bit_decomposition_gadget(cs, k, k_assign, n);
+    let hi = lo.split_off((n + 1) / 2);
+    (lo, hi)
+}
+
+fn coords(p: &SonnyEdwardsPointGadget) -> [LC; 4] {
+    [p.X.clone(), p.Y.clone(), p.Z.clone(), p.T.clone()]
+}
+
+/// Selects `O`, `P`, `Q` or `P + Q` from the bits `b1` (of `P`) and `b2` (of
+/// `Q`), a missing bit being taken as zero.
+fn select(
+    cs: &mut dyn CS,
+    p: &SonnyEdwardsPointGadget,
+    q: &SonnyEdwardsPointGadget,
+    pq: &SonnyEdwardsPointGadget,
+    b1: Option<Variable>,
+    b2: Option<Variable>,
+) -> SonnyEdwardsPointGadget {
+    let o = coords(&SonnyEdwardsPointGadget::identity());
+    let (p, q, pq) = (coords(p), coords(q), coords(pq));
+    let b12 = match (b1, b2) {
+        (Some(b1), Some(b2)) => Some(cs.multiply(b1.into(), b2.into()).2),
+        _ => None,
+    };
+    // O + b1 * (P - O) + b2 * (Q - O) + b1 * b2 * (P + Q - P - Q + O)
+    let mut res = o.clone();
+    for i in 0..4 {
+        if let Some(b1) = b1 {
+            let (_, _, term) = cs.multiply(b1.into(), p[i].clone() - o[i].clone());
+            res[i] = res[i].clone() + term;
+        }
+        if let Some(b2) = b2 {
+            let (_, _, term) = cs.multiply(b2.into(), q[i].clone() - o[i].clone());
+            res[i] = res[i].clone() + term;
+        }
+        if let Some(b12) = b12 {
+            let diff = pq[i].clone() - p[i].clone() - q[i].clone() + o[i].clone();
+            let (_, _, term) = cs.multiply(b12.into(), diff);
+            res[i] = res[i].clone() + term;
+        }
+    }
+    let [X, Y, Z, T] = res;
+    SonnyEdwardsPointGadget { X, Y, Z, T }
+}
+
+/// Computes `k1 * P + k2 * Q` with a single ladder. The scalars are given by
+/// their little-endian bits, which must already be boolean-constrained (e.g. by
+/// `decompose_scalar_gadget`), and may have different lengths.
+pub fn double_scalar_mul(
+    cs: &mut dyn CS,
+    p: &SonnyEdwardsPointGadget,
+    k1: &[Variable],
+    q: &SonnyEdwardsPointGadget,
+    k2: &[Variable],
+) -> SonnyEdwardsPointGadget {
+    let pq = p.add(q, cs);
+    let mut acc = SonnyEdwardsPointGadget::identity();
+    for i in (0..k1.len().max(k2.len())).rev() {
+        acc = acc.double(cs);
+        let term = select(cs, p, q, &pq, k1.get(i).copied(), k2.get(i).copied());
+        acc = acc.add(&term, cs);
+    }
+    acc
+}
+
+/// Computes `k * P` for an `n`-bit `k` (`n < 253`) by decomposing it with
+/// `decompose_scalar_gadget` and running `double_scalar_mul` over `P` and
+/// `[2^h]P`, the latter computed with `mul_by_pow2`.
+pub fn scalar_mul_decomposed(
+    cs: &mut dyn CS,
+    p: &SonnyEdwardsPointGadget,
+    k: LC,
+    k_assign: Option<Scalar>,
+    n: usize,
+) -> SonnyEdwardsPointGadget {
+    let (lo, hi) = decompose_scalar_gadget(cs, k, k_assign, n);
+    let p_hi = p.mul_by_pow2(lo.len(), cs);
+    double_scalar_mul(cs, p, &lo, &p_hi, &hi)
+}
+
+mod test {
+    use super::*;
+    use crate::gadgets::point::witness;
+    use crate::helpers::bits_to_bytes;
+    use crate::recording::RecordingCS;
+    use zerocaf::edwards::EdwardsPoint as SonnyEdwardsPoint;
+    use zerocaf::scalar::Scalar as SonnyScalar;
+
+    fn random_bits(n: usize) -> Vec<u8> {
+        SonnyScalar::random(&mut rand::thread_rng()).into_bits()[..n].to_vec()
+    }
+
+    #[test]
+    fn double_scalar_mul_matches_witness() {
+        let mut rng = rand::thread_rng();
+        let (p, q) = (
+            SonnyEdwardsPoint::new_random_point(&mut rng),
+            SonnyEdwardsPoint::new_random_point(&mut rng),
+        );
+        let (k1, k2) = (random_bits(12), random_bits(7));
+        let expected = witness::edwards::add(
+            &witness::edwards::scalar_mul(&p, &k1),
+            &witness::edwards::scalar_mul(&q, &k2),
+        );
+
+        let mut cs = RecordingCS::new(b"DoubleScalarMul");
+        let mut alloc = |bits: &[u8]| -> Vec<Variable> {
+            bits.iter()
+                .map(|b| cs.allocate(Some(Scalar::from(*b))).unwrap())
+                .collect()
+        };
+        let (v1, v2) = (alloc(&k1), alloc(&k2));
+        let res = double_scalar_mul(
+            &mut cs,
+            &SonnyEdwardsPointGadget::from_point(&p),
+            &v1,
+            &SonnyEdwardsPointGadget::from_point(&q),
+            &v2,
+        );
+        res.equal_extended(&SonnyEdwardsPointGadget::from_point(&expected), &mut cs);
+        assert!(cs.first_unsatisfied().is_none());
+    }
+
+    #[test]
+    fn decomposed_scalar_mul() {
+        let n = 252;
+        let p = SonnyEdwardsPoint::new_random_point(&mut rand::thread_rng());
+        let bits = random_bits(n);
+        let k = Scalar::from_bytes_mod_order(bits_to_bytes(&bits));
+
+        let mut cs = RecordingCS::new(b"DecomposedScalarMul");
+        let k_lc = LC::from(cs.commit(Some(k)));
+        let p_gadget = SonnyEdwardsPointGadget::from_point(&p);
+        let res = scalar_mul_decomposed(&mut cs, &p_gadget, k_lc, Some(k), n);
+        let expected = witness::edwards::scalar_mul(&p, &bits);
+        res.equal_extended(&SonnyEdwardsPointGadget::from_point(&expected), &mut cs);
+        assert!(cs.first_unsatisfied().is_none());
+        let decomposed = cs.multipliers();
+
+        // The plain ladder over the same bits.
+        let mut cs = RecordingCS::new(b"DecomposedScalarMul");
+        let vars = (0..n).map(|_| cs.allocate(None).unwrap()).collect();
+        SonnyEdwardsPointGadget::scalar_mul(p_gadget, None, vars, None, &mut cs);
+        assert!(decomposed < cs.multipliers());
+    }
+}
@@ -1,2 +1,11 @@
+pub mod chain;
+pub mod comb;
 pub mod edwards_point;
+pub mod fixed_base;
+pub mod hash_to_point;
+pub mod joint;
+pub mod ops;
 pub mod ristretto_point;
+pub mod witness;
+#[cfg(feature = "prover")]
+pub mod witnessed;
@@ -0,0 +1,96 @@
+//! Operator overloading for `SonnyEdwardsPointGadget`.
+//!
+//! Since the operators cannot access the CS, `&p + &q - &t` does not add any
+//! constraint by itself: it builds an `EdwardsPointExpr` which is later turned into
+//! a gadget with `synthesize`:
+//!
+//! ```ignore
+//! let r = (&p + &q - &t).synthesize(cs);
+//! ```
+use crate::gadgets::point::edwards_point::SonnyEdwardsPointGadget;
+use bulletproofs::r1cs::ConstraintSystem as CS;
+use std::ops::{Add, Neg, Sub};
+
+/// A deferred expression over point gadgets.
+#[derive(Clone, Debug)]
+pub enum EdwardsPointExpr {
+    Point(SonnyEdwardsPointGadget),
+    Add(Box<EdwardsPointExpr>, Box<EdwardsPointExpr>),
+    Neg(Box<EdwardsPointExpr>),
+}
+
+impl EdwardsPointExpr {
+    /// Adds to the CS the constraints of the expression, returning the resulting point.
+    /// Negations are free, every addition costs one `SonnyEdwardsPointGadget::add`.
+    pub fn synthesize(self, cs: &mut dyn CS) -> SonnyEdwardsPointGadget {
+        match self {
+            EdwardsPointExpr::Point(p) => p,
+            EdwardsPointExpr::Add(l, r) => {
+                let l = l.synthesize(cs);
+                let r = r.synthesize(cs);
+                l.add(&r, cs)
+            }
+            EdwardsPointExpr::Neg(e) => e.synthesize(cs).neg(),
+        }
+    }
+}
+
+impl From<SonnyEdwardsPointGadget> for EdwardsPointExpr {
+    fn from(p: SonnyEdwardsPointGadget) -> EdwardsPointExpr {
+        EdwardsPointExpr::Point(p)
+    }
+}
+
+impl<'a> From<&'a SonnyEdwardsPointGadget> for EdwardsPointExpr {
+    fn from(p: &'a SonnyEdwardsPointGadget) -> EdwardsPointExpr {
+        EdwardsPointExpr::Point(p.clone())
+    }
+}
+
+impl<R: Into<EdwardsPointExpr>> Add<R> for EdwardsPointExpr {
+    type Output = EdwardsPointExpr;
+
+    fn add(self, rhs: R) -> EdwardsPointExpr {
+        EdwardsPointExpr::Add(Box::new(self), Box::new(rhs.into()))
+    }
+}
+
+impl<R: Into<EdwardsPointExpr>> Sub<R> for EdwardsPointExpr {
+    type Output = EdwardsPointExpr;
+
+    fn sub(self, rhs: R) -> EdwardsPointExpr {
+        self + (-rhs.into())
+    }
+}
+
+impl Neg for EdwardsPointExpr {
+    type Output = EdwardsPointExpr;
+
+    fn neg(self) -> EdwardsPointExpr {
+        EdwardsPointExpr::Neg(Box::new(self))
+    }
+}
+
+impl<'a, R: Into<EdwardsPointExpr>> Add<R> for &'a SonnyEdwardsPointGadget {
+    type Output = EdwardsPointExpr;
+
+    fn add(self, rhs: R) -> EdwardsPointExpr {
+        EdwardsPointExpr::from(self) + rhs
+    }
+}
+
+impl<'a, R: Into<EdwardsPointExpr>> Sub<R> for &'a SonnyEdwardsPointGadget {
+    type Output = EdwardsPointExpr;
+
+    fn sub(self, rhs: R) -> EdwardsPointExpr {
+        EdwardsPointExpr::from(self) - rhs
+    }
+}
+
+impl<'a> Neg for &'a SonnyEdwardsPointGadget {
+    type Output = EdwardsPointExpr;
+
+    fn neg(self) -> EdwardsPointExpr {
+        -EdwardsPointExpr::from(self)
+    }
+}
@@ -1,11 +1,20 @@
+use crate::errors::GadgetError;
+use crate::gadgets::point::edwards_point::SonnyEdwardsPointGadget;
+use crate::gadgets::point::witness;
 use crate::gadgets::scalar::nonzero_gadget;
+use crate::helpers;
+use crate::instrument;
+use crate::recording::format_lc;
 use bulletproofs::r1cs::{
     ConstraintSystem, LinearCombination, R1CSError, RandomizedConstraintSystem, Variable,
+    Verifier,
 };
+#[cfg(feature = "prover")]
+use bulletproofs::r1cs::Prover;
 use curve25519_dalek::{ristretto::CompressedRistretto, scalar::Scalar};
+use std::fmt;
 use zerocaf::ristretto::RistrettoPoint as SonnyRistrettoPoint;
 use zerocaf::scalar::Scalar as SonnyScalar;
-use zerocaf::traits::ops::Double;
 
 #[derive(Clone)]
 // Represents a Sonny Edwards Point using Twisted Edwards Extended Coordinates
@@ -16,7 +25,63 @@ pub struct SonnyRistrettoPointGadget {
     pub T: LinearCombination,
 }
 
+impl fmt::Debug for SonnyRistrettoPointGadget {
+    /// Prints the LC of every coordinate symbolically (see `recording::format_lc`).
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("SonnyRistrettoPointGadget")
+            .field("X", &format_args!("{}", format_lc(&self.X)))
+            .field("Y", &format_args!("{}", format_lc(&self.Y)))
+            .field("Z", &format_args!("{}", format_lc(&self.Z)))
+            .field("T", &format_args!("{}", format_lc(&self.T)))
+            .finish()
+    }
+}
+
+impl From<SonnyRistrettoPointGadget> for SonnyEdwardsPointGadget {
+    /// Every Ristretto point is a valid Edwards point, so no constraints are needed.
+    fn from(p: SonnyRistrettoPointGadget) -> SonnyEdwardsPointGadget {
+        SonnyEdwardsPointGadget {
+            X: p.X,
+            Y: p.Y,
+            Z: p.Z,
+            T: p.T,
+        }
+    }
+}
+
 impl SonnyRistrettoPointGadget {
+    /// Number of commitments (one per coordinate) a committed point takes.
+    pub const COMMITMENTS: usize = 4;
+
+    /// The identity point `(0, 1, 1, 0)`, as a constant. Being a known Ristretto
+    /// point, it needs no constraints.
+    pub fn identity() -> SonnyRistrettoPointGadget {
+        SonnyRistrettoPointGadget {
+            X: Scalar::zero().into(),
+            Y: Scalar::one().into(),
+            Z: Scalar::one().into(),
+            T: Scalar::zero().into(),
+        }
+    }
+
+    /// The basepoint of the Sonny Ristretto group, as a constant. Being a known
+    /// Ristretto point, it needs no constraints (unlike `from_point`).
+    pub fn basepoint() -> SonnyRistrettoPointGadget {
+        let point = zerocaf::constants::RISTRETTO_BASEPOINT.0;
+        SonnyRistrettoPointGadget {
+            X: Scalar::from_bytes_mod_order(point.X.to_bytes()).into(),
+            Y: Scalar::from_bytes_mod_order(point.Y.to_bytes()).into(),
+            Z: Scalar::from_bytes_mod_order(point.Z.to_bytes()).into(),
+            T: Scalar::from_bytes_mod_order(point.T.to_bytes()).into(),
+        }
+    }
+
+    /// Converts the gadget into a `SonnyEdwardsPointGadget`. It is free, since every
+    /// Ristretto point is a valid Edwards point.
+    pub fn into_edwards(self) -> SonnyEdwardsPointGadget {
+        self.into()
+    }
+
     /// Builds a `SonnyRistrettoPointGadget` from a `SonnyRistrettoPoint` adding a constrain
     /// that checks that the point relies on the curve and another one checking that
     /// it is indeed a RistrettoPoint.
@@ -31,8 +96,18 @@ impl SonnyRistrettoPointGadget {
         gadget_p
     }
 
-    pub fn from_lcs(lcs: Vec<LinearCombination>, cs: &mut ConstraintSystem) -> Self {
-        assert!(lcs.len() == 4);
+    /// Builds a `SonnyRistrettoPointGadget` from the LCs of its `X, Y, Z, T` coordinates,
+    /// adding the constraints that check that it is a RistrettoPoint.
+    pub fn from_lcs(
+        lcs: Vec<LinearCombination>,
+        cs: &mut dyn ConstraintSystem,
+    ) -> Result<Self, GadgetError> {
+        if lcs.len() != 4 {
+            return Err(GadgetError::InvalidLCsLength {
+                expected: 4,
+                found: lcs.len(),
+            });
+        }
         let gadget = SonnyRistrettoPointGadget {
             X: lcs[0].clone(),
             Y: lcs[1].clone(),
@@ -41,7 +116,31 @@ impl SonnyRistrettoPointGadget {
         };
 
         gadget.ristretto_gadget(cs, None);
-        gadget
+        Ok(gadget)
+    }
+
+    /// Commits the coordinates of a point as the prover, adding the constraints
+    /// that check that it is a RistrettoPoint.
+    #[cfg(feature = "prover")]
+    pub fn prover_commit_to_sonny_ristretto_point(
+        prover: &mut Prover,
+        p: &SonnyRistrettoPoint,
+    ) -> (Self, Vec<CompressedRistretto>) {
+        let (mut lcs, commitments) = helpers::prover_commit_points(prover, &[*p]);
+        let gadget: SonnyRistrettoPointGadget = lcs.remove(0).into();
+        gadget.ristretto_gadget(prover, Some(*p));
+        (gadget, commitments)
+    }
+
+    /// Commits the coordinates of a point as the verifier, adding the constraints
+    /// that check that it is a RistrettoPoint.
+    /// Fails if `commitments` does not contain exactly `COMMITMENTS` elements.
+    pub fn verifier_commit_to_sonny_ristretto_point(
+        verifier: &mut Verifier,
+        commitments: &[CompressedRistretto],
+    ) -> Result<Self, GadgetError> {
+        let lcs = helpers::verifier_commit_points(verifier, commitments, 1)?.remove(0);
+        SonnyRistrettoPointGadget::from_lcs(lcs.to_vec(), verifier)
     }
 
     /// Adds constrains to validate only points that lie on the prime sub-group and excludes the others
@@ -52,41 +151,27 @@ impl SonnyRistrettoPointGadget {
         cs: &mut dyn ConstraintSystem,
         point_assign: Option<SonnyRistrettoPoint>,
     ) {
-        // XXX: Here we should check that the point relies on the curve.
-
-        let two_p = self.double(cs);
-        let four_p = two_p.double(cs);
-        let eight_p = four_p.double(cs);
-        // Constrain that 8*P != Identity point
-        match point_assign {
-            Some(point) => {
-                // Constrain X != 0
-                let point_8 = point.double().double().double();
-                nonzero_gadget(
-                    eight_p.X,
-                    Some(Scalar::from_bytes_mod_order(point_8.0.X.to_bytes())),
-                    cs,
-                );
-                // Constrain (Y - Z) != 0
-                let y_m_z = eight_p.Y.clone() - eight_p.Z.clone();
-                cs.constrain(eight_p.Y.clone() - eight_p.Z - y_m_z.clone());
-                nonzero_gadget(
-                    y_m_z.into(),
-                    Some(Scalar::from_bytes_mod_order(
-                        (point_8.0.Y - point_8.0.Z).to_bytes(),
-                    )),
-                    cs,
-                );
-            }
-            None => {
-                // Constrain X != 0
-                nonzero_gadget(eight_p.X, None, cs);
-                // Constrain (Y - Z) != 0
-                let y_m_z = eight_p.Y.clone() - eight_p.Z.clone();
-                cs.constrain(eight_p.Y.clone() - eight_p.Z + y_m_z.clone());
-                nonzero_gadget(y_m_z.into(), None, cs);
-            }
-        }
+        instrument::synthesis("ristretto_gadget", cs, |cs| {
+            // XXX: Here we should check that the point relies on the curve.
+
+            let two_p = self.double(cs);
+            let four_p = two_p.double(cs);
+            let eight_p = four_p.double(cs);
+            // Constrain that 8*P != Identity point, i.e. X != 0 and (Y - Z) != 0.
+            // The assignments follow the in-circuit doublings.
+            let double = witness::ristretto::double;
+            let point_8 = point_assign.map(|p| double(&double(&double(&p))));
+            nonzero_gadget(
+                eight_p.X,
+                point_8.map(|p| helpers::fe_to_scalar(&p.0.X)),
+                cs,
+            );
+            nonzero_gadget(
+                eight_p.Y - eight_p.Z,
+                point_8.map(|p| helpers::fe_to_scalar(&(p.0.Y - p.0.Z))),
+                cs,
+            );
+        })
     }
 
     pub fn add(
@@ -94,84 +179,88 @@ impl SonnyRistrettoPointGadget {
         cs: &mut dyn ConstraintSystem,
         other: SonnyRistrettoPointGadget,
     ) -> SonnyRistrettoPointGadget {
-        // XXX: public constants should be defined at a higher level
-        let a: Scalar = Scalar::from_bytes_mod_order(zerocaf::constants::EDWARDS_A.to_bytes());
-        let d: Scalar = Scalar::from_bytes_mod_order(zerocaf::constants::EDWARDS_D.to_bytes());
-
-        // Point addition impl
-        // A = p1_x * p2_x
-        // B = p1_y * p2_y
-        // C = d*(p1_t * p2_t)
-        // D = p1_z * p2_z
-        // E = (p1_x + p1_y) * (p2_x + p2_y) + a*A + a*B
-        // F = D - C
-        // G = D + C
-        // H = B + A
-        // X3 = E * F , Y3 = G * H, Z3 = F * G, T3 = E * H
-        //
-        // Compute A
-        let (_, _, A) = cs.multiply(self.X.clone(), other.X.clone());
-
-        // Compute B
-        let (_, _, B) = cs.multiply(self.Y.clone(), other.Y.clone());
-
-        // Compute C
-        let (_, _, pt) = cs.multiply(self.T, other.T);
-        let (_, _, C) = cs.multiply(pt.into(), d.into());
-
-        // Compute D
-        let (_, _, D) = cs.multiply(self.Z, other.Z);
-
-        // Compute E
-        let E = {
-            let E1 = self.X.clone() + self.Y.clone();
-            cs.constrain(E1.clone() - self.X - self.Y);
-
-            let E2 = other.X.clone() + other.Y.clone();
-            cs.constrain(E2.clone() - other.X - other.Y);
-
-            let (_, _, E12) = cs.multiply(E1, E2);
-
-            let (_, _, aA) = cs.multiply(a.into(), A.into());
-            let (_, _, bB) = cs.multiply(a.into(), B.into());
-
-            let E = aA + bB + E12;
-            cs.constrain(E.clone() - aA - bB - E12);
-
-            E
-        };
+        instrument::synthesis("ristretto addition", cs, |cs| {
+            // XXX: public constants should be defined at a higher level
+            let a: Scalar = Scalar::from_bytes_mod_order(zerocaf::constants::EDWARDS_A.to_bytes());
+            let d: Scalar = Scalar::from_bytes_mod_order(zerocaf::constants::EDWARDS_D.to_bytes());
 
-        // Compute F
-        let F = D - C;
-        cs.constrain(F.clone() - D + C);
+            // Point addition impl
+            // A = p1_x * p2_x
+            // B = p1_y * p2_y
+            // C = d*(p1_t * p2_t)
+            // D = p1_z * p2_z
+            // E = (p1_x + p1_y) * (p2_x + p2_y) + a*A + a*B
+            // F = D - C
+            // G = D + C
+            // H = B + A
+            // X3 = E * F , Y3 = G * H, Z3 = F * G, T3 = E * H
+            //
+            // Compute A
+            let (_, _, A) = cs.multiply(self.X.clone(), other.X.clone());
 
-        // Compute G
-        let G = D + C;
-        cs.constrain(G.clone() - D - C);
+            // Compute B
+            let (_, _, B) = cs.multiply(self.Y.clone(), other.Y.clone());
 
-        // Compute H
-        let H = B + A;
-        cs.constrain(H.clone() - B - A);
+            // Compute C
+            let (_, _, pt) = cs.multiply(self.T, other.T);
+            let (_, _, C) = cs.multiply(pt.into(), d.into());
 
-        // Compute resulting point
-        let (E, F, X) = cs.multiply(E, F);
-        let (G, H, Y) = cs.multiply(G, H);
-        let (_, _, Z) = cs.multiply(F.into(), G.into());
-        let (_, _, T) = cs.multiply(E.into(), H.into());
+            // Compute D
+            let (_, _, D) = cs.multiply(self.Z, other.Z);
 
-        SonnyRistrettoPointGadget {
-            X: X.into(),
-            Y: Y.into(),
-            Z: Z.into(),
-            T: T.into(),
-        }
+            // Compute E
+            let E = {
+                let E1 = self.X.clone() + self.Y.clone();
+                cs.constrain(E1.clone() - self.X - self.Y);
+
+                let E2 = other.X.clone() + other.Y.clone();
+                cs.constrain(E2.clone() - other.X - other.Y);
+
+                let (_, _, E12) = cs.multiply(E1, E2);
+
+                let (_, _, aA) = cs.multiply(a.into(), A.into());
+                let (_, _, bB) = cs.multiply(a.into(), B.into());
+
+                let E = aA + bB + E12;
+                cs.constrain(E.clone() - aA - bB - E12);
+
+                E
+            };
+
+            // Compute F
+            let F = D - C;
+            cs.constrain(F.clone() - D + C);
+
+            // Compute G
+            let G = D + C;
+            cs.constrain(G.clone() - D - C);
+
+            // Compute H
+            let H = B + A;
+            cs.constrain(H.clone() - B - A);
+
+            // Compute resulting point
+            let (E, F, X) = cs.multiply(E, F);
+            let (G, H, Y) = cs.multiply(G, H);
+            let (_, _, Z) = cs.multiply(F.into(), G.into());
+            let (_, _, T) = cs.multiply(E.into(), H.into());
+
+            SonnyRistrettoPointGadget {
+                X: X.into(),
+                Y: Y.into(),
+                Z: Z.into(),
+                T: T.into(),
+            }
+        })
     }
     /// Adds a constraint into the R1CS that checks equalty for two `SonnyRistrettoPointGadget`s
     /// by constraining -> `X1*Y2 == Y1*X2`.
     pub fn equals(&self, cs: &mut dyn ConstraintSystem, other: SonnyRistrettoPointGadget) {
-        let (_, _, x1y2) = cs.multiply(self.X.clone(), other.Y);
-        let (_, _, y1x2) = cs.multiply(self.Y.clone(), other.X);
-        cs.constrain(x1y2 - y1x2);
+        instrument::synthesis("ristretto equals", cs, |cs| {
+            let (_, _, x1y2) = cs.multiply(self.X.clone(), other.Y);
+            let (_, _, y1x2) = cs.multiply(self.Y.clone(), other.X);
+            cs.constrain(x1y2 - y1x2);
+        })
     }
 
     pub fn double(&self, cs: &mut dyn ConstraintSystem) -> SonnyRistrettoPointGadget {
@@ -186,42 +275,44 @@ impl SonnyRistrettoPointGadget {
         bit: LinearCombination,
         cs: &mut dyn ConstraintSystem,
     ) -> Self {
-        let one = LinearCombination::from(Scalar::one());
-
-        // x' = x if bit = 1
-        // x' = 0 if bit = 0 =>
-        // x' = x * bit
-        let (_, bit, x_prime) = cs.multiply(self.X.clone(), bit);
-
-        // y' = y if bit = 1
-        // y' = 1 if bit = 0 =>
-        // y' = bit * y + (1 - bit)
-        let y_prime = {
-            let (bit, _, bit_t_y) = cs.multiply(bit.into(), self.Y.clone());
-            let y_prime = bit_t_y + one.clone() - bit;
-            cs.constrain(y_prime.clone() - bit_t_y - one.clone() + bit);
-            y_prime
-        };
+        instrument::synthesis("ristretto conditionally_select", cs, |cs| {
+            let one = LinearCombination::from(Scalar::one());
 
-        // z' = z if bit = 1
-        // z' = 1 if bit = 0 =>
-        // z' = bit * z + (1 - bit)
-        let z_prime = {
-            let (bit, _, bit_t_z) = cs.multiply(bit.into(), self.Z.clone());
-            let z_prime = bit_t_z + one.clone() - bit;
-            cs.constrain(z_prime.clone() - bit_t_z - one + bit);
-            z_prime
-        };
+            // x' = x if bit = 1
+            // x' = 0 if bit = 0 =>
+            // x' = x * bit
+            let (_, bit, x_prime) = cs.multiply(self.X.clone(), bit);
 
-        // t' = t if bit = 1
-        // t' = 0 if bit = 0 =>
-        // t' = t * bit
-        let (_, _, t_prime) = cs.multiply(self.T.clone(), bit.into());
-        SonnyRistrettoPointGadget {
-            X: x_prime.into(),
-            Y: y_prime.into(),
-            Z: z_prime.into(),
-            T: t_prime.into(),
-        }
+            // y' = y if bit = 1
+            // y' = 1 if bit = 0 =>
+            // y' = bit * y + (1 - bit)
+            let y_prime = {
+                let (bit, _, bit_t_y) = cs.multiply(bit.into(), self.Y.clone());
+                let y_prime = bit_t_y + one.clone() - bit;
+                cs.constrain(y_prime.clone() - bit_t_y - one.clone() + bit);
+                y_prime
+            };
+
+            // z' = z if bit = 1
+            // z' = 1 if bit = 0 =>
+            // z' = bit * z + (1 - bit)
+            let z_prime = {
+                let (bit, _, bit_t_z) = cs.multiply(bit.into(), self.Z.clone());
+                let z_prime = bit_t_z + one.clone() - bit;
+                cs.constrain(z_prime.clone() - bit_t_z - one + bit);
+                z_prime
+            };
+
+            // t' = t if bit = 1
+            // t' = 0 if bit = 0 =>
+            // t' = t * bit
+            let (_, _, t_prime) = cs.multiply(self.T.clone(), bit.into());
+            SonnyRistrettoPointGadget {
+                X: x_prime.into(),
+                Y: y_prime.into(),
+                Z: z_prime.into(),
+                T: t_prime.into(),
+            }
+        })
     }
 }
@@ -0,0 +1,224 @@
+//! Native counterparts of the point gadgets.
+//!
+//! Every function here mirrors, formula by formula, the circuit built by the
+//! gadget of the same name. That way the prover obtains exactly the extended
+//! coordinates the constraints will hold, instead of recomputing them by hand
+//! with zerocaf and hoping both stay in sync.
+
+/// Witness computations for `SonnyEdwardsPointGadget`.
+pub mod edwards {
+    use crate::gadgets::point::chain::{double_add_chain, ChainStep};
+    use crate::gadgets::point::edwards_point::IdentityPolicy;
+    use crate::precomp::{window_index, FixedBaseTable};
+    use zerocaf::edwards::EdwardsPoint as SonnyEdwardsPoint;
+    use zerocaf::field::FieldElement;
+
+    #[cfg(feature = "parallel")]
+    use rayon::prelude::*;
+
+    /// Returns the Identity point in the same extended coordinates
+    /// `(0, 1, 1, 0)` used by the gadgets.
+    pub fn identity() -> SonnyEdwardsPoint {
+        SonnyEdwardsPoint {
+            X: FieldElement::zero(),
+            Y: FieldElement::one(),
+            Z: FieldElement::one(),
+            T: FieldElement::zero(),
+        }
+    }
+
+    /// Native version of `SonnyEdwardsPointGadget::add`.
+    pub fn add(p: &SonnyEdwardsPoint, q: &SonnyEdwardsPoint) -> SonnyEdwardsPoint {
+        let a = zerocaf::constants::EDWARDS_A;
+        let d = zerocaf::constants::EDWARDS_D;
+
+        let A = p.X * q.X;
+        let B = p.Y * q.Y;
+        let C = d * (p.T * q.T);
+        let D = p.Z * q.Z;
+        let E = (p.X + p.Y) * (q.X + q.Y) + a * A + a * B;
+        let F = D - C;
+        let G = D + C;
+        let H = B + A;
+
+        SonnyEdwardsPoint {
+            X: E * F,
+            Y: G * H,
+            Z: F * G,
+            T: E * H,
+        }
+    }
+
+    /// Native version of `SonnyEdwardsPointGadget::add_dedicated`. Its `Z` is
+    /// zero on the inputs the formulas do not handle.
+    pub fn add_dedicated(p: &SonnyEdwardsPoint, q: &SonnyEdwardsPoint) -> SonnyEdwardsPoint {
+        let a = zerocaf::constants::EDWARDS_A;
+
+        let A = p.X * q.X;
+        let B = p.Y * q.Y;
+        let C = p.Z * q.T;
+        let D = p.T * q.Z;
+        let E = D + C;
+        let F = (p.X - p.Y) * (q.X + q.Y) + B - A;
+        let G = B + a * A;
+        let H = D - C;
+
+        SonnyEdwardsPoint {
+            X: E * F,
+            Y: G * H,
+            Z: F * G,
+            T: E * H,
+        }
+    }
+
+    /// Native version of `SonnyEdwardsPointGadget::add_with_policy`.
+    pub fn add_with_policy(
+        p: &SonnyEdwardsPoint,
+        q: &SonnyEdwardsPoint,
+        policy: IdentityPolicy,
+    ) -> SonnyEdwardsPoint {
+        match policy {
+            IdentityPolicy::Allow => add(p, q),
+            IdentityPolicy::Forbid => add_dedicated(p, q),
+        }
+    }
+
+    /// Native version of `SonnyEdwardsPointGadget::double`.
+    pub fn double(p: &SonnyEdwardsPoint) -> SonnyEdwardsPoint {
+        let a = zerocaf::constants::EDWARDS_A;
+        let two = FieldElement::one() + FieldElement::one();
+
+        let A = p.X * p.X;
+        let B = p.Y * p.Y;
+        let C = two * (p.Z * p.Z);
+        let D = a * A;
+        let E = (p.X + p.Y) * (p.X + p.Y) - A - B;
+        let G = D + B;
+        let F = G - C;
+        let H = D - B;
+
+        SonnyEdwardsPoint {
+            X: E * F,
+            Y: G * H,
+            Z: F * G,
+            T: E * H,
+        }
+    }
+
+    /// Native version of `SonnyEdwardsPointGadget::mul_by_pow2`.
+    pub fn mul_by_pow2(p: &SonnyEdwardsPoint, k: usize) -> SonnyEdwardsPoint {
+        (0..k).fold(*p, |acc, _| double(&acc))
+    }
+
+    /// Native version of `SonnyEdwardsPointGadget::mul_by_const_scalar`.
+    pub fn mul_by_const_scalar(p: &SonnyEdwardsPoint, k: u64) -> SonnyEdwardsPoint {
+        if k == 0 {
+            return identity();
+        }
+        double_add_chain(k)
+            .into_iter()
+            .fold(*p, |acc, step| match step {
+                ChainStep::Double(n) => mul_by_pow2(&acc, n),
+                ChainStep::Add => add(&acc, p),
+                ChainStep::Sub => add(&acc, &neg(p)),
+            })
+    }
+
+    /// Native version of `SonnyEdwardsPointGadget::neg`.
+    pub fn neg(p: &SonnyEdwardsPoint) -> SonnyEdwardsPoint {
+        SonnyEdwardsPoint {
+            X: FieldElement::zero() - p.X,
+            Y: p.Y,
+            Z: p.Z,
+            T: FieldElement::zero() - p.T,
+        }
+    }
+
+    /// Native version of `SonnyEdwardsPointGadget::conditionally_select`.
+    pub fn conditionally_select(p: &SonnyEdwardsPoint, bit: u8) -> SonnyEdwardsPoint {
+        match bit {
+            0 => identity(),
+            _ => *p,
+        }
+    }
+
+    /// Native version of `SonnyEdwardsPointGadget::scalar_mul`.
+    /// The bits are expected in the same order as `zerocaf::scalar::Scalar::into_bits`
+    /// returns them (least significant bit first).
+    pub fn scalar_mul(p: &SonnyEdwardsPoint, bits: &[u8]) -> SonnyEdwardsPoint {
+        let mut Q = identity();
+        for bit in bits.iter().rev() {
+            Q = double(&Q);
+            Q = add(&Q, &conditionally_select(p, *bit));
+        }
+        Q
+    }
+
+    /// Native version of `SonnyEdwardsPointGadget::scalar_mul_prefixes`.
+    pub fn scalar_mul_prefixes(p: &SonnyEdwardsPoint, bits: &[u8]) -> Vec<SonnyEdwardsPoint> {
+        let mut Q = identity();
+        let mut prefixes = Vec::with_capacity(bits.len());
+        for bit in bits.iter().rev() {
+            Q = double(&Q);
+            Q = add(&Q, &conditionally_select(p, *bit));
+            prefixes.push(Q);
+        }
+        prefixes
+    }
+
+    /// Native version of `SonnyEdwardsPointGadget::multiscalar_mul`. With the
+    /// `parallel` feature the terms are computed concurrently; they are
+    /// summed in order, as the gadget does.
+    pub fn multiscalar_mul(points: &[SonnyEdwardsPoint], scalars: &[Vec<u8>]) -> SonnyEdwardsPoint {
+        assert_eq!(points.len(), scalars.len());
+        let term = |(p, bits): (&SonnyEdwardsPoint, &Vec<u8>)| scalar_mul(p, bits);
+        #[cfg(feature = "parallel")]
+        let terms: Vec<SonnyEdwardsPoint> = points.par_iter().zip(scalars).map(term).collect();
+        #[cfg(not(feature = "parallel"))]
+        let terms: Vec<SonnyEdwardsPoint> = points.iter().zip(scalars).map(term).collect();
+        terms.iter().fold(identity(), |acc, t| add(&acc, t))
+    }
+
+    /// Native version of `fixed_base::fixed_base_mul_gadget`.
+    pub fn fixed_base_mul(table: &FixedBaseTable, bits: &[u8]) -> SonnyEdwardsPoint {
+        let mut terms = bits
+            .chunks(table.window)
+            .zip(&table.rows)
+            .map(|(chunk, row)| row[window_index(chunk)]);
+        let first = terms.next().unwrap_or_else(identity);
+        terms.fold(first, |acc, term| add(&acc, &term))
+    }
+}
+
+/// Witness computations for `SonnyRistrettoPointGadget`.
+pub mod ristretto {
+    use super::edwards;
+    use zerocaf::ristretto::RistrettoPoint as SonnyRistrettoPoint;
+
+    /// Native version of `SonnyRistrettoPointGadget::add`.
+    pub fn add(p: &SonnyRistrettoPoint, q: &SonnyRistrettoPoint) -> SonnyRistrettoPoint {
+        SonnyRistrettoPoint(edwards::add(&p.0, &q.0))
+    }
+
+    /// Native version of `SonnyRistrettoPointGadget::double`, which is
+    /// implemented in-circuit as `P + P`.
+    pub fn double(p: &SonnyRistrettoPoint) -> SonnyRistrettoPoint {
+        add(p, p)
+    }
+
+    /// Native version of `SonnyRistrettoPointGadget::conditionally_select`.
+    pub fn conditionally_select(p: &SonnyRistrettoPoint, bit: u8) -> SonnyRistrettoPoint {
+        SonnyRistrettoPoint(edwards::conditionally_select(&p.0, bit))
+    }
+
+    /// Native version of the double-and-add ladder used by `sk_knowledge_gadget`.
+    /// The bits are expected least significant bit first.
+    pub fn scalar_mul(p: &SonnyRistrettoPoint, bits: &[u8]) -> SonnyRistrettoPoint {
+        let mut Q = SonnyRistrettoPoint(edwards::identity());
+        for bit in bits.iter().rev() {
+            Q = double(&Q);
+            Q = add(&Q, &conditionally_select(p, *bit));
+        }
+        Q
+    }
+}
@@ -0,0 +1,196 @@
+//! Point gadgets carrying their assignment, for the prover.
+//!
+//! The prover of a circuit over points needs the assignments of the points the
+//! gadgets compute, to invert a coordinate or to get affine coordinates, say.
+//! Keeping a `SonnyEdwardsPoint` next to every gadget by hand is error-prone:
+//! an operation applied to one and not to the other, or computed natively with
+//! other formulas, leaves the witness out of sync with the constraints and the
+//! proof fails without a hint at the cause.
+//!
+//! A `WitnessedPoint` holds both, and every operation updates the gadget and
+//! its native value in lockstep, the latter with the functions of `witness`.
+use crate::gadgets::point::edwards_point::{IdentityPolicy, SonnyEdwardsPointGadget};
+use crate::gadgets::point::witness;
+use bulletproofs::r1cs::{ConstraintSystem as CS, LinearCombination as LC, Prover, Variable};
+use curve25519_dalek::ristretto::CompressedRistretto;
+use zerocaf::edwards::EdwardsPoint as SonnyEdwardsPoint;
+
+/// A point gadget along with its assignment, in the extended coordinates the
+/// gadget holds.
+#[derive(Clone, Debug)]
+pub struct WitnessedPoint {
+    pub gadget: SonnyEdwardsPointGadget,
+    pub native: SonnyEdwardsPoint,
+}
+
+impl WitnessedPoint {
+    /// The public point `p`, as a constant.
+    pub fn constant(p: &SonnyEdwardsPoint) -> WitnessedPoint {
+        WitnessedPoint {
+            gadget: SonnyEdwardsPointGadget::from_point(p),
+            native: *p,
+        }
+    }
+
+    pub fn identity() -> WitnessedPoint {
+        WitnessedPoint {
+            gadget: SonnyEdwardsPointGadget::identity(),
+            native: witness::edwards::identity(),
+        }
+    }
+
+    /// Commits the coordinates of `p`, see
+    /// `SonnyEdwardsPointGadget::prover_commit_to_sonny_edwards_point`.
+    pub fn commit(
+        prover: &mut Prover,
+        p: &SonnyEdwardsPoint,
+    ) -> (WitnessedPoint, Vec<CompressedRistretto>) {
+        let (gadget, commitments) =
+            SonnyEdwardsPointGadget::prover_commit_to_sonny_edwards_point(prover, p);
+        (WitnessedPoint { gadget, native: *p }, commitments)
+    }
+
+    pub fn add(&self, other: &WitnessedPoint, cs: &mut dyn CS) -> WitnessedPoint {
+        WitnessedPoint {
+            gadget: self.gadget.add(&other.gadget, cs),
+            native: witness::edwards::add(&self.native, &other.native),
+        }
+    }
+
+    /// See `SonnyEdwardsPointGadget::add_with_policy`.
+    pub fn add_with_policy(
+        &self,
+        other: &WitnessedPoint,
+        policy: IdentityPolicy,
+        cs: &mut dyn CS,
+    ) -> WitnessedPoint {
+        WitnessedPoint {
+            gadget: self.gadget.add_with_policy(
+                &other.gadget,
+                policy,
+                Some((self.native, other.native)),
+                cs,
+            ),
+            native: witness::edwards::add_with_policy(&self.native, &other.native, policy),
+        }
+    }
+
+    pub fn neg(&self) -> WitnessedPoint {
+        WitnessedPoint {
+            gadget: self.gadget.neg(),
+            native: witness::edwards::neg(&self.native),
+        }
+    }
+
+    pub fn double(&self, cs: &mut dyn CS) -> WitnessedPoint {
+        WitnessedPoint {
+            gadget: self.gadget.double(cs),
+            native: witness::edwards::double(&self.native),
+        }
+    }
+
+    pub fn mul_by_pow2(&self, k: usize, cs: &mut dyn CS) -> WitnessedPoint {
+        WitnessedPoint {
+            gadget: self.gadget.mul_by_pow2(k, cs),
+            native: witness::edwards::mul_by_pow2(&self.native, k),
+        }
+    }
+
+    pub fn mul_by_const_scalar(&self, k: u64, cs: &mut dyn CS) -> WitnessedPoint {
+        WitnessedPoint {
+            gadget: self.gadget.mul_by_const_scalar(k, cs),
+            native: witness::edwards::mul_by_const_scalar(&self.native, k),
+        }
+    }
+
+    /// The point if the boolean variable `bit`, of assignment `bit_assign`, is
+    /// set, the identity otherwise.
+    pub fn conditionally_select(
+        &self,
+        bit: Variable,
+        bit_assign: u8,
+        cs: &mut dyn CS,
+    ) -> WitnessedPoint {
+        WitnessedPoint {
+            gadget: self.gadget.conditionally_select(LC::from(bit), cs),
+            native: witness::edwards::conditionally_select(&self.native, bit_assign),
+        }
+    }
+
+    /// Multiplies the point by the scalar of little-endian bits `bits`, of
+    /// assignments `bits_assign` (see `SonnyEdwardsPointGadget::scalar_mul`).
+    pub fn scalar_mul(
+        &self,
+        bits: Vec<Variable>,
+        bits_assign: &[u8],
+        cs: &mut dyn CS,
+    ) -> WitnessedPoint {
+        let (gadget, native) = SonnyEdwardsPointGadget::scalar_mul(
+            self.gadget.clone(),
+            Some(self.native),
+            bits,
+            Some(bits_assign),
+            cs,
+        );
+        WitnessedPoint {
+            gadget,
+            native: native.expect("the assignments are provided"),
+        }
+    }
+
+    /// See `SonnyEdwardsPointGadget::to_affine`.
+    pub fn to_affine(&self, cs: &mut dyn CS) -> (Variable, Variable) {
+        self.gadget.to_affine(Some(self.native), cs)
+    }
+
+    /// See `SonnyEdwardsPointGadget::forbid_identity`.
+    pub fn forbid_identity(&self, cs: &mut dyn CS) {
+        self.gadget.forbid_identity(Some(self.native), cs);
+    }
+}
+
+mod test {
+    use super::*;
+    use crate::helpers::affine_coords;
+    use crate::recording::RecordingCS;
+    use curve25519_dalek::scalar::Scalar;
+
+    #[test]
+    fn lockstep() {
+        let mut rng = rand::thread_rng();
+        let p = SonnyEdwardsPoint::new_random_point(&mut rng);
+        let q = SonnyEdwardsPoint::new_random_point(&mut rng);
+        let bits = [1u8, 0, 1, 1];
+
+        let mut cs = RecordingCS::new(b"Witnessed");
+        let bit_vars: Vec<Variable> = bits
+            .iter()
+            .map(|b| cs.commit(Some(Scalar::from(*b))))
+            .collect();
+        let (p, q) = (WitnessedPoint::constant(&p), WitnessedPoint::constant(&q));
+        let selected = p.conditionally_select(bit_vars[1], bits[1], &mut cs);
+        let r = p
+            .add(&q, &mut cs)
+            .double(&mut cs)
+            .add_with_policy(&q.neg(), IdentityPolicy::Forbid, &mut cs)
+            .mul_by_const_scalar(5, &mut cs)
+            .scalar_mul(bit_vars, &bits, &mut cs)
+            .add(&selected, &mut cs);
+        r.forbid_identity(&mut cs);
+
+        // The native values are the assignments of the gadgets.
+        let coords = [&r.gadget.X, &r.gadget.Y, &r.gadget.Z, &r.gadget.T];
+        let natives = [r.native.X, r.native.Y, r.native.Z, r.native.T];
+        for (lc, native) in coords.iter().zip(natives.iter()) {
+            assert_eq!(
+                cs.eval(lc),
+                Some(Scalar::from_bytes_mod_order(native.to_bytes()))
+            );
+        }
+        let (x, y) = r.to_affine(&mut cs);
+        let (x_assign, y_assign) = affine_coords(&r.native);
+        assert_eq!(cs.eval(&LC::from(x)), Some(x_assign));
+        assert_eq!(cs.eval(&LC::from(y)), Some(y_assign));
+        assert!(cs.first_unsatisfied().is_none());
+    }
+}
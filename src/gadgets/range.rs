@@ -0,0 +1,114 @@
+//! Range constraints over committed values.
+use crate::errors::GadgetError;
+use crate::gadgets::boolean::bit_decomposition_gadget;
+use bulletproofs::r1cs::{ConstraintSystem as CS, LinearCombination as LC};
+use curve25519_dalek::scalar::Scalar;
+
+/// Number of bits used to check every bound of `range_gadget`.
+pub const RANGE_BITS: usize = 64;
+
+/// Constrains `min <= v <= max` by decomposing both `v - min` and `max - v`
+/// into `RANGE_BITS` bits. Takes `2 * RANGE_BITS` bits worth of multipliers.
+/// Fails without adding any constraint if `min > max`.
+pub fn range_gadget(
+    cs: &mut dyn CS,
+    v: LC,
+    v_assign: Option<u64>,
+    min: u64,
+    max: u64,
+) -> Result<(), GadgetError> {
+    if min > max {
+        return Err(GadgetError::EmptyRange { min, max });
+    }
+    bit_decomposition_gadget(
+        cs,
+        v.clone() - Scalar::from(min),
+        v_assign.map(|v| Scalar::from(v) - Scalar::from(min)),
+        RANGE_BITS,
+    );
+    bit_decomposition_gadget(
+        cs,
+        LC::from(Scalar::from(max)) - v,
+        v_assign.map(|v| Scalar::from(max) - Scalar::from(v)),
+        RANGE_BITS,
+    );
+    Ok(())
+}
+
+/// Constrains `a >= b` and returns `a - b`, for balance updates that must not
+/// underflow. `assign` holds the values of `a` and `b`.
+///
+/// Both `b` and `a - b` are decomposed into `n_bits` bits, so they are
+/// integers in `[0, 2^n_bits)` and `a = b + (a - b)` can not wrap around the
+/// field. As a consequence `a` is below `2^(n_bits + 1)`, and a `b` greater
+/// than `a` leaves the circuit unsatisfiable. Takes `2 * n_bits` multipliers.
+pub fn checked_sub_gadget(
+    cs: &mut dyn CS,
+    a: LC,
+    b: LC,
+    assign: Option<(u64, u64)>,
+    n_bits: usize,
+) -> LC {
+    assert!(n_bits <= RANGE_BITS, "the assignments are u64 values");
+    bit_decomposition_gadget(cs, b.clone(), assign.map(|(_, b)| Scalar::from(b)), n_bits);
+    let diff = a - b;
+    bit_decomposition_gadget(
+        cs,
+        diff.clone(),
+        assign.map(|(a, b)| Scalar::from(a) - Scalar::from(b)),
+        n_bits,
+    );
+    diff
+}
+
+mod test {
+    use super::*;
+    use crate::recording::RecordingCS;
+
+    fn satisfied(v: u64, min: u64, max: u64) -> bool {
+        let mut cs = RecordingCS::new(b"Range");
+        let var = cs.commit(Some(Scalar::from(v)));
+        range_gadget(&mut cs, var.into(), Some(v), min, max).unwrap();
+        cs.first_unsatisfied().is_none()
+    }
+
+    #[test]
+    fn range() {
+        assert!(satisfied(18, 18, 120));
+        assert!(satisfied(120, 18, 120));
+        assert!(satisfied(42, 18, 120));
+        assert!(!satisfied(17, 18, 120));
+        assert!(!satisfied(121, 18, 120));
+    }
+
+    #[test]
+    fn empty_range() {
+        let mut cs = RecordingCS::new(b"Range");
+        let var = cs.commit(Some(Scalar::from(42u64)));
+        assert_eq!(
+            range_gadget(&mut cs, var.into(), Some(42), 120, 18),
+            Err(GadgetError::EmptyRange { min: 120, max: 18 })
+        );
+        assert_eq!(cs.multipliers(), 0);
+    }
+
+    fn checked_sub(a: u64, b: u64, n_bits: usize) -> bool {
+        let mut cs = RecordingCS::new(b"CheckedSub");
+        let a_var = cs.commit(Some(Scalar::from(a)));
+        let b_var = cs.commit(Some(Scalar::from(b)));
+        let diff = checked_sub_gadget(&mut cs, a_var.into(), b_var.into(), Some((a, b)), n_bits);
+        cs.constrain(diff - (Scalar::from(a) - Scalar::from(b)));
+        cs.first_unsatisfied().is_none()
+    }
+
+    #[test]
+    fn checked_sub() {
+        assert!(checked_sub(100, 58, 8));
+        assert!(checked_sub(58, 58, 8));
+        assert!(checked_sub(u64::max_value(), 1, 64));
+        assert!(!checked_sub(57, 58, 8));
+        assert!(!checked_sub(0, 1, 64));
+        // The difference does not fit in `n_bits`.
+        assert!(!checked_sub(300, 1, 8));
+    }
+}
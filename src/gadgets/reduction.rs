@@ -0,0 +1,117 @@
+//! Reduction of committed values modulo the order `L` of the Sonny subgroup.
+//!
+//! Scalars derived in-circuit (e.g. from a hash) are elements of the R1CS field,
+//! whose order is about `8 * L`. Before being used as a Sonny scalar, such a
+//! value `a` is written as `a = q * L + r` with a 3-bit quotient `q` and `r < L`:
+//! `q * L + r` is then below `8 * L`, which is below the field order, so the
+//! equation can not wrap around and `r` is exactly `a mod L`.
+//!
+//! The few values in `[8 * L, p)` have no such decomposition and can not be
+//! reduced by the gadget. They are a `2^-125` fraction of the field, so a
+//! uniformly distributed value (a hash output) only hits them with negligible
+//! probability.
+use crate::gadgets::boolean::le_constant_gadget;
+use crate::gadgets::typed::Bit;
+use bulletproofs::r1cs::{ConstraintSystem as CS, LinearCombination as LC};
+use curve25519_dalek::scalar::Scalar;
+
+/// Bits of the order of the Sonny subgroup, and of the reduced values.
+pub const SONNY_ORDER_BITS: usize = 249;
+
+/// Bits of the quotient of the reduction.
+pub const QUOTIENT_BITS: usize = 3;
+
+/// Order of the prime-order Sonny subgroup,
+/// `L = 2^249 - 15145038707218910765482344729778085401`.
+pub fn sonny_order() -> Scalar {
+    Scalar::from_bytes_mod_order([
+        231, 161, 183, 53, 222, 211, 169, 38, 215, 88, 159, 228, 240, 43, 155, 244, 255, 255, 255,
+        255, 255, 255, 255, 255, 255, 255, 255, 255, 255, 255, 255, 1,
+    ])
+}
+
+/// Compares canonical scalars as integers.
+fn less_than(a: &Scalar, b: &Scalar) -> bool {
+    a.to_bytes().iter().rev().lt(b.to_bytes().iter().rev())
+}
+
+/// Native reduction: the `(q, r)` with `a = q * L + r`, `q < 2^QUOTIENT_BITS`
+/// and `r < L`, or `None` if `a` is in `[8 * L, p)`.
+pub fn reduce(a: &Scalar) -> Option<(Scalar, Scalar)> {
+    let l = sonny_order();
+    (0..1u64 << QUOTIENT_BITS)
+        .map(|q| (Scalar::from(q), *a - Scalar::from(q) * l))
+        .find(|(_, r)| less_than(r, &l))
+}
+
+/// Constrains `r = a mod L` and returns the `SONNY_ORDER_BITS` little-endian
+/// bits of `r`, ready to be used as a scalar. Takes `QUOTIENT_BITS` plus
+/// `2 * SONNY_ORDER_BITS` multipliers.
+///
+/// Panics on the prover side if `a_assign` has no reduction (see the module
+/// docs).
+pub fn sonny_reduction_gadget(
+    cs: &mut dyn CS,
+    a: LC,
+    r: LC,
+    a_assign: Option<Scalar>,
+) -> Vec<Bit> {
+    let assign = a_assign.map(|a| reduce(&a).expect("the value can not be reduced"));
+    let q_bits = assign.map(|(q, _)| q.to_bytes()[0]);
+    let mut q = LC::default();
+    for i in 0..QUOTIENT_BITS {
+        let bit = Bit::allocate(cs, q_bits.map(|q| (q >> i) & 1 == 1)).unwrap();
+        q = q + LC::from(bit) * Scalar::from(1u64 << i);
+    }
+    let r_bits = Bit::decompose(cs, r.clone(), assign.map(|(_, r)| r), SONNY_ORDER_BITS);
+    le_constant_gadget(cs, &Bit::variables(&r_bits), &(sonny_order() - Scalar::one()));
+    cs.constrain(a - q * sonny_order() - r);
+    r_bits
+}
+
+mod test {
+    use super::*;
+    use crate::gadgets::point::witness;
+    use crate::gadgets::signature::basepoint;
+    use crate::helpers::affine_coords;
+    use crate::recording::RecordingCS;
+
+    #[test]
+    fn order_of_the_basepoint() {
+        let l = sonny_order().to_bytes();
+        let bits: Vec<u8> = (0..SONNY_ORDER_BITS)
+            .map(|i| (l[i / 8] >> (i % 8)) & 1)
+            .collect();
+        let l_times_b = witness::edwards::scalar_mul(&basepoint(), &bits);
+        assert_eq!(
+            affine_coords(&l_times_b),
+            affine_coords(&witness::edwards::identity())
+        );
+    }
+
+    fn satisfied(a: Scalar, r: Scalar) -> bool {
+        let mut cs = RecordingCS::new(b"Reduction");
+        let a_var = cs.commit(Some(a));
+        let r_var = cs.commit(Some(r));
+        sonny_reduction_gadget(&mut cs, a_var.into(), r_var.into(), Some(a));
+        cs.first_unsatisfied().is_none()
+    }
+
+    #[test]
+    fn reduction() {
+        let l = sonny_order();
+        for _ in 0..4 {
+            let a = Scalar::random(&mut rand::thread_rng());
+            let (q, r) = reduce(&a).unwrap();
+            assert_eq!(q * l + r, a);
+            assert!(satisfied(a, r));
+            assert!(!satisfied(a, r + Scalar::one()));
+        }
+        let a = Scalar::from(7u64) * l + Scalar::from(5u64);
+        assert_eq!(reduce(&a), Some((Scalar::from(7u64), Scalar::from(5u64))));
+        assert!(satisfied(a, Scalar::from(5u64)));
+        // `r + L` fits the equation with `q - 1`, so it must be rejected.
+        assert!(!satisfied(a, Scalar::from(5u64) + l));
+        assert_eq!(reduce(&-Scalar::one()), None);
+    }
+}
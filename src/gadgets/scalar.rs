@@ -1,3 +1,4 @@
+use crate::instrument;
 use bulletproofs::r1cs::{
     ConstraintSystem as CS, LinearCombination as LC, Prover, R1CSError, R1CSProof, Verifier,
 };
@@ -7,22 +8,129 @@ use merlin::Transcript;
 
 /// Adds constraints to the CS which check that a Variable != 0
 pub fn nonzero_gadget(var: LC, var_assigment: Option<Scalar>, cs: &mut dyn CS) {
-    let (inv_var, _, _) = cs
-        .allocate_multiplier(var_assigment.and_then(|q| {
-            let inverse = q.invert();
-            if inverse == Scalar::zero() {
-                panic!("Attempting to divide by 0 on an inversion op.")
+    instrument::synthesis("nonzero", cs, |cs| {
+        let (inv_var, _, _) = cs
+            .allocate_multiplier(var_assigment.and_then(|q| {
+                let inverse = q.invert();
+                if inverse == Scalar::zero() {
+                    panic!("Attempting to divide by 0 on an inversion op.")
+                }
+                Some((
+                    Scalar::from_bytes_mod_order(inverse.to_bytes()),
+                    Scalar::one(),
+                ))
+            }))
+            .unwrap();
+        // Var * Inv(Var) = 1
+        let (_, _, should_be_one) = cs.multiply(inv_var.into(), var);
+        let var_one: LC = Scalar::one().into();
+        cs.constrain(should_be_one - var_one);
+    })
+}
+
+/// Returns the LC of `-a`. Negating an LC is free, no constraints are added.
+pub fn neg_gadget(a: LC) -> LC {
+    -a
+}
+
+/// Returns the LC of `a * b + c`, taking a single multiplier.
+pub fn mul_add_gadget(cs: &mut dyn CS, a: LC, b: LC, c: LC) -> LC {
+    let (_, _, ab) = cs.multiply(a, b);
+    ab + c
+}
+
+/// Exponents up to which `pow_const_gadget` searches for a shortest addition chain.
+pub const OPTIMAL_CHAIN_MAX: u64 = 256;
+
+fn search_chain(
+    chain: &mut Vec<u64>,
+    steps: &mut Vec<(usize, usize)>,
+    e: u64,
+    limit: usize,
+) -> bool {
+    let last = *chain.last().unwrap();
+    if last == e {
+        return true;
+    }
+    let remaining = limit - steps.len();
+    // Even doubling at every remaining step would not reach `e`.
+    if remaining == 0 || last << remaining < e {
+        return false;
+    }
+    for i in (0..chain.len()).rev() {
+        for j in (0..=i).rev() {
+            let next = chain[i] + chain[j];
+            if next <= last || next > e {
+                continue;
+            }
+            chain.push(next);
+            steps.push((i, j));
+            if search_chain(chain, steps, e, limit) {
+                return true;
             }
-            Some((
-                Scalar::from_bytes_mod_order(inverse.to_bytes()),
-                Scalar::one(),
-            ))
-        }))
-        .unwrap();
-    // Var * Inv(Var) = 1
-    let (_, _, should_be_one) = cs.multiply(inv_var.into(), var);
-    let var_one: LC = Scalar::one().into();
-    cs.constrain(should_be_one - var_one);
+            chain.pop();
+            steps.pop();
+        }
+    }
+    false
+}
+
+/// Returns the steps of an addition chain for `e >= 1`: the `k`-th step adds the
+/// elements `i` and `j` of the chain (which starts at `1`) to get the element `k + 1`.
+///
+/// The chain is a shortest one for `e <= OPTIMAL_CHAIN_MAX` (found by iterative
+/// deepening) and the one of the left-to-right binary method otherwise.
+pub fn addition_chain(e: u64) -> Vec<(usize, usize)> {
+    assert!(e >= 1);
+    if e <= OPTIMAL_CHAIN_MAX {
+        let mut limit = 0;
+        loop {
+            let (mut chain, mut steps) = (vec![1], Vec::new());
+            if search_chain(&mut chain, &mut steps, e, limit) {
+                return steps;
+            }
+            limit += 1;
+        }
+    }
+    let mut steps = Vec::new();
+    let mut current = 0;
+    for i in (0..63 - e.leading_zeros()).rev() {
+        steps.push((current, current));
+        current = steps.len();
+        if (e >> i) & 1 == 1 {
+            steps.push((current, 0));
+            current = steps.len();
+        }
+    }
+    steps
+}
+
+/// Returns the LC of `x^e`, taking one multiplier per step of `addition_chain(e)`.
+pub fn pow_const_gadget(cs: &mut dyn CS, x: LC, e: u64) -> LC {
+    instrument::synthesis("pow_const", cs, |cs| {
+        if e == 0 {
+            return LC::from(Scalar::one());
+        }
+        let mut powers = vec![x];
+        for (i, j) in addition_chain(e) {
+            let (_, _, p) = cs.multiply(powers[i].clone(), powers[j].clone());
+            powers.push(p.into());
+        }
+        powers.pop().unwrap()
+    })
+}
+
+/// Constrains the running product of `values` and returns the LC of the
+/// grand product `values[0] * ... * values[n - 1]` (`1` for an empty slice).
+/// Takes `n - 1` multipliers.
+pub fn grand_product_gadget(cs: &mut dyn CS, values: &[LC]) -> LC {
+    instrument::synthesis("grand_product", cs, |cs| match values.split_first() {
+        None => LC::from(Scalar::one()),
+        Some((first, rest)) => rest.iter().fold(first.clone(), |acc, v| {
+            let (_, _, prod) = cs.multiply(acc, v.clone());
+            prod.into()
+        }),
+    })
 }
 
 mod scalar_tests {
@@ -60,7 +168,7 @@ mod scalar_tests {
         let fe_as_lc: LC = Scalar::from_bytes_mod_order(fe.to_bytes()).into();
         nonzero_gadget(fe_as_lc, Some(fe), &mut verifier);
 
-        verifier.verify(&proof, &pc_gens, &bp_gens, &mut rand::thread_rng())?;
+        verify_proof!(verifier, &proof, &pc_gens, &bp_gens)?;
         Ok(())
     }
 
@@ -81,4 +189,74 @@ mod scalar_tests {
         // The next line causes a `panic!` as it is expected to
         //assert!(is_not_zero_roundtrip_helper(Scalar::zero()).is_err());
     }
+
+    ///////////////// Negation and mul-add /////////////////
+
+    #[test]
+    fn neg_and_mul_add() {
+        let (a, b, c) = (Scalar::from(3u64), Scalar::from(5u64), Scalar::from(7u64));
+        let mut cs = crate::recording::RecordingCS::new(b"Mul-add");
+        let a_lc: LC = cs.commit(Some(a)).into();
+        let b_lc: LC = cs.commit(Some(b)).into();
+        let c_lc: LC = cs.commit(Some(c)).into();
+
+        let neg = neg_gadget(a_lc.clone());
+        assert_eq!(cs.eval(&neg), Some(-a));
+        assert_eq!(cs.eval(&(neg + a_lc.clone())), Some(Scalar::zero()));
+
+        let out = mul_add_gadget(&mut cs, a_lc, b_lc, c_lc);
+        assert_eq!(cs.eval(&out), Some(Scalar::from(22u64)));
+        assert_eq!(cs.multipliers(), 1);
+        assert_eq!(cs.first_unsatisfied(), None);
+    }
+
+    ///////////////// Exponentiation /////////////////
+
+    #[test]
+    fn addition_chains() {
+        // Known lengths of shortest addition chains.
+        for (e, len) in &[
+            (1, 0),
+            (2, 1),
+            (3, 2),
+            (5, 3),
+            (7, 4),
+            (15, 5),
+            (17, 5),
+            (127, 10),
+        ] {
+            assert_eq!(addition_chain(*e).len(), *len);
+        }
+        // Binary method: 10 doublings and 2 additions.
+        assert_eq!(addition_chain(1029).len(), 12);
+    }
+
+    #[test]
+    fn pow_const() {
+        let x = Scalar::random(&mut rand::thread_rng());
+        for e in &[0u64, 1, 3, 5, 7, 300] {
+            let mut cs = crate::recording::RecordingCS::new(b"Pow const");
+            let x_lc: LC = cs.commit(Some(x)).into();
+            let res = pow_const_gadget(&mut cs, x_lc, *e);
+            let expected = (0..*e).fold(Scalar::one(), |acc, _| acc * x);
+            assert_eq!(cs.eval(&res), Some(expected));
+            assert_eq!(cs.first_unsatisfied(), None);
+        }
+    }
+
+    ///////////////// Grand product /////////////////
+
+    #[test]
+    fn grand_product() {
+        let values: Vec<Scalar> = (0..5)
+            .map(|_| Scalar::random(&mut rand::thread_rng()))
+            .collect();
+        let mut cs = crate::recording::RecordingCS::new(b"Grand product");
+        let lcs: Vec<LC> = values.iter().map(|v| cs.commit(Some(*v)).into()).collect();
+        let prod = grand_product_gadget(&mut cs, &lcs);
+
+        assert_eq!(cs.eval(&prod), Some(values.iter().product()));
+        assert_eq!(cs.multipliers(), values.len() - 1);
+        assert_eq!(cs.first_unsatisfied(), None);
+    }
 }
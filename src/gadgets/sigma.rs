@@ -0,0 +1,435 @@
+//! Sigma-protocol statements proven inside the R1CS.
+//!
+//! The statements of the usual sigma protocols (knowledge of a discrete log,
+//! equality of discrete logs, knowledge of a representation) are all linear
+//! relations between secret scalars and public points: a set of equations
+//! `P_j = sum_k x_{i_jk} * G_jk` over secrets `x_1, ..., x_n`. A
+//! `LinearRelation` describes such a statement, and `linear_relation_gadget`
+//! proves it over committed secrets, so it is part of the Bulletproof of the
+//! surrounding circuit instead of being sent as a separate proof, and the
+//! secrets can be further constrained.
+//!
+//! `LinearRelation` implements `Statement`: its encoding holds the number of
+//! secrets and, for every equation, the image and the `(secret index, base)`
+//! of every term, so `Statement::transcript` binds the whole relation and a
+//! proof only verifies against the relation it was generated for. This takes
+//! the place of the binding of the statement in the Fiat–Shamir challenge of
+//! the sigma protocol. `prove_relation` and `verify_relation` use it.
+use crate::errors::GadgetError;
+use crate::gadgets::boolean::bit_decomposition_gadget;
+use crate::gadgets::point::edwards_point::SonnyEdwardsPointGadget;
+use crate::gadgets::point::fixed_base::fixed_base_mul_unchecked;
+use crate::gadgets::point::witness;
+use crate::helpers::{affine_coords, sonny_scalar_bits, sonny_scalar_to_scalar};
+use crate::precomp::PrecompTables;
+use crate::statement::{encode_point, encode_u64, Statement};
+use bulletproofs::r1cs::{
+    ConstraintSystem as CS, LinearCombination as LC, R1CSError, R1CSProof, Variable, Verifier,
+};
+#[cfg(feature = "prover")]
+use bulletproofs::r1cs::Prover;
+use bulletproofs::{BulletproofGens, PedersenGens};
+use curve25519_dalek::{ristretto::CompressedRistretto, scalar::Scalar};
+use zerocaf::edwards::EdwardsPoint as SonnyEdwardsPoint;
+use zerocaf::scalar::Scalar as SonnyScalar;
+
+/// Bits every secret is decomposed into. Sonny scalars fit, and the
+/// decomposition does not wrap around the field.
+pub const SECRET_BITS: usize = 252;
+
+/// Window of the tables of the bases.
+pub const SIGMA_WINDOW: usize = 4;
+
+/// The equation `image = sum(secret_i * base)` over the `(i, base)` terms.
+#[derive(Clone, Debug)]
+pub struct Equation {
+    pub image: SonnyEdwardsPoint,
+    pub terms: Vec<(usize, SonnyEdwardsPoint)>,
+}
+
+/// A set of equations over `secrets` secret scalars.
+#[derive(Clone, Debug)]
+pub struct LinearRelation {
+    pub secrets: usize,
+    pub equations: Vec<Equation>,
+}
+
+impl LinearRelation {
+    pub fn new(secrets: usize) -> LinearRelation {
+        LinearRelation {
+            secrets,
+            equations: Vec::new(),
+        }
+    }
+
+    /// Adds the equation `image = sum(secret_i * base)` over `terms`.
+    ///
+    /// Panics if a term refers to a secret out of range.
+    pub fn add_equation(&mut self, image: SonnyEdwardsPoint, terms: &[(usize, SonnyEdwardsPoint)]) {
+        assert!(
+            terms.iter().all(|(i, _)| *i < self.secrets),
+            "unknown secret"
+        );
+        self.equations.push(Equation {
+            image,
+            terms: terms.to_vec(),
+        });
+    }
+
+    /// The relation `P = x * G` and `Q = x * H` over a single secret `x`
+    /// (Chaum–Pedersen).
+    pub fn dlog_equality(
+        g: SonnyEdwardsPoint,
+        p: SonnyEdwardsPoint,
+        h: SonnyEdwardsPoint,
+        q: SonnyEdwardsPoint,
+    ) -> LinearRelation {
+        let mut relation = LinearRelation::new(1);
+        relation.add_equation(p, &[(0, g)]);
+        relation.add_equation(q, &[(0, h)]);
+        relation
+    }
+
+    /// The relation `C = x * G + r * H` over the secrets `x` and `r`, i.e.
+    /// knowledge of the full opening of `C` with respect to `G` and `H`.
+    pub fn representation(
+        g: SonnyEdwardsPoint,
+        h: SonnyEdwardsPoint,
+        c: SonnyEdwardsPoint,
+    ) -> LinearRelation {
+        let mut relation = LinearRelation::new(2);
+        relation.add_equation(c, &[(0, g), (1, h)]);
+        relation
+    }
+
+    /// Whether `secrets` satisfy every equation.
+    pub fn holds(&self, secrets: &[SonnyScalar]) -> bool {
+        secrets.len() == self.secrets
+            && self.equations.iter().all(|eq| {
+                let sum = eq
+                    .terms
+                    .iter()
+                    .fold(witness::edwards::identity(), |acc, (i, base)| {
+                        let bits = sonny_scalar_bits(&secrets[*i], SECRET_BITS);
+                        witness::edwards::add(&acc, &witness::edwards::scalar_mul(base, &bits))
+                    });
+                affine_coords(&sum) == affine_coords(&eq.image)
+            })
+    }
+}
+
+impl Statement for LinearRelation {
+    const LABEL: &'static [u8] = b"Sigma linear relation";
+
+    fn encode(&self, out: &mut Vec<u8>) {
+        encode_u64(out, self.secrets as u64);
+        encode_u64(out, self.equations.len() as u64);
+        for eq in &self.equations {
+            encode_point(out, &eq.image);
+            encode_u64(out, eq.terms.len() as u64);
+            for (i, base) in &eq.terms {
+                encode_u64(out, *i as u64);
+                encode_point(out, base);
+            }
+        }
+    }
+}
+
+/// Constrains the committed `secrets` (as field elements, see
+/// `helpers::sonny_scalar_to_scalar`) to satisfy `relation`, and returns their
+/// bits.
+///
+/// Every secret is decomposed into `SECRET_BITS` bits once, shared by all the
+/// terms it appears in. Every term is a fixed-base multiplication over the
+/// table of its base, taken from `cache`.
+pub fn linear_relation_gadget(
+    cs: &mut dyn CS,
+    cache: &PrecompTables,
+    relation: &LinearRelation,
+    secrets: &[LC],
+    secrets_assign: Option<&[Scalar]>,
+) -> Result<Vec<Vec<Variable>>, GadgetError> {
+    if secrets.len() != relation.secrets {
+        return Err(GadgetError::InvalidLCsLength {
+            expected: relation.secrets,
+            found: secrets.len(),
+        });
+    }
+    let bits: Vec<Vec<Variable>> = secrets
+        .iter()
+        .enumerate()
+        .map(|(i, secret)| {
+            let assign = secrets_assign.map(|a| a[i]);
+            bit_decomposition_gadget(cs, secret.clone(), assign, SECRET_BITS)
+        })
+        .collect();
+    for eq in &relation.equations {
+        let mut acc: Option<SonnyEdwardsPointGadget> = None;
+        for (i, base) in &eq.terms {
+            let table = cache.get(base, SIGMA_WINDOW, SECRET_BITS);
+            let term = fixed_base_mul_unchecked(cs, &table, &bits[*i]);
+            acc = Some(match acc {
+                None => term,
+                Some(acc) => acc.add(&term, cs),
+            });
+        }
+        let acc = acc.unwrap_or_else(SonnyEdwardsPointGadget::identity);
+        SonnyEdwardsPointGadget::from_point(&eq.image).equal(&acc, cs);
+    }
+    Ok(bits)
+}
+
+/// Constrains `P = x * G` and `Q = x * H` for the committed `x`, e.g. to show
+/// that an encryption key and a viewing key belong to the same secret. Both
+/// multiplications share the bits of `x`, which are returned.
+pub fn dlog_equality_gadget(
+    cs: &mut dyn CS,
+    cache: &PrecompTables,
+    x: LC,
+    x_assign: Option<Scalar>,
+    (g, p): (SonnyEdwardsPoint, SonnyEdwardsPoint),
+    (h, q): (SonnyEdwardsPoint, SonnyEdwardsPoint),
+) -> Vec<Variable> {
+    let relation = LinearRelation::dlog_equality(g, p, h, q);
+    let x_assign = x_assign.map(|x| [x]);
+    linear_relation_gadget(
+        cs,
+        cache,
+        &relation,
+        &[x],
+        x_assign.as_ref().map(|x| &x[..]),
+    )
+    .unwrap()
+    .remove(0)
+}
+
+/// Constrains `C = x * G + r * H` for the committed `x` and `r`, and returns
+/// their bits. Unlike `sk_knowledge_gadget`, which covers a single base, both
+/// exponents stay hidden. `x` and `r` are plain LCs of the caller, so either of
+/// them can be further constrained, e.g. `x` with `range_gadget`.
+pub fn representation_gadget(
+    cs: &mut dyn CS,
+    cache: &PrecompTables,
+    (x, x_assign): (LC, Option<Scalar>),
+    (r, r_assign): (LC, Option<Scalar>),
+    g: SonnyEdwardsPoint,
+    h: SonnyEdwardsPoint,
+    c: SonnyEdwardsPoint,
+) -> (Vec<Variable>, Vec<Variable>) {
+    let relation = LinearRelation::representation(g, h, c);
+    let assign = x_assign.and_then(|x| r_assign.map(|r| [x, r]));
+    let mut bits = linear_relation_gadget(
+        cs,
+        cache,
+        &relation,
+        &[x, r],
+        assign.as_ref().map(|a| &a[..]),
+    )
+    .unwrap();
+    let r_bits = bits.remove(1);
+    (bits.remove(0), r_bits)
+}
+
+/// Proves knowledge of `secrets` satisfying `relation`.
+#[cfg(feature = "prover")]
+pub fn prove_relation(
+    pc_gens: &PedersenGens,
+    bp_gens: &BulletproofGens,
+    cache: &PrecompTables,
+    relation: &LinearRelation,
+    secrets: &[SonnyScalar],
+) -> Result<(R1CSProof, Vec<CompressedRistretto>), R1CSError> {
+    let mut transcript = relation.transcript();
+    let mut prover = Prover::new(pc_gens, &mut transcript);
+    let secrets: Vec<Scalar> = secrets.iter().map(sonny_scalar_to_scalar).collect();
+    let (commitments, vars): (Vec<CompressedRistretto>, Vec<Variable>) = secrets
+        .iter()
+        .map(|s| prover.commit(*s, Scalar::random(&mut rand::thread_rng())))
+        .unzip();
+    let lcs: Vec<LC> = vars.into_iter().map(LC::from).collect();
+    linear_relation_gadget(&mut prover, cache, relation, &lcs, Some(&secrets))?;
+    let proof = prover.prove(bp_gens)?;
+    Ok((proof, commitments))
+}
+
+/// Verifies that the secrets behind `commitments` satisfy `relation`.
+pub fn verify_relation(
+    pc_gens: &PedersenGens,
+    bp_gens: &BulletproofGens,
+    cache: &PrecompTables,
+    relation: &LinearRelation,
+    commitments: &[CompressedRistretto],
+    proof: &R1CSProof,
+) -> Result<(), R1CSError> {
+    let mut transcript = relation.transcript();
+    let mut verifier = Verifier::new(&mut transcript);
+    let lcs: Vec<LC> = commitments
+        .iter()
+        .map(|V| verifier.commit(*V).into())
+        .collect();
+    linear_relation_gadget(&mut verifier, cache, relation, &lcs, None)?;
+    verify_proof!(verifier, proof, pc_gens, bp_gens)
+}
+
+#[cfg(feature = "prover")]
+mod test {
+    use super::*;
+    use crate::gadgets::commitment::SonnyPedersenGens;
+    use crate::gadgets::range::range_gadget;
+    use crate::gadgets::signature::basepoint;
+    use crate::recording::RecordingCS;
+
+    fn random_point() -> SonnyEdwardsPoint {
+        SonnyEdwardsPoint::new_random_point(&mut rand::thread_rng())
+    }
+
+    fn mul(base: &SonnyEdwardsPoint, s: &SonnyScalar) -> SonnyEdwardsPoint {
+        witness::edwards::scalar_mul(base, &sonny_scalar_bits(s, SECRET_BITS))
+    }
+
+    fn satisfied(relation: &LinearRelation, secrets: &[SonnyScalar]) -> bool {
+        let mut cs = RecordingCS::new(b"Sigma");
+        let assign: Vec<Scalar> = secrets.iter().map(sonny_scalar_to_scalar).collect();
+        let lcs: Vec<LC> = assign.iter().map(|s| cs.commit(Some(*s)).into()).collect();
+        linear_relation_gadget(
+            &mut cs,
+            &PrecompTables::new(),
+            relation,
+            &lcs,
+            Some(&assign),
+        )
+        .unwrap();
+        cs.first_unsatisfied().is_none()
+    }
+
+    #[test]
+    fn linear_relation() {
+        let mut rng = rand::thread_rng();
+        let (x, r) = (SonnyScalar::random(&mut rng), SonnyScalar::random(&mut rng));
+        let (g, h) = (basepoint(), random_point());
+        // `P = x * G`, `Q = x * H` and `C = x * G + r * H`.
+        let mut relation = LinearRelation::new(2);
+        relation.add_equation(mul(&g, &x), &[(0, g)]);
+        relation.add_equation(mul(&h, &x), &[(0, h)]);
+        relation.add_equation(
+            witness::edwards::add(&mul(&g, &x), &mul(&h, &r)),
+            &[(0, g), (1, h)],
+        );
+        assert!(relation.holds(&[x, r]));
+        assert!(satisfied(&relation, &[x, r]));
+        assert!(!relation.holds(&[x, x]));
+        assert!(!satisfied(&relation, &[x, x]));
+
+        let mut cs = RecordingCS::new(b"Sigma");
+        assert_eq!(
+            linear_relation_gadget(&mut cs, &PrecompTables::new(), &relation, &[], None)
+                .unwrap_err(),
+            GadgetError::InvalidLCsLength {
+                expected: 2,
+                found: 0
+            }
+        );
+    }
+
+    #[test]
+    fn relation_roundtrip() {
+        let pc_gens = PedersenGens::default();
+        let bp_gens = BulletproofGens::new(4096, 1);
+        let cache = PrecompTables::new();
+        let x = SonnyScalar::random(&mut rand::thread_rng());
+        let mut relation = LinearRelation::new(1);
+        relation.add_equation(mul(&basepoint(), &x), &[(0, basepoint())]);
+
+        let (proof, commitments) =
+            prove_relation(&pc_gens, &bp_gens, &cache, &relation, &[x]).unwrap();
+        assert!(
+            verify_relation(&pc_gens, &bp_gens, &cache, &relation, &commitments, &proof).is_ok()
+        );
+
+        // The proof is bound to the relation.
+        let mut other = LinearRelation::new(1);
+        other.add_equation(mul(&basepoint(), &x), &[(0, basepoint())]);
+        other.add_equation(mul(&basepoint(), &x), &[(0, basepoint())]);
+        assert!(verify_relation(&pc_gens, &bp_gens, &cache, &other, &commitments, &proof).is_err());
+    }
+
+    fn dlog_equality_satisfied(
+        x: &SonnyScalar,
+        (g, p): (SonnyEdwardsPoint, SonnyEdwardsPoint),
+        (h, q): (SonnyEdwardsPoint, SonnyEdwardsPoint),
+    ) -> bool {
+        let mut cs = RecordingCS::new(b"DlogEquality");
+        let x = sonny_scalar_to_scalar(x);
+        let var = cs.commit(Some(x));
+        let bits = dlog_equality_gadget(
+            &mut cs,
+            &PrecompTables::new(),
+            var.into(),
+            Some(x),
+            (g, p),
+            (h, q),
+        );
+        assert_eq!(bits.len(), SECRET_BITS);
+        // A single decomposition for both multiplications.
+        let table_muls = 2 * (SECRET_BITS / SIGMA_WINDOW) * 11;
+        let additions = 2 * (SECRET_BITS / SIGMA_WINDOW - 1) * 12;
+        assert_eq!(
+            cs.multipliers(),
+            SECRET_BITS + table_muls + additions + 2 * 4
+        );
+        cs.first_unsatisfied().is_none()
+    }
+
+    #[test]
+    fn dlog_equality() {
+        let mut rng = rand::thread_rng();
+        let (x, y) = (SonnyScalar::random(&mut rng), SonnyScalar::random(&mut rng));
+        let (g, h) = (basepoint(), random_point());
+        assert!(dlog_equality_satisfied(
+            &x,
+            (g, mul(&g, &x)),
+            (h, mul(&h, &x))
+        ));
+        // Different exponents.
+        assert!(!dlog_equality_satisfied(
+            &x,
+            (g, mul(&g, &x)),
+            (h, mul(&h, &y))
+        ));
+        assert!(!LinearRelation::dlog_equality(g, mul(&g, &x), h, mul(&h, &y)).holds(&[x]));
+    }
+
+    fn representation_satisfied(x: u64, r: &SonnyScalar, c: &SonnyEdwardsPoint) -> bool {
+        let mut cs = RecordingCS::new(b"Representation");
+        let x_assign = Scalar::from(x);
+        let r_assign = sonny_scalar_to_scalar(r);
+        let x_lc: LC = cs.commit(Some(x_assign)).into();
+        let r_lc: LC = cs.commit(Some(r_assign)).into();
+        representation_gadget(
+            &mut cs,
+            &PrecompTables::new(),
+            (x_lc.clone(), Some(x_assign)),
+            (r_lc, Some(r_assign)),
+            basepoint(),
+            SonnyPedersenGens::new(0).blinding_gen,
+            *c,
+        );
+        // The hidden `x` is also an amount.
+        range_gadget(&mut cs, x_lc, Some(x), 0, 1000).unwrap();
+        cs.first_unsatisfied().is_none()
+    }
+
+    #[test]
+    fn representation() {
+        let r = SonnyScalar::random(&mut rand::thread_rng());
+        let (g, h) = (basepoint(), SonnyPedersenGens::new(0).blinding_gen);
+        let commit = |x: u64| {
+            let x_bits: Vec<u8> = (0..64).map(|i| ((x >> i) & 1) as u8).collect();
+            witness::edwards::add(&witness::edwards::scalar_mul(&g, &x_bits), &mul(&h, &r))
+        };
+        assert!(representation_satisfied(420, &r, &commit(420)));
+        assert!(!representation_satisfied(421, &r, &commit(420)));
+        // A valid opening of a value out of range.
+        assert!(!representation_satisfied(1001, &r, &commit(1001)));
+    }
+}
@@ -0,0 +1,342 @@
+//! Schnorr signatures over the Sonny curve and the gadget verifying them.
+//!
+//! A signature on the message `m` (a list of field elements) under the key
+//! `PK = sk * B` is `(R, s)` with `R = k * B`, `s = k + c * sk` and the challenge
+//! `c = H(R.x, R.y, PK.x, PK.y, m)` truncated to `CHALLENGE_BITS` bits, where
+//! `H` is the MiMC hash of `gadgets::hash` and `.x`, `.y` are affine coordinates.
+//! It is valid if `s * B = R + c * PK`.
+use crate::errors::GadgetError;
+use crate::gadgets::boolean::binary_constrain_gadget;
+use crate::gadgets::hash::{hash_to_scalar_gadget, mimc_hash, scalar_bits};
+use crate::gadgets::point::edwards_point::SonnyEdwardsPointGadget;
+use crate::gadgets::point::witness;
+use crate::helpers::{affine_coords, bits_to_bytes, sonny_scalar_bits, PointCoords};
+use crate::transcript::TranscriptBackend;
+use bulletproofs::r1cs::{ConstraintSystem as CS, LinearCombination as LC, Variable};
+use curve25519_dalek::scalar::Scalar;
+use rand::{CryptoRng, Rng};
+use zerocaf::edwards::EdwardsPoint as SonnyEdwardsPoint;
+use zerocaf::scalar::Scalar as SonnyScalar;
+
+/// Bits of the challenge. Keeping it below the order of the prime subgroup lets
+/// the signer use it directly as a Sonny scalar.
+pub const CHALLENGE_BITS: usize = 248;
+
+/// Bits of the `s` part of a signature.
+pub const S_BITS: usize = 253;
+
+/// Bits of the random weights of `batch_verify_gadget`.
+pub const BATCH_WEIGHT_BITS: usize = 128;
+
+/// Base point of the signatures.
+pub fn basepoint() -> SonnyEdwardsPoint {
+    zerocaf::constants::RISTRETTO_BASEPOINT.0
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct Signature {
+    pub R: SonnyEdwardsPoint,
+    pub s: SonnyScalar,
+}
+
+fn challenge_inputs(
+    R: &SonnyEdwardsPoint,
+    pk: &SonnyEdwardsPoint,
+    message: &[Scalar],
+) -> Vec<Scalar> {
+    let (rx, ry) = affine_coords(R);
+    let (px, py) = affine_coords(pk);
+    let mut inputs = vec![rx, ry, px, py];
+    inputs.extend_from_slice(message);
+    inputs
+}
+
+/// Little-endian bits of the challenge of a signature.
+pub fn challenge(R: &SonnyEdwardsPoint, pk: &SonnyEdwardsPoint, message: &[Scalar]) -> Vec<u8> {
+    let mut bits = scalar_bits(&mimc_hash(&challenge_inputs(R, pk, message)));
+    bits.truncate(CHALLENGE_BITS);
+    bits
+}
+
+/// Returns the public key of `sk`.
+pub fn public_key(sk: &SonnyScalar) -> SonnyEdwardsPoint {
+    witness::edwards::scalar_mul(&basepoint(), &sonny_scalar_bits(sk, S_BITS))
+}
+
+/// Signs `message` with `sk`.
+pub fn sign<R: Rng + CryptoRng>(sk: &SonnyScalar, message: &[Scalar], rng: &mut R) -> Signature {
+    let k = SonnyScalar::random(rng);
+    let R = witness::edwards::scalar_mul(&basepoint(), &sonny_scalar_bits(&k, S_BITS));
+    let c = SonnyScalar::from_bytes(&bits_to_bytes(&challenge(&R, &public_key(sk), message)));
+    Signature { R, s: k + c * *sk }
+}
+
+/// The signature inside the circuit: the committed `R` and the bits of `s`.
+#[derive(Clone, Debug)]
+pub struct SignatureGadget {
+    pub R: SonnyEdwardsPointGadget,
+    pub s: Vec<Variable>,
+}
+
+/// Constrains `sig` to be a valid signature on `message` under `pk`, with `R`
+/// a coherent point on the curve.
+/// The prover must provide every assignment, since the challenge is allocated.
+pub fn schnorr_verify_gadget(
+    cs: &mut dyn CS,
+    pk: &SonnyEdwardsPointGadget,
+    pk_assign: Option<SonnyEdwardsPoint>,
+    message: &[LC],
+    message_assign: Option<&[Scalar]>,
+    sig: SignatureGadget,
+    sig_assign: Option<Signature>,
+) {
+    // R is committed by the prover, so it must be a coherent point on the curve.
+    sig.R.satisfy_coherence(cs);
+    sig.R.satisfy_curve_eq(cs);
+
+    // c = H(R.x, R.y, PK.x, PK.y, m)
+    let (rx, ry) = sig.R.to_affine(sig_assign.map(|s| s.R), cs);
+    let (px, py) = pk.to_affine(pk_assign, cs);
+    let mut inputs: Vec<LC> = vec![rx.into(), ry.into(), px.into(), py.into()];
+    inputs.extend_from_slice(message);
+    let inputs_assign = match (sig_assign, pk_assign, message_assign) {
+        (Some(sig), Some(pk), Some(m)) => Some(challenge_inputs(&sig.R, &pk, m)),
+        _ => None,
+    };
+    let mut c_bits = hash_to_scalar_gadget(cs, &inputs, inputs_assign.as_deref());
+    c_bits.truncate(CHALLENGE_BITS);
+    let c_assign = inputs_assign.map(|inputs| {
+        let mut bits = scalar_bits(&mimc_hash(&inputs));
+        bits.truncate(CHALLENGE_BITS);
+        bits
+    });
+
+    // s * B == R + c * PK
+    let basep = basepoint();
+    let s_len = sig.s.len();
+    let s_assign = sig_assign.map(|sig| sonny_scalar_bits(&sig.s, s_len));
+    let (sB, _) = SonnyEdwardsPointGadget::scalar_mul(
+        SonnyEdwardsPointGadget::from_point(&basep),
+        Some(basep),
+        sig.s,
+        s_assign.as_deref(),
+        cs,
+    );
+    let (cPK, _) =
+        SonnyEdwardsPointGadget::scalar_mul(pk.clone(), pk_assign, c_bits, c_assign.as_deref(), cs);
+    let rhs = sig.R.add(&cPK, cs);
+    sB.equal(&rhs, cs);
+}
+
+/// Random weights of the equations of a batch, drawn from the transcript.
+fn batch_weights(cs: &mut dyn CS, n: usize) -> Vec<Vec<u8>> {
+    let transcript: &mut dyn TranscriptBackend = cs.transcript();
+    transcript.append_u64(b"batch-size", n as u64);
+    (0..n)
+        .map(|_| {
+            let mut buf = [0u8; BATCH_WEIGHT_BITS / 8];
+            transcript.challenge_bytes(b"batch-weight", &mut buf);
+            (0..BATCH_WEIGHT_BITS)
+                .map(|i| (buf[i / 8] >> (i % 8)) & 1)
+                .collect()
+        })
+        .collect()
+}
+
+/// Returns `p` if `bit = 1` and the identity otherwise. Since `p` is a
+/// constant, the selection is linear and takes no multipliers.
+fn select_constant(p: &SonnyEdwardsPoint, bit: Variable) -> SonnyEdwardsPointGadget {
+    let [x, y, z, t] = p.coords();
+    let one = LC::from(Scalar::one());
+    SonnyEdwardsPointGadget {
+        X: LC::from(bit) * x,
+        Y: one.clone() + LC::from(bit) * (y - Scalar::one()),
+        Z: one + LC::from(bit) * (z - Scalar::one()),
+        T: LC::from(bit) * t,
+    }
+}
+
+/// Constrains every `sigs[i]` to be a valid signature on `messages[i]` under
+/// the public key `pks[i]`.
+///
+/// Instead of checking every `s_i * B = R_i + c_i * PK_i`, the gadget checks
+/// the random linear combination
+/// `sum(s_i * (w_i * B) - c_i * (w_i * PK_i) - w_i * R_i) = 0`, with weights
+/// `w_i` of `BATCH_WEIGHT_BITS` bits drawn from the transcript, so every
+/// commitment (`R_i`, `s_i` and the messages) must have been made before
+/// calling it. The points `w_i * B` and `w_i * PK_i` are public, so all the
+/// terms share a single double-and-add ladder whose selections are free: the
+/// curve arithmetic takes about half the multipliers of `n` calls to
+/// `schnorr_verify_gadget`.
+///
+/// An invalid signature passes with probability `2^-BATCH_WEIGHT_BITS`, unless
+/// its equation only fails by a small-order point: keys and nonces in the
+/// prime-order subgroup make the batch exactly as strict as the single check.
+pub fn batch_verify_gadget(
+    cs: &mut dyn CS,
+    pks: &[SonnyEdwardsPoint],
+    messages: &[Vec<LC>],
+    messages_assign: Option<&[Vec<Scalar>]>,
+    sigs: Vec<SignatureGadget>,
+    sigs_assign: Option<&[Signature]>,
+) -> Result<(), GadgetError> {
+    for len in &[messages.len(), sigs.len()] {
+        if *len != pks.len() {
+            return Err(GadgetError::InvalidLCsLength {
+                expected: pks.len(),
+                found: *len,
+            });
+        }
+    }
+    let weights = batch_weights(cs, pks.len());
+
+    let mut terms: Vec<(SonnyEdwardsPoint, Vec<Variable>)> = Vec::with_capacity(2 * pks.len());
+    let mut nonces = Vec::with_capacity(pks.len());
+    for (i, (sig, w)) in sigs.into_iter().zip(&weights).enumerate() {
+        let sig_assign = sigs_assign.map(|sigs| sigs[i]);
+        sig.R.satisfy_coherence(cs);
+        sig.R.satisfy_curve_eq(cs);
+        // c_i = H(R.x, R.y, PK.x, PK.y, m)
+        let (rx, ry) = sig.R.to_affine(sig_assign.map(|s| s.R), cs);
+        let (px, py) = affine_coords(&pks[i]);
+        let mut inputs: Vec<LC> = vec![rx.into(), ry.into(), px.into(), py.into()];
+        inputs.extend_from_slice(&messages[i]);
+        let inputs_assign = sig_assign
+            .and_then(|sig| messages_assign.map(|m| challenge_inputs(&sig.R, &pks[i], &m[i])));
+        let mut c_bits = hash_to_scalar_gadget(cs, &inputs, inputs_assign.as_deref());
+        c_bits.truncate(CHALLENGE_BITS);
+
+        for bit in &sig.s {
+            binary_constrain_gadget(cs, *bit);
+        }
+        let wB = witness::edwards::scalar_mul(&basepoint(), w);
+        let wPK = witness::edwards::scalar_mul(&pks[i], w);
+        terms.push((wB, sig.s));
+        terms.push((witness::edwards::neg(&wPK), c_bits));
+        nonces.push(sig.R.neg());
+    }
+
+    let top = terms
+        .iter()
+        .map(|(_, bits)| bits.len())
+        .chain(std::iter::once(BATCH_WEIGHT_BITS))
+        .max()
+        .unwrap();
+    let mut acc = SonnyEdwardsPointGadget::identity();
+    for k in (0..top).rev() {
+        acc = acc.double(cs);
+        for (point, bits) in &terms {
+            if let Some(bit) = bits.get(k) {
+                acc = acc.add(&select_constant(point, *bit), cs);
+            }
+        }
+        for (neg_R, w) in nonces.iter().zip(&weights) {
+            if k < BATCH_WEIGHT_BITS && w[k] == 1 {
+                acc = acc.add(neg_R, cs);
+            }
+        }
+    }
+    acc.equal(&SonnyEdwardsPointGadget::identity(), cs);
+    Ok(())
+}
+
+mod test {
+    use super::*;
+    use crate::helpers::PointLCs;
+    use crate::recording::RecordingCS;
+    use zerocaf::field::FieldElement;
+
+    /// Commits the signatures and messages, then runs either the batch gadget
+    /// or one `schnorr_verify_gadget` per signature.
+    fn verify_in_cs(
+        pks: &[SonnyEdwardsPoint],
+        messages: &[Vec<Scalar>],
+        sigs: &[Signature],
+        batch: bool,
+    ) -> RecordingCS {
+        let mut cs = RecordingCS::new(b"Schnorr");
+        let message_lcs: Vec<Vec<LC>> = messages
+            .iter()
+            .map(|m| m.iter().map(|v| cs.commit(Some(*v)).into()).collect())
+            .collect();
+        let sig_gadgets: Vec<SignatureGadget> = sigs
+            .iter()
+            .map(|sig| {
+                let R_vars: Vec<Variable> =
+                    sig.R.coords().iter().map(|c| cs.commit(Some(*c))).collect();
+                let s = sonny_scalar_bits(&sig.s, S_BITS)
+                    .iter()
+                    .map(|b| cs.commit(Some(Scalar::from(*b))))
+                    .collect();
+                let R = PointLCs::from_vars(&R_vars).unwrap().into();
+                SignatureGadget { R, s }
+            })
+            .collect();
+        if batch {
+            batch_verify_gadget(
+                &mut cs,
+                pks,
+                &message_lcs,
+                Some(messages),
+                sig_gadgets,
+                Some(sigs),
+            )
+            .unwrap();
+        } else {
+            for (i, sig) in sig_gadgets.into_iter().enumerate() {
+                schnorr_verify_gadget(
+                    &mut cs,
+                    &SonnyEdwardsPointGadget::from_point(&pks[i]),
+                    Some(pks[i]),
+                    &message_lcs[i],
+                    Some(&messages[i]),
+                    sig,
+                    Some(sigs[i]),
+                );
+            }
+        }
+        cs
+    }
+
+    #[test]
+    fn batch_verification() {
+        let mut rng = rand::thread_rng();
+        let sks: Vec<SonnyScalar> = (0..3).map(|_| SonnyScalar::random(&mut rng)).collect();
+        let pks: Vec<SonnyEdwardsPoint> = sks.iter().map(public_key).collect();
+        let messages: Vec<Vec<Scalar>> = (0..3u64)
+            .map(|i| vec![Scalar::from(i), Scalar::random(&mut rng)])
+            .collect();
+        let mut sigs: Vec<Signature> = sks
+            .iter()
+            .zip(&messages)
+            .map(|(sk, m)| sign(sk, m, &mut rng))
+            .collect();
+
+        let batch = verify_in_cs(&pks, &messages, &sigs, true);
+        assert!(batch.first_unsatisfied().is_none());
+        let single = verify_in_cs(&pks, &messages, &sigs, false);
+        assert!(single.first_unsatisfied().is_none());
+        assert!(batch.multipliers() < single.multipliers());
+
+        // A signature under the wrong key.
+        sigs[1] = sign(&sks[2], &messages[1], &mut rng);
+        let batch = verify_in_cs(&pks, &messages, &sigs, true);
+        assert!(batch.first_unsatisfied().is_some());
+    }
+
+    #[test]
+    fn incoherent_nonce_is_rejected() {
+        let mut rng = rand::thread_rng();
+        let sk = SonnyScalar::random(&mut rng);
+        let pks = vec![public_key(&sk)];
+        let messages = vec![vec![Scalar::random(&mut rng)]];
+        let mut sigs = vec![sign(&sk, &messages[0], &mut rng)];
+        // Same affine coordinates, but `X * Y != Z * T`.
+        sigs[0].R.T = sigs[0].R.T + FieldElement::one();
+
+        for batch in &[false, true] {
+            let cs = verify_in_cs(&pks, &messages, &sigs, *batch);
+            assert!(cs.first_unsatisfied().is_some());
+        }
+    }
+}
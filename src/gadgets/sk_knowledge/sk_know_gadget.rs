@@ -1,13 +1,18 @@
 use crate::{
     gadgets::boolean::binary_constrain_gadget,
+    gadgets::point::edwards_point::SonnyEdwardsPointGadget,
     gadgets::point::ristretto_point::SonnyRistrettoPointGadget,
+    helpers::{self, PointLCs},
+    instrument,
+    statement::{encode_point, Statement},
 };
 use bulletproofs::{
-    r1cs::{ConstraintSystem, LinearCombination, Prover, R1CSError, R1CSProof, Variable, Verifier},
+    r1cs::{ConstraintSystem, LinearCombination, R1CSError, R1CSProof, Variable, Verifier},
     BulletproofGens, PedersenGens,
 };
+#[cfg(feature = "prover")]
+use bulletproofs::r1cs::Prover;
 use curve25519_dalek::{ristretto::CompressedRistretto, scalar::Scalar};
-use merlin::Transcript;
 use zerocaf::{ristretto::RistrettoPoint as SonnyRistrettoPoint, scalar::Scalar as SonnyScalar};
 
 pub fn sk_knowledge_gadget(
@@ -16,13 +21,7 @@ pub fn sk_knowledge_gadget(
     pk: SonnyRistrettoPointGadget,
     mut sk: Vec<Variable>,
 ) {
-    // Generate Identity point without the ristretto constraint
-    let mut Q = SonnyRistrettoPointGadget {
-        X: LinearCombination::from(Scalar::zero()),
-        Y: LinearCombination::from(Scalar::one()),
-        Z: LinearCombination::from(Scalar::one()),
-        T: LinearCombination::from(Scalar::zero()),
-    };
+    let mut Q = SonnyRistrettoPointGadget::identity();
     // Compute pk'
     sk.reverse();
     for var in sk {
@@ -37,6 +36,42 @@ pub fn sk_knowledge_gadget(
     pk.equals(cs, Q);
 }
 
+/// Same as `sk_knowledge_gadget` for the committed public key of coordinates
+/// `pk`, which is first constrained to be coherent, to satisfy the curve
+/// equation and to be a Ristretto point (see
+/// `SonnyEdwardsPointGadget::into_ristretto`). It proves the knowledge of the
+/// secret key of some committed key, as the ring and credential circuits need.
+/// The prover provides `pk_assign`.
+pub fn committed_pk_sk_knowledge_gadget(
+    cs: &mut dyn ConstraintSystem,
+    basep: SonnyRistrettoPointGadget,
+    pk: PointLCs,
+    pk_assign: Option<SonnyRistrettoPoint>,
+    sk: Vec<Variable>,
+) {
+    let pk = SonnyEdwardsPointGadget::from(pk);
+    pk.satisfy_coherence(cs);
+    let pk = pk.into_ristretto(cs, pk_assign.map(|p| p.0));
+    sk_knowledge_gadget(cs, basep, pk, sk);
+}
+
+/// Public values of a proof of knowledge of a secret key.
+#[derive(Clone, Copy, Debug)]
+pub struct SkKnowledgeStatement {
+    pub basep: SonnyRistrettoPoint,
+    pub pk: SonnyRistrettoPoint,
+}
+
+impl Statement for SkKnowledgeStatement {
+    const LABEL: &'static [u8] = b"Sk_knowledge";
+
+    fn encode(&self, out: &mut Vec<u8>) {
+        encode_point(out, &self.basep.0);
+        encode_point(out, &self.pk.0);
+    }
+}
+
+#[cfg(feature = "prover")]
 fn sk_knowledge_proof(
     pc_gens: &PedersenGens,
     bp_gens: &BulletproofGens,
@@ -45,22 +80,26 @@ fn sk_knowledge_proof(
     sk: &[Scalar],
 ) -> Result<(R1CSProof, Vec<CompressedRistretto>), R1CSError> {
     // Generate transcript
-    let mut transcript = Transcript::new(b"Sk_knowledge");
+    let mut transcript = SkKnowledgeStatement { basep, pk }.transcript();
     // Generate prover
     let mut prover = Prover::new(pc_gens, &mut transcript);
     // Commit high-level variables
-    let (commitments, bits): (Vec<CompressedRistretto>, Vec<Variable>) = sk
-        .iter()
-        .map(|x| prover.commit(*x, Scalar::random(&mut rand::thread_rng())))
-        .unzip();
+    let (commitments, bits): (Vec<CompressedRistretto>, Vec<Variable>) =
+        instrument::phase("commitment", "sk_knowledge", || {
+            sk.iter()
+                .map(|x| prover.commit(*x, Scalar::random(&mut rand::thread_rng())))
+                .unzip()
+        });
 
     // Apply sk_knowledge_gadget
-    let basep_gadget = SonnyRistrettoPointGadget::from_point(basep, &mut prover);
-    let pk_gadget = SonnyRistrettoPointGadget::from_point(pk, &mut prover);
-    sk_knowledge_gadget(&mut prover, basep_gadget, pk_gadget, bits);
+    instrument::synthesis("sk_knowledge", &mut prover, |cs| {
+        let basep_gadget = SonnyRistrettoPointGadget::from_point(basep, cs);
+        let pk_gadget = SonnyRistrettoPointGadget::from_point(pk, cs);
+        sk_knowledge_gadget(cs, basep_gadget, pk_gadget, bits);
+    });
 
     // Generate the proof
-    let proof = prover.prove(bp_gens)?;
+    let proof = instrument::phase("proving", "sk_knowledge", || prover.prove(bp_gens))?;
     Ok((proof, commitments))
 }
 
@@ -73,19 +112,104 @@ fn sk_knowledge_verify(
     proof: &R1CSProof,
 ) -> Result<(), R1CSError> {
     // Generate transcript
-    let mut transcript = Transcript::new(b"Sk_knowledge");
+    let mut transcript = SkKnowledgeStatement { basep, pk }.transcript();
     // Generate verifier
     let mut verifier = Verifier::new(&mut transcript);
     // Commit high-level variables
-    let sk_bit_vars: Vec<Variable> = sk_bits_comms.iter().map(|x| verifier.commit(*x)).collect();
+    let sk_bit_vars: Vec<Variable> = instrument::phase("commitment", "sk_knowledge", || {
+        sk_bits_comms.iter().map(|x| verifier.commit(*x)).collect()
+    });
     // Apply sk_knowledge_gadget
-    let basep_gadget = SonnyRistrettoPointGadget::from_point(basep, &mut verifier);
-    let pk_gadget = SonnyRistrettoPointGadget::from_point(pk, &mut verifier);
-    sk_knowledge_gadget(&mut verifier, basep_gadget, pk_gadget, sk_bit_vars);
+    instrument::synthesis("sk_knowledge", &mut verifier, |cs| {
+        let basep_gadget = SonnyRistrettoPointGadget::from_point(basep, cs);
+        let pk_gadget = SonnyRistrettoPointGadget::from_point(pk, cs);
+        sk_knowledge_gadget(cs, basep_gadget, pk_gadget, sk_bit_vars);
+    });
     // Verify the proof
-    verifier.verify(proof, pc_gens, bp_gens, &mut rand::thread_rng())
+    instrument::phase("verification", "sk_knowledge", || {
+        verify_proof!(verifier, proof, pc_gens, bp_gens)
+    })
+}
+
+/// Public values of a proof of knowledge of the secret key of a committed
+/// public key.
+#[derive(Clone, Copy, Debug)]
+pub struct CommittedPkStatement {
+    pub basep: SonnyRistrettoPoint,
+}
+
+impl Statement for CommittedPkStatement {
+    const LABEL: &'static [u8] = b"Sk_knowledge committed pk";
+
+    fn encode(&self, out: &mut Vec<u8>) {
+        encode_point(out, &self.basep.0);
+    }
+}
+
+/// Proof of knowledge of the secret key of a committed public key.
+#[derive(Clone, Debug)]
+pub struct CommittedPkProof {
+    /// Commitments to the bits of the secret key.
+    pub sk_commitments: Vec<CompressedRistretto>,
+    /// Commitments to the `X, Y, Z, T` coordinates of the public key.
+    pub pk_commitments: Vec<CompressedRistretto>,
+    pub proof: R1CSProof,
+}
+
+/// Proves the knowledge of the secret key `sk`, given by its bits, of `pk`
+/// without revealing either of them.
+#[cfg(feature = "prover")]
+pub fn committed_pk_sk_knowledge_proof(
+    pc_gens: &PedersenGens,
+    bp_gens: &BulletproofGens,
+    basep: SonnyRistrettoPoint,
+    pk: SonnyRistrettoPoint,
+    sk: &[Scalar],
+) -> Result<CommittedPkProof, R1CSError> {
+    let mut transcript = CommittedPkStatement { basep }.transcript();
+    let mut prover = Prover::new(pc_gens, &mut transcript);
+    let (sk_commitments, bits): (Vec<CompressedRistretto>, Vec<Variable>) = sk
+        .iter()
+        .map(|x| prover.commit(*x, Scalar::random(&mut rand::thread_rng())))
+        .unzip();
+    let (mut pk_lcs, pk_commitments) = helpers::prover_commit_points(&mut prover, &[pk]);
+
+    instrument::synthesis("sk_knowledge", &mut prover, |cs| {
+        let basep_gadget = SonnyRistrettoPointGadget::from_point(basep, cs);
+        committed_pk_sk_knowledge_gadget(cs, basep_gadget, pk_lcs.remove(0), Some(pk), bits);
+    });
+    let proof = prover.prove(bp_gens)?;
+    Ok(CommittedPkProof {
+        sk_commitments,
+        pk_commitments,
+        proof,
+    })
+}
+
+/// Verifies a proof of `committed_pk_sk_knowledge_proof`.
+pub fn committed_pk_sk_knowledge_verify(
+    pc_gens: &PedersenGens,
+    bp_gens: &BulletproofGens,
+    basep: SonnyRistrettoPoint,
+    proof: &CommittedPkProof,
+) -> Result<(), R1CSError> {
+    let mut transcript = CommittedPkStatement { basep }.transcript();
+    let mut verifier = Verifier::new(&mut transcript);
+    let bits: Vec<Variable> = proof
+        .sk_commitments
+        .iter()
+        .map(|x| verifier.commit(*x))
+        .collect();
+    let mut pk_lcs = helpers::verifier_commit_points(&mut verifier, &proof.pk_commitments, 1)?;
+
+    instrument::synthesis("sk_knowledge", &mut verifier, |cs| {
+        let basep_gadget = SonnyRistrettoPointGadget::from_point(basep, cs);
+        committed_pk_sk_knowledge_gadget(cs, basep_gadget, pk_lcs.remove(0), None, bits);
+    });
+    verify_proof!(verifier, &proof.proof, pc_gens, bp_gens)
 }
 
+#[cfg(feature = "prover")]
 fn sk_knowledge_gadget_roundtrip_helper(
     basep: SonnyRistrettoPoint,
     pk: SonnyRistrettoPoint,
@@ -104,6 +228,7 @@ fn sk_knowledge_gadget_roundtrip_helper(
     sk_knowledge_verify(&pc_gens, &bp_gens, basep, pk, commitments, &proof)
 }
 
+#[cfg(feature = "prover")]
 mod test {
     use super::*;
 
@@ -122,4 +247,25 @@ mod test {
         )
         .is_err());
     }
+
+    #[test]
+    fn committed_pk() {
+        let pc_gens = PedersenGens::default();
+        let bp_gens = BulletproofGens::new(8192, 1);
+        let basep = zerocaf::constants::RISTRETTO_BASEPOINT;
+        let sk = SonnyScalar::random(&mut rand::thread_rng());
+        let sk_bits: Vec<Scalar> = sk
+            .into_bits()
+            .iter()
+            .map(|bit| Scalar::from(*bit))
+            .collect();
+        let roundtrip = |pk: SonnyRistrettoPoint| {
+            let proof = committed_pk_sk_knowledge_proof(&pc_gens, &bp_gens, basep, pk, &sk_bits)?;
+            committed_pk_sk_knowledge_verify(&pc_gens, &bp_gens, basep, &proof)
+        };
+
+        assert!(roundtrip(basep * sk).is_ok());
+        let other = SonnyRistrettoPoint::new_random_point(&mut rand::thread_rng());
+        assert!(roundtrip(other).is_err());
+    }
 }
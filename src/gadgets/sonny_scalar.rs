@@ -0,0 +1,194 @@
+//! Committed scalars of the Sonny curve.
+//!
+//! A `SonnyScalarGadget` is the LC of a value together with, once they have
+//! been needed, its little-endian bits. The bits are cached, so a scalar used
+//! in several multiplications is decomposed once.
+//!
+//! `add` and `mul` work in the R1CS field, not modulo the order `L` of the
+//! Sonny subgroup. Their results are exact as long as the integers do not
+//! exceed the field order (e.g. the sum of two range-checked scalars); other
+//! values are brought back to a Sonny scalar with `reduce`.
+use crate::gadgets::bits::LeBits;
+use crate::gadgets::boolean::le_constant_gadget;
+use crate::gadgets::point::edwards_point::SonnyEdwardsPointGadget;
+use crate::gadgets::reduction::{reduce, sonny_order, sonny_reduction_gadget, SONNY_ORDER_BITS};
+use crate::gadgets::typed::Bit;
+use bulletproofs::r1cs::{ConstraintSystem as CS, LinearCombination as LC};
+use curve25519_dalek::scalar::Scalar;
+use zerocaf::edwards::EdwardsPoint as SonnyEdwardsPoint;
+
+#[derive(Clone, Debug)]
+pub struct SonnyScalarGadget {
+    lc: LC,
+    assign: Option<Scalar>,
+    bits: Option<Vec<Bit>>,
+    range_checked: bool,
+}
+
+impl SonnyScalarGadget {
+    /// The scalar of `lc`, with its assignment on the prover side.
+    pub fn new(lc: LC, assign: Option<Scalar>) -> SonnyScalarGadget {
+        SonnyScalarGadget {
+            lc,
+            assign,
+            bits: None,
+            range_checked: false,
+        }
+    }
+
+    pub fn lc(&self) -> LC {
+        self.lc.clone()
+    }
+
+    pub fn assign(&self) -> Option<Scalar> {
+        self.assign
+    }
+
+    /// The field sum of the scalars. Takes no multiplier.
+    pub fn add(&self, other: &SonnyScalarGadget) -> SonnyScalarGadget {
+        SonnyScalarGadget::new(
+            self.lc.clone() + other.lc.clone(),
+            self.assign.and_then(|a| other.assign.map(|b| a + b)),
+        )
+    }
+
+    /// The field product of the scalars. Takes one multiplier.
+    pub fn mul(&self, other: &SonnyScalarGadget, cs: &mut dyn CS) -> SonnyScalarGadget {
+        let (_, _, prod) = cs.multiply(self.lc.clone(), other.lc.clone());
+        SonnyScalarGadget::new(
+            prod.into(),
+            self.assign.and_then(|a| other.assign.map(|b| a * b)),
+        )
+    }
+
+    /// The `SONNY_ORDER_BITS` little-endian bits of the scalar, decomposed on
+    /// the first call only. They bound the value below `2^SONNY_ORDER_BITS`,
+    /// see `range_check` for `L`.
+    pub fn bits(&mut self, cs: &mut dyn CS) -> &[Bit] {
+        if self.bits.is_none() {
+            self.bits = Some(Bit::decompose(
+                cs,
+                self.lc.clone(),
+                self.assign,
+                SONNY_ORDER_BITS,
+            ));
+        }
+        self.bits.as_ref().unwrap()
+    }
+
+    /// The assignment of `bits`, for the native computations.
+    pub fn bits_assign(&self) -> Option<LeBits> {
+        self.assign
+            .map(|a| LeBits::from_scalar(&a, SONNY_ORDER_BITS))
+    }
+
+    /// Constrains the scalar to be lower than `L`, once.
+    pub fn range_check(&mut self, cs: &mut dyn CS) {
+        if self.range_checked {
+            return;
+        }
+        let bits = Bit::variables(self.bits(cs));
+        le_constant_gadget(cs, &bits, &(sonny_order() - Scalar::one()));
+        self.range_checked = true;
+    }
+
+    /// The scalar modulo `L`, its bits already decomposed and range-checked
+    /// (see `sonny_reduction_gadget`).
+    pub fn reduce(&self, cs: &mut dyn CS) -> SonnyScalarGadget {
+        let r_assign = self
+            .assign
+            .map(|a| reduce(&a).expect("the value can not be reduced").1);
+        let r = cs.allocate(r_assign).unwrap();
+        let bits = sonny_reduction_gadget(cs, self.lc.clone(), r.into(), self.assign);
+        SonnyScalarGadget {
+            lc: r.into(),
+            assign: r_assign,
+            bits: Some(bits),
+            range_checked: true,
+        }
+    }
+
+    /// `scalar * point`, reusing the cached bits (see
+    /// `SonnyEdwardsPointGadget::scalar_mul`).
+    pub fn mul_point(
+        &mut self,
+        point: SonnyEdwardsPointGadget,
+        point_assign: Option<SonnyEdwardsPoint>,
+        cs: &mut dyn CS,
+    ) -> (SonnyEdwardsPointGadget, Option<SonnyEdwardsPoint>) {
+        let bits = Bit::variables(self.bits(cs));
+        let bits_assign = self.bits_assign();
+        SonnyEdwardsPointGadget::scalar_mul(
+            point,
+            point_assign,
+            bits,
+            bits_assign.as_ref().map(LeBits::as_slice),
+            cs,
+        )
+    }
+}
+
+mod test {
+    use super::*;
+    use crate::gadgets::point::witness;
+    use crate::gadgets::signature::basepoint;
+    use crate::helpers::{affine_coords, sonny_scalar_to_scalar};
+    use crate::recording::RecordingCS;
+    use zerocaf::scalar::Scalar as SonnyScalar;
+
+    #[test]
+    fn cached_bits() {
+        let s = sonny_scalar_to_scalar(&SonnyScalar::random(&mut rand::thread_rng()));
+        let mut cs = RecordingCS::new(b"SonnyScalar");
+        let var = cs.commit(Some(s));
+        let mut scalar = SonnyScalarGadget::new(var.into(), Some(s));
+        scalar.range_check(&mut cs);
+        let checked = cs.multipliers();
+        scalar.range_check(&mut cs);
+        scalar.bits(&mut cs);
+        assert_eq!(cs.multipliers(), checked);
+
+        let b = SonnyEdwardsPointGadget::basepoint();
+        let (_, first) = scalar.mul_point(b.clone(), Some(basepoint()), &mut cs);
+        let one_mul = cs.multipliers() - checked;
+        let (_, second) = scalar.mul_point(b, Some(basepoint()), &mut cs);
+        assert_eq!(cs.multipliers() - checked, 2 * one_mul);
+        assert_eq!(
+            affine_coords(&first.unwrap()),
+            affine_coords(&second.unwrap())
+        );
+        assert!(cs.first_unsatisfied().is_none());
+    }
+
+    #[test]
+    fn arithmetic() {
+        let mut rng = rand::thread_rng();
+        let (a, b) = (Scalar::random(&mut rng), Scalar::random(&mut rng));
+        let mut cs = RecordingCS::new(b"SonnyScalar");
+        let a_gadget = SonnyScalarGadget::new(cs.commit(Some(a)).into(), Some(a));
+        let b_gadget = SonnyScalarGadget::new(cs.commit(Some(b)).into(), Some(b));
+        let sum = a_gadget.add(&b_gadget);
+        let prod = a_gadget.mul(&b_gadget, &mut cs);
+        assert_eq!(cs.eval(&sum.lc()), Some(a + b));
+        assert_eq!(cs.eval(&prod.lc()), Some(a * b));
+
+        let reduced = prod.reduce(&mut cs);
+        let (_, r) = reduce(&(a * b)).unwrap();
+        assert_eq!(cs.eval(&reduced.lc()), Some(r));
+        assert_eq!(
+            reduced.bits_assign(),
+            Some(LeBits::from_scalar(&r, SONNY_ORDER_BITS))
+        );
+        assert!(cs.first_unsatisfied().is_none());
+
+        // The reduced scalar multiplies points as the unreduced one.
+        let bits = LeBits::from_scalar(&(a * b), 253);
+        assert_eq!(
+            affine_coords(&witness::edwards::scalar_mul(&basepoint(), bits.as_slice())),
+            affine_coords(&witness::edwards::scalar_mul(
+                &basepoint(),
+                reduced.bits_assign().unwrap().as_slice()
+            ))
+        );
+    }
+}
@@ -0,0 +1,70 @@
+//! Splitting of a committed value into several committed outputs.
+use crate::gadgets::typed::AmountVar;
+use bulletproofs::r1cs::{ConstraintSystem as CS, LinearCombination as LC};
+
+/// Constrains `value = sum(outputs)`. Since every output is in
+/// `[0, 2^RANGE_BITS)`, the sum can not wrap around the field, so no output can
+/// be "negative" and `value` is exactly split. Adds a single constraint, the
+/// outputs being range-checked by `AmountVar::constrain`.
+pub fn split_gadget(cs: &mut dyn CS, value: LC, outputs: &[AmountVar]) {
+    let sum = outputs
+        .iter()
+        .fold(LC::default(), |sum, output| sum + LC::from(*output));
+    cs.constrain(value - sum);
+}
+
+/// Splits a note of value `value` into a `payment` and its `change`.
+pub fn change_payment_gadget(cs: &mut dyn CS, value: LC, payment: AmountVar, change: AmountVar) {
+    split_gadget(cs, value, &[payment, change]);
+}
+
+mod test {
+    use super::*;
+    use crate::recording::RecordingCS;
+    use curve25519_dalek::scalar::Scalar;
+
+    fn split(value: u64, outputs: &[Scalar]) -> bool {
+        let mut cs = RecordingCS::new(b"Split");
+        let value = cs.commit(Some(Scalar::from(value)));
+        // Assignments as the prover would give them, wrapped around u64.
+        let amounts: Vec<AmountVar> = outputs
+            .iter()
+            .map(|o| {
+                let mut buf = [0u8; 8];
+                buf.copy_from_slice(&o.as_bytes()[..8]);
+                let var = cs.commit(Some(*o));
+                AmountVar::constrain(&mut cs, var, Some(u64::from_le_bytes(buf)))
+            })
+            .collect();
+        split_gadget(&mut cs, value.into(), &amounts);
+        cs.first_unsatisfied().is_none()
+    }
+
+    #[test]
+    fn split_value() {
+        let s = |v: u64| Scalar::from(v);
+        assert!(split(100, &[s(20), s(30), s(50)]));
+        assert!(split(0, &[s(0), s(0)]));
+        assert!(split(u64::max_value(), &[s(u64::max_value()), s(0)]));
+        assert!(!split(100, &[s(20), s(30), s(49)]));
+        // A "negative" output compensated by a larger one.
+        assert!(!split(100, &[-s(1), s(101)]));
+    }
+
+    #[test]
+    fn change_payment() {
+        let satisfied = |value: u64, payment: u64, change: u64| {
+            let mut cs = RecordingCS::new(b"ChangePayment");
+            let value = cs.commit(Some(Scalar::from(value)));
+            let payment_var = cs.commit(Some(Scalar::from(payment)));
+            let change_var = cs.commit(Some(Scalar::from(change)));
+            let payment = AmountVar::constrain(&mut cs, payment_var, Some(payment));
+            let change = AmountVar::constrain(&mut cs, change_var, Some(change));
+            change_payment_gadget(&mut cs, value.into(), payment, change);
+            cs.first_unsatisfied().is_none()
+        };
+        assert!(satisfied(100, 70, 30));
+        assert!(satisfied(100, 100, 0));
+        assert!(!satisfied(100, 70, 31));
+    }
+}
@@ -0,0 +1,142 @@
+//! Typed wrappers around variables, so that gadgets expecting a constrained
+//! value can not be handed a raw one.
+//!
+//! A `Bit` is boolean-constrained and an `AmountVar` is in `[0, 2^RANGE_BITS)`:
+//! both are only built by the gadgets adding those constraints. Any variable
+//! holds an element of the base field of Sonny, so `FqVar` converts from
+//! `Variable` for free and only documents what a variable stands for.
+use crate::gadgets::boolean::{binary_constrain_gadget, bit_decomposition_gadget};
+use crate::gadgets::range::RANGE_BITS;
+use bulletproofs::r1cs::{ConstraintSystem as CS, LinearCombination as LC, R1CSError, Variable};
+use curve25519_dalek::scalar::Scalar;
+
+/// A boolean-constrained variable.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Bit(Variable);
+
+impl Bit {
+    /// Boolean-constrains `var`.
+    pub fn constrain(cs: &mut dyn CS, var: Variable) -> Bit {
+        binary_constrain_gadget(cs, var);
+        Bit(var)
+    }
+
+    /// Allocates a new boolean-constrained variable.
+    pub fn allocate(cs: &mut dyn CS, assign: Option<bool>) -> Result<Bit, R1CSError> {
+        let var = cs.allocate(assign.map(|b| Scalar::from(b as u64)))?;
+        Ok(Bit::constrain(cs, var))
+    }
+
+    /// Typed `bit_decomposition_gadget`: the `n` little-endian bits of `value`.
+    pub fn decompose(
+        cs: &mut dyn CS,
+        value: LC,
+        value_assign: Option<Scalar>,
+        n: usize,
+    ) -> Vec<Bit> {
+        bit_decomposition_gadget(cs, value, value_assign, n)
+            .into_iter()
+            .map(Bit)
+            .collect()
+    }
+
+    /// The variables of `bits`, for the gadgets taking untyped bits.
+    pub fn variables(bits: &[Bit]) -> Vec<Variable> {
+        bits.iter().map(|b| b.0).collect()
+    }
+
+    pub fn variable(&self) -> Variable {
+        self.0
+    }
+}
+
+impl From<Bit> for Variable {
+    fn from(bit: Bit) -> Variable {
+        bit.0
+    }
+}
+
+impl From<Bit> for LC {
+    fn from(bit: Bit) -> LC {
+        bit.0.into()
+    }
+}
+
+/// An element of the base field of Sonny (the scalar field of Ristretto),
+/// e.g. a point coordinate.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct FqVar(pub Variable);
+
+impl From<Variable> for FqVar {
+    fn from(var: Variable) -> FqVar {
+        FqVar(var)
+    }
+}
+
+impl From<FqVar> for LC {
+    fn from(x: FqVar) -> LC {
+        x.0.into()
+    }
+}
+
+/// An amount, constrained to `[0, 2^RANGE_BITS)` so that sums of a few of them
+/// can not wrap around the field.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct AmountVar(Variable);
+
+impl AmountVar {
+    /// Decomposes `var` into `RANGE_BITS` bits. Takes `RANGE_BITS` multipliers.
+    pub fn constrain(cs: &mut dyn CS, var: Variable, assign: Option<u64>) -> AmountVar {
+        bit_decomposition_gadget(cs, var.into(), assign.map(Scalar::from), RANGE_BITS);
+        AmountVar(var)
+    }
+
+    pub fn variable(&self) -> Variable {
+        self.0
+    }
+}
+
+impl From<AmountVar> for Variable {
+    fn from(amount: AmountVar) -> Variable {
+        amount.0
+    }
+}
+
+impl From<AmountVar> for LC {
+    fn from(amount: AmountVar) -> LC {
+        amount.0.into()
+    }
+}
+
+mod test {
+    use super::*;
+    use crate::recording::RecordingCS;
+
+    #[test]
+    fn typed_constraints() {
+        let mut cs = RecordingCS::new(b"Typed");
+        let var = cs.commit(Some(Scalar::from(2u64)));
+        Bit::constrain(&mut cs, var);
+        assert!(cs.first_unsatisfied().is_some());
+
+        let mut cs = RecordingCS::new(b"Typed");
+        let var = cs.commit(Some(-Scalar::one()));
+        AmountVar::constrain(&mut cs, var, Some(u64::max_value()));
+        assert!(cs.first_unsatisfied().is_some());
+
+        let mut cs = RecordingCS::new(b"Typed");
+        let var = cs.commit(Some(Scalar::from(6u64)));
+        let bits = Bit::decompose(&mut cs, var.into(), Some(Scalar::from(6u64)), 3);
+        let values: Vec<_> = bits.iter().map(|b| cs.eval(&(*b).into())).collect();
+        assert_eq!(
+            values,
+            vec![
+                Some(Scalar::zero()),
+                Some(Scalar::one()),
+                Some(Scalar::one())
+            ]
+        );
+        assert!(Bit::allocate(&mut cs, Some(true)).is_ok());
+        assert!(cs.first_unsatisfied().is_none());
+    }
+}
@@ -0,0 +1,135 @@
+//! Vector commitments opened at a committed index.
+//!
+//! A vector is committed to as the root of its `MerkleTree`. Opening it proves
+//! that the committed `value` sits at the committed `index`, without revealing
+//! either of them.
+use crate::errors::GadgetError;
+use crate::gadgets::merkle::{merkle_root_gadget, MerkleTree};
+use crate::gadgets::typed::Bit;
+use bulletproofs::r1cs::{ConstraintSystem as CS, LinearCombination as LC};
+use curve25519_dalek::scalar::Scalar;
+
+/// Native side of a vector commitment.
+#[derive(Clone, Debug)]
+pub struct VectorCommitment {
+    tree: MerkleTree,
+}
+
+impl VectorCommitment {
+    /// Commits to `values`, which must fit in `2^depth` positions.
+    pub fn new(values: &[Scalar], depth: usize) -> VectorCommitment {
+        VectorCommitment {
+            tree: MerkleTree::new(values, depth),
+        }
+    }
+
+    /// The public commitment.
+    pub fn root(&self) -> Scalar {
+        self.tree.root()
+    }
+
+    pub fn depth(&self) -> usize {
+        self.tree.depth()
+    }
+
+    /// Opening proof of the position `index`: the Merkle path of its leaf.
+    pub fn opening(&self, index: usize) -> Vec<Scalar> {
+        self.tree.path(index)
+    }
+}
+
+/// In-circuit side of a vector commitment.
+#[derive(Clone, Debug)]
+pub struct VectorCommitmentGadget {
+    pub root: LC,
+    pub depth: usize,
+}
+
+impl VectorCommitmentGadget {
+    pub fn new(root: LC, depth: usize) -> VectorCommitmentGadget {
+        VectorCommitmentGadget { root, depth }
+    }
+
+    /// Constrains the vector to hold `value` at `index`, given the `opening`
+    /// (see `VectorCommitment::opening`). The index is decomposed into `depth`
+    /// bits, which also checks that it is in range.
+    pub fn open(
+        &self,
+        cs: &mut dyn CS,
+        index: LC,
+        index_assign: Option<u64>,
+        value: LC,
+        opening: &[LC],
+    ) -> Result<(), GadgetError> {
+        if opening.len() != self.depth {
+            return Err(GadgetError::InvalidLCsLength {
+                expected: self.depth,
+                found: opening.len(),
+            });
+        }
+        let index_bits =
+            Bit::decompose(cs, index, index_assign.map(Scalar::from), self.depth);
+        let root = merkle_root_gadget(cs, value, &index_bits, opening)?;
+        cs.constrain(root - self.root.clone());
+        Ok(())
+    }
+}
+
+mod test {
+    use super::*;
+    use crate::session;
+    use bulletproofs::r1cs::{Prover, R1CSError, Verifier};
+    use bulletproofs::{BulletproofGens, PedersenGens};
+
+    fn open_roundtrip_helper(
+        vc: &VectorCommitment,
+        index: u64,
+        value: Scalar,
+        opening: Vec<Scalar>,
+    ) -> Result<(), R1CSError> {
+        let pc_gens = PedersenGens::default();
+        let bp_gens = BulletproofGens::new(4096, 1);
+        let depth = vc.depth();
+
+        let mut transcript = session::transcript(b"Vector commitment opening");
+        let mut prover = Prover::new(&pc_gens, &mut transcript);
+        let (commitments, lcs): (Vec<_>, Vec<LC>) = [Scalar::from(index), value]
+            .iter()
+            .cloned()
+            .chain(opening)
+            .map(|v| {
+                let (comm, var) = prover.commit(v, Scalar::random(&mut rand::thread_rng()));
+                (comm, var.into())
+            })
+            .unzip();
+        let gadget = VectorCommitmentGadget::new(vc.root().into(), depth);
+        gadget.open(&mut prover, lcs[0].clone(), Some(index), lcs[1].clone(), &lcs[2..])?;
+        let proof = prover.prove(&bp_gens)?;
+
+        let mut transcript = session::transcript(b"Vector commitment opening");
+        let mut verifier = Verifier::new(&mut transcript);
+        let lcs: Vec<LC> = commitments
+            .iter()
+            .map(|V| verifier.commit(*V).into())
+            .collect();
+        let gadget = VectorCommitmentGadget::new(vc.root().into(), depth);
+        gadget.open(&mut verifier, lcs[0].clone(), None, lcs[1].clone(), &lcs[2..])?;
+        verify_proof!(verifier, &proof, &pc_gens, &bp_gens)
+    }
+
+    #[test]
+    fn open_at_index() {
+        let values: Vec<Scalar> = (0..5)
+            .map(|_| Scalar::random(&mut rand::thread_rng()))
+            .collect();
+        let vc = VectorCommitment::new(&values, 3);
+
+        assert!(open_roundtrip_helper(&vc, 2, values[2], vc.opening(2)).is_ok());
+        // Padding positions hold zero.
+        assert!(open_roundtrip_helper(&vc, 6, Scalar::zero(), vc.opening(6)).is_ok());
+        // Value of another position.
+        assert!(open_roundtrip_helper(&vc, 2, values[3], vc.opening(2)).is_err());
+        // Right value, wrong index.
+        assert!(open_roundtrip_helper(&vc, 3, values[2], vc.opening(2)).is_err());
+    }
+}
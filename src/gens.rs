@@ -0,0 +1,215 @@
+//! Consistency checks of the generators an application proves and verifies
+//! with.
+//!
+//! A verifier deployed with other `PedersenGens` (or other Sonny commitment
+//! generators) than the provers rejects every proof, with nothing pointing at
+//! the generators. Applications pin the `GensDigest` of their generators, a
+//! hash of their compressed encodings, as a constant: `GensDigest::check`
+//! fails loudly at startup on a mismatch, and `append_to_transcript` binds the
+//! digest into the proofs, so proofs generated with other generators are
+//! rejected as such.
+//!
+//! The generators are public constants of the circuits, so an in-circuit check
+//! would only compare constants: binding them into the transcript is what ties
+//! them to the proof.
+//!
+//! `bulletproofs` keeps the points of `BulletproofGens` private and offers no
+//! constructor taking points, but derives them deterministically from their
+//! capacities. They are exported as the `BulletproofGensParams` they are built
+//! from, and `BulletproofGensParams::import` rebuilds them once the parameters
+//! match a pinned digest, before allocating anything.
+use crate::errors::GadgetError;
+use crate::gadgets::commitment::SonnyPedersenGens;
+use crate::helpers::affine_coords;
+use bulletproofs::{BulletproofGens, PedersenGens};
+use merlin::Transcript;
+use std::convert::TryFrom;
+
+/// Hash of a set of generators.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct GensDigest(pub [u8; 32]);
+
+impl GensDigest {
+    fn from_transcript(mut transcript: Transcript) -> GensDigest {
+        let mut digest = [0u8; 32];
+        transcript.challenge_bytes(b"gens-digest", &mut digest);
+        GensDigest(digest)
+    }
+
+    /// Digest of the value and blinding generators of `pc_gens`.
+    pub fn of_pc_gens(pc_gens: &PedersenGens) -> GensDigest {
+        let mut transcript = Transcript::new(b"bulletproofs_gadgets PedersenGens");
+        transcript.append_message(b"B", pc_gens.B.compress().as_bytes());
+        transcript.append_message(b"B_blinding", pc_gens.B_blinding.compress().as_bytes());
+        GensDigest::from_transcript(transcript)
+    }
+
+    /// Digest of the generators of Sonny commitments, by their affine coordinates.
+    pub fn of_sonny_gens(gens: &SonnyPedersenGens) -> GensDigest {
+        let mut transcript = Transcript::new(b"bulletproofs_gadgets SonnyPedersenGens");
+        transcript.append_u64(b"n", gens.value_gens.len() as u64);
+        for gen in gens
+            .value_gens
+            .iter()
+            .chain(std::iter::once(&gens.blinding_gen))
+        {
+            let (x, y) = affine_coords(gen);
+            transcript.append_message(b"x", x.as_bytes());
+            transcript.append_message(b"y", y.as_bytes());
+        }
+        GensDigest::from_transcript(transcript)
+    }
+
+    /// Digest of `bp_gens`, by the parameters they are derived from.
+    pub fn of_bp_gens(bp_gens: &BulletproofGens) -> GensDigest {
+        BulletproofGensParams::of(bp_gens).digest()
+    }
+
+    /// Fails unless the digest is the `expected` one.
+    pub fn check(&self, expected: &GensDigest) -> Result<(), GadgetError> {
+        if self != expected {
+            return Err(GadgetError::UnexpectedGenerators);
+        }
+        Ok(())
+    }
+
+    pub fn append_to_transcript(&self, transcript: &mut Transcript) {
+        transcript.append_message(b"gens-digest", &self.0);
+    }
+}
+
+/// Exportable form of `BulletproofGens`: the capacities they are derived from.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct BulletproofGensParams {
+    pub gens_capacity: usize,
+    pub party_capacity: usize,
+}
+
+impl BulletproofGensParams {
+    /// Size of the encoding of `to_bytes`.
+    pub const SIZE: usize = 16;
+
+    pub fn of(bp_gens: &BulletproofGens) -> BulletproofGensParams {
+        BulletproofGensParams {
+            gens_capacity: bp_gens.gens_capacity,
+            party_capacity: bp_gens.party_capacity,
+        }
+    }
+
+    pub fn digest(&self) -> GensDigest {
+        let mut transcript = Transcript::new(b"bulletproofs_gadgets BulletproofGens");
+        transcript.append_u64(b"gens_capacity", self.gens_capacity as u64);
+        transcript.append_u64(b"party_capacity", self.party_capacity as u64);
+        GensDigest::from_transcript(transcript)
+    }
+
+    /// Encodes both capacities as little-endian `u64`s.
+    pub fn to_bytes(&self) -> [u8; BulletproofGensParams::SIZE] {
+        let mut out = [0u8; BulletproofGensParams::SIZE];
+        out[..8].copy_from_slice(&(self.gens_capacity as u64).to_le_bytes());
+        out[8..].copy_from_slice(&(self.party_capacity as u64).to_le_bytes());
+        out
+    }
+
+    /// Decodes parameters encoded by `to_bytes`.
+    pub fn from_bytes(bytes: &[u8]) -> Result<BulletproofGensParams, GadgetError> {
+        if bytes.len() != BulletproofGensParams::SIZE {
+            return Err(GadgetError::MalformedGens);
+        }
+        let capacity = |b: &[u8]| {
+            let mut buf = [0u8; 8];
+            buf.copy_from_slice(b);
+            usize::try_from(u64::from_le_bytes(buf)).map_err(|_| GadgetError::MalformedGens)
+        };
+        Ok(BulletproofGensParams {
+            gens_capacity: capacity(&bytes[..8])?,
+            party_capacity: capacity(&bytes[8..])?,
+        })
+    }
+
+    /// Builds the generators, failing unless the parameters have the
+    /// `expected` digest.
+    pub fn import(&self, expected: &GensDigest) -> Result<BulletproofGens, GadgetError> {
+        self.digest().check(expected)?;
+        Ok(BulletproofGens::new(
+            self.gens_capacity,
+            self.party_capacity,
+        ))
+    }
+}
+
+mod test {
+    use super::*;
+    use crate::session;
+
+    #[test]
+    fn pinned_generators() {
+        let pc_gens = PedersenGens::default();
+        let expected = GensDigest::of_pc_gens(&pc_gens);
+        assert!(GensDigest::of_pc_gens(&PedersenGens::default())
+            .check(&expected)
+            .is_ok());
+
+        // The value and blinding generators swapped.
+        let swapped = PedersenGens {
+            B: pc_gens.B_blinding,
+            B_blinding: pc_gens.B,
+        };
+        assert_eq!(
+            GensDigest::of_pc_gens(&swapped).check(&expected),
+            Err(GadgetError::UnexpectedGenerators)
+        );
+
+        let sonny = GensDigest::of_sonny_gens(&SonnyPedersenGens::new(2));
+        assert!(GensDigest::of_sonny_gens(&SonnyPedersenGens::new(2))
+            .check(&sonny)
+            .is_ok());
+        assert!(GensDigest::of_sonny_gens(&SonnyPedersenGens::new(3))
+            .check(&sonny)
+            .is_err());
+    }
+
+    #[test]
+    fn bp_gens_export() {
+        let bp_gens = BulletproofGens::new(64, 1);
+        let pinned = GensDigest::of_bp_gens(&bp_gens);
+        let bytes = BulletproofGensParams::of(&bp_gens).to_bytes();
+
+        let params = BulletproofGensParams::from_bytes(&bytes).unwrap();
+        let imported = params.import(&pinned).unwrap();
+        assert_eq!(GensDigest::of_bp_gens(&imported), pinned);
+
+        let other = BulletproofGensParams {
+            gens_capacity: 128,
+            party_capacity: 1,
+        };
+        assert_eq!(
+            other.import(&pinned).err(),
+            Some(GadgetError::UnexpectedGenerators)
+        );
+        assert_eq!(
+            BulletproofGensParams::from_bytes(&bytes[1..]),
+            Err(GadgetError::MalformedGens)
+        );
+    }
+
+    #[test]
+    fn digest_in_transcript() {
+        let challenge = |digest: &GensDigest| {
+            let mut transcript = session::transcript(b"Gens");
+            digest.append_to_transcript(&mut transcript);
+            let mut c = [0u8; 32];
+            transcript.challenge_bytes(b"c", &mut c);
+            c
+        };
+        let pc_gens = PedersenGens::default();
+        let swapped = PedersenGens {
+            B: pc_gens.B_blinding,
+            B_blinding: pc_gens.B,
+        };
+        assert_ne!(
+            challenge(&GensDigest::of_pc_gens(&pc_gens)),
+            challenge(&GensDigest::of_pc_gens(&swapped))
+        );
+    }
+}
@@ -0,0 +1,237 @@
+//! Helpers to move Sonny points (either `SonnyEdwardsPoint` or `SonnyRistrettoPoint`)
+//! into the R1CS, as public constants or as committed high-level variables.
+use crate::errors::GadgetError;
+use crate::gadgets::point::edwards_point::SonnyEdwardsPointGadget;
+use crate::gadgets::point::ristretto_point::SonnyRistrettoPointGadget;
+use bulletproofs::r1cs::{LinearCombination as LC, Variable, Verifier};
+#[cfg(feature = "prover")]
+use bulletproofs::r1cs::Prover;
+use curve25519_dalek::{ristretto::CompressedRistretto, scalar::Scalar};
+use zerocaf::edwards::EdwardsPoint as SonnyEdwardsPoint;
+use zerocaf::field::FieldElement;
+use zerocaf::ristretto::RistrettoPoint as SonnyRistrettoPoint;
+use zerocaf::scalar::Scalar as SonnyScalar;
+
+/// Maps a Sonny `FieldElement` into the scalar field the R1CS works with.
+/// Both fields have the same modulus, so this is the identity on the values.
+pub fn fe_to_scalar(fe: &FieldElement) -> Scalar {
+    Scalar::from_bytes_mod_order(fe.to_bytes())
+}
+
+/// Maps a scalar of the R1CS field back into a Sonny `FieldElement`.
+pub fn scalar_to_fe(s: &Scalar) -> FieldElement {
+    FieldElement::from_bytes(&s.to_bytes())
+}
+
+/// Returns the affine `(x, y) = (X/Z, Y/Z)` coordinates of a point.
+pub fn affine_coords(p: &SonnyEdwardsPoint) -> (Scalar, Scalar) {
+    let z_inv = fe_to_scalar(&p.Z).invert();
+    (fe_to_scalar(&p.X) * z_inv, fe_to_scalar(&p.Y) * z_inv)
+}
+
+/// Points whose extended coordinates `(X, Y, Z, T)` can be moved into the R1CS.
+pub trait PointCoords {
+    fn coords(&self) -> [Scalar; 4];
+}
+
+impl PointCoords for SonnyEdwardsPoint {
+    fn coords(&self) -> [Scalar; 4] {
+        [
+            fe_to_scalar(&self.X),
+            fe_to_scalar(&self.Y),
+            fe_to_scalar(&self.Z),
+            fe_to_scalar(&self.T),
+        ]
+    }
+}
+
+impl PointCoords for SonnyRistrettoPoint {
+    fn coords(&self) -> [Scalar; 4] {
+        self.0.coords()
+    }
+}
+
+/// The LCs of the four extended coordinates of a point. It can be turned into
+/// any of the point gadgets.
+///
+/// Converting it into a `SonnyRistrettoPointGadget` does not add the Ristretto
+/// constraints; use `SonnyRistrettoPointGadget::from_lcs` when they are required.
+#[derive(Clone, Debug)]
+pub struct PointLCs {
+    pub X: LC,
+    pub Y: LC,
+    pub Z: LC,
+    pub T: LC,
+}
+
+impl From<PointLCs> for SonnyEdwardsPointGadget {
+    fn from(p: PointLCs) -> SonnyEdwardsPointGadget {
+        SonnyEdwardsPointGadget {
+            X: p.X,
+            Y: p.Y,
+            Z: p.Z,
+            T: p.T,
+        }
+    }
+}
+
+impl From<PointLCs> for SonnyRistrettoPointGadget {
+    fn from(p: PointLCs) -> SonnyRistrettoPointGadget {
+        SonnyRistrettoPointGadget {
+            X: p.X,
+            Y: p.Y,
+            Z: p.Z,
+            T: p.T,
+        }
+    }
+}
+
+impl PointLCs {
+    /// Maps the four coordinates of a point, in `X, Y, Z, T` order, into LCs.
+    pub(crate) fn from_coords<C, F: FnMut(C) -> LC>([x, y, z, t]: [C; 4], mut f: F) -> PointLCs {
+        PointLCs {
+            X: f(x),
+            Y: f(y),
+            Z: f(z),
+            T: f(t),
+        }
+    }
+
+    /// Builds the point from the LCs of its coordinates, in `X, Y, Z, T` order.
+    /// Fails unless there are exactly four of them.
+    pub fn from_lcs(lcs: &[LC]) -> Result<PointLCs, GadgetError> {
+        match lcs {
+            [x, y, z, t] => Ok(PointLCs::from_coords([x, y, z, t], LC::clone)),
+            _ => Err(GadgetError::InvalidLCsLength {
+                expected: 4,
+                found: lcs.len(),
+            }),
+        }
+    }
+
+    /// Builds the point from its committed coordinates, in `X, Y, Z, T` order.
+    /// Fails unless there are exactly four of them.
+    pub fn from_vars(vars: &[Variable]) -> Result<PointLCs, GadgetError> {
+        let lcs: Vec<LC> = vars.iter().map(|v| LC::from(*v)).collect();
+        PointLCs::from_lcs(&lcs)
+    }
+
+    /// Returns the LCs in `X, Y, Z, T` order.
+    pub fn to_vec(&self) -> Vec<LC> {
+        vec![
+            self.X.clone(),
+            self.Y.clone(),
+            self.Z.clone(),
+            self.T.clone(),
+        ]
+    }
+}
+
+/// Builds constant LCs from the coordinates of every point.
+pub fn n_point_coords_to_LC<P: PointCoords>(points: &[P]) -> Vec<PointLCs> {
+    points
+        .iter()
+        .map(|p| PointLCs::from_coords(p.coords(), LC::from))
+        .collect()
+}
+
+/// Groups already committed variables (four per point, in `X, Y, Z, T` order)
+/// into the LCs of the points they hold.
+/// Fails if the number of variables is not a multiple of four.
+pub fn vars_to_point_lcs(vars: &[Variable]) -> Result<Vec<PointLCs>, GadgetError> {
+    if vars.len() % 4 != 0 {
+        return Err(GadgetError::InvalidLCsLength {
+            expected: 4 * (vars.len() / 4 + 1),
+            found: vars.len(),
+        });
+    }
+    vars.chunks(4).map(PointLCs::from_vars).collect()
+}
+
+/// Commits the coordinates of every point as the prover, returning the LCs of the
+/// committed coordinates and the commitments (four per point, in order).
+#[cfg(feature = "prover")]
+pub fn prover_commit_points<P: PointCoords>(
+    prover: &mut Prover,
+    points: &[P],
+) -> (Vec<PointLCs>, Vec<CompressedRistretto>) {
+    let mut commitments = Vec::with_capacity(points.len() * 4);
+    let lcs = points
+        .iter()
+        .map(|p| {
+            PointLCs::from_coords(p.coords(), |c| {
+                let (comm, var) = prover.commit(c, Scalar::random(&mut rand::thread_rng()));
+                commitments.push(comm);
+                LC::from(var)
+            })
+        })
+        .collect();
+    (lcs, commitments)
+}
+
+/// Commits the coordinates of `n` points as the verifier.
+/// Fails if `commitments` does not contain exactly `4 * n` elements.
+pub fn verifier_commit_points(
+    verifier: &mut Verifier,
+    commitments: &[CompressedRistretto],
+    n: usize,
+) -> Result<Vec<PointLCs>, GadgetError> {
+    if commitments.len() != 4 * n {
+        return Err(GadgetError::InvalidCommitmentsLength {
+            expected: 4 * n,
+            found: commitments.len(),
+        });
+    }
+    let vars: Vec<Variable> = commitments.iter().map(|V| verifier.commit(*V)).collect();
+    vars_to_point_lcs(&vars)
+}
+
+/// Little-endian bits of a Sonny scalar, truncated to `n` bits.
+pub fn sonny_scalar_bits(s: &SonnyScalar, n: usize) -> Vec<u8> {
+    s.into_bits()[..n].to_vec()
+}
+
+/// Packs little-endian bits into (at most 32) little-endian bytes.
+pub fn bits_to_bytes(bits: &[u8]) -> [u8; 32] {
+    let mut bytes = [0u8; 32];
+    for (i, bit) in bits.iter().enumerate() {
+        bytes[i / 8] |= bit << (i % 8);
+    }
+    bytes
+}
+
+/// Maps a Sonny scalar into the scalar field the R1CS works with. Sonny
+/// scalars are smaller than `l`, so the value is preserved.
+pub fn sonny_scalar_to_scalar(s: &SonnyScalar) -> Scalar {
+    Scalar::from_bytes_mod_order(bits_to_bytes(&s.into_bits()))
+}
+
+mod test {
+    use super::*;
+    use crate::recording::RecordingCS;
+
+    #[test]
+    fn point_lcs_lengths() {
+        let mut cs = RecordingCS::new(b"PointLCs");
+        let vars: Vec<Variable> = (0..9u64)
+            .map(|i| cs.commit(Some(Scalar::from(i))))
+            .collect();
+
+        assert_eq!(vars_to_point_lcs(&vars[..8]).unwrap().len(), 2);
+        assert_eq!(
+            vars_to_point_lcs(&vars).err(),
+            Some(GadgetError::InvalidLCsLength {
+                expected: 12,
+                found: 9
+            })
+        );
+        assert!(PointLCs::from_vars(&vars[..4]).is_ok());
+        assert_eq!(
+            PointLCs::from_vars(&vars[..3]).err(),
+            Some(GadgetError::InvalidLCsLength {
+                expected: 4,
+                found: 3
+            })
+        );
+    }
+}
@@ -0,0 +1,85 @@
+//! Instrumentation of the proving pipeline.
+//!
+//! With the `tracing` feature enabled, every phase (commitment, synthesis, proving
+//! and verification) runs inside a span named after it, carrying the gadget name,
+//! the elapsed time and, for synthesis, the multipliers and constraints it added.
+//! Without the feature these helpers just run the closures.
+//!
+//! Synthesis also runs inside an `annotate!` scope named after the gadget, so
+//! the constraints it adds are labelled with it in debug builds, and its span
+//! carries the `path` of the enclosing scopes.
+//!
+//! Besides the pipelines of the sk_knowledge proofs and of `compose::Composer`,
+//! the point, scalar, hash and boolean gadgets synthesize inside their own
+//! span, nested in the span of the gadget calling them. Helpers taking a
+//! single multiplier (`binary_constrain_gadget`, `mul_add_gadget`) or none
+//! (`neg`) are accounted to their caller.
+use bulletproofs::r1cs::ConstraintSystem;
+
+/// Runs a phase of the pipeline that does not synthesize constraints.
+#[cfg(feature = "tracing")]
+pub fn phase<R, F: FnOnce() -> R>(phase: &'static str, gadget: &'static str, f: F) -> R {
+    let span = tracing::info_span!(
+        "phase",
+        phase,
+        gadget,
+        elapsed_us = tracing::field::Empty
+    );
+    let _enter = span.enter();
+    let start = std::time::Instant::now();
+    let res = f();
+    span.record("elapsed_us", &(start.elapsed().as_micros() as u64));
+    res
+}
+
+/// Runs a phase of the pipeline that does not synthesize constraints.
+#[cfg(not(feature = "tracing"))]
+pub fn phase<R, F: FnOnce() -> R>(_: &'static str, _: &'static str, f: F) -> R {
+    f()
+}
+
+/// Synthesizes a gadget into the CS.
+#[cfg(feature = "tracing")]
+pub fn synthesis<R, F>(gadget: &'static str, cs: &mut dyn ConstraintSystem, f: F) -> R
+where
+    F: FnOnce(&mut dyn ConstraintSystem) -> R,
+{
+    let span = tracing::info_span!(
+        "phase",
+        phase = "synthesis",
+        gadget,
+        path = tracing::field::Empty,
+        multipliers = tracing::field::Empty,
+        constraints = tracing::field::Empty,
+        elapsed_us = tracing::field::Empty
+    );
+    let _enter = span.enter();
+    let _scope = annotate!("{}", gadget);
+    if let Some(path) = crate::annotate::current() {
+        span.record("path", &path.as_str());
+    }
+    let before = cs.metrics();
+    let start = std::time::Instant::now();
+    let res = f(cs);
+    let after = cs.metrics();
+    span.record("elapsed_us", &(start.elapsed().as_micros() as u64));
+    span.record(
+        "multipliers",
+        &((after.multipliers - before.multipliers) as u64),
+    );
+    span.record(
+        "constraints",
+        &((after.constraints - before.constraints) as u64),
+    );
+    res
+}
+
+/// Synthesizes a gadget into the CS.
+#[cfg(not(feature = "tracing"))]
+pub fn synthesis<R, F>(gadget: &'static str, cs: &mut dyn ConstraintSystem, f: F) -> R
+where
+    F: FnOnce(&mut dyn ConstraintSystem) -> R,
+{
+    let _scope = annotate!("{}", gadget);
+    f(cs)
+}
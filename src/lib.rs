@@ -1,3 +1,39 @@
 extern crate bulletproofs;
+extern crate curve25519_dalek;
 extern crate zerocaf;
+#[macro_use]
+mod backend;
+#[macro_use]
+pub mod annotate;
+#[cfg(feature = "arkworks")]
+pub mod ark;
+pub mod batch;
+#[cfg(feature = "prover")]
+pub mod bench;
+pub mod circuit_id;
+pub mod circuits;
+pub mod compose;
+pub mod cost;
+pub mod errors;
+pub mod export;
 pub mod gadgets;
+pub mod gens;
+pub mod helpers;
+pub mod instrument;
+pub mod logging;
+pub mod precomp;
+pub mod recording;
+pub mod registry;
+pub mod session;
+#[cfg(feature = "soundness_tests")]
+pub mod soundness_tests;
+pub mod statement;
+pub mod stream;
+#[cfg(feature = "testing")]
+pub mod testing;
+#[cfg(feature = "test_utils")]
+pub mod test_utils;
+pub mod transcript;
+pub mod vectors;
+#[cfg(feature = "prover")]
+pub mod witness;
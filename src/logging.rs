@@ -0,0 +1,230 @@
+//! Logs of the calls a circuit makes to its `ConstraintSystem`, to debug
+//! circuits whose proofs do not verify although the witness satisfies them.
+//!
+//! Such proofs usually come from the prover and the verifier synthesizing
+//! different circuits: a gadget branching on an assignment, or the transcript
+//! receiving different data on both sides. `LoggingCS` wraps the `Prover` or
+//! the `Verifier` and logs every call (without the assignments, which only the
+//! prover has) along with a fingerprint of the transcript at that point.
+//! `first_divergence` compares the logs of both sides and pinpoints the first
+//! call where they differ.
+use crate::recording::{format_lc, var_name};
+use bulletproofs::r1cs::{ConstraintSystem, LinearCombination as LC, Metrics, R1CSError, Variable};
+use curve25519_dalek::scalar::Scalar;
+use merlin::Transcript;
+use std::fmt;
+
+/// A call to the `ConstraintSystem`, with its LCs formatted by `format_lc`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum CsCall {
+    /// A high-level variable committed before the synthesis.
+    Commit(String),
+    Multiply {
+        left: String,
+        right: String,
+    },
+    Allocate,
+    AllocateMultiplier,
+    Constrain(String),
+    /// Access to the transcript, e.g. to draw a challenge.
+    Transcript,
+}
+
+impl fmt::Display for CsCall {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            CsCall::Commit(var) => write!(f, "commit {}", var),
+            CsCall::Multiply { left, right } => write!(f, "multiply ({}) * ({})", left, right),
+            CsCall::Allocate => write!(f, "allocate"),
+            CsCall::AllocateMultiplier => write!(f, "allocate_multiplier"),
+            CsCall::Constrain(lc) => write!(f, "constrain {} = 0", lc),
+            CsCall::Transcript => write!(f, "transcript"),
+        }
+    }
+}
+
+/// A logged call along with the fingerprint of the transcript before it.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct LoggedCall {
+    pub call: CsCall,
+    pub transcript: [u8; 8],
+}
+
+/// Wraps a `ConstraintSystem` and logs every call made to it.
+pub struct LoggingCS<'a> {
+    inner: &'a mut dyn ConstraintSystem,
+    log: Vec<LoggedCall>,
+}
+
+impl<'a> LoggingCS<'a> {
+    /// Wraps `inner`, whose high-level variables `committed` are logged first.
+    pub fn new(inner: &'a mut dyn ConstraintSystem, committed: &[Variable]) -> LoggingCS<'a> {
+        let mut cs = LoggingCS {
+            inner,
+            log: Vec::new(),
+        };
+        for var in committed {
+            cs.push(CsCall::Commit(var_name(var)));
+        }
+        cs
+    }
+
+    /// Calls logged so far.
+    pub fn log(&self) -> &[LoggedCall] {
+        &self.log
+    }
+
+    pub fn into_log(self) -> Vec<LoggedCall> {
+        self.log
+    }
+
+    fn push(&mut self, call: CsCall) {
+        // Drawn from a copy, so the transcript of the proof is left untouched.
+        let mut transcript = [0u8; 8];
+        self.inner
+            .transcript()
+            .clone()
+            .challenge_bytes(b"logging-fingerprint", &mut transcript);
+        self.log.push(LoggedCall { call, transcript });
+    }
+}
+
+impl<'a> ConstraintSystem for LoggingCS<'a> {
+    fn transcript(&mut self) -> &mut Transcript {
+        self.push(CsCall::Transcript);
+        self.inner.transcript()
+    }
+
+    fn multiply(&mut self, left: LC, right: LC) -> (Variable, Variable, Variable) {
+        self.push(CsCall::Multiply {
+            left: format_lc(&left),
+            right: format_lc(&right),
+        });
+        self.inner.multiply(left, right)
+    }
+
+    fn allocate(&mut self, assignment: Option<Scalar>) -> Result<Variable, R1CSError> {
+        self.push(CsCall::Allocate);
+        self.inner.allocate(assignment)
+    }
+
+    fn allocate_multiplier(
+        &mut self,
+        input_assignments: Option<(Scalar, Scalar)>,
+    ) -> Result<(Variable, Variable, Variable), R1CSError> {
+        self.push(CsCall::AllocateMultiplier);
+        self.inner.allocate_multiplier(input_assignments)
+    }
+
+    fn metrics(&self) -> Metrics {
+        self.inner.metrics()
+    }
+
+    fn constrain(&mut self, lc: LC) {
+        self.push(CsCall::Constrain(format_lc(&lc)));
+        self.inner.constrain(lc);
+    }
+}
+
+/// First call where a prover log and a verifier log differ.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Divergence {
+    pub index: usize,
+    /// The calls of both sides at `index`, `None` if the log ended before.
+    pub prover: Option<LoggedCall>,
+    pub verifier: Option<LoggedCall>,
+}
+
+impl fmt::Display for Divergence {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let side = |call: &Option<LoggedCall>| match call {
+            None => "<end of log>".to_string(),
+            Some(c) => format!("{} (transcript {})", c.call, hex(&c.transcript)),
+        };
+        writeln!(f, "prover and verifier diverge at call {}:", self.index)?;
+        writeln!(f, "  prover:   {}", side(&self.prover))?;
+        write!(f, "  verifier: {}", side(&self.verifier))
+    }
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+/// Returns the first call where the logs of the same circuit differ, either
+/// in the call itself or in the state of the transcript before it.
+pub fn first_divergence(prover: &[LoggedCall], verifier: &[LoggedCall]) -> Option<Divergence> {
+    (0..prover.len().max(verifier.len()))
+        .find(|i| prover.get(*i) != verifier.get(*i))
+        .map(|index| Divergence {
+            index,
+            prover: prover.get(index).cloned(),
+            verifier: verifier.get(index).cloned(),
+        })
+}
+
+mod test {
+    use super::*;
+    use crate::session;
+    use bulletproofs::r1cs::{Prover, Verifier};
+    use bulletproofs::PedersenGens;
+
+    /// Squares the committed value, with an extra constraint on the prover side
+    /// when `buggy` (a gadget branching on its assignment).
+    fn circuit(cs: &mut dyn ConstraintSystem, x: Variable, x_assign: Option<Scalar>, buggy: bool) {
+        let (_, _, sq) = cs.multiply(x.into(), x.into());
+        if buggy && x_assign.is_some() {
+            cs.constrain(LC::from(x) - x_assign.unwrap());
+        }
+        let mut challenge = [0u8; 32];
+        cs.transcript().challenge_bytes(b"c", &mut challenge);
+        cs.constrain(LC::from(sq) - Scalar::from(9u64));
+    }
+
+    fn logs(buggy: bool, label: &'static [u8]) -> (Vec<LoggedCall>, Vec<LoggedCall>) {
+        let pc_gens = PedersenGens::default();
+        let x = Scalar::from(3u64);
+
+        let mut transcript = session::transcript(b"Logging");
+        let mut prover = Prover::new(&pc_gens, &mut transcript);
+        let (commitment, var) = prover.commit(x, Scalar::one());
+        let mut cs = LoggingCS::new(&mut prover, &[var]);
+        circuit(&mut cs, var, Some(x), buggy);
+        let prover_log = cs.into_log();
+
+        let mut transcript = session::transcript(label);
+        let mut verifier = Verifier::new(&mut transcript);
+        let var = verifier.commit(commitment);
+        let mut cs = LoggingCS::new(&mut verifier, &[var]);
+        circuit(&mut cs, var, None, buggy);
+        (prover_log, cs.into_log())
+    }
+
+    #[test]
+    fn same_circuit() {
+        let (prover, verifier) = logs(false, b"Logging");
+        assert_eq!(prover.len(), 4);
+        assert_eq!(first_divergence(&prover, &verifier), None);
+    }
+
+    #[test]
+    fn divergent_circuit() {
+        let (prover, verifier) = logs(true, b"Logging");
+        let divergence = first_divergence(&prover, &verifier).unwrap();
+        assert_eq!(divergence.index, 2);
+        assert_eq!(
+            divergence.prover.unwrap().call,
+            CsCall::Constrain("V0 - 3*1".to_string())
+        );
+        assert_eq!(divergence.verifier.unwrap().call, CsCall::Transcript);
+    }
+
+    #[test]
+    fn divergent_transcript() {
+        // Same calls, but the verifier transcript starts with another label.
+        let (prover, verifier) = logs(false, b"Other");
+        let divergence = first_divergence(&prover, &verifier).unwrap();
+        assert_eq!(divergence.index, 0);
+        assert_eq!(divergence.prover.unwrap().call, CsCall::Commit("V0".into()));
+    }
+}
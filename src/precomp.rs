@@ -0,0 +1,152 @@
+//! Native precomputations used by the prover to generate witnesses.
+use crate::helpers::affine_coords;
+use std::collections::HashMap;
+use std::sync::{Arc, RwLock};
+use zerocaf::edwards::EdwardsPoint as SonnyEdwardsPoint;
+use zerocaf::traits::ops::Double;
+use zerocaf::traits::Identity;
+
+#[cfg(feature = "parallel")]
+use rayon::prelude::*;
+
+/// Index of the entry of a row selected by a window of bits (least significant bit first).
+pub(crate) fn window_index(chunk: &[u8]) -> usize {
+    chunk
+        .iter()
+        .rev()
+        .fold(0usize, |index, bit| (index << 1) | (*bit as usize))
+}
+
+/// Window table of a fixed base point `P`: `rows[i][j] = j * 2^(window * i) * P`
+/// for `j` in `0..2^window`.
+///
+/// Multiplying `P` by a scalar then takes one addition per window instead of a
+/// full double-and-add ladder.
+#[derive(Clone, Debug)]
+pub struct FixedBaseTable {
+    pub window: usize,
+    pub rows: Vec<Vec<SonnyEdwardsPoint>>,
+}
+
+impl FixedBaseTable {
+    /// Precomputes the table of `base` for scalars of up to `bits` bits.
+    /// With the `parallel` feature the rows are computed concurrently.
+    pub fn new(base: &SonnyEdwardsPoint, window: usize, bits: usize) -> FixedBaseTable {
+        assert!(window > 0 && window < 16);
+        let n_rows = (bits + window - 1) / window;
+
+        // Row bases `2^(window * i) * P` must be computed sequentially.
+        let mut row_bases = Vec::with_capacity(n_rows);
+        let mut row_base = *base;
+        for _ in 0..n_rows {
+            row_bases.push(row_base);
+            for _ in 0..window {
+                row_base = row_base.double();
+            }
+        }
+
+        let row = |row_base: &SonnyEdwardsPoint| {
+            let mut row = Vec::with_capacity(1 << window);
+            let mut acc = SonnyEdwardsPoint::identity();
+            for _ in 0..(1 << window) {
+                row.push(acc);
+                acc = acc + *row_base;
+            }
+            row
+        };
+
+        #[cfg(feature = "parallel")]
+        let rows = row_bases.par_iter().map(row).collect();
+        #[cfg(not(feature = "parallel"))]
+        let rows = row_bases.iter().map(row).collect();
+
+        FixedBaseTable { window, rows }
+    }
+
+    /// Multiplies the base by the scalar given as bits (least significant bit first).
+    pub fn mul(&self, bits: &[u8]) -> SonnyEdwardsPoint {
+        assert!(bits.len() <= self.rows.len() * self.window);
+        bits.chunks(self.window)
+            .zip(self.rows.iter())
+            .fold(SonnyEdwardsPoint::identity(), |acc, (chunk, row)| {
+                acc + row[window_index(chunk)]
+            })
+    }
+}
+
+/// Identifies a table: the affine coordinates of the base (so every representation
+/// of the same point shares the table), the window and the number of bits.
+type TableKey = ([u8; 32], [u8; 32], usize, usize);
+
+/// Cache of `FixedBaseTable`s for frequently used bases (generators, issuer keys...),
+/// so a long-running prover computes every table once instead of once per proof.
+///
+/// Cloning a `PrecompTables` is cheap and the clones share the same cache, so it
+/// can be handed to every worker thread.
+#[derive(Clone, Debug, Default)]
+pub struct PrecompTables {
+    tables: Arc<RwLock<HashMap<TableKey, Arc<FixedBaseTable>>>>,
+}
+
+impl PrecompTables {
+    pub fn new() -> PrecompTables {
+        PrecompTables::default()
+    }
+
+    /// Returns the table of `base`, computing it on the first request.
+    pub fn get(
+        &self,
+        base: &SonnyEdwardsPoint,
+        window: usize,
+        bits: usize,
+    ) -> Arc<FixedBaseTable> {
+        let (x, y) = affine_coords(base);
+        let key = (x.to_bytes(), y.to_bytes(), window, bits);
+        if let Some(table) = self.tables.read().unwrap().get(&key) {
+            return table.clone();
+        }
+        // Computed without holding the lock; if another thread raced us the
+        // table it inserted first is kept.
+        let table = Arc::new(FixedBaseTable::new(base, window, bits));
+        self.tables
+            .write()
+            .unwrap()
+            .entry(key)
+            .or_insert(table)
+            .clone()
+    }
+
+    /// Number of cached tables.
+    pub fn len(&self) -> usize {
+        self.tables.read().unwrap().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Drops every cached table.
+    pub fn clear(&self) {
+        self.tables.write().unwrap().clear();
+    }
+}
+
+mod test {
+    use super::*;
+
+    #[test]
+    fn tables_are_cached_and_shared() {
+        let cache = PrecompTables::new();
+        let base = SonnyEdwardsPoint::new_random_point(&mut rand::thread_rng());
+
+        let table = cache.get(&base, 3, 64);
+        let shared = cache.clone();
+        assert!(Arc::ptr_eq(&table, &shared.get(&base, 3, 64)));
+        assert_eq!(cache.len(), 1);
+
+        shared.get(&base, 4, 64);
+        assert_eq!(cache.len(), 2);
+        cache.clear();
+        assert!(shared.is_empty());
+    }
+}
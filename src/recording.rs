@@ -0,0 +1,264 @@
+//! A `ConstraintSystem` that does not prove anything but records the
+//! constraints gadgets add to it, so circuits can be inspected or exported.
+//!
+//! When the assignments are provided it also evaluates the circuit, which lets the
+//! prover check a witness against the constraints without generating a proof.
+use bulletproofs::r1cs::{
+    ConstraintSystem, LinearCombination as LC, Metrics, R1CSError, Variable,
+};
+use curve25519_dalek::scalar::Scalar;
+use merlin::Transcript;
+
+/// Returns the `(Variable, coefficient)` terms an LC is made of.
+pub(crate) fn lc_terms(lc: &LC) -> Vec<(Variable, Scalar)> {
+    lc.clone().get_terms()
+}
+
+/// Short name of a variable: `L<i>`, `R<i>`, `O<i>` for the multiplier wires,
+/// `V<i>` for the committed ones and `1` for the constant.
+pub(crate) fn var_name(var: &Variable) -> String {
+    match var {
+        Variable::MultiplierLeft(i) => format!("L{}", i),
+        Variable::MultiplierRight(i) => format!("R{}", i),
+        Variable::MultiplierOutput(i) => format!("O{}", i),
+        Variable::Committed(i) => format!("V{}", i),
+        Variable::One() => "1".to_string(),
+    }
+}
+
+fn small_value(s: &Scalar) -> Option<u64> {
+    let bytes = s.as_bytes();
+    if bytes[8..].iter().all(|b| *b == 0) {
+        let mut low = [0u8; 8];
+        low.copy_from_slice(&bytes[..8]);
+        Some(u64::from_le_bytes(low))
+    } else {
+        None
+    }
+}
+
+/// Formats an LC symbolically, e.g. `2*V0 - L3 + 5*1`. Coefficients that are
+/// not small (in absolute value) are printed as little-endian hex.
+pub fn format_lc(lc: &LC) -> String {
+    let terms = lc_terms(lc);
+    if terms.is_empty() {
+        return "0".to_string();
+    }
+    let mut res = String::new();
+    for (i, (var, coeff)) in terms.iter().enumerate() {
+        let (sign, magnitude) = match (small_value(coeff), small_value(&-coeff)) {
+            (Some(v), _) => ("+", v.to_string()),
+            (None, Some(v)) => ("-", v.to_string()),
+            (None, None) => (
+                "+",
+                coeff
+                    .as_bytes()
+                    .iter()
+                    .map(|b| format!("{:02x}", b))
+                    .collect(),
+            ),
+        };
+        match (i, sign) {
+            (0, "+") => {}
+            (0, _) => res.push('-'),
+            (_, _) => res.push_str(&format!(" {} ", sign)),
+        }
+        if magnitude != "1" {
+            res.push_str(&format!("{}*", magnitude));
+        }
+        res.push_str(&var_name(var));
+    }
+    res
+}
+
+/// Records every multiplier and constraint a circuit adds, allocating the
+/// variables the same way `Prover` and `Verifier` do.
+pub struct RecordingCS {
+    transcript: Transcript,
+    committed: Vec<Option<Scalar>>,
+    multipliers: Vec<(Option<Scalar>, Option<Scalar>, Option<Scalar>)>,
+    pending_multiplier: Option<usize>,
+    constraints: Vec<LC>,
+    /// Path of the `annotate` scopes open when each constraint was added.
+    #[cfg(debug_assertions)]
+    labels: Vec<Option<String>>,
+}
+
+impl RecordingCS {
+    pub fn new(label: &'static [u8]) -> RecordingCS {
+        RecordingCS {
+            transcript: Transcript::new(label),
+            committed: Vec::new(),
+            multipliers: Vec::new(),
+            pending_multiplier: None,
+            constraints: Vec::new(),
+            #[cfg(debug_assertions)]
+            labels: Vec::new(),
+        }
+    }
+
+    /// Allocates a high-level (committed) variable, the counterpart of
+    /// `Prover::commit` and `Verifier::commit`.
+    pub fn commit(&mut self, assignment: Option<Scalar>) -> Variable {
+        self.committed.push(assignment);
+        Variable::Committed(self.committed.len() - 1)
+    }
+
+    /// Number of high-level variables committed so far.
+    pub fn committed(&self) -> usize {
+        self.committed.len()
+    }
+
+    /// Number of multiplication gates allocated so far.
+    pub fn multipliers(&self) -> usize {
+        self.multipliers.len()
+    }
+
+    /// Linear constraints (each of them constrained to be zero) recorded so far,
+    /// including the ones `multiply` adds to bind the gate inputs.
+    pub fn constraints(&self) -> &[LC] {
+        &self.constraints
+    }
+
+    /// Assignments of the committed variables.
+    pub fn committed_assignments(&self) -> &[Option<Scalar>] {
+        &self.committed
+    }
+
+    /// Assignments of the `(left, right, output)` wires of every multiplier.
+    pub fn multiplier_assignments(&self) -> &[(Option<Scalar>, Option<Scalar>, Option<Scalar>)] {
+        &self.multipliers
+    }
+
+    /// Evaluates an LC with the recorded assignments, returning `None` if any
+    /// of the variables it uses has no assignment.
+    pub fn eval(&self, lc: &LC) -> Option<Scalar> {
+        lc_terms(lc)
+            .iter()
+            .map(|(var, coeff)| {
+                let value = match var {
+                    Variable::Committed(i) => self.committed[*i],
+                    Variable::MultiplierLeft(i) => self.multipliers[*i].0,
+                    Variable::MultiplierRight(i) => self.multipliers[*i].1,
+                    Variable::MultiplierOutput(i) => self.multipliers[*i].2,
+                    Variable::One() => Some(Scalar::one()),
+                };
+                value.map(|v| v * coeff)
+            })
+            .sum()
+    }
+
+    /// Returns the index of the first constraint that the assignments do not
+    /// satisfy (or that cannot be evaluated), if any.
+    pub fn first_unsatisfied(&self) -> Option<usize> {
+        self.constraints
+            .iter()
+            .position(|lc| self.eval(lc) != Some(Scalar::zero()))
+    }
+
+    /// Label of the `i`-th constraint (see `annotate`), always `None` in
+    /// release builds.
+    #[cfg(debug_assertions)]
+    pub fn label(&self, i: usize) -> Option<&str> {
+        self.labels[i].as_ref().map(|l| l.as_str())
+    }
+
+    /// Label of the `i`-th constraint (see `annotate`), always `None` in
+    /// release builds.
+    #[cfg(not(debug_assertions))]
+    pub fn label(&self, _: usize) -> Option<&str> {
+        None
+    }
+
+    /// Describes the `i`-th constraint for error messages, with its label when
+    /// there is one, e.g. `constraint 12 (scalar_mul/bit 17/doubling): v0 - v1`.
+    pub fn describe(&self, i: usize) -> String {
+        match self.label(i) {
+            Some(label) => format!(
+                "constraint {} ({}): {}",
+                i,
+                label,
+                format_lc(&self.constraints[i])
+            ),
+            None => format!("constraint {}: {}", i, format_lc(&self.constraints[i])),
+        }
+    }
+
+    fn push_multiplier(&mut self, left: Option<Scalar>, right: Option<Scalar>) -> usize {
+        let out = match (left, right) {
+            (Some(l), Some(r)) => Some(l * r),
+            _ => None,
+        };
+        self.multipliers.push((left, right, out));
+        self.multipliers.len() - 1
+    }
+}
+
+impl ConstraintSystem for RecordingCS {
+    fn transcript(&mut self) -> &mut Transcript {
+        &mut self.transcript
+    }
+
+    fn multiply(&mut self, left: LC, right: LC) -> (Variable, Variable, Variable) {
+        let (l, r) = (self.eval(&left), self.eval(&right));
+        let i = self.push_multiplier(l, r);
+
+        let l_var = Variable::MultiplierLeft(i);
+        let r_var = Variable::MultiplierRight(i);
+        let o_var = Variable::MultiplierOutput(i);
+        self.constrain(left - l_var);
+        self.constrain(right - r_var);
+
+        (l_var, r_var, o_var)
+    }
+
+    fn allocate(&mut self, assignment: Option<Scalar>) -> Result<Variable, R1CSError> {
+        match self.pending_multiplier {
+            None => {
+                let i = self.push_multiplier(assignment, None);
+                self.pending_multiplier = Some(i);
+                Ok(Variable::MultiplierLeft(i))
+            }
+            Some(i) => {
+                self.pending_multiplier = None;
+                let left = self.multipliers[i].0;
+                self.multipliers[i] = (
+                    left,
+                    assignment,
+                    left.and_then(|l| assignment.map(|r| l * r)),
+                );
+                Ok(Variable::MultiplierRight(i))
+            }
+        }
+    }
+
+    fn allocate_multiplier(
+        &mut self,
+        input_assignments: Option<(Scalar, Scalar)>,
+    ) -> Result<(Variable, Variable, Variable), R1CSError> {
+        let i = self.push_multiplier(
+            input_assignments.map(|(l, _)| l),
+            input_assignments.map(|(_, r)| r),
+        );
+        Ok((
+            Variable::MultiplierLeft(i),
+            Variable::MultiplierRight(i),
+            Variable::MultiplierOutput(i),
+        ))
+    }
+
+    fn metrics(&self) -> Metrics {
+        Metrics {
+            multipliers: self.multipliers.len(),
+            constraints: self.constraints.len(),
+            phase_one_constraints: self.constraints.len(),
+            phase_two_constraints: 0,
+        }
+    }
+
+    fn constrain(&mut self, lc: LC) {
+        self.constraints.push(lc);
+        #[cfg(debug_assertions)]
+        self.labels.push(crate::annotate::current());
+    }
+}
@@ -0,0 +1,244 @@
+//! Registry of circuits by stable name and version.
+//!
+//! Long-lived verifiers (on-chain ones, say) must keep verifying proofs
+//! generated for older versions of a circuit. Applications register every
+//! version they support under a stable name, persist the name and version a
+//! proof was made for, or simply the `CircuitId` of its `ProofBundle`, and get
+//! the matching circuit back to verify it. Circuits follow the `ProofBundle`
+//! convention, and their name is the label of their transcripts.
+//!
+//! The id of a circuit is computed when it is registered, so
+//! `CircuitRegistry::verify` can dispatch a bundle to the version it was
+//! proven for.
+use crate::circuit_id::{circuit_id, CircuitId, ProofBundle};
+use crate::errors::GadgetError;
+use bulletproofs::r1cs::{ConstraintSystem, R1CSError, Variable};
+use bulletproofs::{BulletproofGens, PedersenGens};
+use curve25519_dalek::scalar::Scalar;
+use std::collections::BTreeMap;
+
+/// A circuit over committed variables and their witness (`None` on the
+/// verifier side), as taken by `ProofBundle::prove`.
+pub type CircuitFn = Box<dyn Fn(&mut dyn ConstraintSystem, Vec<Variable>, Option<&[Scalar]>)>;
+
+/// A version of a circuit, along with its id.
+pub struct RegisteredCircuit {
+    name: &'static str,
+    version: u64,
+    committed: usize,
+    id: CircuitId,
+    circuit: CircuitFn,
+}
+
+impl RegisteredCircuit {
+    pub fn name(&self) -> &'static str {
+        self.name
+    }
+
+    pub fn version(&self) -> u64 {
+        self.version
+    }
+
+    /// Number of committed variables the circuit takes.
+    pub fn committed(&self) -> usize {
+        self.committed
+    }
+
+    pub fn id(&self) -> &CircuitId {
+        &self.id
+    }
+
+    /// Proves this version of the circuit over `witness`.
+    #[cfg(feature = "prover")]
+    pub fn prove(
+        &self,
+        pc_gens: &PedersenGens,
+        bp_gens: &BulletproofGens,
+        witness: &[Scalar],
+    ) -> Result<ProofBundle, R1CSError> {
+        self.check_committed(witness.len())?;
+        ProofBundle::prove(
+            pc_gens,
+            bp_gens,
+            self.name.as_bytes(),
+            witness,
+            |cs, vars, w| (self.circuit)(cs, vars, w),
+        )
+    }
+
+    /// Verifies `bundle` against this version of the circuit.
+    pub fn verify(
+        &self,
+        pc_gens: &PedersenGens,
+        bp_gens: &BulletproofGens,
+        bundle: &ProofBundle,
+    ) -> Result<(), R1CSError> {
+        self.check_committed(bundle.commitments.len())?;
+        bundle.verify(
+            pc_gens,
+            bp_gens,
+            self.name.as_bytes(),
+            &self.id,
+            |cs, vars, w| (self.circuit)(cs, vars, w),
+        )
+    }
+
+    fn check_committed(&self, found: usize) -> Result<(), GadgetError> {
+        if found != self.committed {
+            return Err(GadgetError::InvalidCommitmentsLength {
+                expected: self.committed,
+                found,
+            });
+        }
+        Ok(())
+    }
+}
+
+/// Circuits by name and version.
+#[derive(Default)]
+pub struct CircuitRegistry {
+    circuits: BTreeMap<(&'static str, u64), RegisteredCircuit>,
+}
+
+impl CircuitRegistry {
+    pub fn new() -> CircuitRegistry {
+        CircuitRegistry::default()
+    }
+
+    /// Registers `circuit` over `committed` variables as `version` of `name`,
+    /// and returns its id. Fails if the version is already registered, or if
+    /// another version has the same structure, since bundles could not tell
+    /// them apart.
+    pub fn register<F>(
+        &mut self,
+        name: &'static str,
+        version: u64,
+        committed: usize,
+        circuit: F,
+    ) -> Result<CircuitId, GadgetError>
+    where
+        F: Fn(&mut dyn ConstraintSystem, Vec<Variable>, Option<&[Scalar]>) + 'static,
+    {
+        let id = circuit_id(name.as_bytes(), committed, |cs, vars| {
+            circuit(cs, vars, None)
+        });
+        if self.circuits.contains_key(&(name, version)) || self.by_id(&id).is_some() {
+            return Err(GadgetError::DuplicateCircuit {
+                name: name.to_string(),
+                version,
+            });
+        }
+        self.circuits.insert(
+            (name, version),
+            RegisteredCircuit {
+                name,
+                version,
+                committed,
+                id,
+                circuit: Box::new(circuit),
+            },
+        );
+        Ok(id)
+    }
+
+    pub fn get(&self, name: &str, version: u64) -> Option<&RegisteredCircuit> {
+        self.circuits
+            .values()
+            .find(|c| c.name == name && c.version == version)
+    }
+
+    /// The highest registered version of `name`.
+    pub fn latest(&self, name: &str) -> Option<&RegisteredCircuit> {
+        self.circuits
+            .values()
+            .filter(|c| c.name == name)
+            .max_by_key(|c| c.version)
+    }
+
+    pub fn by_id(&self, id: &CircuitId) -> Option<&RegisteredCircuit> {
+        self.circuits.values().find(|c| c.id == *id)
+    }
+
+    /// Verifies `bundle` against the registered circuit it was proven for.
+    pub fn verify(
+        &self,
+        pc_gens: &PedersenGens,
+        bp_gens: &BulletproofGens,
+        bundle: &ProofBundle,
+    ) -> Result<(), R1CSError> {
+        let circuit = self
+            .by_id(&bundle.circuit_id)
+            .ok_or(GadgetError::UnknownCircuit)?;
+        circuit.verify(pc_gens, bp_gens, bundle)
+    }
+}
+
+#[cfg(feature = "prover")]
+mod test {
+    use super::*;
+    use bulletproofs::r1cs::LinearCombination as LC;
+
+    fn registry() -> CircuitRegistry {
+        let mut registry = CircuitRegistry::new();
+        // a * b = 6
+        registry
+            .register("product", 1, 2, |cs, vars, _| {
+                let (_, _, o) = cs.multiply(vars[0].into(), vars[1].into());
+                cs.constrain(LC::from(o) - Scalar::from(6u64));
+            })
+            .unwrap();
+        // a * b = 6 and a = 2
+        registry
+            .register("product", 2, 2, |cs, vars, _| {
+                let (_, _, o) = cs.multiply(vars[0].into(), vars[1].into());
+                cs.constrain(LC::from(o) - Scalar::from(6u64));
+                cs.constrain(LC::from(vars[0]) - Scalar::from(2u64));
+            })
+            .unwrap();
+        registry
+    }
+
+    #[test]
+    fn registry_dispatch() {
+        let pc_gens = PedersenGens::default();
+        let bp_gens = BulletproofGens::new(8, 1);
+        let registry = registry();
+        assert_eq!(registry.latest("product").unwrap().version(), 2);
+        assert!(registry.get("product", 3).is_none());
+
+        let witness = [Scalar::from(3u64), Scalar::from(2u64)];
+        let v1 = registry.get("product", 1).unwrap();
+        let bundle = v1.prove(&pc_gens, &bp_gens, &witness).unwrap();
+        assert_eq!(registry.by_id(&bundle.circuit_id).unwrap().version(), 1);
+        assert!(registry.verify(&pc_gens, &bp_gens, &bundle).is_ok());
+        // The bundle does not verify against the other version.
+        let v2 = registry.get("product", 2).unwrap();
+        assert!(v2.verify(&pc_gens, &bp_gens, &bundle).is_err());
+
+        let unknown = ProofBundle {
+            circuit_id: CircuitId([0; 32]),
+            ..bundle
+        };
+        assert!(registry.verify(&pc_gens, &bp_gens, &unknown).is_err());
+    }
+
+    #[test]
+    fn duplicates() {
+        let mut registry = registry();
+        let duplicate = GadgetError::DuplicateCircuit {
+            name: "product".to_string(),
+            version: 1,
+        };
+        assert_eq!(
+            registry.register("product", 1, 1, |_, _, _| {}),
+            Err(duplicate)
+        );
+        // Same structure as version 1.
+        assert!(registry
+            .register("product", 3, 2, |cs, vars, _| {
+                let (_, _, o) = cs.multiply(vars[0].into(), vars[1].into());
+                cs.constrain(LC::from(o) - Scalar::from(6u64));
+            })
+            .is_err());
+    }
+}
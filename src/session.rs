@@ -0,0 +1,857 @@
+//! Session layer shared by the provers and verifiers of this crate.
+//!
+//! Every transcript created here gets the crate domain tag and the version of the
+//! gadget set appended, so a proof generated with one version of the circuits
+//! cannot verify against a structurally different version by accident.
+//!
+//! High-level variables must all be committed before the synthesis adds any
+//! multiplier or constraint: commitments are bound into the transcript as they
+//! are made, so a commitment made after gadgets drew their challenges ends up
+//! on a prover transcript that the verifier does not reproduce, and the proof
+//! fails to verify without a hint at the cause. `checked_commit` catches it.
+//!
+//! Services synthesizing circuits whose size depends on user input (a tree
+//! depth, a number of outputs...) can bound it with a `SessionConfig`, whose
+//! strict mode also rejects circuits leaving an allocated variable
+//! unconstrained.
+//!
+//! Verifiers receiving commitments from the network can reject the ones that
+//! are not valid Ristretto points with `check_commitments`, before any
+//! transcript or synthesis work.
+//!
+//! A value used by several gadgets (an amount both range-checked and hashed,
+//! say) is committed once through a `ProverSession` / `VerifierSession`:
+//! `shared_scalar` commits it under a label on its first call and hands out
+//! the same variable afterwards, so every gadget talks about the same value
+//! and the proof carries a single commitment for it.
+//!
+//! Gadgets multiplying by the same constants over and over (the coordinates of
+//! a constant point added several times, say) can be synthesized on a
+//! `ConstantPool`, which reuses the multiplier of a product by a constant it
+//! already holds.
+use crate::errors::GadgetError;
+use crate::recording::var_name;
+use crate::transcript::TranscriptBackend;
+#[cfg(feature = "prover")]
+use bulletproofs::r1cs::Prover;
+use bulletproofs::r1cs::{
+    ConstraintSystem, LinearCombination as LC, Metrics, R1CSError, Variable, Verifier,
+};
+use curve25519_dalek::{ristretto::CompressedRistretto, scalar::Scalar};
+use merlin::Transcript;
+use std::collections::{BTreeMap, HashMap, HashSet};
+
+/// Domain separator appended to every transcript.
+pub const DOMAIN_TAG: &[u8] = b"dusk-network/bulletproofs_gadgets";
+
+/// Version of the gadget set. It must be bumped whenever any gadget changes the
+/// circuit it synthesizes.
+pub const GADGETS_VERSION: u64 = 1;
+
+/// Which gadget-set versions a verifier accepts proofs for.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CompatibilityPolicy {
+    /// Only proofs generated with `GADGETS_VERSION`.
+    Exact,
+    /// Proofs generated with any version in `[min, max]`. Only useful when the
+    /// circuits being verified did not change between those versions.
+    Range { min: u64, max: u64 },
+}
+
+impl Default for CompatibilityPolicy {
+    fn default() -> Self {
+        CompatibilityPolicy::Exact
+    }
+}
+
+impl CompatibilityPolicy {
+    pub fn accepts(&self, version: u64) -> bool {
+        match *self {
+            CompatibilityPolicy::Exact => version == GADGETS_VERSION,
+            CompatibilityPolicy::Range { min, max } => min <= version && version <= max,
+        }
+    }
+}
+
+/// Creates a transcript bound to the current version of the gadget set.
+pub fn transcript(label: &'static [u8]) -> Transcript {
+    versioned_transcript(label, GADGETS_VERSION)
+}
+
+/// Creates a transcript bound to the given version of the gadget set.
+pub fn versioned_transcript(label: &'static [u8], version: u64) -> Transcript {
+    let mut transcript = Transcript::new(label);
+    bind_version(&mut transcript, version);
+    transcript
+}
+
+/// Appends the crate domain tag and the gadget-set `version` to `transcript`.
+pub fn bind_version(transcript: &mut dyn TranscriptBackend, version: u64) {
+    transcript.append_message(b"dom-sep", DOMAIN_TAG);
+    transcript.append_u64(b"gadgets-version", version);
+}
+
+/// Creates the verifier transcript for a proof generated with `version`, failing
+/// if the policy does not accept that version.
+pub fn verifier_transcript(
+    label: &'static [u8],
+    version: u64,
+    policy: CompatibilityPolicy,
+) -> Result<Transcript, GadgetError> {
+    if !policy.accepts(version) {
+        return Err(GadgetError::IncompatibleVersion { version });
+    }
+    Ok(versioned_transcript(label, version))
+}
+
+/// Checks that every commitment decompresses to a Ristretto point, failing
+/// with the index of the first one which does not. The backend would only
+/// reject them once the whole circuit is synthesized.
+pub fn check_commitments<'a, I>(commitments: I) -> Result<(), GadgetError>
+where
+    I: IntoIterator<Item = &'a CompressedRistretto>,
+{
+    match commitments
+        .into_iter()
+        .position(|V| V.decompress().is_none())
+    {
+        Some(index) => Err(GadgetError::MalformedCommitment { index }),
+        None => Ok(()),
+    }
+}
+
+/// Runs `commit` (e.g. `|prover| prover.commit(v, blinding)`, or the same on a
+/// `Verifier`) on `cs`, failing instead if `cs` already holds multipliers or
+/// constraints.
+pub fn checked_commit<C, R, F>(cs: &mut C, commit: F) -> Result<R, GadgetError>
+where
+    C: ConstraintSystem,
+    F: FnOnce(&mut C) -> R,
+{
+    let metrics = cs.metrics();
+    if metrics.multipliers > 0 || metrics.constraints > 0 {
+        return Err(GadgetError::CommitAfterConstraints {
+            multipliers: metrics.multipliers,
+            constraints: metrics.constraints,
+        });
+    }
+    Ok(commit(cs))
+}
+
+/// Limits of the circuits synthesized in a session.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct SessionConfig {
+    /// Maximum number of multipliers of the CS, `None` for no limit.
+    pub max_multipliers: Option<usize>,
+    /// Whether every variable allocated by the circuit must appear in a
+    /// constraint, see `synthesize`.
+    pub strict: bool,
+}
+
+impl SessionConfig {
+    /// Runs `circuit` on `cs`, failing with `CircuitTooLarge` if the CS ends
+    /// up with more than `max_multipliers` multipliers.
+    ///
+    /// The calls past the limit are not forwarded to `cs`: the circuit keeps
+    /// running on dummy variables, so an oversized circuit costs its
+    /// synthesis loop but neither the memory nor the proving time of its
+    /// constraints. `cs` is left with at most one multiplier over the limit
+    /// and must be discarded on error.
+    ///
+    /// In strict mode it also fails with `UnconstrainedVariable` if a variable
+    /// allocated by the circuit (an `allocate`d one or the output of a
+    /// `multiply`) appears in no constraint and in no multiplier input:
+    /// nothing but its own multiplication gate binds it, which is most likely
+    /// a forgotten output. The three wires of an `allocate_multiplier` are
+    /// bound together by their gate, and gadgets often need only one of them
+    /// (`nonzero_gadget` keeps the left one), so they are rejected only if
+    /// none of them is used. The committed variables are made outside of the
+    /// CS and not checked.
+    pub fn synthesize<R, F>(
+        &self,
+        cs: &mut dyn ConstraintSystem,
+        circuit: F,
+    ) -> Result<R, GadgetError>
+    where
+        F: FnOnce(&mut dyn ConstraintSystem) -> R,
+    {
+        if !self.strict {
+            return self.synthesize_limited(cs, circuit);
+        }
+        let mut strict = StrictCS {
+            inner: cs,
+            allocated: Vec::new(),
+            used: HashSet::new(),
+        };
+        let res = self.synthesize_limited(&mut strict, circuit)?;
+        strict.check()?;
+        Ok(res)
+    }
+
+    fn synthesize_limited<R, F>(
+        &self,
+        cs: &mut dyn ConstraintSystem,
+        circuit: F,
+    ) -> Result<R, GadgetError>
+    where
+        F: FnOnce(&mut dyn ConstraintSystem) -> R,
+    {
+        let max_multipliers = match self.max_multipliers {
+            None => return Ok(circuit(cs)),
+            Some(max) => max,
+        };
+        let mut limited = LimitedCS {
+            inner: cs,
+            max_multipliers,
+            exceeded: false,
+        };
+        limited.check();
+        let res = circuit(&mut limited);
+        if limited.exceeded {
+            return Err(GadgetError::CircuitTooLarge { max_multipliers });
+        }
+        Ok(res)
+    }
+}
+
+/// Forwards the calls to `inner`, recording the variables it allocates and
+/// the ones the constraints and multiplier inputs use. The variables are
+/// allocated in groups, one of which must be used.
+struct StrictCS<'a> {
+    inner: &'a mut dyn ConstraintSystem,
+    allocated: Vec<Vec<Variable>>,
+    used: HashSet<(u8, usize)>,
+}
+
+impl<'a> StrictCS<'a> {
+    fn mark_used(&mut self, lc: &LC) {
+        for (var, _) in lc.clone().get_terms() {
+            self.used.insert(var_key(&var));
+        }
+    }
+
+    /// Fails with the first variable of the first allocated group none of
+    /// whose variables was used.
+    fn check(&self) -> Result<(), GadgetError> {
+        match self
+            .allocated
+            .iter()
+            .find(|group| !group.iter().any(|var| self.used.contains(&var_key(var))))
+        {
+            Some(group) => Err(GadgetError::UnconstrainedVariable {
+                variable: var_name(&group[0]),
+            }),
+            None => Ok(()),
+        }
+    }
+}
+
+impl<'a> ConstraintSystem for StrictCS<'a> {
+    fn transcript(&mut self) -> &mut Transcript {
+        self.inner.transcript()
+    }
+
+    fn multiply(&mut self, left: LC, right: LC) -> (Variable, Variable, Variable) {
+        self.mark_used(&left);
+        self.mark_used(&right);
+        let (l, r, o) = self.inner.multiply(left, right);
+        // The input wires are constrained to the input LCs.
+        self.used.insert(var_key(&l));
+        self.used.insert(var_key(&r));
+        self.allocated.push(vec![o]);
+        (l, r, o)
+    }
+
+    fn allocate(&mut self, assignment: Option<Scalar>) -> Result<Variable, R1CSError> {
+        let var = self.inner.allocate(assignment)?;
+        self.allocated.push(vec![var]);
+        Ok(var)
+    }
+
+    fn allocate_multiplier(
+        &mut self,
+        input_assignments: Option<(Scalar, Scalar)>,
+    ) -> Result<(Variable, Variable, Variable), R1CSError> {
+        let (l, r, o) = self.inner.allocate_multiplier(input_assignments)?;
+        self.allocated.push(vec![l, r, o]);
+        Ok((l, r, o))
+    }
+
+    fn metrics(&self) -> Metrics {
+        self.inner.metrics()
+    }
+
+    fn constrain(&mut self, lc: LC) {
+        self.mark_used(&lc);
+        self.inner.constrain(lc);
+    }
+}
+
+/// Forwards the calls to `inner` until it holds more than `max_multipliers`.
+struct LimitedCS<'a> {
+    inner: &'a mut dyn ConstraintSystem,
+    max_multipliers: usize,
+    exceeded: bool,
+}
+
+impl<'a> LimitedCS<'a> {
+    fn check(&mut self) {
+        self.exceeded = self.exceeded || self.inner.metrics().multipliers > self.max_multipliers;
+    }
+}
+
+impl<'a> ConstraintSystem for LimitedCS<'a> {
+    fn transcript(&mut self) -> &mut Transcript {
+        self.inner.transcript()
+    }
+
+    fn multiply(&mut self, left: LC, right: LC) -> (Variable, Variable, Variable) {
+        if self.exceeded {
+            return (Variable::One(), Variable::One(), Variable::One());
+        }
+        let vars = self.inner.multiply(left, right);
+        self.check();
+        vars
+    }
+
+    fn allocate(&mut self, assignment: Option<Scalar>) -> Result<Variable, R1CSError> {
+        if self.exceeded {
+            return Ok(Variable::One());
+        }
+        let var = self.inner.allocate(assignment);
+        self.check();
+        var
+    }
+
+    fn allocate_multiplier(
+        &mut self,
+        input_assignments: Option<(Scalar, Scalar)>,
+    ) -> Result<(Variable, Variable, Variable), R1CSError> {
+        if self.exceeded {
+            return Ok((Variable::One(), Variable::One(), Variable::One()));
+        }
+        let vars = self.inner.allocate_multiplier(input_assignments);
+        self.check();
+        vars
+    }
+
+    fn metrics(&self) -> Metrics {
+        self.inner.metrics()
+    }
+
+    fn constrain(&mut self, lc: LC) {
+        if !self.exceeded {
+            self.inner.constrain(lc);
+        }
+    }
+}
+
+/// A `Prover` committing every shared scalar once, see `shared_scalar`.
+/// Gadgets are synthesized on the session itself.
+#[cfg(feature = "prover")]
+pub struct ProverSession<'a, 't, 'g> {
+    prover: &'a mut Prover<'t, 'g>,
+    shared: HashMap<&'static str, (Variable, Scalar)>,
+    commitments: Vec<CompressedRistretto>,
+}
+
+#[cfg(feature = "prover")]
+impl<'a, 't, 'g> ProverSession<'a, 't, 'g> {
+    pub fn new(prover: &'a mut Prover<'t, 'g>) -> ProverSession<'a, 't, 'g> {
+        ProverSession {
+            prover,
+            shared: HashMap::new(),
+            commitments: Vec::new(),
+        }
+    }
+
+    /// Returns the variable of `label`, committing `value` under it on the
+    /// first call (which must come before any multiplier or constraint, see
+    /// `checked_commit`). Fails if `label` already holds another value.
+    pub fn shared_scalar(
+        &mut self,
+        label: &'static str,
+        value: Scalar,
+    ) -> Result<Variable, GadgetError> {
+        if let Some((var, shared)) = self.shared.get(label) {
+            if *shared != value {
+                return Err(GadgetError::SharedScalarMismatch {
+                    label: label.to_string(),
+                });
+            }
+            return Ok(*var);
+        }
+        let (commitment, var) = checked_commit(&mut *self.prover, |p| {
+            p.transcript()
+                .append_message(b"shared-scalar", label.as_bytes());
+            p.commit(value, Scalar::random(&mut rand::thread_rng()))
+        })?;
+        self.shared.insert(label, (var, value));
+        self.commitments.push(commitment);
+        Ok(var)
+    }
+
+    /// Commitments of the shared scalars, in the order of their first use.
+    pub fn commitments(&self) -> &[CompressedRistretto] {
+        &self.commitments
+    }
+}
+
+#[cfg(feature = "prover")]
+impl<'a, 't, 'g> ConstraintSystem for ProverSession<'a, 't, 'g> {
+    fn transcript(&mut self) -> &mut Transcript {
+        self.prover.transcript()
+    }
+
+    fn multiply(&mut self, left: LC, right: LC) -> (Variable, Variable, Variable) {
+        self.prover.multiply(left, right)
+    }
+
+    fn allocate(&mut self, assignment: Option<Scalar>) -> Result<Variable, R1CSError> {
+        self.prover.allocate(assignment)
+    }
+
+    fn allocate_multiplier(
+        &mut self,
+        input_assignments: Option<(Scalar, Scalar)>,
+    ) -> Result<(Variable, Variable, Variable), R1CSError> {
+        self.prover.allocate_multiplier(input_assignments)
+    }
+
+    fn metrics(&self) -> Metrics {
+        self.prover.metrics()
+    }
+
+    fn constrain(&mut self, lc: LC) {
+        self.prover.constrain(lc);
+    }
+}
+
+/// The `Verifier` side of a `ProverSession`: the shared scalars take the
+/// received commitments in order, and must be requested in the same order as
+/// the prover did.
+pub struct VerifierSession<'a, 't> {
+    verifier: &'a mut Verifier<'t>,
+    shared: HashMap<&'static str, Variable>,
+    commitments: Vec<CompressedRistretto>,
+    used: usize,
+}
+
+impl<'a, 't> VerifierSession<'a, 't> {
+    pub fn new(
+        verifier: &'a mut Verifier<'t>,
+        commitments: &[CompressedRistretto],
+    ) -> VerifierSession<'a, 't> {
+        VerifierSession {
+            verifier,
+            shared: HashMap::new(),
+            commitments: commitments.to_vec(),
+            used: 0,
+        }
+    }
+
+    /// Returns the variable of `label`, committing the next received
+    /// commitment under it on the first call.
+    pub fn shared_scalar(&mut self, label: &'static str) -> Result<Variable, GadgetError> {
+        if let Some(var) = self.shared.get(label) {
+            return Ok(*var);
+        }
+        let commitment = match self.commitments.get(self.used) {
+            Some(commitment) => *commitment,
+            None => {
+                return Err(GadgetError::InvalidCommitmentsLength {
+                    expected: self.used + 1,
+                    found: self.commitments.len(),
+                })
+            }
+        };
+        let var = checked_commit(&mut *self.verifier, |v| {
+            v.transcript()
+                .append_message(b"shared-scalar", label.as_bytes());
+            v.commit(commitment)
+        })?;
+        self.shared.insert(label, var);
+        self.used += 1;
+        Ok(var)
+    }
+
+    /// Fails if some received commitments were not taken by a shared scalar.
+    pub fn finish(&self) -> Result<(), GadgetError> {
+        if self.used != self.commitments.len() {
+            return Err(GadgetError::InvalidCommitmentsLength {
+                expected: self.used,
+                found: self.commitments.len(),
+            });
+        }
+        Ok(())
+    }
+}
+
+impl<'a, 't> ConstraintSystem for VerifierSession<'a, 't> {
+    fn transcript(&mut self) -> &mut Transcript {
+        self.verifier.transcript()
+    }
+
+    fn multiply(&mut self, left: LC, right: LC) -> (Variable, Variable, Variable) {
+        self.verifier.multiply(left, right)
+    }
+
+    fn allocate(&mut self, assignment: Option<Scalar>) -> Result<Variable, R1CSError> {
+        self.verifier.allocate(assignment)
+    }
+
+    fn allocate_multiplier(
+        &mut self,
+        input_assignments: Option<(Scalar, Scalar)>,
+    ) -> Result<(Variable, Variable, Variable), R1CSError> {
+        self.verifier.allocate_multiplier(input_assignments)
+    }
+
+    fn metrics(&self) -> Metrics {
+        self.verifier.metrics()
+    }
+
+    fn constrain(&mut self, lc: LC) {
+        self.verifier.constrain(lc);
+    }
+}
+
+/// A term of an LC, with the variable as `(kind, index)` and the coefficient
+/// as its canonical encoding.
+type TermKey = ((u8, usize), [u8; 32]);
+
+fn var_key(var: &Variable) -> (u8, usize) {
+    match var {
+        Variable::One() => (0, 0),
+        Variable::Committed(i) => (1, *i),
+        Variable::MultiplierLeft(i) => (2, *i),
+        Variable::MultiplierRight(i) => (3, *i),
+        Variable::MultiplierOutput(i) => (4, *i),
+    }
+}
+
+/// Terms of `lc` with the like ones merged and the null ones dropped, sorted,
+/// so equal LCs get the same key however they were built.
+fn lc_key(lc: &LC) -> Vec<TermKey> {
+    let mut terms: BTreeMap<(u8, usize), Scalar> = BTreeMap::new();
+    for (var, coeff) in lc.clone().get_terms() {
+        *terms.entry(var_key(&var)).or_insert_with(Scalar::zero) += coeff;
+    }
+    terms
+        .into_iter()
+        .filter(|(_, coeff)| *coeff != Scalar::zero())
+        .map(|(var, coeff)| (var, coeff.to_bytes()))
+        .collect()
+}
+
+/// A CS memoizing the products by a constant: a `multiply` whose inputs, one
+/// of them constant, equal the ones of an earlier call returns the variables
+/// of that call instead of allocating another multiplier. Its output is
+/// already constrained to be the product, so reusing it is sound. Gadgets are
+/// synthesized on the pool itself, and the cache lives as long as it does.
+pub struct ConstantPool<'a> {
+    inner: &'a mut dyn ConstraintSystem,
+    products: HashMap<(Vec<TermKey>, Vec<TermKey>), (Variable, Variable, Variable)>,
+    hits: usize,
+}
+
+impl<'a> ConstantPool<'a> {
+    pub fn new(inner: &'a mut dyn ConstraintSystem) -> ConstantPool<'a> {
+        ConstantPool {
+            inner,
+            products: HashMap::new(),
+            hits: 0,
+        }
+    }
+
+    /// Number of multipliers saved so far.
+    pub fn hits(&self) -> usize {
+        self.hits
+    }
+}
+
+impl<'a> ConstraintSystem for ConstantPool<'a> {
+    fn transcript(&mut self) -> &mut Transcript {
+        self.inner.transcript()
+    }
+
+    fn multiply(&mut self, left: LC, right: LC) -> (Variable, Variable, Variable) {
+        let (left_key, right_key) = (lc_key(&left), lc_key(&right));
+        let constant = |key: &[TermKey]| key.iter().all(|((kind, _), _)| *kind == 0);
+        if !constant(&left_key) && !constant(&right_key) {
+            return self.inner.multiply(left, right);
+        }
+        // The product commutes, the inputs are stored in any order.
+        let key = (left_key, right_key);
+        let swapped = (key.1.clone(), key.0.clone());
+        if let Some((l, r, o)) = self.products.get(&key) {
+            self.hits += 1;
+            return (*l, *r, *o);
+        }
+        if let Some((l, r, o)) = self.products.get(&swapped) {
+            self.hits += 1;
+            return (*r, *l, *o);
+        }
+        let vars = self.inner.multiply(left, right);
+        self.products.insert(key, vars);
+        vars
+    }
+
+    fn allocate(&mut self, assignment: Option<Scalar>) -> Result<Variable, R1CSError> {
+        self.inner.allocate(assignment)
+    }
+
+    fn allocate_multiplier(
+        &mut self,
+        input_assignments: Option<(Scalar, Scalar)>,
+    ) -> Result<(Variable, Variable, Variable), R1CSError> {
+        self.inner.allocate_multiplier(input_assignments)
+    }
+
+    fn metrics(&self) -> Metrics {
+        self.inner.metrics()
+    }
+
+    fn constrain(&mut self, lc: LC) {
+        self.inner.constrain(lc);
+    }
+}
+
+mod test {
+    use super::*;
+    use bulletproofs::{BulletproofGens, PedersenGens};
+
+    #[test]
+    #[cfg(feature = "prover")]
+    fn commit_after_constraints() {
+        let pc_gens = PedersenGens::default();
+        let mut prover_transcript = transcript(b"Session");
+        let mut prover = Prover::new(&pc_gens, &mut prover_transcript);
+        let (commitment, x) =
+            checked_commit(&mut prover, |p| p.commit(Scalar::one(), Scalar::one())).unwrap();
+        let (_, _, o) = prover.multiply(x.into(), x.into());
+        prover.constrain(o - x);
+        // `multiply` also constrains its two inputs.
+        assert_eq!(
+            checked_commit(&mut prover, |p| p.commit(Scalar::one(), Scalar::one())).unwrap_err(),
+            GadgetError::CommitAfterConstraints {
+                multipliers: 1,
+                constraints: 3
+            }
+        );
+
+        let mut verifier_transcript = transcript(b"Session");
+        let mut verifier = Verifier::new(&mut verifier_transcript);
+        assert!(checked_commit(&mut verifier, |v| v.commit(commitment)).is_ok());
+        verifier.allocate(None).unwrap();
+        assert!(checked_commit(&mut verifier, |v| v.commit(commitment)).is_err());
+    }
+
+    #[test]
+    fn malformed_commitments() {
+        let pc_gens = PedersenGens::default();
+        let valid = pc_gens.commit(Scalar::one(), Scalar::one()).compress();
+        // Not the encoding of a field element.
+        let invalid = CompressedRistretto([0xff; 32]);
+        assert!(check_commitments(&[valid, valid]).is_ok());
+        assert_eq!(
+            check_commitments(&[valid, invalid, invalid]).unwrap_err(),
+            GadgetError::MalformedCommitment { index: 1 }
+        );
+    }
+
+    #[test]
+    fn size_limit() {
+        let squares = |n: usize| {
+            move |cs: &mut dyn ConstraintSystem| {
+                let x = cs.allocate(Some(Scalar::one())).unwrap();
+                for _ in 0..n {
+                    cs.multiply(x.into(), x.into());
+                }
+            }
+        };
+        let config = SessionConfig {
+            max_multipliers: Some(8),
+            ..SessionConfig::default()
+        };
+        let mut cs = crate::recording::RecordingCS::new(b"Session");
+        assert!(config.synthesize(&mut cs, squares(7)).is_ok());
+        assert_eq!(cs.multipliers(), 8);
+
+        let mut cs = crate::recording::RecordingCS::new(b"Session");
+        assert_eq!(
+            config.synthesize(&mut cs, squares(1000)).unwrap_err(),
+            GadgetError::CircuitTooLarge { max_multipliers: 8 }
+        );
+        assert_eq!(cs.multipliers(), 9);
+
+        let mut cs = crate::recording::RecordingCS::new(b"Session");
+        assert!(SessionConfig::default()
+            .synthesize(&mut cs, squares(1000))
+            .is_ok());
+    }
+
+    #[test]
+    fn strict_mode() {
+        let config = SessionConfig {
+            strict: true,
+            ..SessionConfig::default()
+        };
+        // x^2 = 4, then x^4 computed and forgotten.
+        let squares = |forget: bool| {
+            move |cs: &mut dyn ConstraintSystem| {
+                let x = cs.allocate(Some(Scalar::from(2u64))).unwrap();
+                let (_, _, sq) = cs.multiply(x.into(), x.into());
+                cs.constrain(sq - Scalar::from(4u64));
+                if forget {
+                    cs.multiply(sq.into(), sq.into());
+                }
+            }
+        };
+        let mut cs = crate::recording::RecordingCS::new(b"Session");
+        assert!(config.synthesize(&mut cs, squares(false)).is_ok());
+
+        let mut cs = crate::recording::RecordingCS::new(b"Session");
+        assert_eq!(
+            config.synthesize(&mut cs, squares(true)).unwrap_err(),
+            GadgetError::UnconstrainedVariable {
+                variable: "O2".to_string()
+            }
+        );
+        let mut cs = crate::recording::RecordingCS::new(b"Session");
+        assert!(SessionConfig::default()
+            .synthesize(&mut cs, squares(true))
+            .is_ok());
+    }
+
+    #[test]
+    fn strict_mode_point_gadgets() {
+        use crate::gadgets::point::edwards_point::{IdentityPolicy, SonnyEdwardsPointGadget};
+        use crate::helpers::{affine_coords, PointCoords};
+        use zerocaf::edwards::EdwardsPoint as SonnyEdwardsPoint;
+
+        let config = SessionConfig {
+            strict: true,
+            ..SessionConfig::default()
+        };
+        let mut rng = rand::thread_rng();
+        let p = SonnyEdwardsPoint::new_random_point(&mut rng);
+        let q = SonnyEdwardsPoint::new_random_point(&mut rng);
+        let mut cs = crate::recording::RecordingCS::new(b"Session");
+        let mut commit_point = |point: &SonnyEdwardsPoint| {
+            let coords: Vec<LC> = point
+                .coords()
+                .iter()
+                .map(|coord| cs.commit(Some(*coord)).into())
+                .collect();
+            SonnyEdwardsPointGadget {
+                X: coords[0].clone(),
+                Y: coords[1].clone(),
+                Z: coords[2].clone(),
+                T: coords[3].clone(),
+            }
+        };
+        let (p_gadget, q_gadget) = (commit_point(&p), commit_point(&q));
+
+        // `nonzero_gadget` leaves two wires of its `allocate_multiplier` unused.
+        let res = config.synthesize(&mut cs, |cs| {
+            p_gadget.forbid_identity(Some(p), cs);
+            let sum = p_gadget.add_with_policy(&q_gadget, IdentityPolicy::Forbid, Some((p, q)), cs);
+            sum.equal_extended(&SonnyEdwardsPointGadget::from_point(&(p + q)), cs);
+            let (x, y) = sum.to_affine(Some(p + q), cs);
+            let (x_assign, y_assign) = affine_coords(&(p + q));
+            cs.constrain(LC::from(x) - x_assign);
+            cs.constrain(LC::from(y) - y_assign);
+        });
+        assert!(res.is_ok());
+        assert!(cs.first_unsatisfied().is_none());
+    }
+
+    #[test]
+    fn constant_pool() {
+        use crate::gadgets::point::edwards_point::SonnyEdwardsPointGadget;
+        use crate::helpers::PointCoords;
+        use zerocaf::edwards::EdwardsPoint as SonnyEdwardsPoint;
+
+        let mut rng = rand::thread_rng();
+        let p = SonnyEdwardsPoint::new_random_point(&mut rng);
+        let c = SonnyEdwardsPointGadget::from_point(&SonnyEdwardsPoint::new_random_point(&mut rng));
+        let mut cs = crate::recording::RecordingCS::new(b"Session");
+        let coords: Vec<LC> = p
+            .coords()
+            .iter()
+            .map(|coord| cs.commit(Some(*coord)).into())
+            .collect();
+        let p = SonnyEdwardsPointGadget {
+            X: coords[0].clone(),
+            Y: coords[1].clone(),
+            Z: coords[2].clone(),
+            T: coords[3].clone(),
+        };
+
+        let mut pool = ConstantPool::new(&mut cs);
+        let sum = p.add(&c, &mut pool);
+        let again = p.add(&c, &mut pool);
+        // Only the last four products of the addition are not by a constant.
+        assert_eq!(pool.hits(), 8);
+        sum.equal(&again, &mut pool);
+        assert_eq!(cs.multipliers(), 12 + 4 + 4);
+        assert!(cs.first_unsatisfied().is_none());
+    }
+
+    /// Range-checks the shared `amount` and constrains it to `amount_assign`
+    /// as two separate gadgets.
+    fn amount_gadgets(cs: &mut dyn ConstraintSystem, amount: Variable, amount_assign: Option<u64>) {
+        crate::gadgets::typed::AmountVar::constrain(cs, amount, amount_assign);
+        cs.constrain(LC::from(amount) - Scalar::from(42u64));
+    }
+
+    #[test]
+    #[cfg(feature = "prover")]
+    fn shared_scalars() {
+        let pc_gens = PedersenGens::default();
+        let bp_gens = BulletproofGens::new(128, 1);
+
+        let mut prover_transcript = transcript(b"Session");
+        let mut prover = Prover::new(&pc_gens, &mut prover_transcript);
+        let mut session = ProverSession::new(&mut prover);
+        let amount = session
+            .shared_scalar("amount", Scalar::from(42u64))
+            .unwrap();
+        amount_gadgets(&mut session, amount, Some(42));
+        let again = session
+            .shared_scalar("amount", Scalar::from(42u64))
+            .unwrap();
+        assert_eq!(again, amount);
+        amount_gadgets(&mut session, again, Some(42));
+        assert_eq!(
+            session.shared_scalar("amount", Scalar::from(43u64)),
+            Err(GadgetError::SharedScalarMismatch {
+                label: "amount".to_string()
+            })
+        );
+        // Too late for a new commitment.
+        assert!(session.shared_scalar("fee", Scalar::one()).is_err());
+        let commitments = session.commitments().to_vec();
+        assert_eq!(commitments.len(), 1);
+        let proof = prover.prove(&bp_gens).unwrap();
+
+        let mut verifier_transcript = transcript(b"Session");
+        let mut verifier = Verifier::new(&mut verifier_transcript);
+        let mut session = VerifierSession::new(&mut verifier, &commitments);
+        let amount = session.shared_scalar("amount").unwrap();
+        amount_gadgets(&mut session, amount, None);
+        let again = session.shared_scalar("amount").unwrap();
+        amount_gadgets(&mut session, again, None);
+        assert!(session.finish().is_ok());
+        assert!(verify_proof!(verifier, &proof, &pc_gens, &bp_gens).is_ok());
+
+        // The labels are bound to the transcript.
+        let mut verifier_transcript = transcript(b"Session");
+        let mut verifier = Verifier::new(&mut verifier_transcript);
+        let mut session = VerifierSession::new(&mut verifier, &commitments);
+        let amount = session.shared_scalar("value").unwrap();
+        amount_gadgets(&mut session, amount, None);
+        assert!(verify_proof!(verifier, &proof, &pc_gens, &bp_gens).is_err());
+    }
+}
@@ -0,0 +1,144 @@
+//! Soundness checks of the point gadgets against tampered outputs.
+//!
+//! For every operation of `SonnyEdwardsPointGadget`, the honest result is
+//! committed as the claimed output and compared with the gadget output through
+//! `equal_extended`, and then every coordinate of the claim is perturbed in
+//! turn: the circuit must accept the former and reject every one of the latter.
+//! A gadget that leaves a coordinate unconstrained lets a prover claim garbage
+//! as a valid result, which these checks catch.
+use crate::gadgets::point::edwards_point::SonnyEdwardsPointGadget;
+use crate::helpers::{PointCoords, PointLCs};
+use crate::recording::RecordingCS;
+use bulletproofs::r1cs::ConstraintSystem as CS;
+use curve25519_dalek::scalar::Scalar;
+use zerocaf::edwards::EdwardsPoint as SonnyEdwardsPoint;
+
+/// Names of the extended coordinates, in `PointCoords` order.
+pub const COORDINATES: [&str; 4] = ["X", "Y", "Z", "T"];
+
+/// Returns the coordinates of `p` with the `i`-th one (see `COORDINATES`)
+/// incremented by one.
+pub fn tampered(p: &SonnyEdwardsPoint, i: usize) -> [Scalar; 4] {
+    let mut coords = p.coords();
+    coords[i] += Scalar::one();
+    coords
+}
+
+fn commit_point(cs: &mut RecordingCS, coords: &[Scalar; 4]) -> SonnyEdwardsPointGadget {
+    PointLCs::from_coords(*coords, |c| cs.commit(Some(c)).into()).into()
+}
+
+/// Commits `inputs`, applies `op` to them and returns whether the circuit is
+/// satisfied when `claimed` is claimed to be the output.
+pub fn output_accepted<F>(inputs: &[SonnyEdwardsPoint], claimed: &[Scalar; 4], op: F) -> bool
+where
+    F: FnOnce(&mut dyn CS, Vec<SonnyEdwardsPointGadget>) -> SonnyEdwardsPointGadget,
+{
+    let mut cs = RecordingCS::new(b"Soundness");
+    let inputs = inputs
+        .iter()
+        .map(|p| commit_point(&mut cs, &p.coords()))
+        .collect();
+    let output = op(&mut cs, inputs);
+    let claimed = commit_point(&mut cs, claimed);
+    output.equal_extended(&claimed, &mut cs);
+    cs.first_unsatisfied().is_none()
+}
+
+/// Asserts that `op` accepts `expected` as output of `inputs`, and rejects it
+/// with any of the `coordinates` tampered.
+pub fn assert_rejects_tampering<F>(
+    inputs: &[SonnyEdwardsPoint],
+    expected: &SonnyEdwardsPoint,
+    coordinates: &[usize],
+    op: F,
+) where
+    F: Fn(&mut dyn CS, Vec<SonnyEdwardsPointGadget>) -> SonnyEdwardsPointGadget,
+{
+    assert!(
+        output_accepted(inputs, &expected.coords(), &op),
+        "the honest output is rejected"
+    );
+    for i in coordinates {
+        assert!(
+            !output_accepted(inputs, &tampered(expected, *i), &op),
+            "an output with a tampered {} coordinate is accepted",
+            COORDINATES[*i]
+        );
+    }
+}
+
+mod test {
+    use super::*;
+    use crate::gadgets::point::witness;
+    use bulletproofs::r1cs::LinearCombination as LC;
+
+    const ALL: [usize; 4] = [0, 1, 2, 3];
+
+    fn random_point() -> SonnyEdwardsPoint {
+        SonnyEdwardsPoint::new_random_point(&mut rand::thread_rng())
+    }
+
+    #[test]
+    fn add() {
+        let (p, q) = (random_point(), random_point());
+        assert_rejects_tampering(&[p, q], &witness::edwards::add(&p, &q), &ALL, |cs, ps| {
+            ps[0].add(&ps[1], cs)
+        });
+    }
+
+    #[test]
+    fn double() {
+        let p = random_point();
+        assert_rejects_tampering(&[p], &witness::edwards::double(&p), &ALL, |cs, ps| {
+            ps[0].double(cs)
+        });
+    }
+
+    #[test]
+    fn neg() {
+        let p = random_point();
+        assert_rejects_tampering(&[p], &witness::edwards::neg(&p), &ALL, |_, ps| ps[0].neg());
+    }
+
+    #[test]
+    fn conditionally_select() {
+        let p = random_point();
+        for bit in 0..2u8 {
+            let expected = witness::edwards::conditionally_select(&p, bit);
+            assert_rejects_tampering(&[p], &expected, &ALL, |cs, ps| {
+                let bit = cs.allocate(Some(Scalar::from(bit))).unwrap();
+                ps[0].conditionally_select(LC::from(bit), cs)
+            });
+        }
+    }
+
+    #[test]
+    fn scalar_mul() {
+        let p = random_point();
+        let bits = [1u8, 0, 1, 1, 0, 1, 0, 1];
+        let expected = witness::edwards::scalar_mul(&p, &bits);
+        assert_rejects_tampering(&[p], &expected, &ALL, |cs, mut ps| {
+            let vars = bits
+                .iter()
+                .map(|b| cs.allocate(Some(Scalar::from(*b))).unwrap())
+                .collect();
+            SonnyEdwardsPointGadget::scalar_mul(ps.remove(0), Some(p), vars, Some(&bits), cs).0
+        });
+    }
+
+    #[test]
+    fn equal_t_coordinate() {
+        let p = random_point();
+        assert_rejects_tampering(&[p], &p, &[3], |_, mut ps| ps.remove(0));
+
+        // Plain `equal` only compares X and Y.
+        let mut cs = RecordingCS::new(b"Soundness");
+        let honest = commit_point(&mut cs, &p.coords());
+        let incoherent = commit_point(&mut cs, &tampered(&p, 3));
+        honest.equal(&incoherent, &mut cs);
+        assert!(cs.first_unsatisfied().is_none());
+        incoherent.satisfy_coherence(&mut cs);
+        assert!(cs.first_unsatisfied().is_some());
+    }
+}
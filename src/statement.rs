@@ -0,0 +1,136 @@
+//! Public statements of the shipped circuits.
+//!
+//! Every circuit has a typed statement holding all the public values its
+//! verifier depends on, with a canonical byte encoding. Provers and verifiers
+//! build their transcript with `Statement::transcript`, which binds the
+//! `statement_hash`, so a proof only verifies against the exact public values
+//! it was generated for instead of relying on every wrapper to bind each one.
+//! `Statement::audited_transcript` also returns the log of what was appended.
+use crate::helpers::affine_coords;
+use crate::session;
+use crate::transcript::{AuditLog, AuditTranscript, TranscriptBackend};
+use curve25519_dalek::{ristretto::CompressedRistretto, scalar::Scalar};
+use merlin::Transcript;
+use zerocaf::edwards::EdwardsPoint as SonnyEdwardsPoint;
+
+/// The public values of a circuit.
+pub trait Statement {
+    /// Transcript label of the circuit.
+    const LABEL: &'static [u8];
+
+    /// Appends the canonical encoding of the statement to `out`.
+    fn encode(&self, out: &mut Vec<u8>);
+
+    /// Canonical encoding of the statement.
+    fn to_bytes(&self) -> Vec<u8> {
+        let mut out = Vec::new();
+        self.encode(&mut out);
+        out
+    }
+
+    /// Hash of the canonical encoding, domain-separated by the circuit label.
+    fn statement_hash(&self) -> [u8; 32] {
+        let mut transcript = Transcript::new(b"bulletproofs_gadgets statement");
+        transcript.append_message(b"circuit", Self::LABEL);
+        transcript.append_message(b"statement", &self.to_bytes());
+        let mut hash = [0u8; 32];
+        transcript.challenge_bytes(b"statement-hash", &mut hash);
+        hash
+    }
+
+    /// Creates the transcript of a proof of this statement.
+    fn transcript(&self) -> Transcript {
+        let mut transcript = session::transcript(Self::LABEL);
+        transcript.append_message(b"statement-hash", &self.statement_hash());
+        transcript
+    }
+
+    /// `transcript`, along with the log of what it holds, for auditors to check
+    /// which public data the proof is bound to. See `AuditTranscript` for what
+    /// the log misses once proving starts.
+    fn audited_transcript(&self) -> (Transcript, AuditLog) {
+        let mut transcript = AuditTranscript::new(Self::LABEL);
+        session::bind_version(&mut transcript, session::GADGETS_VERSION);
+        transcript.append_message(b"statement-hash", &self.statement_hash());
+        transcript.into_parts()
+    }
+}
+
+/// Encodes `v` as 8 little-endian bytes. Lengths of lists are encoded this way
+/// too, so the encoding of a statement is never ambiguous.
+pub fn encode_u64(out: &mut Vec<u8>, v: u64) {
+    out.extend_from_slice(&v.to_le_bytes());
+}
+
+pub fn encode_scalar(out: &mut Vec<u8>, s: &Scalar) {
+    out.extend_from_slice(s.as_bytes());
+}
+
+/// Encodes a Sonny point by its affine coordinates, so every representation of
+/// the same point has the same encoding.
+pub fn encode_point(out: &mut Vec<u8>, p: &SonnyEdwardsPoint) {
+    let (x, y) = affine_coords(p);
+    encode_scalar(out, &x);
+    encode_scalar(out, &y);
+}
+
+pub fn encode_commitment(out: &mut Vec<u8>, c: &CompressedRistretto) {
+    out.extend_from_slice(c.as_bytes());
+}
+
+mod test {
+    use super::*;
+
+    struct Pair(u64, u64);
+
+    impl Statement for Pair {
+        const LABEL: &'static [u8] = b"Pair";
+
+        fn encode(&self, out: &mut Vec<u8>) {
+            encode_u64(out, self.0);
+            encode_u64(out, self.1);
+        }
+    }
+
+    #[test]
+    fn statement_hash_binds_every_value() {
+        assert_eq!(Pair(1, 2).to_bytes().len(), 16);
+        assert_eq!(Pair(1, 2).statement_hash(), Pair(1, 2).statement_hash());
+        assert_ne!(Pair(1, 2).statement_hash(), Pair(2, 1).statement_hash());
+    }
+
+    #[test]
+    fn audited_transcript() {
+        let (mut audited, log) = Pair(1, 2).audited_transcript();
+        let mut transcript = Pair(1, 2).transcript();
+        assert_eq!(
+            audited.challenge_scalar(b"challenge"),
+            transcript.challenge_scalar(b"challenge")
+        );
+        assert_eq!(
+            log.messages(b"dom-sep"),
+            vec![Pair::LABEL, session::DOMAIN_TAG]
+        );
+        assert_eq!(
+            log.messages(b"statement-hash"),
+            vec![&Pair(1, 2).statement_hash()[..]]
+        );
+    }
+
+    #[test]
+    fn points_are_encoded_canonically() {
+        let p = SonnyEdwardsPoint::new_random_point(&mut rand::thread_rng());
+        // The same point with every extended coordinate doubled.
+        let two = zerocaf::field::FieldElement::one() + zerocaf::field::FieldElement::one();
+        let q = SonnyEdwardsPoint {
+            X: p.X * two,
+            Y: p.Y * two,
+            Z: p.Z * two,
+            T: p.T * two,
+        };
+        let (mut a, mut b) = (Vec::new(), Vec::new());
+        encode_point(&mut a, &p);
+        encode_point(&mut b, &q);
+        assert_eq!(a, b);
+    }
+}
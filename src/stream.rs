@@ -0,0 +1,149 @@
+//! Chunked commitment of witnesses, for circuits with thousands of committed
+//! inputs (e.g. batched transactions).
+//!
+//! Values are committed as they stream in, one chunk at a time, and every chunk
+//! is handed to a callback that can add its constraints and write the commitments
+//! out, so neither the values nor the commitments need to be held in memory at once.
+//! The commitments enter the transcript in stream order, so the verifier only has
+//! to replay the same stream (with the same chunk size) to stay in sync.
+use bulletproofs::r1cs::{ConstraintSystem, Variable, Verifier};
+#[cfg(feature = "prover")]
+use bulletproofs::r1cs::Prover;
+use curve25519_dalek::{ristretto::CompressedRistretto, scalar::Scalar};
+
+/// A chunk of committed values.
+#[derive(Clone, Debug)]
+pub struct Chunk {
+    /// Position of the first value of the chunk in the stream.
+    pub offset: usize,
+    pub commitments: Vec<CompressedRistretto>,
+    pub vars: Vec<Variable>,
+}
+
+/// Splits `items` into chunks of (at most) `chunk_size` elements, calling
+/// `f(offset, chunk)` for every one of them. Returns the number of items.
+fn for_each_chunk<T, I, F>(items: I, chunk_size: usize, mut f: F) -> usize
+where
+    I: IntoIterator<Item = T>,
+    F: FnMut(usize, Vec<T>),
+{
+    assert!(chunk_size > 0);
+    let mut items = items.into_iter();
+    let mut offset = 0;
+    loop {
+        let chunk: Vec<T> = items.by_ref().take(chunk_size).collect();
+        if chunk.is_empty() {
+            return offset;
+        }
+        let len = chunk.len();
+        f(offset, chunk);
+        offset += len;
+    }
+}
+
+/// Commits `values` as the prover, `chunk_size` at a time, calling
+/// `on_chunk` after every chunk. Returns the number of committed values.
+#[cfg(feature = "prover")]
+pub fn prover_commit_stream<I, F>(
+    prover: &mut Prover,
+    values: I,
+    chunk_size: usize,
+    mut on_chunk: F,
+) -> usize
+where
+    I: IntoIterator<Item = Scalar>,
+    F: FnMut(&mut dyn ConstraintSystem, Chunk),
+{
+    for_each_chunk(values, chunk_size, |offset, chunk| {
+        let (commitments, vars) = chunk
+            .into_iter()
+            .map(|v| prover.commit(v, Scalar::random(&mut rand::thread_rng())))
+            .unzip();
+        on_chunk(
+            &mut *prover,
+            Chunk {
+                offset,
+                commitments,
+                vars,
+            },
+        );
+    })
+}
+
+/// Commits `commitments` as the verifier, `chunk_size` at a time, calling
+/// `on_chunk` after every chunk. Returns the number of committed values.
+pub fn verifier_commit_stream<I, F>(
+    verifier: &mut Verifier,
+    commitments: I,
+    chunk_size: usize,
+    mut on_chunk: F,
+) -> usize
+where
+    I: IntoIterator<Item = CompressedRistretto>,
+    F: FnMut(&mut dyn ConstraintSystem, Chunk),
+{
+    for_each_chunk(commitments, chunk_size, |offset, commitments| {
+        let vars = commitments.iter().map(|V| verifier.commit(*V)).collect();
+        on_chunk(
+            &mut *verifier,
+            Chunk {
+                offset,
+                commitments,
+                vars,
+            },
+        );
+    })
+}
+
+#[cfg(feature = "prover")]
+mod test {
+    use super::*;
+    use crate::gadgets::range::range_gadget;
+    use crate::session;
+    use bulletproofs::r1cs::R1CSError;
+    use bulletproofs::{BulletproofGens, PedersenGens};
+
+    fn stream_roundtrip_helper(
+        amounts: &[u64],
+        tamper: impl Fn(&mut Vec<CompressedRistretto>),
+    ) -> Result<(), R1CSError> {
+        let pc_gens = PedersenGens::default();
+        let bp_gens = BulletproofGens::new(2048, 1);
+
+        let mut transcript = session::transcript(b"Stream");
+        let mut prover = Prover::new(&pc_gens, &mut transcript);
+        let mut commitments = Vec::new();
+        let n = prover_commit_stream(
+            &mut prover,
+            amounts.iter().map(|a| Scalar::from(*a)),
+            4,
+            |cs, chunk| {
+                for (i, var) in chunk.vars.iter().enumerate() {
+                    range_gadget(cs, (*var).into(), Some(amounts[chunk.offset + i]), 0, 1000)
+                        .unwrap();
+                }
+                commitments.extend(chunk.commitments);
+            },
+        );
+        assert_eq!(n, amounts.len());
+        let proof = prover.prove(&bp_gens)?;
+        tamper(&mut commitments);
+
+        let mut transcript = session::transcript(b"Stream");
+        let mut verifier = Verifier::new(&mut transcript);
+        verifier_commit_stream(&mut verifier, commitments, 4, |cs, chunk| {
+            for var in chunk.vars {
+                range_gadget(cs, var.into(), None, 0, 1000).unwrap();
+            }
+        });
+        verify_proof!(verifier, &proof, &pc_gens, &bp_gens)
+    }
+
+    #[test]
+    fn stream() {
+        let amounts: Vec<u64> = (0..10).map(|i| i * 100).collect();
+        assert!(stream_roundtrip_helper(&amounts, |_| {}).is_ok());
+        // The order of the commitments is part of the transcript.
+        assert!(stream_roundtrip_helper(&amounts, |c| c.swap(0, 9)).is_err());
+    }
+}
@@ -0,0 +1,286 @@
+//! Generators and roundtrip helpers shared by the tests of this crate and
+//! of the crates that build circuits on top of it.
+use crate::session;
+use crate::testing::CircuitShape;
+use bulletproofs::r1cs::{
+    ConstraintSystem, Prover, R1CSError, R1CSProof, Variable, Verifier,
+};
+use bulletproofs::{BulletproofGens, PedersenGens};
+use curve25519_dalek::{ristretto::CompressedRistretto, scalar::Scalar};
+use proptest::prelude::*;
+use rand::{rngs::StdRng, SeedableRng};
+use zerocaf::edwards::EdwardsPoint as SonnyEdwardsPoint;
+use zerocaf::ristretto::RistrettoPoint as SonnyRistrettoPoint;
+use zerocaf::scalar::Scalar as SonnyScalar;
+
+/// Generates random points of the Sonny curve in Extended Coordinates.
+pub fn sonny_edwards_point() -> impl Strategy<Value = SonnyEdwardsPoint> {
+    any::<[u8; 32]>()
+        .prop_map(|seed| SonnyEdwardsPoint::new_random_point(&mut StdRng::from_seed(seed)))
+}
+
+/// Generates random points of the Sonny Ristretto group.
+pub fn sonny_ristretto_point() -> impl Strategy<Value = SonnyRistrettoPoint> {
+    any::<[u8; 32]>()
+        .prop_map(|seed| SonnyRistrettoPoint::new_random_point(&mut StdRng::from_seed(seed)))
+}
+
+/// Generates canonical (fully reduced) scalars of the Sonny curve.
+pub fn sonny_scalar() -> impl Strategy<Value = SonnyScalar> {
+    any::<[u8; 32]>().prop_map(|seed| SonnyScalar::random(&mut StdRng::from_seed(seed)))
+}
+
+/// Generates canonical scalars of the field the R1CS works with.
+pub fn scalar() -> impl Strategy<Value = Scalar> {
+    any::<[u8; 32]>().prop_map(|seed| Scalar::random(&mut StdRng::from_seed(seed)))
+}
+
+/// Generates a Sonny scalar together with its bit decomposition, least
+/// significant bit first, already mapped into R1CS scalars.
+pub fn sonny_scalar_bits() -> impl Strategy<Value = (SonnyScalar, Vec<Scalar>)> {
+    sonny_scalar().prop_map(|s| {
+        let bits = s.into_bits().iter().map(|bit| Scalar::from(*bit)).collect();
+        (s, bits)
+    })
+}
+
+/// Generates vectors of `n` bits mapped into R1CS scalars.
+pub fn bit_vector(n: usize) -> impl Strategy<Value = Vec<Scalar>> {
+    proptest::collection::vec(any::<bool>(), n)
+        .prop_map(|bits| bits.into_iter().map(|b| Scalar::from(b as u8)).collect())
+}
+
+/// Commits `witness` as the prover, synthesizes `circuit`, and returns the proof
+/// together with the commitments. The circuit receives the committed variables
+/// and the witness assignments.
+pub fn prove<F>(
+    pc_gens: &PedersenGens,
+    bp_gens: &BulletproofGens,
+    label: &'static [u8],
+    witness: &[Scalar],
+    circuit: F,
+) -> Result<(R1CSProof, Vec<CompressedRistretto>), R1CSError>
+where
+    F: Fn(&mut dyn ConstraintSystem, Vec<Variable>, Option<&[Scalar]>),
+{
+    let mut transcript = session::transcript(label);
+    let mut prover = Prover::new(pc_gens, &mut transcript);
+
+    let (commitments, vars): (Vec<CompressedRistretto>, Vec<Variable>) = witness
+        .iter()
+        .map(|v| prover.commit(*v, Scalar::random(&mut rand::thread_rng())))
+        .unzip();
+    circuit(&mut prover, vars, Some(witness));
+
+    let proof = prover.prove(bp_gens)?;
+    Ok((proof, commitments))
+}
+
+/// Commits the commitments as the verifier, synthesizes `circuit` without
+/// assignments and verifies the proof.
+pub fn verify<F>(
+    pc_gens: &PedersenGens,
+    bp_gens: &BulletproofGens,
+    label: &'static [u8],
+    commitments: &[CompressedRistretto],
+    proof: &R1CSProof,
+    circuit: F,
+) -> Result<(), R1CSError>
+where
+    F: Fn(&mut dyn ConstraintSystem, Vec<Variable>, Option<&[Scalar]>),
+{
+    let mut transcript = session::transcript(label);
+    let mut verifier = Verifier::new(&mut transcript);
+
+    let vars: Vec<Variable> = commitments.iter().map(|V| verifier.commit(*V)).collect();
+    circuit(&mut verifier, vars, None);
+
+    verify_proof!(verifier, proof, pc_gens, bp_gens)
+}
+
+/// Proves and verifies `circuit` over the committed `witness`, the same way every
+/// `*_roundtrip_helper` of the tests does.
+pub fn roundtrip_helper<F>(
+    witness: &[Scalar],
+    gens_capacity: usize,
+    circuit: F,
+) -> Result<(), R1CSError>
+where
+    F: Fn(&mut dyn ConstraintSystem, Vec<Variable>, Option<&[Scalar]>),
+{
+    // Common
+    let pc_gens = PedersenGens::default();
+    let bp_gens = BulletproofGens::new(gens_capacity, 1);
+
+    let (proof, commitments) = prove(&pc_gens, &bp_gens, b"RoundtripHelper", witness, &circuit)?;
+
+    verify(
+        &pc_gens,
+        &bp_gens,
+        b"RoundtripHelper",
+        &commitments,
+        &proof,
+        &circuit,
+    )
+}
+
+/// Ways of corrupting a single committed input of a witness.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Tamper {
+    /// Flips the bit at the given position of the canonical encoding.
+    FlipBit(usize),
+    /// Adds one to the value.
+    AddOne,
+    /// Swaps the value with the next committed input (wrapping around),
+    /// which e.g. exchanges two coordinates of a committed point.
+    SwapWithNext,
+}
+
+impl Tamper {
+    /// The corruptions applied by `assert_rejects_tampered_witnesses` to every input.
+    pub const ALL: [Tamper; 4] = [
+        Tamper::FlipBit(0),
+        Tamper::FlipBit(1),
+        Tamper::AddOne,
+        Tamper::SwapWithNext,
+    ];
+
+    /// Returns a copy of `witness` with the input at `index` corrupted.
+    pub fn apply(&self, witness: &[Scalar], index: usize) -> Vec<Scalar> {
+        let mut tampered = witness.to_vec();
+        match *self {
+            Tamper::FlipBit(pos) => {
+                let mut bytes = tampered[index].to_bytes();
+                bytes[pos / 8] ^= 1 << (pos % 8);
+                tampered[index] = Scalar::from_bytes_mod_order(bytes);
+            }
+            Tamper::AddOne => tampered[index] += Scalar::one(),
+            Tamper::SwapWithNext => tampered.swap(index, (index + 1) % witness.len()),
+        }
+        tampered
+    }
+}
+
+/// Proves `circuit` with every input of `witness` corrupted in every way listed in
+/// `Tamper::ALL` and returns the corruptions for which the proof still verified.
+///
+/// Corruptions that leave the witness unchanged are skipped, and a prover that
+/// panics on a corrupted witness counts as a rejection.
+pub fn accepted_tampered_witnesses<F>(
+    witness: &[Scalar],
+    gens_capacity: usize,
+    circuit: F,
+) -> Vec<(usize, Tamper)>
+where
+    F: Fn(&mut dyn ConstraintSystem, Vec<Variable>, Option<&[Scalar]>),
+{
+    let mut accepted = Vec::new();
+    for index in 0..witness.len() {
+        for tamper in Tamper::ALL.iter() {
+            let tampered = tamper.apply(witness, index);
+            if tampered == witness {
+                continue;
+            }
+            let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+                roundtrip_helper(&tampered, gens_capacity, &circuit)
+            }));
+            if let Ok(Ok(())) = result {
+                accepted.push((index, *tamper));
+            }
+        }
+    }
+    accepted
+}
+
+/// Fails if any corruption of any committed input still produces a valid proof.
+pub fn assert_rejects_tampered_witnesses<F>(witness: &[Scalar], gens_capacity: usize, circuit: F)
+where
+    F: Fn(&mut dyn ConstraintSystem, Vec<Variable>, Option<&[Scalar]>),
+{
+    let accepted = accepted_tampered_witnesses(witness, gens_capacity, circuit);
+    assert!(
+        accepted.is_empty(),
+        "the circuit accepted corrupted witnesses (input index, corruption): {:?}",
+        accepted
+    );
+}
+
+/// Shape of `circuit` synthesized over the committed `witness`.
+pub fn shape<F>(witness: &[Scalar], circuit: F) -> CircuitShape
+where
+    F: Fn(&mut dyn ConstraintSystem, Vec<Variable>, Option<&[Scalar]>),
+{
+    let pc_gens = PedersenGens::default();
+    let mut transcript = session::transcript(b"RoundtripHelper");
+    let mut prover = Prover::new(&pc_gens, &mut transcript);
+    let vars: Vec<Variable> = witness
+        .iter()
+        .map(|v| prover.commit(*v, Scalar::random(&mut rand::thread_rng())).1)
+        .collect();
+    circuit(&mut prover, vars, Some(witness));
+    CircuitShape::of(&prover)
+}
+
+/// Emits the module `$name` with the usual tests of a gadget: the proof of
+/// `circuit` over `witness` verifies, no corruption of the witness (see
+/// `Tamper`) does, and the circuit keeps the given shape.
+///
+/// `witness` and `circuit` are evaluated once per test, so a circuit depending
+/// on public values must get the same ones every time (e.g. from a seeded RNG).
+///
+/// ```ignore
+/// gadget_roundtrip_tests!(
+///     point_equality,
+///     witness = point_witness(),
+///     circuit = point_equality_circuit,
+///     gens_capacity = 16,
+///     multipliers = 4,
+///     constraints = 10
+/// );
+/// ```
+#[macro_export]
+macro_rules! gadget_roundtrip_tests {
+    (
+        $name:ident,
+        witness = $witness:expr,
+        circuit = $circuit:expr,
+        gens_capacity = $capacity:expr,
+        multipliers = $m:expr,
+        constraints = $c:expr
+    ) => {
+        mod $name {
+            use super::*;
+
+            #[test]
+            fn roundtrip() {
+                let witness: Vec<Scalar> = $witness;
+                assert!(
+                    $crate::test_utils::roundtrip_helper(&witness, $capacity, $circuit).is_ok()
+                );
+            }
+
+            #[test]
+            fn rejects_tampered_witnesses() {
+                let witness: Vec<Scalar> = $witness;
+                $crate::test_utils::assert_rejects_tampered_witnesses(
+                    &witness, $capacity, $circuit,
+                );
+            }
+
+            #[test]
+            fn shape() {
+                let witness: Vec<Scalar> = $witness;
+                let shape = $crate::test_utils::shape(&witness, $circuit);
+                let expected = $crate::testing::CircuitShape {
+                    multipliers: $m,
+                    constraints: $c,
+                };
+                assert_eq!(
+                    shape, expected,
+                    "the circuit shape changed: got {:?}, expected {:?}",
+                    shape, expected
+                );
+            }
+        }
+    };
+}
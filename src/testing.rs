@@ -0,0 +1,61 @@
+//! Helpers to snapshot the size of a circuit so that changes on the number of
+//! multipliers/constraints a gadget generates (and therefore on the `BulletproofGens`
+//! capacity it requires) are caught by the tests.
+use bulletproofs::r1cs::{ConstraintSystem, Prover};
+use bulletproofs::PedersenGens;
+use merlin::Transcript;
+
+/// Number of multipliers and constraints that a circuit adds to a CS.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct CircuitShape {
+    pub multipliers: usize,
+    pub constraints: usize,
+}
+
+impl CircuitShape {
+    /// Reads the current shape of the given CS.
+    pub fn of(cs: &dyn ConstraintSystem) -> CircuitShape {
+        let metrics = cs.metrics();
+        CircuitShape {
+            multipliers: metrics.multipliers,
+            constraints: metrics.constraints,
+        }
+    }
+}
+
+/// Synthesizes the circuit inside a fresh `Prover` and returns the shape of it.
+///
+/// Since the shape does not depend on the assignments, the closure is free to
+/// feed the gadgets with any witness (or constant LCs).
+pub fn measure<F>(circuit: F) -> CircuitShape
+where
+    F: FnOnce(&mut dyn ConstraintSystem),
+{
+    let pc_gens = PedersenGens::default();
+    let mut transcript = Transcript::new(b"CircuitShape");
+    let mut prover = Prover::new(&pc_gens, &mut transcript);
+    circuit(&mut prover);
+    CircuitShape::of(&prover)
+}
+
+/// Fails if the circuit built by the closure does not have the expected
+/// number of multipliers and constraints.
+///
+/// ```ignore
+/// assert_circuit_shape!(|cs| { p.double(cs); }, multipliers = 10, constraints = 24);
+/// ```
+#[macro_export]
+macro_rules! assert_circuit_shape {
+    ($circuit:expr, multipliers = $m:expr, constraints = $c:expr) => {{
+        let shape = $crate::testing::measure($circuit);
+        let expected = $crate::testing::CircuitShape {
+            multipliers: $m,
+            constraints: $c,
+        };
+        assert_eq!(
+            shape, expected,
+            "the circuit shape changed: got {:?}, expected {:?}",
+            shape, expected
+        );
+    }};
+}
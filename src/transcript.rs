@@ -0,0 +1,403 @@
+//! Fiat–Shamir backends.
+//!
+//! The gadgets that need verifier challenges (the accumulator, the permutation
+//! argument, batch verification) draw them through `TranscriptBackend` instead
+//! of calling `merlin` directly, so another backend can be plugged in without
+//! touching them. `merlin::Transcript` implements it, and is still the backend
+//! `Prover` and `Verifier` run on: the R1CS proof itself is bound to `merlin`
+//! by `bulletproofs`. `MimcTranscript` is an algebraic backend built on the
+//! MiMC hash of `gadgets::hash`, which is cheap to recompute inside a circuit.
+//!
+//! Besides bytes, `MimcTranscript` absorbs field elements (`append_scalar`) and
+//! squeezes field elements (`challenge_field`). For every `MimcTranscript`,
+//! `MimcTranscriptGadget` recomputes the same state inside the circuit, with
+//! committed values absorbed as LCs, so the challenges of a sigma protocol over
+//! committed values can be derived and used within the R1CS:
+//!
+//! - `append_message(label, m)`: `state = H(state, pack(label), pack(m))`;
+//! - `append_scalar(label, v)`: `state = H(state, H(pack(label)), v)`;
+//! - `challenge_field(label)`: `state = H(state, H(pack(label)))`, returned,
+//!
+//! where `H` is `mimc_hash` and `pack` prefixes bytes with their length and
+//! splits them into 31-byte field elements.
+//!
+//! `AuditTranscript` is a `merlin` transcript recording what is appended to it
+//! into an `AuditLog`, see `Statement::audited_transcript`.
+use crate::gadgets::hash::{mimc, mimc_gadget, mimc_hash, round_constants};
+use bulletproofs::r1cs::{ConstraintSystem as CS, LinearCombination as LC};
+use curve25519_dalek::{ristretto::CompressedRistretto, scalar::Scalar};
+use merlin::Transcript;
+
+/// A Fiat–Shamir transcript.
+pub trait TranscriptBackend {
+    /// Appends a labeled message.
+    fn append_message(&mut self, label: &'static [u8], message: &[u8]);
+
+    /// Fills `dest` with challenge bytes depending on every message appended so far.
+    fn challenge_bytes(&mut self, label: &'static [u8], dest: &mut [u8]);
+
+    /// Appends `v` as 8 little-endian bytes.
+    fn append_u64(&mut self, label: &'static [u8], v: u64) {
+        self.append_message(label, &v.to_le_bytes());
+    }
+
+    /// Draws a uniform scalar from 64 challenge bytes.
+    fn challenge_scalar(&mut self, label: &'static [u8]) -> Scalar {
+        let mut buf = [0u8; 64];
+        self.challenge_bytes(label, &mut buf);
+        Scalar::from_bytes_mod_order_wide(&buf)
+    }
+}
+
+impl TranscriptBackend for Transcript {
+    fn append_message(&mut self, label: &'static [u8], message: &[u8]) {
+        Transcript::append_message(self, label, message);
+    }
+
+    fn challenge_bytes(&mut self, label: &'static [u8], dest: &mut [u8]) {
+        Transcript::challenge_bytes(self, label, dest);
+    }
+
+    fn append_u64(&mut self, label: &'static [u8], v: u64) {
+        Transcript::append_u64(self, label, v);
+    }
+}
+
+/// An operation on an audited transcript.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum TranscriptEntry {
+    Message {
+        label: &'static [u8],
+        message: Vec<u8>,
+    },
+    /// `len` challenge bytes were drawn.
+    Challenge { label: &'static [u8], len: usize },
+}
+
+/// Everything appended to an `AuditTranscript`, in order.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct AuditLog {
+    entries: Vec<TranscriptEntry>,
+}
+
+impl AuditLog {
+    pub fn entries(&self) -> &[TranscriptEntry] {
+        &self.entries
+    }
+
+    /// The messages appended under `label`.
+    pub fn messages(&self, label: &[u8]) -> Vec<&[u8]> {
+        self.entries
+            .iter()
+            .filter_map(|entry| match entry {
+                TranscriptEntry::Message { label: l, message } if *l == label => {
+                    Some(message.as_slice())
+                }
+                _ => None,
+            })
+            .collect()
+    }
+
+    /// Records the `commitments` of a proof, which `Prover::commit` appends to
+    /// the transcript under the label `V`.
+    pub fn record_commitments(&mut self, commitments: &[CompressedRistretto]) {
+        for c in commitments {
+            self.entries.push(TranscriptEntry::Message {
+                label: b"V",
+                message: c.as_bytes().to_vec(),
+            });
+        }
+    }
+}
+
+/// A `merlin::Transcript` logging every message appended and every challenge
+/// drawn through it, so auditors can check which public data a proof binds.
+///
+/// `Prover` and `Verifier` take the `merlin::Transcript` itself: once it is
+/// handed over with `into_parts`, what they and the gadgets append is not
+/// logged, except for the commitments, which can be added to the log with
+/// `AuditLog::record_commitments`.
+pub struct AuditTranscript {
+    transcript: Transcript,
+    log: AuditLog,
+}
+
+impl AuditTranscript {
+    /// Logs the domain separator `merlin::Transcript::new` appends.
+    pub fn new(label: &'static [u8]) -> AuditTranscript {
+        AuditTranscript {
+            transcript: Transcript::new(label),
+            log: AuditLog {
+                entries: vec![TranscriptEntry::Message {
+                    label: b"dom-sep",
+                    message: label.to_vec(),
+                }],
+            },
+        }
+    }
+
+    pub fn log(&self) -> &AuditLog {
+        &self.log
+    }
+
+    pub fn into_parts(self) -> (Transcript, AuditLog) {
+        (self.transcript, self.log)
+    }
+}
+
+impl TranscriptBackend for AuditTranscript {
+    fn append_message(&mut self, label: &'static [u8], message: &[u8]) {
+        self.log.entries.push(TranscriptEntry::Message {
+            label,
+            message: message.to_vec(),
+        });
+        self.transcript.append_message(label, message);
+    }
+
+    fn challenge_bytes(&mut self, label: &'static [u8], dest: &mut [u8]) {
+        self.log.entries.push(TranscriptEntry::Challenge {
+            label,
+            len: dest.len(),
+        });
+        self.transcript.challenge_bytes(label, dest);
+    }
+}
+
+/// Bytes packed into every field element, so the packing is injective.
+const CHUNK_BYTES: usize = 31;
+
+/// Packs `bytes`, prefixed by their length, into field elements.
+fn pack(bytes: &[u8]) -> Vec<Scalar> {
+    let mut out = vec![Scalar::from(bytes.len() as u64)];
+    out.extend(bytes.chunks(CHUNK_BYTES).map(|chunk| {
+        let mut buf = [0u8; 32];
+        buf[..chunk.len()].copy_from_slice(chunk);
+        Scalar::from_bytes_mod_order(buf)
+    }));
+    out
+}
+
+/// Sponge-like transcript over the MiMC hash: every message is absorbed into a
+/// single field element, and challenges are squeezed out of it.
+#[derive(Clone, Debug)]
+pub struct MimcTranscript {
+    state: Scalar,
+}
+
+impl MimcTranscript {
+    pub fn new(label: &'static [u8]) -> MimcTranscript {
+        let mut transcript = MimcTranscript {
+            state: Scalar::zero(),
+        };
+        transcript.append_message(b"dom-sep", label);
+        transcript
+    }
+
+    fn absorb(&mut self, label: &[u8], message: &[u8]) {
+        let mut inputs = vec![self.state];
+        inputs.extend(pack(label));
+        inputs.extend(pack(message));
+        self.state = mimc_hash(&inputs);
+    }
+
+    /// Absorbs the field element `v`, see `MimcTranscriptGadget::append_scalar`.
+    pub fn append_scalar(&mut self, label: &'static [u8], v: Scalar) {
+        self.state = mimc_hash(&[self.state, label_tag(label), v]);
+    }
+
+    /// Squeezes a challenge field element, see `MimcTranscriptGadget::challenge`.
+    pub fn challenge_field(&mut self, label: &'static [u8]) -> Scalar {
+        self.state = mimc_hash(&[self.state, label_tag(label)]);
+        self.state
+    }
+}
+
+/// Single field element standing for `label` when absorbing field elements.
+fn label_tag(label: &[u8]) -> Scalar {
+    mimc_hash(&pack(label))
+}
+
+impl TranscriptBackend for MimcTranscript {
+    fn append_message(&mut self, label: &'static [u8], message: &[u8]) {
+        self.absorb(label, message);
+    }
+
+    fn challenge_bytes(&mut self, label: &'static [u8], dest: &mut [u8]) {
+        self.absorb(label, &(dest.len() as u64).to_le_bytes());
+        // Only the low 31 bytes of every block are used, which are uniform up
+        // to a negligible bias.
+        for (i, chunk) in dest.chunks_mut(CHUNK_BYTES).enumerate() {
+            let block = mimc_hash(&[self.state, Scalar::from(i as u64)]);
+            chunk.copy_from_slice(&block.as_bytes()[..chunk.len()]);
+        }
+        // Ratchet, so the next challenges do not reuse the same blocks.
+        self.state = mimc_hash(&[self.state, -Scalar::one()]);
+    }
+}
+
+/// In-circuit recomputation of a `MimcTranscript`.
+///
+/// As long as only public data is absorbed, the state is a known constant and
+/// is updated natively, taking no multipliers. From the first committed value
+/// on, every absorbed field element takes `3 * ROUNDS` multipliers: absorbing a
+/// scalar takes three of them (one while the state is still constant), and a
+/// challenge two.
+pub struct MimcTranscriptGadget {
+    state: LC,
+    /// Value of the state while it does not depend on committed values.
+    constant: Option<Scalar>,
+    constants: Vec<Scalar>,
+}
+
+impl MimcTranscriptGadget {
+    /// Starts from the state of `transcript`, which may already hold public data.
+    pub fn new(transcript: &MimcTranscript) -> MimcTranscriptGadget {
+        MimcTranscriptGadget {
+            state: transcript.state.into(),
+            constant: Some(transcript.state),
+            constants: round_constants(),
+        }
+    }
+
+    /// Absorbs `inputs`, given along with their value when they are constants,
+    /// as `mimc_hash` does from the current state.
+    fn absorb(&mut self, cs: &mut dyn CS, inputs: Vec<(LC, Option<Scalar>)>) {
+        let mut h = LC::from(Scalar::zero());
+        let mut h_constant = Some(Scalar::zero());
+        let state = (self.state.clone(), self.constant);
+        for (m, m_constant) in std::iter::once(state).chain(inputs) {
+            match (h_constant, m_constant) {
+                (Some(h_value), Some(m_value)) => {
+                    let next = mimc(m_value, h_value, &self.constants) + h_value + m_value;
+                    h = next.into();
+                    h_constant = Some(next);
+                }
+                _ => {
+                    h = mimc_gadget(cs, m.clone(), h.clone(), &self.constants) + h + m;
+                    h_constant = None;
+                }
+            }
+        }
+        self.state = h;
+        self.constant = h_constant;
+    }
+
+    /// Absorbs public bytes, as `TranscriptBackend::append_message`.
+    pub fn append_message(&mut self, cs: &mut dyn CS, label: &'static [u8], message: &[u8]) {
+        let inputs = pack(label)
+            .into_iter()
+            .chain(pack(message))
+            .map(|v| (v.into(), Some(v)))
+            .collect();
+        self.absorb(cs, inputs);
+    }
+
+    /// Absorbs the (typically committed) value `v`, as `MimcTranscript::append_scalar`.
+    pub fn append_scalar(&mut self, cs: &mut dyn CS, label: &'static [u8], v: LC) {
+        let tag = label_tag(label);
+        self.absorb(cs, vec![(tag.into(), Some(tag)), (v, None)]);
+    }
+
+    /// Returns the LC of the challenge `MimcTranscript::challenge_field` draws.
+    pub fn challenge(&mut self, cs: &mut dyn CS, label: &'static [u8]) -> LC {
+        let tag = label_tag(label);
+        self.absorb(cs, vec![(tag.into(), Some(tag))]);
+        self.state.clone()
+    }
+}
+
+mod test {
+    use super::*;
+    use crate::gadgets::hash::ROUNDS;
+    use crate::recording::RecordingCS;
+
+    fn challenge<T: TranscriptBackend>(transcript: &mut T, message: &[u8]) -> Scalar {
+        transcript.append_message(b"message", message);
+        transcript.challenge_scalar(b"challenge")
+    }
+
+    #[test]
+    fn merlin_backend_is_merlin() {
+        let mut a = Transcript::new(b"Backend");
+        let mut b = Transcript::new(b"Backend");
+        TranscriptBackend::append_u64(&mut a, b"v", 7);
+        b.append_u64(b"v", 7);
+        let mut buf = [0u8; 64];
+        b.challenge_bytes(b"challenge", &mut buf);
+        assert_eq!(
+            a.challenge_scalar(b"challenge"),
+            Scalar::from_bytes_mod_order_wide(&buf)
+        );
+    }
+
+    #[test]
+    fn audit_transcript() {
+        let mut audited = AuditTranscript::new(b"Audit");
+        let c = challenge(&mut audited, b"hello");
+        assert_eq!(c, challenge(&mut Transcript::new(b"Audit"), b"hello"));
+        assert_eq!(
+            audited.log().entries(),
+            &[
+                TranscriptEntry::Message {
+                    label: b"dom-sep",
+                    message: b"Audit".to_vec(),
+                },
+                TranscriptEntry::Message {
+                    label: b"message",
+                    message: b"hello".to_vec(),
+                },
+                TranscriptEntry::Challenge {
+                    label: b"challenge",
+                    len: 64,
+                },
+            ]
+        );
+        assert_eq!(audited.log().messages(b"message"), vec![&b"hello"[..]]);
+    }
+
+    #[test]
+    fn mimc_backend() {
+        let c = challenge(&mut MimcTranscript::new(b"Backend"), b"hello");
+        assert_eq!(c, challenge(&mut MimcTranscript::new(b"Backend"), b"hello"));
+        assert_ne!(c, challenge(&mut MimcTranscript::new(b"Backend"), b"hellO"));
+        assert_ne!(c, challenge(&mut MimcTranscript::new(b"Other"), b"hello"));
+
+        // Successive challenges differ.
+        let mut transcript = MimcTranscript::new(b"Backend");
+        let first = challenge(&mut transcript, b"hello");
+        assert_ne!(first, transcript.challenge_scalar(b"challenge"));
+    }
+
+    /// Proves a Schnorr-like relation `r + c * x = s` over committed `r` and
+    /// `x`, with `c` derived from them in-circuit, and returns whether it holds.
+    fn challenge_recomputed(r: Scalar, x: Scalar, c: Scalar) -> (bool, usize) {
+        let mut cs = RecordingCS::new(b"Challenge");
+        let r_var = cs.commit(Some(r));
+        let x_var = cs.commit(Some(x));
+        let mut transcript = MimcTranscript::new(b"Sigma");
+        transcript.append_message(b"statement", b"public data");
+        let mut gadget = MimcTranscriptGadget::new(&transcript);
+        gadget.append_scalar(&mut cs, b"r", r_var.into());
+        gadget.append_scalar(&mut cs, b"x", x_var.into());
+        let c_lc = gadget.challenge(&mut cs, b"c");
+        let (_, _, cx) = cs.multiply(c_lc, x_var.into());
+        cs.constrain(LC::from(r_var) + cx - (r + c * x));
+        (cs.first_unsatisfied().is_none(), cs.multipliers())
+    }
+
+    #[test]
+    fn challenge_gadget() {
+        let (r, x) = (Scalar::from(11u64), Scalar::from(42u64));
+        let mut transcript = MimcTranscript::new(b"Sigma");
+        transcript.append_message(b"statement", b"public data");
+        transcript.append_scalar(b"r", r);
+        transcript.append_scalar(b"x", x);
+        let c = transcript.challenge_field(b"c");
+
+        let (satisfied, multipliers) = challenge_recomputed(r, x, c);
+        assert!(satisfied);
+        // One input for `r`, three for `x` and two for the challenge.
+        assert_eq!(multipliers, 6 * 3 * ROUNDS + 1);
+        assert!(!challenge_recomputed(r, x, c + Scalar::one()).0);
+    }
+}
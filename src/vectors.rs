@@ -0,0 +1,342 @@
+//! Known-answer test vectors for the shipped circuits.
+//!
+//! Vectors are generated from a seeded RNG (witnesses, blinding factors and points)
+//! and with fixed transcript labels, so the public inputs and the commitments are
+//! reproducible across implementations. The proof bytes are valid proofs but the
+//! backend draws its own prover randomness, so they are not byte-for-byte
+//! reproducible: implementations are expected to verify them, not regenerate them.
+//!
+//! The text format is one vector per block, blocks separated by an empty line:
+//!
+//! ```text
+//! circuit: <name>
+//! public: <hex> <hex> ...
+//! commitments: <hex> <hex> ...
+//! proof: <hex>
+//! ```
+use crate::gadgets::point::edwards_point::SonnyEdwardsPointGadget;
+use crate::gadgets::point::ristretto_point::SonnyRistrettoPointGadget;
+#[cfg(feature = "prover")]
+use crate::gadgets::point::witness;
+use crate::gadgets::sk_knowledge::sk_know_gadget::sk_knowledge_gadget;
+use crate::session;
+use bulletproofs::r1cs::{ConstraintSystem, R1CSError, R1CSProof, Variable, Verifier};
+#[cfg(feature = "prover")]
+use bulletproofs::r1cs::Prover;
+use bulletproofs::{BulletproofGens, PedersenGens};
+use curve25519_dalek::{ristretto::CompressedRistretto, scalar::Scalar};
+#[cfg(feature = "prover")]
+use rand::{rngs::StdRng, SeedableRng};
+use zerocaf::edwards::EdwardsPoint as SonnyEdwardsPoint;
+use zerocaf::field::FieldElement;
+use zerocaf::ristretto::RistrettoPoint as SonnyRistrettoPoint;
+#[cfg(feature = "prover")]
+use zerocaf::scalar::Scalar as SonnyScalar;
+
+/// Names of the circuits vectors are generated for.
+pub const CIRCUITS: [&str; 3] = ["point_equality", "edwards_scalar_mul", "sk_knowledge"];
+
+/// A proof for one of the shipped circuits together with everything needed to verify it.
+#[derive(Clone, Debug, PartialEq)]
+pub struct TestVector {
+    pub circuit: String,
+    /// Public inputs, each one a 32-byte encoding (point coordinates are encoded
+    /// as their four extended coordinates `X, Y, Z, T`).
+    pub public_inputs: Vec<[u8; 32]>,
+    pub commitments: Vec<CompressedRistretto>,
+    pub proof: Vec<u8>,
+}
+
+fn label(circuit: &str) -> Result<&'static [u8], R1CSError> {
+    match circuit {
+        "point_equality" => Ok(b"KAT point_equality"),
+        "edwards_scalar_mul" => Ok(b"KAT edwards_scalar_mul"),
+        "sk_knowledge" => Ok(b"KAT sk_knowledge"),
+        _ => Err(R1CSError::FormatError),
+    }
+}
+
+fn gens_capacity(circuit: &str) -> usize {
+    match circuit {
+        "point_equality" => 16,
+        _ => 8192,
+    }
+}
+
+#[cfg(feature = "prover")]
+fn point_to_inputs(p: &SonnyEdwardsPoint) -> Vec<[u8; 32]> {
+    vec![p.X.to_bytes(), p.Y.to_bytes(), p.Z.to_bytes(), p.T.to_bytes()]
+}
+
+fn point_from_inputs(inputs: &[[u8; 32]]) -> Result<SonnyEdwardsPoint, R1CSError> {
+    if inputs.len() != 4 {
+        return Err(R1CSError::FormatError);
+    }
+    Ok(SonnyEdwardsPoint {
+        X: FieldElement::from_bytes(&inputs[0]),
+        Y: FieldElement::from_bytes(&inputs[1]),
+        Z: FieldElement::from_bytes(&inputs[2]),
+        T: FieldElement::from_bytes(&inputs[3]),
+    })
+}
+
+/// Synthesizes the named circuit over the public inputs and the committed variables.
+fn synthesize(
+    circuit: &str,
+    cs: &mut dyn ConstraintSystem,
+    public_inputs: &[[u8; 32]],
+    vars: Vec<Variable>,
+) -> Result<(), R1CSError> {
+    match circuit {
+        "point_equality" => {
+            // Committed point equals the public one.
+            if vars.len() != 4 {
+                return Err(R1CSError::FormatError);
+            }
+            let p = point_from_inputs(public_inputs)?;
+            let committed = SonnyEdwardsPointGadget {
+                X: vars[0].into(),
+                Y: vars[1].into(),
+                Z: vars[2].into(),
+                T: vars[3].into(),
+            };
+            committed.equal(&SonnyEdwardsPointGadget::from_point(&p), cs);
+        }
+        "edwards_scalar_mul" => {
+            // Public `P` and `kP`, committed bits of `k`.
+            if public_inputs.len() != 8 {
+                return Err(R1CSError::FormatError);
+            }
+            let p = point_from_inputs(&public_inputs[..4])?;
+            let kp = point_from_inputs(&public_inputs[4..])?;
+            let (res, _) = SonnyEdwardsPointGadget::scalar_mul(
+                SonnyEdwardsPointGadget::from_point(&p),
+                None,
+                vars,
+                None,
+                cs,
+            );
+            res.equal(&SonnyEdwardsPointGadget::from_point(&kp), cs);
+        }
+        "sk_knowledge" => {
+            // Public base point and public key, committed bits of the secret key.
+            if public_inputs.len() != 8 {
+                return Err(R1CSError::FormatError);
+            }
+            let basep = SonnyRistrettoPoint(point_from_inputs(&public_inputs[..4])?);
+            let pk = SonnyRistrettoPoint(point_from_inputs(&public_inputs[4..])?);
+            let basep_gadget = SonnyRistrettoPointGadget::from_point(basep, cs);
+            let pk_gadget = SonnyRistrettoPointGadget::from_point(pk, cs);
+            sk_knowledge_gadget(cs, basep_gadget, pk_gadget, vars);
+        }
+        _ => return Err(R1CSError::FormatError),
+    }
+    Ok(())
+}
+
+#[cfg(feature = "prover")]
+fn prove(
+    circuit: &str,
+    public_inputs: Vec<[u8; 32]>,
+    witness: &[Scalar],
+    rng: &mut StdRng,
+) -> Result<TestVector, R1CSError> {
+    let pc_gens = PedersenGens::default();
+    let bp_gens = BulletproofGens::new(gens_capacity(circuit), 1);
+    let mut transcript = session::transcript(label(circuit)?);
+    let mut prover = Prover::new(&pc_gens, &mut transcript);
+
+    let (commitments, vars): (Vec<CompressedRistretto>, Vec<Variable>) = witness
+        .iter()
+        .map(|v| prover.commit(*v, Scalar::random(rng)))
+        .unzip();
+    synthesize(circuit, &mut prover, &public_inputs, vars)?;
+    let proof = prover.prove(&bp_gens)?;
+
+    Ok(TestVector {
+        circuit: circuit.to_string(),
+        public_inputs,
+        commitments,
+        proof: proof.to_bytes(),
+    })
+}
+
+/// Generates one vector per shipped circuit from the given seed.
+#[cfg(feature = "prover")]
+pub fn generate(seed: [u8; 32]) -> Result<Vec<TestVector>, R1CSError> {
+    let mut rng = StdRng::from_seed(seed);
+    let mut vectors = Vec::new();
+
+    let p = SonnyEdwardsPoint::new_random_point(&mut rng);
+    let coords: Vec<Scalar> = point_to_inputs(&p)
+        .into_iter()
+        .map(Scalar::from_bytes_mod_order)
+        .collect();
+    vectors.push(prove("point_equality", point_to_inputs(&p), &coords, &mut rng)?);
+
+    let p = SonnyEdwardsPoint::new_random_point(&mut rng);
+    let k = SonnyScalar::random(&mut rng).into_bits();
+    let kp = witness::edwards::scalar_mul(&p, &k);
+    let bits: Vec<Scalar> = k.iter().map(|bit| Scalar::from(*bit)).collect();
+    let mut public_inputs = point_to_inputs(&p);
+    public_inputs.extend(point_to_inputs(&kp));
+    vectors.push(prove("edwards_scalar_mul", public_inputs, &bits, &mut rng)?);
+
+    let basep = zerocaf::constants::RISTRETTO_BASEPOINT;
+    let sk = SonnyScalar::random(&mut rng).into_bits();
+    let pk = witness::ristretto::scalar_mul(&basep, &sk);
+    let bits: Vec<Scalar> = sk.iter().map(|bit| Scalar::from(*bit)).collect();
+    let mut public_inputs = point_to_inputs(&basep.0);
+    public_inputs.extend(point_to_inputs(&pk.0));
+    vectors.push(prove("sk_knowledge", public_inputs, &bits, &mut rng)?);
+
+    Ok(vectors)
+}
+
+/// Verifies the proof of a vector against its public inputs and commitments.
+pub fn verify(vector: &TestVector) -> Result<(), R1CSError> {
+    let circuit = vector.circuit.as_str();
+    let pc_gens = PedersenGens::default();
+    let bp_gens = BulletproofGens::new(gens_capacity(circuit), 1);
+    let proof = R1CSProof::from_bytes(&vector.proof)?;
+
+    let mut transcript = session::transcript(label(circuit)?);
+    let mut verifier = Verifier::new(&mut transcript);
+    let vars: Vec<Variable> = vector
+        .commitments
+        .iter()
+        .map(|V| verifier.commit(*V))
+        .collect();
+    synthesize(circuit, &mut verifier, &vector.public_inputs, vars)?;
+    verify_proof!(verifier, &proof, &pc_gens, &bp_gens)
+}
+
+fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+fn from_hex(s: &str) -> Result<Vec<u8>, R1CSError> {
+    if s.len() % 2 != 0 {
+        return Err(R1CSError::FormatError);
+    }
+    // Works on bytes: slicing the string could split a non-ASCII character.
+    let digit = |b: u8| {
+        (b as char)
+            .to_digit(16)
+            .map(|d| d as u8)
+            .ok_or(R1CSError::FormatError)
+    };
+    s.as_bytes()
+        .chunks(2)
+        .map(|pair| Ok(digit(pair[0])? << 4 | digit(pair[1])?))
+        .collect()
+}
+
+fn from_hex_32(s: &str) -> Result<[u8; 32], R1CSError> {
+    let bytes = from_hex(s)?;
+    if bytes.len() != 32 {
+        return Err(R1CSError::FormatError);
+    }
+    let mut res = [0u8; 32];
+    res.copy_from_slice(&bytes);
+    Ok(res)
+}
+
+/// Serializes the vectors in the text format described at the module level.
+pub fn to_string(vectors: &[TestVector]) -> String {
+    vectors
+        .iter()
+        .map(|v| {
+            format!(
+                "circuit: {}\npublic: {}\ncommitments: {}\nproof: {}\n",
+                v.circuit,
+                v.public_inputs
+                    .iter()
+                    .map(|i| to_hex(i))
+                    .collect::<Vec<_>>()
+                    .join(" "),
+                v.commitments
+                    .iter()
+                    .map(|c| to_hex(c.as_bytes()))
+                    .collect::<Vec<_>>()
+                    .join(" "),
+                to_hex(&v.proof)
+            )
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// Parses vectors written in the text format described at the module level.
+pub fn from_str(s: &str) -> Result<Vec<TestVector>, R1CSError> {
+    s.split("\n\n")
+        .filter(|block| !block.trim().is_empty())
+        .map(|block| {
+            let mut lines = block.lines();
+            let mut field = |name: &str| -> Result<String, R1CSError> {
+                let line = lines.next().ok_or(R1CSError::FormatError)?;
+                let prefix = format!("{}:", name);
+                if !line.starts_with(&prefix) {
+                    return Err(R1CSError::FormatError);
+                }
+                Ok(line[prefix.len()..].trim().to_string())
+            };
+            let circuit = field("circuit")?;
+            let public_inputs = field("public")?
+                .split_whitespace()
+                .map(from_hex_32)
+                .collect::<Result<_, _>>()?;
+            let commitments = field("commitments")?
+                .split_whitespace()
+                .map(|c| from_hex_32(c).map(CompressedRistretto))
+                .collect::<Result<_, _>>()?;
+            let proof = from_hex(&field("proof")?)?;
+            Ok(TestVector {
+                circuit,
+                public_inputs,
+                commitments,
+                proof,
+            })
+        })
+        .collect()
+}
+
+/// Reads the vectors stored at `path` and verifies all of them.
+pub fn verify_file<P: AsRef<std::path::Path>>(path: P) -> Result<(), R1CSError> {
+    let contents = std::fs::read_to_string(path).map_err(|e| R1CSError::GadgetError {
+        description: e.to_string(),
+    })?;
+    for vector in from_str(&contents)? {
+        verify(&vector)?;
+    }
+    Ok(())
+}
+
+#[cfg(feature = "prover")]
+mod test {
+    use super::*;
+
+    #[test]
+    fn vectors_roundtrip() {
+        let vectors = generate([7u8; 32]).unwrap();
+        let parsed = from_str(&to_string(&vectors)).unwrap();
+        assert!(parsed == vectors);
+        for vector in &parsed {
+            assert!(verify(vector).is_ok());
+        }
+        // Commitments only depend on the seed.
+        let again = generate([7u8; 32]).unwrap();
+        assert!(again
+            .iter()
+            .zip(vectors.iter())
+            .all(|(a, b)| a.commitments == b.commitments));
+    }
+
+    #[test]
+    fn malformed_hex() {
+        assert_eq!(from_hex("00ff7A").unwrap(), vec![0x00, 0xff, 0x7a]);
+        for s in &["0", "0g", "+f", "é0", "0é"] {
+            assert!(from_hex(s).is_err());
+        }
+    }
+}
@@ -0,0 +1,497 @@
+//! Witnesses that can be shipped to a remote prover.
+//!
+//! A `Witness` holds every secret a circuit needs: the values to commit, the
+//! auxiliary assignments its gadgets allocate, and a seed from which all the
+//! commitment blindings are derived. Since the blindings are not sampled by the
+//! prover, the party that generates the witness knows the commitments in
+//! advance (`Witness::commitments`), and a proving service that receives it
+//! reconstructs exactly the same prover.
+//!
+//! The encoding of a witness (`Witness::to_bytes`) is not encrypted: callers
+//! shipping it must seal it under a key shared with the service. With the
+//! `aead` feature, `Witness::seal` and `Witness::open` do so with
+//! ChaCha20-Poly1305. A `Witness` never prints its secrets and wipes them from
+//! memory when dropped.
+//!
+//! `PublishedCommitments` computes the commitments of a witness a few at a
+//! time, for a device that publishes them before proving (e.g. a mobile
+//! wallet), and can be saved in between so that computing them survives an
+//! interruption. It does not save any proving state: `Prover::commit`
+//! computes the commitment itself and there is no way to hand it a precomputed
+//! one, nor to restore the transcript of an interrupted `Prover`.
+//! `PublishedCommitments::prove` runs the whole proof from the start, and only
+//! checks that it commits to the published values.
+use crate::errors::GadgetError;
+use bulletproofs::r1cs::{ConstraintSystem, Prover, R1CSError, R1CSProof, Variable};
+use bulletproofs::{BulletproofGens, PedersenGens};
+#[cfg(feature = "aead")]
+use chacha20poly1305::aead::{Aead, NewAead};
+#[cfg(feature = "aead")]
+use chacha20poly1305::{ChaCha20Poly1305, Key, Nonce};
+use curve25519_dalek::{ristretto::CompressedRistretto, scalar::Scalar};
+use merlin::Transcript;
+use rand::{CryptoRng, Rng};
+use std::fmt;
+use zeroize::Zeroize;
+
+/// Header of the encoding of a witness.
+const MAGIC: &[u8] = b"bulletproofs_gadgets witness v1";
+
+/// Header of the encoding of `PublishedCommitments`.
+const PUBLISHED_MAGIC: &[u8] = b"bulletproofs_gadgets published commitments v1";
+
+/// Secret assignments of a circuit.
+#[derive(Clone, PartialEq)]
+pub struct Witness {
+    seed: [u8; 32],
+    committed: Vec<Scalar>,
+    auxiliary: Vec<Scalar>,
+}
+
+impl Witness {
+    /// Creates a witness with a fresh blinding seed.
+    pub fn new<R: Rng + CryptoRng>(
+        committed: Vec<Scalar>,
+        auxiliary: Vec<Scalar>,
+        rng: &mut R,
+    ) -> Witness {
+        let mut seed = [0u8; 32];
+        rng.fill_bytes(&mut seed);
+        Witness {
+            seed,
+            committed,
+            auxiliary,
+        }
+    }
+
+    /// Values committed as high-level variables, in commitment order.
+    pub fn committed(&self) -> &[Scalar] {
+        &self.committed
+    }
+
+    /// Assignments of the variables the circuit allocates itself.
+    pub fn auxiliary(&self) -> &[Scalar] {
+        &self.auxiliary
+    }
+
+    /// Blinding of the `i`-th commitment.
+    pub fn blinding(&self, i: usize) -> Scalar {
+        let mut transcript = Transcript::new(b"bulletproofs_gadgets witness blinding");
+        transcript.append_message(b"seed", &self.seed);
+        transcript.append_u64(b"index", i as u64);
+        let mut buf = [0u8; 64];
+        transcript.challenge_bytes(b"blinding", &mut buf);
+        Scalar::from_bytes_mod_order_wide(&buf)
+    }
+
+    /// Commitments the prover will output.
+    pub fn commitments(&self, pc_gens: &PedersenGens) -> Vec<CompressedRistretto> {
+        self.committed
+            .iter()
+            .enumerate()
+            .map(|(i, v)| pc_gens.commit(*v, self.blinding(i)).compress())
+            .collect()
+    }
+
+    /// Commits to the witness and proves `circuit`, which receives the
+    /// committed variables and the witness (`None` on the verifier side).
+    pub fn prove<F>(
+        &self,
+        pc_gens: &PedersenGens,
+        bp_gens: &BulletproofGens,
+        transcript: &mut Transcript,
+        circuit: F,
+    ) -> Result<(Vec<CompressedRistretto>, R1CSProof), R1CSError>
+    where
+        F: FnOnce(
+            &mut dyn ConstraintSystem,
+            Vec<Variable>,
+            Option<&Witness>,
+        ) -> Result<(), R1CSError>,
+    {
+        self.prove_checked(pc_gens, bp_gens, transcript, &[], circuit)
+    }
+
+    /// `prove`, failing before the synthesis if the first commitments differ
+    /// from `expected`.
+    fn prove_checked<F>(
+        &self,
+        pc_gens: &PedersenGens,
+        bp_gens: &BulletproofGens,
+        transcript: &mut Transcript,
+        expected: &[CompressedRistretto],
+        circuit: F,
+    ) -> Result<(Vec<CompressedRistretto>, R1CSProof), R1CSError>
+    where
+        F: FnOnce(
+            &mut dyn ConstraintSystem,
+            Vec<Variable>,
+            Option<&Witness>,
+        ) -> Result<(), R1CSError>,
+    {
+        let mut prover = Prover::new(pc_gens, transcript);
+        let (commitments, vars): (Vec<CompressedRistretto>, Vec<Variable>) = self
+            .committed
+            .iter()
+            .enumerate()
+            .map(|(i, v)| prover.commit(*v, self.blinding(i)))
+            .unzip();
+        if commitments.len() < expected.len() || commitments[..expected.len()] != *expected {
+            return Err(GadgetError::CommitmentsMismatch.into());
+        }
+        circuit(&mut prover, vars, Some(self))?;
+        let proof = prover.prove(bp_gens)?;
+        Ok((commitments, proof))
+    }
+
+    /// Encodes the witness, seed included.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut out = MAGIC.to_vec();
+        out.extend_from_slice(&self.seed);
+        for list in &[&self.committed, &self.auxiliary] {
+            out.extend_from_slice(&(list.len() as u64).to_le_bytes());
+            for v in list.iter() {
+                out.extend_from_slice(v.as_bytes());
+            }
+        }
+        out
+    }
+
+    /// Decodes a witness encoded by `to_bytes`.
+    pub fn from_bytes(bytes: &[u8]) -> Result<Witness, GadgetError> {
+        let mut reader = Reader(bytes);
+        if reader.take(MAGIC.len())? != MAGIC {
+            return Err(GadgetError::MalformedWitness);
+        }
+        let mut seed = [0u8; 32];
+        seed.copy_from_slice(reader.take(32)?);
+        let committed = reader.scalars()?;
+        let auxiliary = reader.scalars()?;
+        if !reader.0.is_empty() {
+            return Err(GadgetError::MalformedWitness);
+        }
+        Ok(Witness {
+            seed,
+            committed,
+            auxiliary,
+        })
+    }
+
+    /// Encrypts the encoding of the witness under `key` with
+    /// ChaCha20-Poly1305, prefixed with the random nonce.
+    #[cfg(feature = "aead")]
+    pub fn seal<R: Rng + CryptoRng>(&self, key: &[u8; 32], rng: &mut R) -> Vec<u8> {
+        let mut nonce = [0u8; 12];
+        rng.fill_bytes(&mut nonce);
+        let mut bytes = self.to_bytes();
+        let sealed = ChaCha20Poly1305::new(Key::from_slice(key))
+            .encrypt(Nonce::from_slice(&nonce), &bytes[..])
+            .expect("the witness fits in a ChaCha20-Poly1305 message");
+        bytes.zeroize();
+        let mut out = nonce.to_vec();
+        out.extend_from_slice(&sealed);
+        out
+    }
+
+    /// Decrypts a witness sealed by `seal` under `key`. Fails if it was sealed
+    /// under another key or tampered with.
+    #[cfg(feature = "aead")]
+    pub fn open(key: &[u8; 32], sealed: &[u8]) -> Result<Witness, GadgetError> {
+        if sealed.len() < 12 {
+            return Err(GadgetError::MalformedWitness);
+        }
+        let (nonce, ciphertext) = sealed.split_at(12);
+        let mut bytes = ChaCha20Poly1305::new(Key::from_slice(key))
+            .decrypt(Nonce::from_slice(nonce), ciphertext)
+            .map_err(|_| GadgetError::MalformedWitness)?;
+        let witness = Witness::from_bytes(&bytes);
+        bytes.zeroize();
+        witness
+    }
+}
+
+impl fmt::Debug for Witness {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Witness")
+            .field("seed", &"<redacted>")
+            .field(
+                "committed",
+                &format_args!("<{} redacted>", self.committed.len()),
+            )
+            .field(
+                "auxiliary",
+                &format_args!("<{} redacted>", self.auxiliary.len()),
+            )
+            .finish()
+    }
+}
+
+impl Drop for Witness {
+    fn drop(&mut self) {
+        self.seed.zeroize();
+        self.committed.zeroize();
+        self.auxiliary.zeroize();
+    }
+}
+
+/// Commitments of a witness computed ahead of its proof, see the module docs.
+#[derive(Clone, Debug, PartialEq)]
+pub struct PublishedCommitments {
+    witness: Witness,
+    commitments: Vec<CompressedRistretto>,
+}
+
+impl PublishedCommitments {
+    /// Starts computing the commitments of `witness`, with none computed yet.
+    pub fn new(witness: Witness) -> PublishedCommitments {
+        PublishedCommitments {
+            witness,
+            commitments: Vec::new(),
+        }
+    }
+
+    /// Commits to up to `n` more values. Returns whether every value is
+    /// committed.
+    pub fn commit_next(&mut self, pc_gens: &PedersenGens, n: usize) -> bool {
+        let start = self.commitments.len();
+        let end = (start + n).min(self.witness.committed.len());
+        for i in start..end {
+            let v = self.witness.committed[i];
+            self.commitments
+                .push(pc_gens.commit(v, self.witness.blinding(i)).compress());
+        }
+        self.is_complete()
+    }
+
+    pub fn is_complete(&self) -> bool {
+        self.commitments.len() == self.witness.committed.len()
+    }
+
+    /// Commitments computed so far, e.g. to publish them as they are.
+    pub fn commitments(&self) -> &[CompressedRistretto] {
+        &self.commitments
+    }
+
+    /// Proves `circuit` with `Witness::prove`, committing again to every
+    /// value (see the module docs). Fails with
+    /// `GadgetError::CommitmentsMismatch` if the commitments differ from the
+    /// published ones (e.g. the generators changed), before the circuit is
+    /// synthesized.
+    pub fn prove<F>(
+        &self,
+        pc_gens: &PedersenGens,
+        bp_gens: &BulletproofGens,
+        transcript: &mut Transcript,
+        circuit: F,
+    ) -> Result<(Vec<CompressedRistretto>, R1CSProof), R1CSError>
+    where
+        F: FnOnce(
+            &mut dyn ConstraintSystem,
+            Vec<Variable>,
+            Option<&Witness>,
+        ) -> Result<(), R1CSError>,
+    {
+        self.witness
+            .prove_checked(pc_gens, bp_gens, transcript, &self.commitments, circuit)
+    }
+
+    /// Encodes the commitments along with the witness and its seed: the
+    /// encoding must be stored as securely as the witness.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let witness = self.witness.to_bytes();
+        let mut out = PUBLISHED_MAGIC.to_vec();
+        out.extend_from_slice(&(witness.len() as u64).to_le_bytes());
+        out.extend_from_slice(&witness);
+        out.extend_from_slice(&(self.commitments.len() as u64).to_le_bytes());
+        for c in &self.commitments {
+            out.extend_from_slice(c.as_bytes());
+        }
+        out
+    }
+
+    /// Decodes commitments encoded by `to_bytes`.
+    pub fn from_bytes(bytes: &[u8]) -> Result<PublishedCommitments, GadgetError> {
+        let mut reader = Reader(bytes);
+        if reader.take(PUBLISHED_MAGIC.len())? != PUBLISHED_MAGIC {
+            return Err(GadgetError::MalformedWitness);
+        }
+        let len = reader.length()?;
+        let witness = Witness::from_bytes(reader.take(len)?)?;
+        let n = reader.length()?;
+        if n > witness.committed.len() || reader.0.len() != 32 * n {
+            return Err(GadgetError::MalformedWitness);
+        }
+        let commitments = (0..n)
+            .map(|_| CompressedRistretto::from_slice(reader.take(32).unwrap()))
+            .collect();
+        Ok(PublishedCommitments {
+            witness,
+            commitments,
+        })
+    }
+}
+
+struct Reader<'a>(&'a [u8]);
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, n: usize) -> Result<&'a [u8], GadgetError> {
+        if self.0.len() < n {
+            return Err(GadgetError::MalformedWitness);
+        }
+        let (head, tail) = self.0.split_at(n);
+        self.0 = tail;
+        Ok(head)
+    }
+
+    fn length(&mut self) -> Result<usize, GadgetError> {
+        let mut len = [0u8; 8];
+        len.copy_from_slice(self.take(8)?);
+        Ok(u64::from_le_bytes(len) as usize)
+    }
+
+    fn scalars(&mut self) -> Result<Vec<Scalar>, GadgetError> {
+        let len = self.length()?;
+        // Rejects lengths the input cannot hold before allocating.
+        if self.0.len() / 32 < len {
+            return Err(GadgetError::MalformedWitness);
+        }
+        (0..len)
+            .map(|_| {
+                let mut buf = [0u8; 32];
+                buf.copy_from_slice(self.take(32)?);
+                Scalar::from_canonical_bytes(buf).ok_or(GadgetError::MalformedWitness)
+            })
+            .collect()
+    }
+}
+
+mod test {
+    use super::*;
+    use crate::session;
+    use bulletproofs::r1cs::Verifier;
+
+    /// Proves knowledge of a square root of the committed value.
+    fn square_root(
+        cs: &mut dyn ConstraintSystem,
+        vars: Vec<Variable>,
+        witness: Option<&Witness>,
+    ) -> Result<(), R1CSError> {
+        let r = witness.map(|w| w.auxiliary()[0]);
+        let (a, b, o) = cs.allocate_multiplier(r.map(|r| (r, r)))?;
+        cs.constrain(a - b);
+        cs.constrain(o - vars[0]);
+        Ok(())
+    }
+
+    #[test]
+    fn remote_proving() {
+        let pc_gens = PedersenGens::default();
+        let bp_gens = BulletproofGens::new(8, 1);
+        let mut rng = rand::thread_rng();
+
+        // Client side.
+        let r = Scalar::from(5u64);
+        let witness = Witness::new(vec![r * r], vec![r], &mut rng);
+        let expected = witness.commitments(&pc_gens);
+        let sent = witness.to_bytes();
+
+        // Service side.
+        let received = Witness::from_bytes(&sent).unwrap();
+        assert_eq!(received, witness);
+        let (commitments, proof) = received
+            .prove(
+                &pc_gens,
+                &bp_gens,
+                &mut session::transcript(b"Witness"),
+                square_root,
+            )
+            .unwrap();
+        assert_eq!(commitments, expected);
+
+        let mut transcript = session::transcript(b"Witness");
+        let mut verifier = Verifier::new(&mut transcript);
+        let vars = commitments.iter().map(|V| verifier.commit(*V)).collect();
+        square_root(&mut verifier, vars, None).unwrap();
+        assert!(verify_proof!(verifier, &proof, &pc_gens, &bp_gens).is_ok());
+    }
+
+    #[test]
+    fn published_commitments() {
+        let pc_gens = PedersenGens::default();
+        let bp_gens = BulletproofGens::new(8, 1);
+        let r = Scalar::from(5u64);
+        let committed = vec![r * r, Scalar::one(), Scalar::from(2u64)];
+        let witness = Witness::new(committed, vec![r], &mut rand::thread_rng());
+
+        // Interrupted after the first commitment.
+        let mut published = PublishedCommitments::new(witness.clone());
+        assert!(!published.commit_next(&pc_gens, 1));
+        let saved = published.to_bytes();
+        assert!(PublishedCommitments::from_bytes(&saved[..saved.len() - 1]).is_err());
+
+        let mut resumed = PublishedCommitments::from_bytes(&saved).unwrap();
+        assert_eq!(resumed, published);
+        assert!(resumed.commit_next(&pc_gens, 5));
+        assert_eq!(resumed.commitments(), &witness.commitments(&pc_gens)[..]);
+        let (commitments, proof) = resumed
+            .prove(
+                &pc_gens,
+                &bp_gens,
+                &mut session::transcript(b"Witness"),
+                square_root,
+            )
+            .unwrap();
+        assert_eq!(commitments, resumed.commitments());
+
+        let mut transcript = session::transcript(b"Witness");
+        let mut verifier = Verifier::new(&mut transcript);
+        let vars = commitments.iter().map(|V| verifier.commit(*V)).collect();
+        square_root(&mut verifier, vars, None).unwrap();
+        assert!(verify_proof!(verifier, &proof, &pc_gens, &bp_gens).is_ok());
+
+        // Proving with other generators.
+        let other_gens = PedersenGens {
+            B: pc_gens.B_blinding,
+            B_blinding: pc_gens.B,
+        };
+        assert!(published
+            .prove(
+                &other_gens,
+                &bp_gens,
+                &mut session::transcript(b"Witness"),
+                square_root,
+            )
+            .is_err());
+    }
+
+    #[test]
+    fn malformed_witness() {
+        let witness = Witness::new(vec![Scalar::one()], vec![], &mut rand::thread_rng());
+        assert_eq!(
+            format!("{:?}", witness),
+            r#"Witness { seed: "<redacted>", committed: <1 redacted>, auxiliary: <0 redacted> }"#
+        );
+
+        let bytes = witness.to_bytes();
+        assert_eq!(Witness::from_bytes(&bytes), Ok(witness));
+        assert!(Witness::from_bytes(&bytes[..bytes.len() - 1]).is_err());
+    }
+
+    #[test]
+    #[cfg(feature = "aead")]
+    fn sealed_witness() {
+        let mut rng = rand::thread_rng();
+        let witness = Witness::new(vec![Scalar::one()], vec![Scalar::one()], &mut rng);
+        let key = [7u8; 32];
+        let mut sealed = witness.seal(&key, &mut rng);
+        assert_eq!(Witness::open(&key, &sealed), Ok(witness));
+
+        assert!(Witness::open(&[8u8; 32], &sealed).is_err());
+        let last = sealed.len() - 1;
+        sealed[last] ^= 1;
+        assert_eq!(
+            Witness::open(&key, &sealed),
+            Err(GadgetError::MalformedWitness)
+        );
+    }
+}
@@ -0,0 +1,77 @@
+#![cfg(feature = "testing")]
+#[macro_use]
+extern crate bulletproofs_gadgets;
+extern crate curve25519_dalek;
+extern crate rand;
+extern crate zerocaf;
+
+use bulletproofs_gadgets::gadgets::point::edwards_point::{
+    IdentityPolicy, SonnyEdwardsPointGadget,
+};
+use curve25519_dalek::scalar::Scalar;
+use zerocaf::edwards::EdwardsPoint as SonnyEdwardsPoint;
+
+fn random_gadget() -> SonnyEdwardsPointGadget {
+    SonnyEdwardsPointGadget::from_point(&SonnyEdwardsPoint::new_random_point(
+        &mut rand::thread_rng(),
+    ))
+}
+
+#[test]
+fn edwards_point_gadgets_shape() {
+    let (p, q) = (random_gadget(), random_gadget());
+
+    assert_circuit_shape!(
+        |cs| {
+            p.add(&q, cs);
+        },
+        multipliers = 12,
+        constraints = 30
+    );
+    assert_circuit_shape!(
+        |cs| {
+            p.double(cs);
+        },
+        multipliers = 10,
+        constraints = 24
+    );
+    assert_circuit_shape!(
+        |cs| {
+            p.mul_by_pow2(3, cs);
+        },
+        multipliers = 22,
+        constraints = 44
+    );
+    // `16P - P`.
+    assert_circuit_shape!(
+        |cs| {
+            p.mul_by_const_scalar(15, cs);
+        },
+        multipliers = 41,
+        constraints = 88
+    );
+    let (r, s) = (
+        SonnyEdwardsPoint::new_random_point(&mut rand::thread_rng()),
+        SonnyEdwardsPoint::new_random_point(&mut rand::thread_rng()),
+    );
+    let (r_gadget, s_gadget) = (
+        SonnyEdwardsPointGadget::from_point(&r),
+        SonnyEdwardsPointGadget::from_point(&s),
+    );
+    assert_circuit_shape!(
+        |cs| {
+            r_gadget.add_with_policy(&s_gadget, IdentityPolicy::Forbid, Some((r, s)), cs);
+        },
+        multipliers = 11,
+        constraints = 21
+    );
+    assert_circuit_shape!(|cs| p.equal(&q, cs), multipliers = 4, constraints = 10);
+    assert_circuit_shape!(|cs| p.satisfy_curve_eq(cs), multipliers = 8, constraints = 19);
+    assert_circuit_shape!(
+        |cs| {
+            p.conditionally_select(Scalar::one().into(), cs);
+        },
+        multipliers = 4,
+        constraints = 10
+    );
+}
@@ -1,3 +1,4 @@
+#![cfg(feature = "prover")]
 extern crate bulletproofs;
 extern crate bulletproofs_gadgets;
 extern crate curve25519_dalek;
@@ -5,14 +6,19 @@ extern crate merlin;
 extern crate rand;
 extern crate zerocaf;
 
-use bulletproofs::r1cs::{Prover, R1CSError, R1CSProof, Verifier};
+use bulletproofs::r1cs::{ConstraintSystem, Prover, R1CSError, R1CSProof, Variable, Verifier};
 use bulletproofs::{BulletproofGens, PedersenGens};
 use bulletproofs_gadgets::gadgets::point::edwards_point::*;
+use bulletproofs_gadgets::gadgets::point::witness;
+use bulletproofs_gadgets::recording::RecordingCS;
 use curve25519_dalek::ristretto::CompressedRistretto;
 use curve25519_dalek::scalar::Scalar;
 use merlin::Transcript;
 use zerocaf::traits::ops::Double;
-use zerocaf::{edwards::EdwardsPoint as SonnyEdwardsPoint, field::FieldElement};
+use zerocaf::{
+    edwards::EdwardsPoint as SonnyEdwardsPoint, field::FieldElement,
+    ristretto::RistrettoPoint as SonnyRistrettoPoint, scalar::Scalar as SonnyScalar,
+};
 
 ///////////////// Conditional Selection /////////////////
 fn cond_select_proof(
@@ -277,6 +283,29 @@ fn test_point_committing() {
     assert!(point_committing_roundtrip_helper(A, B).is_err());
 }
 
+#[test]
+fn verifier_commit_rejects_wrong_commitments_length() {
+    let mut transcript = Transcript::new(b"PointCommitments");
+    let mut verifier = Verifier::new(&mut transcript);
+    let commitments = vec![CompressedRistretto::default(); 7];
+
+    assert!(
+        SonnyEdwardsPointGadget::verifier_commit_to_sonny_edwards_point(
+            &mut verifier,
+            &commitments[..3]
+        )
+        .is_err()
+    );
+    assert!(
+        SonnyEdwardsPointGadget::verifier_commit_to_sonny_edwards_points(
+            &mut verifier,
+            &commitments,
+            2
+        )
+        .is_err()
+    );
+}
+
 fn point_committing_roundtrip_helper(
     p1: SonnyEdwardsPoint,
     p2: SonnyEdwardsPoint,
@@ -332,20 +361,57 @@ fn point_committing_verify(
     // Create the verifier
     let mut verifier = Verifier::new(&mut transcript);
 
-    let points: Vec<&[CompressedRistretto]> = commitments.chunks(4).collect();
-    let P1_Gadget =
-        SonnyEdwardsPointGadget::verifier_commit_to_sonny_edwards_point(&mut verifier, points[0]);
-    let P2_Gadget =
-        SonnyEdwardsPointGadget::verifier_commit_to_sonny_edwards_point(&mut verifier, points[1]);
+    let points = SonnyEdwardsPointGadget::verifier_commit_to_sonny_edwards_points(
+        &mut verifier,
+        &commitments,
+        2,
+    )?;
+    let (P1_Gadget, P2_Gadget) = (&points[0], &points[1]);
 
     // Ensure we have the points are equal
-    P1_Gadget.equal(&P2_Gadget, &mut verifier);
+    P1_Gadget.equal(P2_Gadget, &mut verifier);
 
     verifier
         .verify(&proof, &pc_gens, &bp_gens, &mut rand::thread_rng())
         .map_err(|_| R1CSError::VerificationError)
 }
 
+///////////////// Ristretto conversion of committed points /////////////////
+
+#[test]
+fn into_ristretto_roundtrip() {
+    let P = SonnyRistrettoPoint::new_random_point(&mut rand::thread_rng()).0;
+    let off_curve = SonnyEdwardsPoint {
+        X: P.X + FieldElement::one(),
+        ..P
+    };
+    assert!(into_ristretto_roundtrip_helper(P).is_ok());
+    assert!(into_ristretto_roundtrip_helper(off_curve).is_err());
+}
+
+fn into_ristretto_roundtrip_helper(point: SonnyEdwardsPoint) -> Result<(), R1CSError> {
+    let pc_gens = PedersenGens::default();
+    let bp_gens = BulletproofGens::new(128, 1);
+
+    let mut transcript = Transcript::new(b"IntoRistretto");
+    let mut prover = Prover::new(&pc_gens, &mut transcript);
+    let (gadget, commitments) =
+        SonnyEdwardsPointGadget::prover_commit_to_sonny_edwards_point(&mut prover, &point);
+    gadget.into_ristretto(&mut prover, Some(point));
+    let proof = prover.prove(&bp_gens)?;
+
+    let mut transcript = Transcript::new(b"IntoRistretto");
+    let mut verifier = Verifier::new(&mut transcript);
+    let gadget = SonnyEdwardsPointGadget::verifier_commit_to_sonny_edwards_point(
+        &mut verifier,
+        &commitments,
+    )?;
+    gadget.into_ristretto(&mut verifier, None);
+    verifier
+        .verify(&proof, &pc_gens, &bp_gens, &mut rand::thread_rng())
+        .map_err(|_| R1CSError::VerificationError)
+}
+
 ///////////////// Curve eq satisfy constraint /////////////////
 
 #[test]
@@ -412,3 +478,408 @@ fn curve_eq_verify(
         .verify(&proof, &pc_gens, &bp_gens, &mut rand::thread_rng())
         .map_err(|_| R1CSError::VerificationError)
 }
+
+///////////////// Scalar multiplication with witness /////////////////
+
+#[test]
+fn scalar_mul_with_witness() {
+    let P = SonnyEdwardsPoint::new_random_point(&mut rand::thread_rng());
+    let k = SonnyScalar::random(&mut rand::thread_rng());
+    let bad_point = SonnyEdwardsPoint::new_random_point(&mut rand::thread_rng());
+    assert!(scalar_mul_roundtrip_helper(P, k, P * k).is_ok());
+    assert!(scalar_mul_roundtrip_helper(P, k, bad_point).is_err());
+}
+
+fn scalar_mul_roundtrip_helper(
+    P: SonnyEdwardsPoint,
+    k: SonnyScalar,
+    kP: SonnyEdwardsPoint,
+) -> Result<(), R1CSError> {
+    // Common
+    let pc_gens = PedersenGens::default();
+    let bp_gens = BulletproofGens::new(8192, 1);
+
+    let (proof, commitments) = scalar_mul_proof(&pc_gens, &bp_gens, P, k, kP)?;
+
+    scalar_mul_verify(&pc_gens, &bp_gens, P, kP, proof, commitments)
+}
+
+// Proves that k * P = kP
+fn scalar_mul_proof(
+    pc_gens: &PedersenGens,
+    bp_gens: &BulletproofGens,
+    P: SonnyEdwardsPoint,
+    k: SonnyScalar,
+    kP: SonnyEdwardsPoint,
+) -> Result<(R1CSProof, Vec<CompressedRistretto>), R1CSError> {
+    let mut transcript = Transcript::new(b"ScalarMul");
+
+    // 1. Create a prover
+    let mut prover = Prover::new(pc_gens, &mut transcript);
+
+    // 2. Commit high-level variables
+    let bits = k.into_bits();
+    let (commitments, bit_vars): (Vec<CompressedRistretto>, Vec<Variable>) = bits
+        .iter()
+        .map(|bit| prover.commit(Scalar::from(*bit), Scalar::random(&mut rand::thread_rng())))
+        .unzip();
+
+    // 3. Compute k * P and the expected result natively
+    let p_gadget = SonnyEdwardsPointGadget::from_point(&P);
+    let (kp_gadget, expected) =
+        SonnyEdwardsPointGadget::scalar_mul(p_gadget, Some(P), bit_vars, Some(&bits), &mut prover);
+    // The native witness must be the same point as the one computed with zerocaf.
+    assert!(expected.unwrap() == P * k);
+    kp_gadget.equal(&SonnyEdwardsPointGadget::from_point(&kP), &mut prover);
+
+    let proof = prover.prove(bp_gens)?;
+    Ok((proof, commitments))
+}
+
+fn scalar_mul_verify(
+    pc_gens: &PedersenGens,
+    bp_gens: &BulletproofGens,
+    P: SonnyEdwardsPoint,
+    kP: SonnyEdwardsPoint,
+    proof: R1CSProof,
+    commitments: Vec<CompressedRistretto>,
+) -> Result<(), R1CSError> {
+    let mut transcript = Transcript::new(b"ScalarMul");
+
+    // Create the verifier
+    let mut verifier = Verifier::new(&mut transcript);
+
+    let bit_vars: Vec<Variable> = commitments.iter().map(|V| verifier.commit(*V)).collect();
+
+    let p_gadget = SonnyEdwardsPointGadget::from_point(&P);
+    let (kp_gadget, _) =
+        SonnyEdwardsPointGadget::scalar_mul(p_gadget, None, bit_vars, None, &mut verifier);
+    kp_gadget.equal(&SonnyEdwardsPointGadget::from_point(&kP), &mut verifier);
+
+    verifier
+        .verify(&proof, &pc_gens, &bp_gens, &mut rand::thread_rng())
+        .map_err(|_| R1CSError::VerificationError)
+}
+
+///////////////// Intermediate results of scalar multiplications /////////////////
+
+fn scalar_mul_prefix_roundtrip_helper(
+    p: SonnyEdwardsPoint,
+    bits: &[u8],
+    i: usize,
+    res: SonnyEdwardsPoint,
+) -> Result<(), R1CSError> {
+    let pc_gens = PedersenGens::default();
+    let bp_gens = BulletproofGens::new(512, 1);
+
+    // Proves that the `i`-th accumulator of the ladder computing k * P is res
+    let mut transcript = Transcript::new(b"ScalarMulPrefix");
+    let mut prover = Prover::new(&pc_gens, &mut transcript);
+    let (commitments, bit_vars): (Vec<CompressedRistretto>, Vec<Variable>) = bits
+        .iter()
+        .map(|bit| prover.commit(Scalar::from(*bit), Scalar::random(&mut rand::thread_rng())))
+        .unzip();
+    let mut prefixes = SonnyEdwardsPointGadget::scalar_mul_prefixes(
+        SonnyEdwardsPointGadget::from_point(&p),
+        Some(p),
+        bit_vars,
+        Some(bits),
+        &mut prover,
+    );
+    assert_eq!(prefixes.len(), bits.len());
+    let (prefix, prefix_assign) = prefixes.remove(i);
+    assert!(
+        prefix_assign.unwrap() == witness::edwards::scalar_mul(&p, &bits[bits.len() - 1 - i..])
+    );
+    prefix.equal(&SonnyEdwardsPointGadget::from_point(&res), &mut prover);
+    let proof = prover.prove(&bp_gens)?;
+
+    let mut transcript = Transcript::new(b"ScalarMulPrefix");
+    let mut verifier = Verifier::new(&mut transcript);
+    let bit_vars: Vec<Variable> = commitments.iter().map(|V| verifier.commit(*V)).collect();
+    let (prefix, _) = SonnyEdwardsPointGadget::scalar_mul_prefixes(
+        SonnyEdwardsPointGadget::from_point(&p),
+        None,
+        bit_vars,
+        None,
+        &mut verifier,
+    )
+    .remove(i);
+    prefix.equal(&SonnyEdwardsPointGadget::from_point(&res), &mut verifier);
+    verifier.verify(&proof, &pc_gens, &bp_gens, &mut rand::thread_rng())
+}
+
+#[test]
+fn scalar_mul_prefixes() {
+    let p = SonnyEdwardsPoint::new_random_point(&mut rand::thread_rng());
+    // k = 0b1011_0101, least significant bit first
+    let bits = [1, 0, 1, 0, 1, 1, 0, 1];
+    // The 3 most significant bits: 0b101
+    let res = witness::edwards::scalar_mul(&p, &[1, 0, 1]);
+    assert!(scalar_mul_prefix_roundtrip_helper(p, &bits, 2, res).is_ok());
+    assert!(scalar_mul_prefix_roundtrip_helper(p, &bits, 3, res).is_err());
+    let k = witness::edwards::scalar_mul(&p, &bits);
+    assert!(scalar_mul_prefix_roundtrip_helper(p, &bits, 7, k).is_ok());
+}
+
+///////////////// Doubling chains with public points /////////////////
+
+fn mul_by_pow2_roundtrip_helper(
+    p: SonnyEdwardsPoint,
+    k: usize,
+    res: SonnyEdwardsPoint,
+) -> Result<(), R1CSError> {
+    // Common
+    let pc_gens = PedersenGens::default();
+    let bp_gens = BulletproofGens::new(64, 1);
+
+    // Proves that [2^k]P = res, T coordinate included
+    let circuit = |cs: &mut dyn ConstraintSystem| {
+        let r = SonnyEdwardsPointGadget::from_point(&p).mul_by_pow2(k, cs);
+        r.equal_extended(&SonnyEdwardsPointGadget::from_point(&res), cs);
+    };
+
+    let mut transcript = Transcript::new(b"MulByPow2");
+    let mut prover = Prover::new(&pc_gens, &mut transcript);
+    circuit(&mut prover);
+    let proof = prover.prove(&bp_gens)?;
+
+    let mut transcript = Transcript::new(b"MulByPow2");
+    let mut verifier = Verifier::new(&mut transcript);
+    circuit(&mut verifier);
+    verifier.verify(&proof, &pc_gens, &bp_gens, &mut rand::thread_rng())
+}
+
+#[test]
+fn mul_by_pow2() {
+    let p = SonnyEdwardsPoint::new_random_point(&mut rand::thread_rng());
+    for k in &[0, 1, 4] {
+        let res = (0..*k).fold(p, |acc, _| acc.double());
+        assert!(mul_by_pow2_roundtrip_helper(p, *k, res).is_ok());
+        assert!(mul_by_pow2_roundtrip_helper(p, *k, res.double()).is_err());
+    }
+}
+
+/// Checks that the extended coordinates of `gadget` evaluate to those of
+/// `native`, and that the circuit is satisfied.
+fn assert_matches_witness(
+    cs: &RecordingCS,
+    gadget: &SonnyEdwardsPointGadget,
+    native: &SonnyEdwardsPoint,
+) {
+    let coords = [&gadget.X, &gadget.Y, &gadget.Z, &gadget.T];
+    let natives = [native.X, native.Y, native.Z, native.T];
+    for (lc, native) in coords.iter().zip(natives.iter()) {
+        assert_eq!(
+            cs.eval(lc),
+            Some(Scalar::from_bytes_mod_order(native.to_bytes()))
+        );
+    }
+    assert!(cs.first_unsatisfied().is_none());
+}
+
+#[test]
+fn mul_by_pow2_matches_witness() {
+    let p = SonnyEdwardsPoint::new_random_point(&mut rand::thread_rng());
+    for k in 0..8 {
+        let mut cs = RecordingCS::new(b"MulByPow2");
+        let r = SonnyEdwardsPointGadget::from_point(&p).mul_by_pow2(k, &mut cs);
+        assert_matches_witness(&cs, &r, &witness::edwards::mul_by_pow2(&p, k));
+    }
+}
+
+///////////////// Multiplications by constant scalars /////////////////
+
+fn mul_by_const_scalar_roundtrip_helper(
+    p: SonnyEdwardsPoint,
+    k: u64,
+    res: SonnyEdwardsPoint,
+) -> Result<(), R1CSError> {
+    // Common
+    let pc_gens = PedersenGens::default();
+    let bp_gens = BulletproofGens::new(256, 1);
+
+    // Proves that [k]P = res
+    let circuit = |cs: &mut dyn ConstraintSystem| {
+        let r = SonnyEdwardsPointGadget::from_point(&p).mul_by_const_scalar(k, cs);
+        r.equal(&SonnyEdwardsPointGadget::from_point(&res), cs);
+    };
+
+    let mut transcript = Transcript::new(b"MulByConstScalar");
+    let mut prover = Prover::new(&pc_gens, &mut transcript);
+    circuit(&mut prover);
+    let proof = prover.prove(&bp_gens)?;
+
+    let mut transcript = Transcript::new(b"MulByConstScalar");
+    let mut verifier = Verifier::new(&mut transcript);
+    circuit(&mut verifier);
+    verifier.verify(&proof, &pc_gens, &bp_gens, &mut rand::thread_rng())
+}
+
+#[test]
+fn mul_by_const_scalar() {
+    let p = SonnyEdwardsPoint::new_random_point(&mut rand::thread_rng());
+    for k in &[1u64, 8, 15, 23] {
+        let res = (1..*k).fold(p, |acc, _| acc + p);
+        assert!(mul_by_const_scalar_roundtrip_helper(p, *k, res).is_ok());
+        assert!(mul_by_const_scalar_roundtrip_helper(p, *k, res + p).is_err());
+    }
+}
+
+#[test]
+fn mul_by_const_scalar_matches_witness() {
+    let p = SonnyEdwardsPoint::new_random_point(&mut rand::thread_rng());
+    let large = [255u64, 256, 1 << 32, (1 << 32) - 1, u64::MAX];
+    for k in (0..=128u64).chain(large.iter().cloned()) {
+        let mut cs = RecordingCS::new(b"MulByConstScalar");
+        let r = SonnyEdwardsPointGadget::from_point(&p).mul_by_const_scalar(k, &mut cs);
+        assert_matches_witness(&cs, &r, &witness::edwards::mul_by_const_scalar(&p, k));
+    }
+}
+
+///////////////// Additions with an identity policy /////////////////
+
+fn add_with_policy_roundtrip_helper(
+    p: SonnyEdwardsPoint,
+    q: SonnyEdwardsPoint,
+    policy: IdentityPolicy,
+    res: SonnyEdwardsPoint,
+) -> Result<(), R1CSError> {
+    // Common
+    let pc_gens = PedersenGens::default();
+    let bp_gens = BulletproofGens::new(32, 1);
+
+    // Proves that P + Q = res
+    let circuit = |cs: &mut dyn ConstraintSystem, assign: Option<_>| {
+        let p_gadget = SonnyEdwardsPointGadget::from_point(&p);
+        let q_gadget = SonnyEdwardsPointGadget::from_point(&q);
+        let r = p_gadget.add_with_policy(&q_gadget, policy, assign, cs);
+        r.equal(&SonnyEdwardsPointGadget::from_point(&res), cs);
+    };
+
+    let mut transcript = Transcript::new(b"AddWithPolicy");
+    let mut prover = Prover::new(&pc_gens, &mut transcript);
+    circuit(&mut prover, Some((p, q)));
+    let proof = prover.prove(&bp_gens)?;
+
+    let mut transcript = Transcript::new(b"AddWithPolicy");
+    let mut verifier = Verifier::new(&mut transcript);
+    circuit(&mut verifier, None);
+    verifier.verify(&proof, &pc_gens, &bp_gens, &mut rand::thread_rng())
+}
+
+#[test]
+fn add_with_policy() {
+    let p = SonnyEdwardsPoint::new_random_point(&mut rand::thread_rng());
+    let q = SonnyEdwardsPoint::new_random_point(&mut rand::thread_rng());
+    for policy in &[IdentityPolicy::Forbid, IdentityPolicy::Allow] {
+        assert!(add_with_policy_roundtrip_helper(p, q, *policy, p + q).is_ok());
+        assert!(add_with_policy_roundtrip_helper(p, q, *policy, p + p).is_err());
+    }
+    assert!(add_with_policy_roundtrip_helper(p, p, IdentityPolicy::Allow, p.double()).is_ok());
+}
+
+#[test]
+#[should_panic]
+fn dedicated_doubling() {
+    let p = SonnyEdwardsPoint::new_random_point(&mut rand::thread_rng());
+    let _ = add_with_policy_roundtrip_helper(p, p, IdentityPolicy::Forbid, p.double());
+}
+
+///////////////// Affine x-coordinate of a committed point /////////////////
+
+fn affine_x_roundtrip_helper(p: SonnyEdwardsPoint, x: Scalar) -> Result<(), R1CSError> {
+    // Common
+    let pc_gens = PedersenGens::default();
+    let bp_gens = BulletproofGens::new(16, 1);
+
+    // Proves that the committed x is the affine x-coordinate of the committed P
+    let mut transcript = Transcript::new(b"AffineX");
+    let mut prover = Prover::new(&pc_gens, &mut transcript);
+    let (p_gadget, mut commitments) =
+        SonnyEdwardsPointGadget::prover_commit_to_sonny_edwards_point(&mut prover, &p);
+    let (x_commitment, x_var) = prover.commit(x, Scalar::random(&mut rand::thread_rng()));
+    commitments.push(x_commitment);
+    p_gadget.equal_affine_x(x_var.into(), Some(p), &mut prover);
+    let proof = prover.prove(&bp_gens)?;
+
+    let mut transcript = Transcript::new(b"AffineX");
+    let mut verifier = Verifier::new(&mut transcript);
+    let p_gadget = SonnyEdwardsPointGadget::verifier_commit_to_sonny_edwards_point(
+        &mut verifier,
+        &commitments[..4],
+    )?;
+    let x_var = verifier.commit(commitments[4]);
+    p_gadget.equal_affine_x(x_var.into(), None, &mut verifier);
+    verifier.verify(&proof, &pc_gens, &bp_gens, &mut rand::thread_rng())
+}
+
+#[test]
+fn affine_x() {
+    let p = SonnyEdwardsPoint::new_random_point(&mut rand::thread_rng());
+    let (x, y) = bulletproofs_gadgets::helpers::affine_coords(&p);
+    assert!(affine_x_roundtrip_helper(p, x).is_ok());
+    assert!(affine_x_roundtrip_helper(p, y).is_err());
+}
+
+///////////////// Point expressions with public points /////////////////
+
+fn expression_roundtrip_helper(
+    p1: SonnyEdwardsPoint,
+    p2: SonnyEdwardsPoint,
+    p3: SonnyEdwardsPoint,
+    res: SonnyEdwardsPoint,
+) -> Result<(), R1CSError> {
+    // Common
+    let pc_gens = PedersenGens::default();
+    let bp_gens = BulletproofGens::new(64, 1);
+
+    // Proves that P1 + P2 - P3 = res
+    let circuit = |cs: &mut dyn ConstraintSystem| {
+        let p1 = SonnyEdwardsPointGadget::from_point(&p1);
+        let p2 = SonnyEdwardsPointGadget::from_point(&p2);
+        let p3 = SonnyEdwardsPointGadget::from_point(&p3);
+        let r = (&p1 + &p2 - &p3).synthesize(cs);
+        r.equal(&SonnyEdwardsPointGadget::from_point(&res), cs);
+    };
+
+    let mut transcript = Transcript::new(b"PointExpression");
+    let mut prover = Prover::new(&pc_gens, &mut transcript);
+    circuit(&mut prover);
+    let proof = prover.prove(&bp_gens)?;
+
+    let mut transcript = Transcript::new(b"PointExpression");
+    let mut verifier = Verifier::new(&mut transcript);
+    circuit(&mut verifier);
+    verifier.verify(&proof, &pc_gens, &bp_gens, &mut rand::thread_rng())
+}
+
+#[test]
+fn point_expression() {
+    let p1 = SonnyEdwardsPoint::new_random_point(&mut rand::thread_rng());
+    let p2 = SonnyEdwardsPoint::new_random_point(&mut rand::thread_rng());
+    let p3 = SonnyEdwardsPoint::new_random_point(&mut rand::thread_rng());
+    assert!(expression_roundtrip_helper(p1, p2, p3, p1 + p2 - p3).is_ok());
+    assert!(expression_roundtrip_helper(p1, p2, p3, p1 + p2 + p3).is_err());
+}
+
+///////////////// Proof bundles /////////////////
+
+#[test]
+fn addition_and_doubling_bundles() {
+    let pc_gens = PedersenGens::default();
+    let bp_gens = BulletproofGens::new(128, 1);
+    let mut rng = rand::thread_rng();
+    let p = SonnyEdwardsPoint::new_random_point(&mut rng);
+    let q = SonnyEdwardsPoint::new_random_point(&mut rng);
+
+    let bundle = prove_addition(&p, &q, &pc_gens, &bp_gens, &mut rng).unwrap();
+    assert!(verify_addition(&bundle, &(p + q), &pc_gens, &bp_gens).is_ok());
+    assert!(verify_addition(&bundle, &(p + p), &pc_gens, &bp_gens).is_err());
+
+    let bundle = prove_doubling(&p, &pc_gens, &bp_gens, &mut rng).unwrap();
+    assert!(verify_doubling(&bundle, &p.double(), &pc_gens, &bp_gens).is_ok());
+    assert!(verify_doubling(&bundle, &q.double(), &pc_gens, &bp_gens).is_err());
+    // A doubling does not pass for an addition.
+    assert!(verify_addition(&bundle, &p.double(), &pc_gens, &bp_gens).is_err());
+}
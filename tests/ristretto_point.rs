@@ -180,3 +180,54 @@ fn point_doubling_verify(
         .map_err(|_| R1CSError::VerificationError)
 }
 */
+
+///////////////// Commit points as prover & Verifier /////////////////
+
+#[test]
+#[cfg(feature = "prover")]
+fn test_ristretto_point_committing() {
+    let A = SonnyRistrettoPoint::new_random_point(&mut rand::thread_rng());
+    let B = SonnyRistrettoPoint::new_random_point(&mut rand::thread_rng());
+    let pc_gens = PedersenGens::default();
+    let bp_gens = BulletproofGens::new(128, 1);
+
+    let (proof, commitments) = ristretto_committing_proof(&pc_gens, &bp_gens, &A).unwrap();
+    assert!(ristretto_committing_verify(&pc_gens, &bp_gens, &proof, &commitments).is_ok());
+    // The proof does not pass against the commitments of another point.
+    let (_, other_commitments) = ristretto_committing_proof(&pc_gens, &bp_gens, &B).unwrap();
+    assert!(ristretto_committing_verify(&pc_gens, &bp_gens, &proof, &other_commitments).is_err());
+}
+
+#[cfg(feature = "prover")]
+fn ristretto_committing_proof(
+    pc_gens: &PedersenGens,
+    bp_gens: &BulletproofGens,
+    P: &SonnyRistrettoPoint,
+) -> Result<(R1CSProof, Vec<CompressedRistretto>), R1CSError> {
+    let mut transcript = Transcript::new(b"RistrettoCommit");
+    let mut prover = Prover::new(pc_gens, &mut transcript);
+    let (_, commitments) =
+        SonnyRistrettoPointGadget::prover_commit_to_sonny_ristretto_point(&mut prover, P);
+    let proof = prover.prove(bp_gens)?;
+
+    Ok((proof, commitments))
+}
+
+#[cfg(feature = "prover")]
+fn ristretto_committing_verify(
+    pc_gens: &PedersenGens,
+    bp_gens: &BulletproofGens,
+    proof: &R1CSProof,
+    commitments: &[CompressedRistretto],
+) -> Result<(), R1CSError> {
+    let mut transcript = Transcript::new(b"RistrettoCommit");
+    let mut verifier = Verifier::new(&mut transcript);
+    SonnyRistrettoPointGadget::verifier_commit_to_sonny_ristretto_point(
+        &mut verifier,
+        commitments,
+    )?;
+
+    verifier
+        .verify(proof, &pc_gens, &bp_gens, &mut rand::thread_rng())
+        .map_err(|_| R1CSError::VerificationError)
+}
@@ -0,0 +1,69 @@
+#![cfg(feature = "test_utils")]
+extern crate bulletproofs;
+#[macro_use]
+extern crate bulletproofs_gadgets;
+extern crate curve25519_dalek;
+extern crate proptest;
+extern crate rand;
+extern crate zerocaf;
+
+use bulletproofs::r1cs::{ConstraintSystem, Variable};
+use bulletproofs_gadgets::gadgets::point::edwards_point::SonnyEdwardsPointGadget;
+use bulletproofs_gadgets::gadgets::point::witness;
+use bulletproofs_gadgets::test_utils::*;
+use curve25519_dalek::scalar::Scalar;
+use proptest::prelude::*;
+use rand::{rngs::StdRng, SeedableRng};
+use zerocaf::edwards::EdwardsPoint as SonnyEdwardsPoint;
+
+proptest! {
+    #![proptest_config(ProptestConfig::with_cases(16))]
+
+    #[test]
+    fn edwards_witness_matches_zerocaf(p in sonny_edwards_point(), q in sonny_edwards_point()) {
+        prop_assert!(witness::edwards::add(&p, &q) == p + q);
+    }
+
+    #[test]
+    fn edwards_scalar_mul_witness_matches_zerocaf(p in sonny_edwards_point(), (k, _) in sonny_scalar_bits()) {
+        prop_assert!(witness::edwards::scalar_mul(&p, &k.into_bits()) == p * k);
+    }
+}
+
+/// The point of the equality circuit, the same for every test.
+fn point() -> SonnyEdwardsPoint {
+    SonnyEdwardsPoint::new_random_point(&mut StdRng::from_seed([7; 32]))
+}
+
+fn point_witness() -> Vec<Scalar> {
+    let p = point();
+    [p.X, p.Y, p.Z]
+        .iter()
+        .map(|c| Scalar::from_bytes_mod_order(c.to_bytes()))
+        .collect()
+}
+
+// Only X, Y and Z are committed: `equal` does not constrain T.
+fn point_equality_circuit(
+    cs: &mut dyn ConstraintSystem,
+    vars: Vec<Variable>,
+    _: Option<&[Scalar]>,
+) {
+    let p = point();
+    let committed = SonnyEdwardsPointGadget {
+        X: vars[0].into(),
+        Y: vars[1].into(),
+        Z: vars[2].into(),
+        T: Scalar::from_bytes_mod_order(p.T.to_bytes()).into(),
+    };
+    committed.equal(&SonnyEdwardsPointGadget::from_point(&p), cs);
+}
+
+gadget_roundtrip_tests!(
+    committed_points_equality,
+    witness = point_witness(),
+    circuit = point_equality_circuit,
+    gens_capacity = 16,
+    multipliers = 4,
+    constraints = 10
+);